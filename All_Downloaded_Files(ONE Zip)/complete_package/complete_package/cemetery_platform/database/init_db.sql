@@ -1,9 +1,14 @@
 -- Najaf Cemetery Database Initialization Script
 -- This script sets up the complete database schema for the cemetery management system
+-- The rust-processor service applies the same schema as versioned migrations
+-- (services/rust-processor/migrations); keep the two in step
 
 -- Enable PostGIS extension
 CREATE EXTENSION IF NOT EXISTS postgis;
 
+-- Enable trigram matching for fuzzy name search
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
 -- Create deceased_records table (main data from government)
 CREATE TABLE IF NOT EXISTS deceased_records (
     id SERIAL PRIMARY KEY,
@@ -25,10 +30,13 @@ CREATE TABLE IF NOT EXISTS deceased_records (
     
     -- Geospatial data
     coordinates GEOMETRY(Point, 4326),
+    footprint GEOMETRY(Polygon, 4326),  -- outline of the grave, when surveyed
     
     -- Additional metadata
+    birth_date DATE,
     age_at_death INTEGER,
     cause_of_death VARCHAR(255),
+    cause_of_death_code VARCHAR(20),  -- cause_of_death in the controlled vocabulary
     national_id VARCHAR(50),
     family_contact VARCHAR(255),
     
@@ -42,22 +50,47 @@ CREATE TABLE IF NOT EXISTS deceased_records (
     
     -- Timestamps
     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    deleted_at TIMESTAMP  -- soft delete; NULL for live records
 );
 
+-- Soft delete column for databases created before it existed
+ALTER TABLE deceased_records ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMP;
+ALTER TABLE deceased_records ADD COLUMN IF NOT EXISTS birth_date DATE;
+ALTER TABLE deceased_records ADD COLUMN IF NOT EXISTS footprint GEOMETRY(Polygon, 4326);
+ALTER TABLE deceased_records ADD COLUMN IF NOT EXISTS cause_of_death_code VARCHAR(20);
+
 -- Create indexes for deceased_records
 CREATE INDEX IF NOT EXISTS idx_deceased_coordinates 
     ON deceased_records USING GIST (coordinates);
 
+CREATE INDEX IF NOT EXISTS idx_deceased_coordinates_geography 
+    ON deceased_records USING GIST ((coordinates::geography));
+
+CREATE INDEX IF NOT EXISTS idx_deceased_footprint 
+    ON deceased_records USING GIST (footprint);
+
 CREATE INDEX IF NOT EXISTS idx_deceased_burial_date 
     ON deceased_records (burial_date DESC);
 
+CREATE INDEX IF NOT EXISTS idx_deceased_burial_date_id 
+    ON deceased_records (burial_date DESC, id DESC);
+
 CREATE INDEX IF NOT EXISTS idx_deceased_death_date 
     ON deceased_records (death_date DESC);
 
 CREATE INDEX IF NOT EXISTS idx_deceased_record_id 
     ON deceased_records (record_id);
 
+-- Imports with "conflict_key": "national_id" or "name_and_death_date" upsert
+-- on a unique index over those columns. Create the one a source needs once
+-- existing duplicates are merged; they are not created by default, as other
+-- sources may legitimately share these values:
+--   CREATE UNIQUE INDEX idx_deceased_national_id_key
+--       ON deceased_records (national_id);
+--   CREATE UNIQUE INDEX idx_deceased_name_death_date_key
+--       ON deceased_records (deceased_name, death_date);
+
 CREATE INDEX IF NOT EXISTS idx_deceased_name 
     ON deceased_records (deceased_name);
 
@@ -67,6 +100,13 @@ CREATE INDEX IF NOT EXISTS idx_deceased_location
 CREATE INDEX IF NOT EXISTS idx_deceased_status 
     ON deceased_records (processing_status);
 
+-- Trigram indexes for fuzzy name matching (name search and family linking)
+CREATE INDEX IF NOT EXISTS idx_deceased_name_trgm 
+    ON deceased_records USING GIN (lower(deceased_name) gin_trgm_ops);
+
+CREATE INDEX IF NOT EXISTS idx_deceased_name_arabic_trgm 
+    ON deceased_records USING GIN (translate(deceased_name_arabic, 'آأإٱًٌٍَُِّْٰـ', 'اااا') gin_trgm_ops);
+
 -- Create GIN index for JSONB additional_data
 CREATE INDEX IF NOT EXISTS idx_deceased_additional_data 
     ON deceased_records USING GIN (additional_data);
@@ -88,7 +128,9 @@ CREATE INDEX IF NOT EXISTS idx_cemetery_features_geometry
 CREATE INDEX IF NOT EXISTS idx_cemetery_features_properties 
     ON najaf_cemetery_features USING GIN (properties);
 
-CREATE INDEX IF NOT EXISTS idx_cemetery_features_feature_id 
+-- One feature per record, so imports can upsert just the records they touch
+DROP INDEX IF EXISTS idx_cemetery_features_feature_id;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_cemetery_features_feature_id_unique 
     ON najaf_cemetery_features (feature_id);
 
 -- Create sync_history table
@@ -114,7 +156,7 @@ CREATE TABLE IF NOT EXISTS file_processing_log (
     filename VARCHAR(255) NOT NULL,
     file_hash VARCHAR(64) UNIQUE,
     file_size BIGINT,
-    download_time TIMESTAMP,
+    download_time TIMESTAMPTZ,
     extraction_time TIMESTAMP,
     processing_start_time TIMESTAMP,
     processing_end_time TIMESTAMP,
@@ -122,12 +164,25 @@ CREATE TABLE IF NOT EXISTS file_processing_log (
     records_total INTEGER,
     records_processed INTEGER,
     records_failed INTEGER,
+    -- Records that passed validation with coordinates, i.e. that can be mapped
+    records_with_coordinates INTEGER,
     status VARCHAR(50),  -- 'downloaded', 'extracted', 'processing', 'completed', 'failed'
     error_message TEXT,
     metadata JSONB,
     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
 );
 
+ALTER TABLE file_processing_log ADD COLUMN IF NOT EXISTS records_with_coordinates INTEGER;
+
+-- download_time was a zone-less TIMESTAMP before it was filled in
+DO $$
+BEGIN
+    IF (SELECT data_type FROM information_schema.columns
+        WHERE table_name = 'file_processing_log' AND column_name = 'download_time') = 'timestamp without time zone' THEN
+        ALTER TABLE file_processing_log ALTER COLUMN download_time TYPE TIMESTAMPTZ USING download_time AT TIME ZONE 'UTC';
+    END IF;
+END $$;
+
 CREATE INDEX IF NOT EXISTS idx_file_processing_filename 
     ON file_processing_log (filename);
 
@@ -137,6 +192,111 @@ CREATE INDEX IF NOT EXISTS idx_file_processing_status
 CREATE INDEX IF NOT EXISTS idx_file_processing_time 
     ON file_processing_log (created_at DESC);
 
+-- Create processing_checkpoints table (how far an interrupted run committed)
+CREATE TABLE IF NOT EXISTS processing_checkpoints (
+    file_hash VARCHAR(64) NOT NULL,
+    file_name TEXT NOT NULL,
+    records_done INTEGER NOT NULL,  -- records of the file read and committed
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (file_hash, file_name)
+);
+
+-- Create processing_jobs table (background /api/process runs)
+CREATE TABLE IF NOT EXISTS processing_jobs (
+    id UUID PRIMARY KEY,
+    status VARCHAR(20) NOT NULL DEFAULT 'queued',  -- 'queued', 'running', 'completed', 'failed'
+    request JSONB NOT NULL,
+    requested_at TIMESTAMPTZ,  -- the request's timestamp
+    result JSONB,
+    error_message TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    started_at TIMESTAMP,
+    finished_at TIMESTAMP
+);
+
+ALTER TABLE processing_jobs ADD COLUMN IF NOT EXISTS requested_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_processing_jobs_status 
+    ON processing_jobs (status, created_at);
+
+-- Create idempotency_keys table (the job each Idempotency-Key queued)
+CREATE TABLE IF NOT EXISTS idempotency_keys (
+    key VARCHAR(255) PRIMARY KEY,
+    request_hash CHAR(64) NOT NULL,  -- SHA-256 of the request the key was first sent with
+    job_id UUID NOT NULL REFERENCES processing_jobs (id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_idempotency_keys_expires 
+    ON idempotency_keys (expires_at);
+
+-- Create record_merges table (audit of /api/records/merge)
+CREATE TABLE IF NOT EXISTS record_merges (
+    id SERIAL PRIMARY KEY,
+    primary_record_id VARCHAR(50) NOT NULL,
+    merged_record_ids TEXT[] NOT NULL,
+    primary_before JSONB NOT NULL,
+    primary_after JSONB NOT NULL,
+    merged_records JSONB NOT NULL,  -- the duplicates as they were before being soft-deleted
+    merged_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_record_merges_primary 
+    ON record_merges (primary_record_id);
+
+-- Create family_links table (suggested fathers, matched on father and grandfather names)
+CREATE TABLE IF NOT EXISTS family_links (
+    record_id VARCHAR(50) NOT NULL,
+    father_record_id VARCHAR(50) NOT NULL,
+    confidence REAL NOT NULL,  -- 0 to 1; a suggestion, not a fact
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (record_id, father_record_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_family_links_father 
+    ON family_links (father_record_id);
+
+-- Create record_history table (previous state of changed or deleted records)
+CREATE TABLE IF NOT EXISTS record_history (
+    id BIGSERIAL PRIMARY KEY,
+    record_id VARCHAR(50) NOT NULL,
+    operation VARCHAR(10) NOT NULL,  -- UPDATE or DELETE
+    changed_fields TEXT[] NOT NULL,
+    previous JSONB NOT NULL,  -- the whole row before the change
+    source_file VARCHAR(255),  -- source_file of the row after the change
+    changed_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_record_history_record 
+    ON record_history (record_id, changed_at DESC);
+
+-- Create rejected_records table (records imports rejected, for review)
+CREATE TABLE IF NOT EXISTS rejected_records (
+    id BIGSERIAL PRIMARY KEY,
+    source_file TEXT NOT NULL,  -- the delivery, as in file_processing_log.filename
+    file_name TEXT NOT NULL,  -- the file within it the record came from
+    record_id TEXT,
+    line_number BIGINT,
+    fields JSONB,  -- the source row, or the record as parsed when it failed validation
+    errors JSONB NOT NULL,  -- [{"message", "code", "field"}]
+    rejected_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_rejected_records_source_file 
+    ON rejected_records (source_file, id);
+
+CREATE INDEX IF NOT EXISTS idx_rejected_records_file_name 
+    ON rejected_records (file_name, id);
+
+-- Create processed_files table (content hashes of the files imports wrote)
+CREATE TABLE IF NOT EXISTS processed_files (
+    file_hash VARCHAR(64) PRIMARY KEY,
+    file_name TEXT NOT NULL,  -- the file's path within the delivery
+    source_file TEXT NOT NULL,  -- the delivery, as in file_processing_log.filename
+    processed_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
 -- Create burial_sections table (for cemetery layout)
 CREATE TABLE IF NOT EXISTS burial_sections (
     id SERIAL PRIMARY KEY,
@@ -157,6 +317,27 @@ CREATE INDEX IF NOT EXISTS idx_burial_sections_geometry
 CREATE INDEX IF NOT EXISTS idx_burial_sections_code 
     ON burial_sections (section_code);
 
+-- Create cemetery_sections table (section and plot outlines imported from shapefiles)
+CREATE TABLE IF NOT EXISTS cemetery_sections (
+    id SERIAL PRIMARY KEY,
+    layer VARCHAR(500) NOT NULL,  -- shapefile the outline came from; re-importing it replaces its rows
+    shape_index INTEGER NOT NULL,
+    part_index INTEGER NOT NULL DEFAULT 0,
+    section VARCHAR(50),
+    plot_number INTEGER,
+    attributes JSONB,
+    geometry GEOMETRY(Polygon, 4326) NOT NULL,
+    source_file VARCHAR(500),
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (layer, shape_index, part_index)
+);
+
+CREATE INDEX IF NOT EXISTS idx_cemetery_sections_geometry 
+    ON cemetery_sections USING GIST (geometry);
+
+CREATE INDEX IF NOT EXISTS idx_cemetery_sections_section 
+    ON cemetery_sections (section);
+
 -- Create function to update updated_at timestamp
 CREATE OR REPLACE FUNCTION update_updated_at_column()
 RETURNS TRIGGER AS $$
@@ -179,6 +360,37 @@ CREATE TRIGGER update_burial_sections_updated_at
     BEFORE UPDATE ON burial_sections
     FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
 
+-- Create function and trigger keeping record history
+-- (updates that only touch updated_at are not changes)
+CREATE OR REPLACE FUNCTION capture_record_history()
+RETURNS TRIGGER AS $$
+DECLARE
+    old_row JSONB := to_jsonb(OLD);
+    changed TEXT[];
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        INSERT INTO record_history (record_id, operation, changed_fields, previous, source_file)
+        VALUES (OLD.record_id, TG_OP, ARRAY[]::TEXT[], old_row, OLD.source_file);
+        RETURN OLD;
+    END IF;
+    
+    SELECT array_agg(new_field.key ORDER BY new_field.key) INTO changed
+    FROM jsonb_each(to_jsonb(NEW)) AS new_field
+    WHERE new_field.key <> 'updated_at'
+        AND new_field.value IS DISTINCT FROM old_row -> new_field.key;
+    
+    IF changed IS NOT NULL THEN
+        INSERT INTO record_history (record_id, operation, changed_fields, previous, source_file)
+        VALUES (NEW.record_id, TG_OP, changed, old_row, NEW.source_file);
+    END IF;
+    RETURN NEW;
+END;
+$$ language 'plpgsql';
+
+CREATE TRIGGER capture_deceased_records_history 
+    AFTER UPDATE OR DELETE ON deceased_records
+    FOR EACH ROW EXECUTE FUNCTION capture_record_history();
+
 -- Create view for daily statistics
 CREATE OR REPLACE VIEW daily_burial_stats AS
 SELECT 
@@ -188,7 +400,8 @@ SELECT
     MIN(created_at) as first_recorded,
     MAX(created_at) as last_recorded
 FROM deceased_records
-WHERE processing_status = 'completed'
+WHERE processing_status IN ('completed', 'needs_review', 'geocode_failed')
+    AND deleted_at IS NULL
 GROUP BY burial_date
 ORDER BY burial_date DESC;
 
@@ -199,10 +412,11 @@ SELECT
     s.section_name,
     s.capacity,
     COUNT(d.id) as current_occupancy,
-    ROUND((COUNT(d.id)::FLOAT / NULLIF(s.capacity, 0) * 100), 2) as occupancy_percentage
+    ROUND((COUNT(d.id)::numeric / NULLIF(s.capacity, 0) * 100), 2) as occupancy_percentage
 FROM burial_sections s
 LEFT JOIN deceased_records d ON d.section = s.section_code
-WHERE d.processing_status = 'completed'
+WHERE d.processing_status IN ('completed', 'needs_review', 'geocode_failed')
+    AND d.deleted_at IS NULL
 GROUP BY s.id, s.section_code, s.section_name, s.capacity
 ORDER BY occupancy_percentage DESC;
 
@@ -219,7 +433,8 @@ SELECT
     created_at
 FROM deceased_records
 WHERE burial_date >= CURRENT_DATE - INTERVAL '30 days'
-    AND processing_status = 'completed'
+    AND processing_status IN ('completed', 'needs_review', 'geocode_failed')
+    AND deleted_at IS NULL
 ORDER BY burial_date DESC, created_at DESC;
 
 -- Insert sample burial sections (Wadi al-Salam cemetery sections)
@@ -62,6 +62,7 @@ class FTPMonitor:
         # Rust microservice configuration
         self.rust_service_url = config.get('rust_service_url', 'http://localhost:8080/process')
         self.rust_service_enabled = config.get('rust_service_enabled', True)
+        self.rust_service_api_key = config.get('rust_service_api_key')
         
         # Load processed files history
         self.processed_files = self._load_processed_files()
@@ -215,7 +216,9 @@ class FTPMonitor:
         
         try:
             payload = {
-                'data_path': str(extracted_path.absolute()),
+                # The processor only accepts paths inside its data directory,
+                # where the extraction directory is mounted
+                'data_path': str(extracted_path.relative_to(self.extract_dir)),
                 'metadata': metadata,
                 'timestamp': datetime.now().isoformat(),
                 'source': 'ftp_monitor'
@@ -224,9 +227,14 @@ class FTPMonitor:
             logger.info(f"Triggering Rust microservice at {self.rust_service_url}")
             logger.info(f"Payload: {json.dumps(payload, indent=2)}")
             
+            headers = {}
+            if self.rust_service_api_key:
+                headers['Authorization'] = f"Bearer {self.rust_service_api_key}"
+            
             response = requests.post(
                 self.rust_service_url,
                 json=payload,
+                headers=headers,
                 timeout=300  # 5 minutes timeout for processing
             )
             
@@ -432,7 +440,8 @@ def main():
         
         # Rust microservice
         'rust_service_url': 'http://localhost:8080/api/process',
-        'rust_service_enabled': True
+        'rust_service_enabled': True,
+        'rust_service_api_key': os.environ.get('RUST_SERVICE_API_KEY')
     }
     
     # Initialize monitor
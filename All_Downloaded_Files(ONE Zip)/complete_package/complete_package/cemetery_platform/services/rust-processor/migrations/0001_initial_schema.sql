@@ -0,0 +1,384 @@
+-- Baseline schema, matching database/init_db.sql at the time migrations were
+-- introduced. Every statement is idempotent so databases set up by that
+-- script can adopt migrations; grants are left to the deployment.
+
+-- Enable PostGIS extension
+CREATE EXTENSION IF NOT EXISTS postgis;
+
+-- Enable trigram matching for fuzzy name search
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- Create deceased_records table (main data from government)
+CREATE TABLE IF NOT EXISTS deceased_records (
+    id SERIAL PRIMARY KEY,
+    record_id VARCHAR(50) UNIQUE NOT NULL,
+    deceased_name VARCHAR(255) NOT NULL,
+    deceased_name_arabic VARCHAR(255),
+    father_name VARCHAR(255),
+    grandfather_name VARCHAR(255),
+    death_date DATE NOT NULL,
+    death_location VARCHAR(255),
+    burial_date DATE NOT NULL,
+    burial_location VARCHAR(255) NOT NULL,
+    
+    -- Cemetery location details
+    section VARCHAR(50),
+    row_number INTEGER,
+    plot_number INTEGER,
+    grave_number VARCHAR(50),
+    
+    -- Geospatial data
+    coordinates GEOMETRY(Point, 4326),
+    footprint GEOMETRY(Polygon, 4326),  -- outline of the grave, when surveyed
+    
+    -- Additional metadata
+    birth_date DATE,
+    age_at_death INTEGER,
+    cause_of_death VARCHAR(255),
+    cause_of_death_code VARCHAR(20),  -- cause_of_death in the controlled vocabulary
+    national_id VARCHAR(50),
+    family_contact VARCHAR(255),
+    
+    -- JSON for flexible additional data
+    additional_data JSONB,
+    
+    -- Processing metadata
+    source_file VARCHAR(255),
+    processing_status VARCHAR(50) DEFAULT 'pending',
+    processing_error TEXT,
+    
+    -- Timestamps
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    deleted_at TIMESTAMP  -- soft delete; NULL for live records
+);
+
+-- Soft delete column for databases created before it existed
+ALTER TABLE deceased_records ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMP;
+ALTER TABLE deceased_records ADD COLUMN IF NOT EXISTS birth_date DATE;
+ALTER TABLE deceased_records ADD COLUMN IF NOT EXISTS footprint GEOMETRY(Polygon, 4326);
+ALTER TABLE deceased_records ADD COLUMN IF NOT EXISTS cause_of_death_code VARCHAR(20);
+
+-- Create indexes for deceased_records
+CREATE INDEX IF NOT EXISTS idx_deceased_coordinates 
+    ON deceased_records USING GIST (coordinates);
+
+CREATE INDEX IF NOT EXISTS idx_deceased_footprint 
+    ON deceased_records USING GIST (footprint);
+
+CREATE INDEX IF NOT EXISTS idx_deceased_burial_date 
+    ON deceased_records (burial_date DESC);
+
+CREATE INDEX IF NOT EXISTS idx_deceased_death_date 
+    ON deceased_records (death_date DESC);
+
+CREATE INDEX IF NOT EXISTS idx_deceased_record_id 
+    ON deceased_records (record_id);
+
+-- Imports with "conflict_key": "national_id" or "name_and_death_date" upsert
+-- on a unique index over those columns. Create the one a source needs once
+-- existing duplicates are merged; they are not created by default, as other
+-- sources may legitimately share these values:
+--   CREATE UNIQUE INDEX idx_deceased_national_id_key
+--       ON deceased_records (national_id);
+--   CREATE UNIQUE INDEX idx_deceased_name_death_date_key
+--       ON deceased_records (deceased_name, death_date);
+
+CREATE INDEX IF NOT EXISTS idx_deceased_name 
+    ON deceased_records (deceased_name);
+
+CREATE INDEX IF NOT EXISTS idx_deceased_location 
+    ON deceased_records (section, row_number, plot_number);
+
+CREATE INDEX IF NOT EXISTS idx_deceased_status 
+    ON deceased_records (processing_status);
+
+-- Trigram indexes for fuzzy name matching (name search and family linking)
+CREATE INDEX IF NOT EXISTS idx_deceased_name_trgm 
+    ON deceased_records USING GIN (lower(deceased_name) gin_trgm_ops);
+
+CREATE INDEX IF NOT EXISTS idx_deceased_name_arabic_trgm 
+    ON deceased_records USING GIN (translate(deceased_name_arabic, 'آأإٱًٌٍَُِّْٰـ', 'اااا') gin_trgm_ops);
+
+-- Create GIN index for JSONB additional_data
+CREATE INDEX IF NOT EXISTS idx_deceased_additional_data 
+    ON deceased_records USING GIN (additional_data);
+
+-- Create najaf_cemetery_features table (for GeoJSON export)
+CREATE TABLE IF NOT EXISTS najaf_cemetery_features (
+    id SERIAL PRIMARY KEY,
+    feature_id VARCHAR(255),
+    geometry GEOMETRY(Geometry, 4326),
+    properties JSONB,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Create indexes for najaf_cemetery_features
+CREATE INDEX IF NOT EXISTS idx_cemetery_features_geometry 
+    ON najaf_cemetery_features USING GIST (geometry);
+
+CREATE INDEX IF NOT EXISTS idx_cemetery_features_properties 
+    ON najaf_cemetery_features USING GIN (properties);
+
+-- One feature per record, so imports can upsert just the records they touch
+DROP INDEX IF EXISTS idx_cemetery_features_feature_id;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_cemetery_features_feature_id_unique 
+    ON najaf_cemetery_features (feature_id);
+
+-- Create sync_history table
+CREATE TABLE IF NOT EXISTS sync_history (
+    id SERIAL PRIMARY KEY,
+    sync_time TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    sync_type VARCHAR(50),  -- 'ftp_download', 'data_processing', 'geojson_export'
+    features_count INTEGER,
+    status VARCHAR(50),
+    error_message TEXT,
+    metadata JSONB
+);
+
+CREATE INDEX IF NOT EXISTS idx_sync_history_time 
+    ON sync_history (sync_time DESC);
+
+CREATE INDEX IF NOT EXISTS idx_sync_history_type 
+    ON sync_history (sync_type);
+
+-- Create file_processing_log table
+CREATE TABLE IF NOT EXISTS file_processing_log (
+    id SERIAL PRIMARY KEY,
+    filename VARCHAR(255) NOT NULL,
+    file_hash VARCHAR(64) UNIQUE,
+    file_size BIGINT,
+    download_time TIMESTAMPTZ,
+    extraction_time TIMESTAMP,
+    processing_start_time TIMESTAMP,
+    processing_end_time TIMESTAMP,
+    processing_duration_seconds FLOAT,
+    records_total INTEGER,
+    records_processed INTEGER,
+    records_failed INTEGER,
+    -- Records that passed validation with coordinates, i.e. that can be mapped
+    records_with_coordinates INTEGER,
+    status VARCHAR(50),  -- 'downloaded', 'extracted', 'processing', 'completed', 'failed'
+    error_message TEXT,
+    metadata JSONB,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+ALTER TABLE file_processing_log ADD COLUMN IF NOT EXISTS records_with_coordinates INTEGER;
+
+-- download_time was a zone-less TIMESTAMP before it was filled in
+DO $$
+BEGIN
+    IF (SELECT data_type FROM information_schema.columns
+        WHERE table_name = 'file_processing_log' AND column_name = 'download_time') = 'timestamp without time zone' THEN
+        ALTER TABLE file_processing_log ALTER COLUMN download_time TYPE TIMESTAMPTZ USING download_time AT TIME ZONE 'UTC';
+    END IF;
+END $$;
+
+CREATE INDEX IF NOT EXISTS idx_file_processing_filename 
+    ON file_processing_log (filename);
+
+CREATE INDEX IF NOT EXISTS idx_file_processing_status 
+    ON file_processing_log (status);
+
+CREATE INDEX IF NOT EXISTS idx_file_processing_time 
+    ON file_processing_log (created_at DESC);
+
+-- Create processing_checkpoints table (how far an interrupted run committed)
+CREATE TABLE IF NOT EXISTS processing_checkpoints (
+    file_hash VARCHAR(64) NOT NULL,
+    file_name TEXT NOT NULL,
+    records_done INTEGER NOT NULL,  -- records of the file read and committed
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (file_hash, file_name)
+);
+
+-- Create processing_jobs table (background /api/process runs)
+CREATE TABLE IF NOT EXISTS processing_jobs (
+    id UUID PRIMARY KEY,
+    status VARCHAR(20) NOT NULL DEFAULT 'queued',  -- 'queued', 'running', 'completed', 'failed'
+    request JSONB NOT NULL,
+    requested_at TIMESTAMPTZ,  -- the request's timestamp
+    result JSONB,
+    error_message TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    started_at TIMESTAMP,
+    finished_at TIMESTAMP
+);
+
+ALTER TABLE processing_jobs ADD COLUMN IF NOT EXISTS requested_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_processing_jobs_status 
+    ON processing_jobs (status, created_at);
+
+-- Create record_merges table (audit of /api/records/merge)
+CREATE TABLE IF NOT EXISTS record_merges (
+    id SERIAL PRIMARY KEY,
+    primary_record_id VARCHAR(50) NOT NULL,
+    merged_record_ids TEXT[] NOT NULL,
+    primary_before JSONB NOT NULL,
+    primary_after JSONB NOT NULL,
+    merged_records JSONB NOT NULL,  -- the duplicates as they were before being soft-deleted
+    merged_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_record_merges_primary 
+    ON record_merges (primary_record_id);
+
+-- Create family_links table (suggested fathers, matched on father and grandfather names)
+CREATE TABLE IF NOT EXISTS family_links (
+    record_id VARCHAR(50) NOT NULL,
+    father_record_id VARCHAR(50) NOT NULL,
+    confidence REAL NOT NULL,  -- 0 to 1; a suggestion, not a fact
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (record_id, father_record_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_family_links_father 
+    ON family_links (father_record_id);
+
+-- Create burial_sections table (for cemetery layout)
+CREATE TABLE IF NOT EXISTS burial_sections (
+    id SERIAL PRIMARY KEY,
+    section_code VARCHAR(50) UNIQUE NOT NULL,
+    section_name VARCHAR(255),
+    section_name_arabic VARCHAR(255),
+    capacity INTEGER,
+    occupied_count INTEGER DEFAULT 0,
+    geometry GEOMETRY(Polygon, 4326),
+    description TEXT,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_burial_sections_geometry 
+    ON burial_sections USING GIST (geometry);
+
+CREATE INDEX IF NOT EXISTS idx_burial_sections_code 
+    ON burial_sections (section_code);
+
+-- Create cemetery_sections table (section and plot outlines imported from shapefiles)
+CREATE TABLE IF NOT EXISTS cemetery_sections (
+    id SERIAL PRIMARY KEY,
+    layer VARCHAR(500) NOT NULL,  -- shapefile the outline came from; re-importing it replaces its rows
+    shape_index INTEGER NOT NULL,
+    part_index INTEGER NOT NULL DEFAULT 0,
+    section VARCHAR(50),
+    plot_number INTEGER,
+    attributes JSONB,
+    geometry GEOMETRY(Polygon, 4326) NOT NULL,
+    source_file VARCHAR(500),
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (layer, shape_index, part_index)
+);
+
+CREATE INDEX IF NOT EXISTS idx_cemetery_sections_geometry 
+    ON cemetery_sections USING GIST (geometry);
+
+CREATE INDEX IF NOT EXISTS idx_cemetery_sections_section 
+    ON cemetery_sections (section);
+
+-- Create function to update updated_at timestamp
+CREATE OR REPLACE FUNCTION update_updated_at_column()
+RETURNS TRIGGER AS $$
+BEGIN
+    NEW.updated_at = CURRENT_TIMESTAMP;
+    RETURN NEW;
+END;
+$$ language 'plpgsql';
+
+-- Create triggers for updated_at, replacing any an earlier setup created
+DROP TRIGGER IF EXISTS update_deceased_records_updated_at ON deceased_records;
+CREATE TRIGGER update_deceased_records_updated_at 
+    BEFORE UPDATE ON deceased_records
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+DROP TRIGGER IF EXISTS update_cemetery_features_updated_at ON najaf_cemetery_features;
+CREATE TRIGGER update_cemetery_features_updated_at 
+    BEFORE UPDATE ON najaf_cemetery_features
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+DROP TRIGGER IF EXISTS update_burial_sections_updated_at ON burial_sections;
+CREATE TRIGGER update_burial_sections_updated_at 
+    BEFORE UPDATE ON burial_sections
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+-- Create view for daily statistics
+CREATE OR REPLACE VIEW daily_burial_stats AS
+SELECT 
+    burial_date,
+    COUNT(*) as burials_count,
+    COUNT(DISTINCT section) as sections_used,
+    MIN(created_at) as first_recorded,
+    MAX(created_at) as last_recorded
+FROM deceased_records
+WHERE processing_status = 'completed'
+    AND deleted_at IS NULL
+GROUP BY burial_date
+ORDER BY burial_date DESC;
+
+-- Create view for section occupancy
+CREATE OR REPLACE VIEW section_occupancy AS
+SELECT 
+    s.section_code,
+    s.section_name,
+    s.capacity,
+    COUNT(d.id) as current_occupancy,
+    ROUND((COUNT(d.id)::numeric / NULLIF(s.capacity, 0) * 100), 2) as occupancy_percentage
+FROM burial_sections s
+LEFT JOIN deceased_records d ON d.section = s.section_code
+WHERE d.processing_status = 'completed'
+    AND d.deleted_at IS NULL
+GROUP BY s.id, s.section_code, s.section_name, s.capacity
+ORDER BY occupancy_percentage DESC;
+
+-- Create view for recent burials (last 30 days)
+CREATE OR REPLACE VIEW recent_burials AS
+SELECT 
+    record_id,
+    deceased_name,
+    burial_date,
+    section,
+    row_number,
+    plot_number,
+    ST_AsGeoJSON(coordinates) as coordinates_geojson,
+    created_at
+FROM deceased_records
+WHERE burial_date >= CURRENT_DATE - INTERVAL '30 days'
+    AND processing_status = 'completed'
+    AND deleted_at IS NULL
+ORDER BY burial_date DESC, created_at DESC;
+
+-- Insert sample burial sections (Wadi al-Salam cemetery sections)
+INSERT INTO burial_sections (section_code, section_name, section_name_arabic, capacity, geometry) VALUES
+('A', 'Section A', 'القسم أ', 10000, ST_GeomFromText('POLYGON((44.310 32.015, 44.315 32.015, 44.315 32.020, 44.310 32.020, 44.310 32.015))', 4326)),
+('B', 'Section B', 'القسم ب', 10000, ST_GeomFromText('POLYGON((44.315 32.015, 44.320 32.015, 44.320 32.020, 44.315 32.020, 44.315 32.015))', 4326)),
+('C', 'Section C', 'القسم ج', 10000, ST_GeomFromText('POLYGON((44.310 32.020, 44.315 32.020, 44.315 32.025, 44.310 32.025, 44.310 32.020))', 4326))
+ON CONFLICT (section_code) DO NOTHING;
+
+-- Create database info view
+CREATE OR REPLACE VIEW database_info AS
+SELECT 
+    'deceased_records' as table_name,
+    COUNT(*) as record_count,
+    COUNT(CASE WHEN processing_status = 'completed' THEN 1 END) as completed,
+    COUNT(CASE WHEN processing_status = 'failed' THEN 1 END) as failed,
+    MIN(created_at) as oldest_record,
+    MAX(created_at) as newest_record
+FROM deceased_records
+UNION ALL
+SELECT 
+    'file_processing_log' as table_name,
+    COUNT(*) as record_count,
+    COUNT(CASE WHEN status = 'completed' THEN 1 END) as completed,
+    COUNT(CASE WHEN status = 'failed' THEN 1 END) as failed,
+    MIN(created_at) as oldest_record,
+    MAX(created_at) as newest_record
+FROM file_processing_log;
+
+COMMENT ON TABLE deceased_records IS 'Main table storing deceased person records from government data';
+COMMENT ON TABLE najaf_cemetery_features IS 'GeoJSON features for map visualization';
+COMMENT ON TABLE sync_history IS 'History of all sync operations';
+COMMENT ON TABLE file_processing_log IS 'Log of all processed ZIP files';
+COMMENT ON TABLE burial_sections IS 'Cemetery section layout and capacity information';
@@ -0,0 +1,49 @@
+-- Records awaiting review are still burials: count them in the views, as
+-- the map and section counts do, leaving out only flagged records and
+-- rows no import has processed.
+
+-- Create view for daily statistics
+CREATE OR REPLACE VIEW daily_burial_stats AS
+SELECT 
+    burial_date,
+    COUNT(*) as burials_count,
+    COUNT(DISTINCT section) as sections_used,
+    MIN(created_at) as first_recorded,
+    MAX(created_at) as last_recorded
+FROM deceased_records
+WHERE processing_status IN ('completed', 'needs_review', 'geocode_failed')
+    AND deleted_at IS NULL
+GROUP BY burial_date
+ORDER BY burial_date DESC;
+
+-- Create view for section occupancy
+CREATE OR REPLACE VIEW section_occupancy AS
+SELECT 
+    s.section_code,
+    s.section_name,
+    s.capacity,
+    COUNT(d.id) as current_occupancy,
+    ROUND((COUNT(d.id)::numeric / NULLIF(s.capacity, 0) * 100), 2) as occupancy_percentage
+FROM burial_sections s
+LEFT JOIN deceased_records d ON d.section = s.section_code
+WHERE d.processing_status IN ('completed', 'needs_review', 'geocode_failed')
+    AND d.deleted_at IS NULL
+GROUP BY s.id, s.section_code, s.section_name, s.capacity
+ORDER BY occupancy_percentage DESC;
+
+-- Create view for recent burials (last 30 days)
+CREATE OR REPLACE VIEW recent_burials AS
+SELECT 
+    record_id,
+    deceased_name,
+    burial_date,
+    section,
+    row_number,
+    plot_number,
+    ST_AsGeoJSON(coordinates) as coordinates_geojson,
+    created_at
+FROM deceased_records
+WHERE burial_date >= CURRENT_DATE - INTERVAL '30 days'
+    AND processing_status IN ('completed', 'needs_review', 'geocode_failed')
+    AND deleted_at IS NULL
+ORDER BY burial_date DESC, created_at DESC;
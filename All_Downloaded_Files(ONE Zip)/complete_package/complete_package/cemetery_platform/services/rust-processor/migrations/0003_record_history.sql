@@ -0,0 +1,46 @@
+-- Keep the previous state of every deceased record that is changed or
+-- permanently deleted, so corrections to the register can be audited.
+
+CREATE TABLE IF NOT EXISTS record_history (
+    id BIGSERIAL PRIMARY KEY,
+    record_id VARCHAR(50) NOT NULL,
+    operation VARCHAR(10) NOT NULL,  -- UPDATE or DELETE
+    changed_fields TEXT[] NOT NULL,
+    previous JSONB NOT NULL,  -- the whole row before the change
+    source_file VARCHAR(255),  -- source_file of the row after the change
+    changed_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_record_history_record 
+    ON record_history (record_id, changed_at DESC);
+
+-- Updates that only touch updated_at are not changes
+CREATE OR REPLACE FUNCTION capture_record_history()
+RETURNS TRIGGER AS $$
+DECLARE
+    old_row JSONB := to_jsonb(OLD);
+    changed TEXT[];
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        INSERT INTO record_history (record_id, operation, changed_fields, previous, source_file)
+        VALUES (OLD.record_id, TG_OP, ARRAY[]::TEXT[], old_row, OLD.source_file);
+        RETURN OLD;
+    END IF;
+    
+    SELECT array_agg(new_field.key ORDER BY new_field.key) INTO changed
+    FROM jsonb_each(to_jsonb(NEW)) AS new_field
+    WHERE new_field.key <> 'updated_at'
+        AND new_field.value IS DISTINCT FROM old_row -> new_field.key;
+    
+    IF changed IS NOT NULL THEN
+        INSERT INTO record_history (record_id, operation, changed_fields, previous, source_file)
+        VALUES (NEW.record_id, TG_OP, changed, old_row, NEW.source_file);
+    END IF;
+    RETURN NEW;
+END;
+$$ language 'plpgsql';
+
+DROP TRIGGER IF EXISTS capture_deceased_records_history ON deceased_records;
+CREATE TRIGGER capture_deceased_records_history 
+    AFTER UPDATE OR DELETE ON deceased_records
+    FOR EACH ROW EXECUTE FUNCTION capture_record_history();
@@ -0,0 +1,13 @@
+-- Remember the job each Idempotency-Key queued, so a client retrying
+-- /api/process after a timeout gets that job back instead of a second run.
+
+CREATE TABLE IF NOT EXISTS idempotency_keys (
+    key VARCHAR(255) PRIMARY KEY,
+    request_hash CHAR(64) NOT NULL,  -- SHA-256 of the request the key was first sent with
+    job_id UUID NOT NULL REFERENCES processing_jobs (id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_idempotency_keys_expires 
+    ON idempotency_keys (expires_at);
@@ -0,0 +1,19 @@
+-- Keep the records an import rejects, with what the source gave and why
+-- they failed, so they can be reviewed and corrected after the run.
+
+CREATE TABLE IF NOT EXISTS rejected_records (
+    id BIGSERIAL PRIMARY KEY,
+    source_file TEXT NOT NULL,  -- the delivery, as in file_processing_log.filename
+    file_name TEXT NOT NULL,  -- the file within it the record came from
+    record_id TEXT,
+    line_number BIGINT,
+    fields JSONB,  -- the source row, or the record as parsed when it failed validation
+    errors JSONB NOT NULL,  -- [{"message", "code", "field"}]
+    rejected_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_rejected_records_source_file 
+    ON rejected_records (source_file, id);
+
+CREATE INDEX IF NOT EXISTS idx_rejected_records_file_name 
+    ON rejected_records (file_name, id);
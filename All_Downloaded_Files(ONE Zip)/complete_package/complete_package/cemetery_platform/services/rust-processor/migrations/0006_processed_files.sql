@@ -0,0 +1,9 @@
+-- Note the content hash of each file a completed import wrote, so a later
+-- directory run can leave out the files an earlier run already imported.
+
+CREATE TABLE IF NOT EXISTS processed_files (
+    file_hash VARCHAR(64) PRIMARY KEY,
+    file_name TEXT NOT NULL,  -- the file's path within the delivery
+    source_file TEXT NOT NULL,  -- the delivery, as in file_processing_log.filename
+    processed_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
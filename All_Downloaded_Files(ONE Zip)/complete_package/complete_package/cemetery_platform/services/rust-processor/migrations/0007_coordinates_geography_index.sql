@@ -0,0 +1,5 @@
+-- Index the record points as geography, so nearby searches measured in
+-- metres (ST_DWithin on coordinates::geography) can use an index.
+
+CREATE INDEX IF NOT EXISTS idx_deceased_coordinates_geography 
+    ON deceased_records USING GIST ((coordinates::geography));
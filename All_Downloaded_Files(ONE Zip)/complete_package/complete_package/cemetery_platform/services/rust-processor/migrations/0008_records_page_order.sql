@@ -0,0 +1,5 @@
+-- Index the order /api/records pages in, latest burials first, so that
+-- continuing from a cursor does not sort the whole register.
+
+CREATE INDEX IF NOT EXISTS idx_deceased_burial_date_id 
+    ON deceased_records (burial_date DESC, id DESC);
@@ -0,0 +1,156 @@
+use crate::export::CSV_EXPORT_COLUMNS;
+use crate::models::{ExportRecord, GeoJsonFeature};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::BTreeSet;
+use uuid::Uuid;
+
+/// Fields left out of an anonymized export when `ANONYMIZE_FIELDS` is not set.
+pub const DEFAULT_STRIPPED_FIELDS: [&str; 3] = ["national_id", "family_contact", "cause_of_death"];
+
+/// Fields an anonymized export may leave out. Dates, section, row, plot and
+/// coordinates are always kept.
+pub const STRIPPABLE_FIELDS: [&str; 7] = [
+    "record_id", "deceased_name", "deceased_name_arabic", "burial_location",
+    "cause_of_death", "national_id", "family_contact",
+];
+
+/// Start of a pseudonymous name, and how many hex digits of the HMAC follow.
+const PSEUDONYM_PREFIX: &str = "anon-";
+const PSEUDONYM_HEX_DIGITS: usize = 16;
+
+/// Takes personal details out of exported records and features: the
+/// configured fields are dropped, and names can be replaced by a token
+/// derived from the record id, so the same grave gets the same token in
+/// every export without revealing who is buried there.
+#[derive(Clone)]
+pub struct Anonymizer {
+    stripped: BTreeSet<&'static str>,
+    key: Vec<u8>,
+    /// Whether `key` was configured, rather than made up for this process
+    stable: bool,
+    pseudonymize_names: bool,
+}
+
+impl Anonymizer {
+    /// Strips `fields`, each one of [`STRIPPABLE_FIELDS`], and derives
+    /// pseudonyms under `key`.
+    pub fn new(fields: &[&str], key: impl Into<Vec<u8>>) -> Result<Self, String> {
+        let stripped = fields
+            .iter()
+            .map(|field| {
+                STRIPPABLE_FIELDS.iter().find(|&&known| known == field.trim()).copied().ok_or_else(|| {
+                    format!(
+                        "'{}' cannot be stripped from exports; expected one of {}",
+                        field.trim(),
+                        STRIPPABLE_FIELDS.join(", ")
+                    )
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        
+        Ok(Self { stripped, key: key.into(), stable: true, pseudonymize_names: false })
+    }
+    
+    /// Reads the fields to strip from `ANONYMIZE_FIELDS`, a comma-separated
+    /// list that may be empty, falling back to [`DEFAULT_STRIPPED_FIELDS`].
+    /// Pseudonyms are keyed by `ANONYMIZE_SECRET`; without it a random key is
+    /// used, so they change whenever the service restarts.
+    pub fn from_env() -> Result<Self, String> {
+        let fields: Vec<String> = match std::env::var("ANONYMIZE_FIELDS") {
+            Ok(value) => value
+                .split(',')
+                .map(str::trim)
+                .filter(|field| !field.is_empty())
+                .map(str::to_string)
+                .collect(),
+            Err(_) => DEFAULT_STRIPPED_FIELDS.iter().map(|field| field.to_string()).collect(),
+        };
+        let fields: Vec<&str> = fields.iter().map(String::as_str).collect();
+        
+        match std::env::var("ANONYMIZE_SECRET") {
+            Ok(secret) if !secret.trim().is_empty() => Self::new(&fields, secret),
+            _ => {
+                let key = [Uuid::new_v4().into_bytes(), Uuid::new_v4().into_bytes()].concat();
+                let mut anonymizer = Self::new(&fields, key)?;
+                anonymizer.stable = false;
+                Ok(anonymizer)
+            }
+        }
+    }
+    
+    /// Replaces names with their pseudonym, and drops the Arabic name so it
+    /// cannot give them away.
+    pub fn with_pseudonymized_names(mut self, pseudonymize_names: bool) -> Self {
+        self.pseudonymize_names = pseudonymize_names;
+        self
+    }
+    
+    /// Whether pseudonyms stay the same across restarts.
+    pub fn pseudonyms_are_stable(&self) -> bool {
+        self.stable
+    }
+    
+    pub fn stripped_fields(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.stripped.iter().copied()
+    }
+    
+    /// The token standing in for the name of `record_id`: `anon-` and the
+    /// start of its HMAC-SHA256 in hex.
+    pub fn pseudonym(&self, record_id: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts keys of any length");
+        mac.update(record_id.as_bytes());
+        
+        let digest: String = mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect();
+        format!("{}{}", PSEUDONYM_PREFIX, &digest[..PSEUDONYM_HEX_DIGITS])
+    }
+    
+    fn drops(&self, field: &str) -> bool {
+        self.stripped.contains(field) || (self.pseudonymize_names && field == "deceased_name_arabic")
+    }
+    
+    /// Header of an anonymized CSV export: [`CSV_EXPORT_COLUMNS`] less the
+    /// stripped fields.
+    pub fn csv_columns(&self) -> Vec<&'static str> {
+        CSV_EXPORT_COLUMNS.iter().copied().filter(|column| !self.drops(column)).collect()
+    }
+    
+    /// The values of `record` under [`Anonymizer::csv_columns`].
+    pub fn csv_row(&self, record: &ExportRecord) -> Result<Vec<String>, serde_json::Error> {
+        let values = serde_json::to_value(record)?;
+        
+        Ok(self
+            .csv_columns()
+            .into_iter()
+            .map(|column| {
+                if column == "deceased_name" && self.pseudonymize_names {
+                    return self.pseudonym(&record.record_id);
+                }
+                match &values[column] {
+                    serde_json::Value::Null => String::new(),
+                    serde_json::Value::String(value) => value.clone(),
+                    value => value.to_string(),
+                }
+            })
+            .collect())
+    }
+    
+    /// Drops the stripped fields from a feature's properties, where the name
+    /// is kept as `name`, and pseudonymizes the name if asked to.
+    pub fn anonymize_feature(&self, feature: &mut GeoJsonFeature) {
+        let Some(properties) = feature.properties.as_object_mut() else { return };
+        
+        let pseudonym = properties
+            .get("record_id")
+            .and_then(serde_json::Value::as_str)
+            .map(|record_id| self.pseudonym(record_id));
+        
+        for field in STRIPPABLE_FIELDS.iter().filter(|field| self.drops(field)) {
+            properties.remove(if *field == "deceased_name" { "name" } else { field });
+        }
+        
+        if self.pseudonymize_names && !self.drops("deceased_name") && properties.contains_key("name") {
+            properties.insert("name".to_string(), pseudonym.into());
+        }
+    }
+}
@@ -0,0 +1,76 @@
+/// Harakat, shadda, sukun, superscript alef and tatweel. These are optional
+/// in written Arabic, so they are dropped before names are compared.
+const IGNORED_MARKS: &[char] = &[
+    '\u{064B}', '\u{064C}', '\u{064D}', '\u{064E}', '\u{064F}',
+    '\u{0650}', '\u{0651}', '\u{0652}', '\u{0670}', '\u{0640}',
+];
+
+/// Hamza and madda forms of alef (أ إ آ ٱ), all compared as a bare alef.
+const ALEF_VARIANTS: &[char] = &['\u{0622}', '\u{0623}', '\u{0625}', '\u{0671}'];
+
+const ALEF: char = '\u{0627}';
+
+/// Normalizes Arabic text for matching so that e.g. "مُحَمَّد" and "محمد"
+/// compare equal. Non-Arabic text passes through unchanged.
+///
+/// `Database::search_records` applies the same rules in SQL via `translate`,
+/// so the two must be kept in step.
+pub fn normalize_arabic(text: &str) -> String {
+    text.chars()
+        .filter(|c| !IGNORED_MARKS.contains(c))
+        .map(|c| if ALEF_VARIANTS.contains(&c) { ALEF } else { c })
+        .collect()
+}
+
+/// Replaces Arabic-Indic (٠١٢…) and Eastern Arabic-Indic (۰۱۲…) digits
+/// with their ASCII counterparts, leaving everything else as it is.
+pub fn normalize_digits(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '\u{0660}'..='\u{0669}' => char::from(b'0' + (c as u32 - 0x0660) as u8),
+            '\u{06F0}'..='\u{06F9}' => char::from(b'0' + (c as u32 - 0x06F0) as u8),
+            c => c,
+        })
+        .collect()
+}
+
+/// Words joining the generations of a lineage name: "son of" (بن, ابن) and
+/// "daughter of" (بنت), compared after [`normalize_arabic`].
+const LINEAGE_CONNECTORS: &[&str] = &["\u{0628}\u{0646}", "\u{0627}\u{0628}\u{0646}", "\u{0628}\u{0646}\u{062A}"];
+
+/// The generations named by a lineage name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameLineage {
+    pub given_name: String,
+    pub father_name: String,
+    pub grandfather_name: Option<String>,
+}
+
+/// Splits a lineage name such as "محمد بن علي بن حسين" into the given name
+/// and the names of the father and grandfather; further ancestors are
+/// ignored. Each part may be several words ("عبد الله بن ..."). Returns `None`
+/// for names that do not follow the convention, including ones that start or
+/// end with a connector, such as the family name "ابن سينا".
+pub fn split_lineage(full_name: &str) -> Option<NameLineage> {
+    let mut parts: Vec<Vec<&str>> = vec![Vec::new()];
+    
+    for word in full_name.split_whitespace() {
+        if LINEAGE_CONNECTORS.contains(&normalize_arabic(word).as_str()) {
+            parts.push(Vec::new());
+        } else {
+            parts.last_mut().expect("parts is never empty").push(word);
+        }
+    }
+    
+    if parts.len() < 2 || parts.iter().any(|words| words.is_empty()) {
+        return None;
+    }
+    
+    let mut names = parts.into_iter().map(|words| words.join(" "));
+    
+    Some(NameLineage {
+        given_name: names.next()?,
+        father_name: names.next()?,
+        grandfather_name: names.next(),
+    })
+}
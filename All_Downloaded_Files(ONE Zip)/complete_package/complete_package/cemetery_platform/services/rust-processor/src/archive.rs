@@ -0,0 +1,111 @@
+use flate2::read::MultiGzDecoder;
+use log::{info, warn};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use zip::ZipArchive;
+
+/// Default cap on the bytes one archive may expand to (2 GiB).
+pub const DEFAULT_MAX_UNCOMPRESSED_SIZE: u64 = 2 * 1024 * 1024 * 1024;
+
+/// Reads the archive size cap from `ZIP_MAX_UNCOMPRESSED_MB`, falling back
+/// to [`DEFAULT_MAX_UNCOMPRESSED_SIZE`].
+pub fn max_uncompressed_size_from_env() -> Result<u64, String> {
+    match std::env::var("ZIP_MAX_UNCOMPRESSED_MB") {
+        Ok(value) => value
+            .trim()
+            .parse::<u64>()
+            .ok()
+            .filter(|&mb| mb > 0)
+            .map(|mb| mb * 1024 * 1024)
+            .ok_or_else(|| format!("ZIP_MAX_UNCOMPRESSED_MB must be a positive number, got '{}'", value)),
+        Err(_) => Ok(DEFAULT_MAX_UNCOMPRESSED_SIZE),
+    }
+}
+
+/// Whether `path` is gzip-compressed, going by a `.gz` extension.
+pub fn is_gzipped(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("gz"))
+}
+
+/// Opens `path` for reading, decompressing it as it is read when
+/// [`is_gzipped`]. Files of several concatenated gzip members, as written
+/// by parallel compressors, are read to the end.
+pub fn open_decompressed(path: &Path) -> std::io::Result<Box<dyn Read + Send>> {
+    let file = File::open(path)?;
+    
+    if is_gzipped(path) {
+        Ok(Box::new(MultiGzDecoder::new(BufReader::new(file))))
+    } else {
+        Ok(Box::new(file))
+    }
+}
+
+/// What [`extract_zip`] wrote.
+#[derive(Debug, Default)]
+pub struct ExtractedArchive {
+    pub files: usize,
+    pub bytes: u64,
+    /// Entries left out: archives nested inside the archive
+    pub skipped: Vec<String>,
+}
+
+/// Extracts `archive` into `destination`, refusing entries whose path would
+/// land outside it (zip-slip) and stopping once more than `max_size` bytes
+/// have been written (zip bombs). Sizes are counted as the data is
+/// decompressed, so an entry that understates its size is still caught.
+/// Nested archives are skipped rather than expanded.
+pub fn extract_zip(
+    archive: &Path,
+    destination: &Path,
+    max_size: u64,
+) -> Result<ExtractedArchive, anyhow::Error> {
+    info!("Extracting {:?} to {:?}", archive, destination);
+    
+    let mut zip = ZipArchive::new(File::open(archive)?)?;
+    let mut extracted = ExtractedArchive::default();
+    
+    for index in 0..zip.len() {
+        let mut entry = zip.by_index(index)?;
+        
+        let relative = entry.enclosed_name().ok_or_else(|| {
+            anyhow::anyhow!("Archive entry {:?} has an unsafe path", entry.name())
+        })?;
+        let target = destination.join(&relative);
+        
+        if entry.is_dir() {
+            std::fs::create_dir_all(&target)?;
+            continue;
+        }
+        
+        let is_archive = relative.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("zip"));
+        if is_archive {
+            warn!("Skipping nested archive {:?}", relative);
+            extracted.skipped.push(relative.to_string_lossy().into_owned());
+            continue;
+        }
+        
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        
+        // Read one byte past the budget to tell "exactly full" from "over"
+        let budget = max_size - extracted.bytes;
+        let mut output = File::create(&target)?;
+        let written = std::io::copy(&mut (&mut entry).take(budget + 1), &mut output)?;
+        
+        if written > budget {
+            return Err(anyhow::anyhow!(
+                "Archive {:?} expands to more than {} bytes",
+                archive.file_name().unwrap_or_default(),
+                max_size
+            ));
+        }
+        
+        extracted.bytes += written;
+        extracted.files += 1;
+    }
+    
+    info!("Extracted {} files ({} bytes)", extracted.files, extracted.bytes);
+    Ok(extracted)
+}
@@ -0,0 +1,81 @@
+use sha2::{Digest, Sha256};
+
+/// Shortest API key accepted, so keys cannot be guessed.
+pub const MIN_API_KEY_LENGTH: usize = 16;
+
+/// The API keys that may call the service. Several can be configured at
+/// once, so a key can be rotated by adding the new one, moving clients over
+/// and then removing the old one.
+pub struct ApiKeys {
+    /// SHA-256 of each key, so comparisons take the same time whatever the
+    /// presented key has in common with a real one
+    digests: Vec<[u8; 32]>,
+}
+
+impl ApiKeys {
+    pub fn new<I, S>(keys: I) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut digests = Vec::new();
+        
+        for (index, key) in keys.into_iter().enumerate() {
+            let key = key.as_ref();
+            if key.chars().count() < MIN_API_KEY_LENGTH {
+                return Err(format!(
+                    "API key {} is shorter than {} characters",
+                    index + 1,
+                    MIN_API_KEY_LENGTH
+                ));
+            }
+            digests.push(Sha256::digest(key.as_bytes()).into());
+        }
+        
+        if digests.is_empty() {
+            return Err("At least one API key is required".to_string());
+        }
+        
+        Ok(Self { digests })
+    }
+    
+    /// Reads comma-separated keys from `API_KEYS`. Unset or blank means
+    /// authentication is off.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        match std::env::var("API_KEYS") {
+            Ok(value) if !value.trim().is_empty() => {
+                let keys = value.split(',').map(str::trim).filter(|key| !key.is_empty());
+                Self::new(keys).map(Some).map_err(|e| format!("API_KEYS: {}", e))
+            }
+            _ => Ok(None),
+        }
+    }
+    
+    pub fn len(&self) -> usize {
+        self.digests.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.digests.is_empty()
+    }
+    
+    /// Whether an `Authorization` header value, `Bearer <key>`, carries one
+    /// of the keys.
+    pub fn authorizes(&self, authorization: Option<&str>) -> bool {
+        let Some(key) = authorization.and_then(bearer_token) else {
+            return false;
+        };
+        let presented: [u8; 32] = Sha256::digest(key.as_bytes()).into();
+        
+        // Every key is compared in full, without returning early
+        self.digests.iter().fold(false, |found, digest| {
+            let difference = digest.iter().zip(&presented).fold(0u8, |acc, (a, b)| acc | (a ^ b));
+            found | (difference == 0)
+        })
+    }
+}
+
+fn bearer_token(authorization: &str) -> Option<&str> {
+    let (scheme, token) = authorization.trim().split_once(' ')?;
+    scheme.eq_ignore_ascii_case("bearer").then(|| token.trim()).filter(|token| !token.is_empty())
+}
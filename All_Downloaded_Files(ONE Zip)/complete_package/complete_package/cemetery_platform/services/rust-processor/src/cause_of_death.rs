@@ -0,0 +1,154 @@
+use crate::arabic::normalize_arabic;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// One controlled cause of death and the ways registers write it.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct CauseEntry {
+    /// Stored in `cause_of_death_code`, e.g. the ICD-10 category `I21`
+    pub code: String,
+    pub label: String,
+    /// Spellings in English or Arabic; case, harakat, hamza on alef and
+    /// punctuation are ignored when matching
+    #[serde(default)]
+    pub synonyms: Vec<String>,
+}
+
+/// The built-in vocabulary: common causes with their ICD-10 category.
+const BUILTIN_CAUSES: &[(&str, &str, &[&str])] = &[
+    ("I21", "Heart attack", &[
+        "heart attack", "myocardial infarction", "acute myocardial infarction", "mi",
+        "نوبة قلبية", "جلطة قلبية", "جلطة في القلب", "احتشاء عضلة القلب",
+    ]),
+    ("I46", "Cardiac arrest", &["cardiac arrest", "heart stopped", "سكتة قلبية", "توقف القلب"]),
+    ("I50", "Heart failure", &["heart failure", "congestive heart failure", "عجز القلب", "فشل القلب", "قصور القلب"]),
+    ("I64", "Stroke", &[
+        "stroke", "cerebrovascular accident", "cva", "brain stroke",
+        "سكتة دماغية", "جلطة دماغية", "جلطة في الدماغ",
+    ]),
+    ("I10", "High blood pressure", &["hypertension", "high blood pressure", "ارتفاع ضغط الدم", "ضغط الدم", "الضغط"]),
+    ("C80", "Cancer", &["cancer", "malignancy", "tumor", "tumour", "malignant tumor", "سرطان", "مرض السرطان", "ورم خبيث", "ورم"]),
+    ("E14", "Diabetes", &["diabetes", "diabetes mellitus", "سكري", "السكري", "داء السكري", "مرض السكري", "السكر"]),
+    ("J18", "Pneumonia", &["pneumonia", "chest infection", "ذات الرئة", "التهاب رئوي", "التهاب الرئة"]),
+    ("U07.1", "COVID-19", &["covid", "covid 19", "covid19", "coronavirus", "corona", "كورونا", "كوفيد", "كوفيد 19", "فايروس كورونا"]),
+    ("N19", "Kidney failure", &["kidney failure", "renal failure", "فشل كلوي", "الفشل الكلوي", "عجز كلوي"]),
+    ("K72", "Liver failure", &["liver failure", "hepatic failure", "فشل كبدي", "تليف الكبد"]),
+    ("R54", "Old age", &["old age", "natural causes", "senility", "كبر السن", "الشيخوخة", "وفاة طبيعية", "طبيعية"]),
+    ("V89", "Traffic accident", &["traffic accident", "car accident", "road accident", "accident", "حادث سير", "حادث مروري", "حادث"]),
+    ("X95", "Gunshot", &["gunshot", "gunshot wound", "shooting", "طلق ناري", "اطلاق نار"]),
+    ("X96", "Explosion", &["explosion", "bombing", "blast", "انفجار", "تفجير", "عبوة ناسفة"]),
+    ("W74", "Drowning", &["drowning", "drowned", "غرق"]),
+    ("X00", "Fire", &["fire", "burns", "حريق", "حروق"]),
+    ("R99", "Unknown", &["unknown", "unspecified", "not known", "غير معروف", "مجهول", "غير محدد"]),
+];
+
+/// Maps free-text causes of death to a controlled set of codes, so they can
+/// be counted. Each cause matches on its code, its label and its synonyms.
+#[derive(Debug, Clone)]
+pub struct CauseVocabulary {
+    causes: Vec<CauseEntry>,
+    /// Match key to the index of its cause
+    lookup: HashMap<String, usize>,
+}
+
+impl CauseVocabulary {
+    /// Fails if a code is repeated or a spelling is given for two causes.
+    pub fn new(causes: Vec<CauseEntry>) -> Result<Self, String> {
+        let mut lookup: HashMap<String, usize> = HashMap::new();
+        
+        for (index, cause) in causes.iter().enumerate() {
+            if cause.code.trim().is_empty() {
+                return Err("every cause needs a code".to_string());
+            }
+            if causes[..index].iter().any(|earlier| earlier.code == cause.code) {
+                return Err(format!("code '{}' is listed twice", cause.code));
+            }
+            
+            let spellings = std::iter::once(&cause.code).chain(std::iter::once(&cause.label)).chain(&cause.synonyms);
+            for spelling in spellings {
+                let key = match_key(spelling);
+                if key.is_empty() {
+                    continue;
+                }
+                if let Some(&other) = lookup.get(&key) {
+                    if other != index {
+                        return Err(format!(
+                            "'{}' is given for both {} and {}",
+                            spelling, causes[other].code, cause.code
+                        ));
+                    }
+                }
+                lookup.insert(key, index);
+            }
+        }
+        
+        Ok(Self { causes, lookup })
+    }
+    
+    pub fn builtin() -> Self {
+        let causes = BUILTIN_CAUSES
+            .iter()
+            .map(|(code, label, synonyms)| CauseEntry {
+                code: code.to_string(),
+                label: label.to_string(),
+                synonyms: synonyms.iter().map(|synonym| synonym.to_string()).collect(),
+            })
+            .collect();
+        
+        Self::new(causes).expect("the built-in vocabulary is consistent")
+    }
+    
+    /// Reads a JSON array of [`CauseEntry`]s.
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+        let causes = serde_json::from_str(&text).map_err(|e| format!("Invalid vocabulary {:?}: {}", path, e))?;
+        Self::new(causes).map_err(|e| format!("Invalid vocabulary {:?}: {}", path, e))
+    }
+    
+    /// Reads `CAUSE_OF_DEATH_VOCABULARY`: unset or `off` leaves causes
+    /// alone, `builtin` uses [`CauseVocabulary::builtin`], and anything else
+    /// is the path of a JSON vocabulary file.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        match std::env::var("CAUSE_OF_DEATH_VOCABULARY") {
+            Ok(value) => match value.trim() {
+                "" | "off" => Ok(None),
+                "builtin" => Ok(Some(Self::builtin())),
+                path => Self::from_file(Path::new(path))
+                    .map(Some)
+                    .map_err(|e| format!("CAUSE_OF_DEATH_VOCABULARY: {}", e)),
+            },
+            Err(_) => Ok(None),
+        }
+    }
+    
+    pub fn causes(&self) -> &[CauseEntry] {
+        &self.causes
+    }
+    
+    /// The cause `text` is a spelling of, if any.
+    pub fn normalize(&self, text: &str) -> Option<&CauseEntry> {
+        self.lookup.get(&match_key(text)).map(|&index| &self.causes[index])
+    }
+    
+    pub fn label(&self, code: &str) -> Option<&str> {
+        self.causes.iter().find(|cause| cause.code == code).map(|cause| cause.label.as_str())
+    }
+}
+
+/// What causes are compared on: lowercase, Arabic normalized as for names
+/// with final ى and ة folded into ي and ه, and punctuation turned into
+/// single spaces.
+fn match_key(text: &str) -> String {
+    let folded: String = normalize_arabic(&text.to_lowercase())
+        .chars()
+        .map(|c| match c {
+            '\u{0649}' => '\u{064A}',
+            '\u{0629}' => '\u{0647}',
+            c if c.is_alphanumeric() => c,
+            _ => ' ',
+        })
+        .collect();
+    
+    folded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
@@ -0,0 +1,174 @@
+use crate::anonymize::Anonymizer;
+use crate::archive::max_uncompressed_size_from_env;
+use crate::auth::ApiKeys;
+use crate::cause_of_death::CauseVocabulary;
+use crate::database::{batch_size_from_env, PoolSettings, RetryPolicy};
+use crate::migrations::run_migrations_from_env;
+use crate::models::{CemeteryBounds, DateLimits};
+use crate::national_id::NationalIdRules;
+use crate::processor::{
+    checkpoint_every_from_env, data_base_dir_from_env, derive_coordinates_from_env, keep_rejected_from_env,
+    max_errors_from_env, parallel_files_from_env, ErrorLimit,
+};
+use crate::rate_limit::RateLimiter;
+use crate::response_cache::ResponseCache;
+use crate::timestamps::timezone_from_env;
+use crate::upload::UploadSettings;
+use crate::webhook::WebhookNotifier;
+use chrono_tz::Tz;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Address the server listens on when `SERVER_HOST` is not set.
+pub const DEFAULT_SERVER_HOST: &str = "0.0.0.0";
+
+/// Port the server listens on when `SERVER_PORT` is not set.
+pub const DEFAULT_SERVER_PORT: u16 = 8080;
+
+/// Failure ratio a completed job may reach and still be reported with 200
+/// when `PARTIAL_FAILURE_THRESHOLD` is not set: any failure gives 207.
+pub const DEFAULT_PARTIAL_FAILURE_THRESHOLD: f64 = 0.0;
+
+/// Hours an `Idempotency-Key` is remembered for when `IDEMPOTENCY_KEY_TTL_HOURS`
+/// is not set.
+pub const DEFAULT_IDEMPOTENCY_KEY_TTL_HOURS: i64 = 24;
+
+/// Every setting the service takes from the environment, read and checked
+/// once at startup. The README's Setup section lists the variables.
+pub struct Config {
+    /// `DATABASE_URL`, the only variable without a default
+    pub database_url: String,
+    pub server_host: String,
+    pub server_port: u16,
+    pub pool: PoolSettings,
+    /// Apply pending schema migrations before serving
+    pub run_migrations: bool,
+    /// Retries of transient database errors during inserts
+    pub retry: RetryPolicy,
+    /// Records per INSERT statement
+    pub batch_size: usize,
+    /// Canonical directory that `data_path` must lie in
+    pub data_base_dir: PathBuf,
+    /// Where `/api/upload` stages files, and its size limit
+    pub upload: UploadSettings,
+    /// Bytes a ZIP archive may expand to
+    pub max_archive_size: u64,
+    /// Files of a directory parsed at the same time
+    pub parallel_files: usize,
+    /// Records a run reads between checkpoints; `None` commits runs whole
+    pub checkpoint_every: Option<usize>,
+    /// Failed records that abort a run; `None` tries every record
+    pub max_errors: Option<ErrorLimit>,
+    /// Place records without coordinates at their plot or section
+    pub derive_coordinates: bool,
+    /// Keep the records runs reject in `rejected_records`
+    pub keep_rejected: bool,
+    /// Coordinates outside these are rejected; `None` checks only the
+    /// global ranges
+    pub cemetery_bounds: Option<CemeteryBounds>,
+    pub date_limits: DateLimits,
+    /// Zone of request times given without an offset
+    pub timezone: Tz,
+    pub national_ids: NationalIdRules,
+    /// Vocabulary causes of death are coded by; `None` stores them as written
+    pub causes: Option<CauseVocabulary>,
+    /// Failure ratio above which a completed job is reported with 207
+    pub partial_failure_threshold: f64,
+    /// How long a retry with the same `Idempotency-Key` gets the first job back
+    pub idempotency_key_ttl: chrono::Duration,
+    /// Keys callers must present; `None` leaves the API open
+    pub api_keys: Option<Arc<ApiKeys>>,
+    /// Request budget per client; `None` when limiting is off
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    /// Responses of `/api/stats` and `/api/features`; `None` when caching
+    /// is off
+    pub response_cache: Option<Arc<ResponseCache>>,
+    /// Signs and sends completion callbacks; `None` rejects `callback_url`
+    pub webhooks: Option<WebhookNotifier>,
+    /// Fields left out of exports asked for with `anonymize`
+    pub anonymizer: Arc<Anonymizer>,
+}
+
+impl Config {
+    /// Reads every setting, failing with the first variable that is missing
+    /// or invalid. The data and upload directories must be usable.
+    pub fn from_env() -> Result<Self, String> {
+        let database_url = std::env::var("DATABASE_URL")
+            .ok()
+            .filter(|url| !url.trim().is_empty())
+            .ok_or_else(|| "DATABASE_URL must be set".to_string())?;
+        
+        let server_host = std::env::var("SERVER_HOST").unwrap_or_else(|_| DEFAULT_SERVER_HOST.to_string());
+        let server_port = match std::env::var("SERVER_PORT") {
+            Ok(value) => value
+                .trim()
+                .parse::<u16>()
+                .map_err(|_| format!("SERVER_PORT must be a valid port number, got '{}'", value))?,
+            Err(_) => DEFAULT_SERVER_PORT,
+        };
+        
+        let cemetery_bounds = CemeteryBounds::from_env()
+            .map_err(|e| format!("CEMETERY_BOUNDS must be min_lon,min_lat,max_lon,max_lat: {}", e))?;
+        
+        Ok(Self {
+            database_url,
+            server_host,
+            server_port,
+            pool: PoolSettings::from_env()?,
+            run_migrations: run_migrations_from_env()?,
+            retry: RetryPolicy::from_env()?,
+            batch_size: batch_size_from_env()?,
+            data_base_dir: data_base_dir_from_env()?,
+            upload: UploadSettings::from_env()?,
+            max_archive_size: max_uncompressed_size_from_env()?,
+            parallel_files: parallel_files_from_env()?,
+            checkpoint_every: checkpoint_every_from_env()?,
+            max_errors: max_errors_from_env()?,
+            derive_coordinates: derive_coordinates_from_env()?,
+            keep_rejected: keep_rejected_from_env()?,
+            cemetery_bounds,
+            date_limits: DateLimits::from_env()?,
+            timezone: timezone_from_env()?,
+            national_ids: NationalIdRules::from_env()?,
+            causes: CauseVocabulary::from_env()?,
+            partial_failure_threshold: partial_failure_threshold_from_env()?,
+            idempotency_key_ttl: idempotency_key_ttl_from_env()?,
+            api_keys: ApiKeys::from_env()?.map(Arc::new),
+            rate_limiter: RateLimiter::from_env()?.map(Arc::new),
+            response_cache: ResponseCache::from_env()?.map(Arc::new),
+            webhooks: WebhookNotifier::from_env()?,
+            anonymizer: Arc::new(Anonymizer::from_env().map_err(|e| format!("ANONYMIZE_FIELDS: {}", e))?),
+        })
+    }
+}
+
+/// Reads `PARTIAL_FAILURE_THRESHOLD`, a failure ratio from 0 to 1, falling
+/// back to [`DEFAULT_PARTIAL_FAILURE_THRESHOLD`].
+pub fn partial_failure_threshold_from_env() -> Result<f64, String> {
+    match std::env::var("PARTIAL_FAILURE_THRESHOLD") {
+        Ok(value) => value
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|threshold| (0.0..=1.0).contains(threshold))
+            .ok_or_else(|| format!("PARTIAL_FAILURE_THRESHOLD must be between 0 and 1, got '{}'", value)),
+        Err(_) => Ok(DEFAULT_PARTIAL_FAILURE_THRESHOLD),
+    }
+}
+
+/// Reads how many hours an `Idempotency-Key` is kept from
+/// `IDEMPOTENCY_KEY_TTL_HOURS`, falling back to
+/// [`DEFAULT_IDEMPOTENCY_KEY_TTL_HOURS`].
+pub fn idempotency_key_ttl_from_env() -> Result<chrono::Duration, String> {
+    let hours = match std::env::var("IDEMPOTENCY_KEY_TTL_HOURS") {
+        Ok(value) => value
+            .trim()
+            .parse::<i64>()
+            .ok()
+            .filter(|hours| (1..=24 * 365).contains(hours))
+            .ok_or_else(|| format!("IDEMPOTENCY_KEY_TTL_HOURS must be a number of hours from 1 to 8760, got '{}'", value))?,
+        Err(_) => DEFAULT_IDEMPOTENCY_KEY_TTL_HOURS,
+    };
+    
+    Ok(chrono::Duration::hours(hours))
+}
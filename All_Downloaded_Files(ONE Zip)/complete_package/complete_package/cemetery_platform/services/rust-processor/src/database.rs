@@ -1,162 +1,2401 @@
-use crate::models::DeceasedRecord;
-use sqlx::{PgPool, Postgres, QueryBuilder};
-use log::{info, error};
+use crate::family::{FamilyLink, Person};
+use crate::footprint::footprint_wkt;
+use crate::models::{
+    name_and_death_date, CauseCount, CemeteryStats, ConflictKey, DbDeceasedRecord, DeceasedRecord, DeceasedRecordDetail, ErrorDetails,
+    ExportRecord, FamilyRelations, GeoJsonFeature, GeoJsonGeometry, GeoJsonShapeFeature, KeyedJob, MergeOutcome, NearbyRecord,
+    ProcessingJob, ProcessingLogEntry, ProcessingTotals, RecordChange, RecordCursor, RecordFilter, RecordHistory, RecordMerge, RecordStatus, RejectedRecord,
+    Rejection, RelatedRecord,
+    SearchResult, SectionCount,
+    SectionOutline,
+    SharedNationalId, UncodedCauseCount, YearCount,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use futures_util::stream::BoxStream;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgPoolOptions;
+use sqlx::types::Json;
+use sqlx::{Connection, PgPool, Postgres, QueryBuilder, Transaction};
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use utoipa::ToSchema;
+use uuid::Uuid;
+use log::{info, warn, error};
 
+/// Records per multi-row INSERT when `BATCH_SIZE` is not set. Each row
+/// binds 24 parameters, which keeps a full chunk well under Postgres's limit
+/// of 65535 per statement.
+pub const INSERT_CHUNK_SIZE: usize = 1000;
+
+/// Most records one INSERT can take: Postgres allows 65535 bind parameters
+/// per statement and each record binds one per column.
+pub const MAX_BATCH_SIZE: usize = 65535 / (UPSERT_COLUMNS.len() + 1);
+
+/// Checks a batch size against [`MAX_BATCH_SIZE`].
+pub fn check_batch_size(size: usize) -> Result<usize, String> {
+    if (1..=MAX_BATCH_SIZE).contains(&size) {
+        Ok(size)
+    } else {
+        Err(format!("batch_size must be between 1 and {}, got {}", MAX_BATCH_SIZE, size))
+    }
+}
+
+/// Reads the records per INSERT from `BATCH_SIZE`, falling back to
+/// [`INSERT_CHUNK_SIZE`].
+pub fn batch_size_from_env() -> Result<usize, String> {
+    match env_number("BATCH_SIZE")? {
+        Some(size) => check_batch_size(size).map_err(|e| format!("BATCH_SIZE: {}", e)),
+        None => Ok(INSERT_CHUNK_SIZE),
+    }
+}
+
+/// Columns written by an upsert besides `record_id`, in bind order.
+const UPSERT_COLUMNS: [&str; 23] = [
+    "deceased_name", "deceased_name_arabic", "father_name", "grandfather_name",
+    "death_date", "death_location", "burial_date", "burial_location",
+    "section", "row_number", "plot_number", "grave_number",
+    "coordinates", "footprint",
+    "birth_date", "age_at_death", "cause_of_death", "cause_of_death_code", "national_id", "family_contact",
+    "additional_data", "source_file", "processing_status",
+];
+
+/// Columns a delivery repeating a stored record refreshes.
+const REFRESHED_COLUMNS: [&str; 6] = [
+    "deceased_name", "burial_date", "coordinates", "footprint", "source_file", "processing_status",
+];
+
+/// What an upsert does to a row whose `record_id` already exists.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictAction {
+    /// Refresh the `REFRESHED_COLUMNS`; the normal behaviour when a
+    /// delivery repeats a record from an earlier one
+    #[default]
+    Update,
+    /// Leave the existing row alone
+    Ignore,
+    /// Overwrite every column
+    Replace,
+    /// Overwrite columns the new record has a value for, keeping the rest
+    Fill,
+}
+
+impl ConflictAction {
+    fn clause(self, key: ConflictKey) -> String {
+        let target = key.columns().join(", ");
+        let assignments: Vec<String> = match self {
+            ConflictAction::Ignore => return format!(" ON CONFLICT ({}) DO NOTHING", target),
+            ConflictAction::Update => REFRESHED_COLUMNS
+                .iter()
+                .map(|column| format!("{0} = EXCLUDED.{0}", column))
+                .collect(),
+            ConflictAction::Replace => UPSERT_COLUMNS
+                .iter()
+                .map(|column| format!("{0} = EXCLUDED.{0}", column))
+                .collect(),
+            ConflictAction::Fill => UPSERT_COLUMNS
+                .iter()
+                .map(|&column| match column {
+                    // jsonb `||` merges the two objects, the new keys winning
+                    "additional_data" => "additional_data = COALESCE(deceased_records.additional_data || EXCLUDED.additional_data, EXCLUDED.additional_data, deceased_records.additional_data)".to_string(),
+                    "death_date" | "burial_date" | "row_number" | "plot_number" | "coordinates" | "footprint"
+                    | "birth_date" | "age_at_death" => {
+                        format!("{0} = COALESCE(EXCLUDED.{0}, deceased_records.{0})", column)
+                    }
+                    // Blank CSV cells arrive as empty strings rather than NULL
+                    _ => format!("{0} = COALESCE(NULLIF(EXCLUDED.{0}, ''), deceased_records.{0})", column),
+                })
+                .collect(),
+        };
+        
+        format!(
+            " ON CONFLICT ({}) DO UPDATE SET {}, updated_at = CURRENT_TIMESTAMP",
+            target,
+            assignments.join(", ")
+        )
+    }
+}
+
+/// How an import treats records whose `record_id` is already stored.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    /// Insert new records and update existing ones
+    #[default]
+    Upsert,
+    /// Insert new records only, leaving existing ones untouched
+    InsertOnly,
+    /// Update existing records only, skipping new ones
+    UpdateOnly,
+}
+
+impl ImportMode {
+    /// Whether a record is written, given whether its id is already stored.
+    pub fn writes(self, exists: bool) -> bool {
+        match self {
+            ImportMode::Upsert => true,
+            ImportMode::InsertOnly => !exists,
+            ImportMode::UpdateOnly => exists,
+        }
+    }
+    
+    /// How the records that are written handle a conflict on `record_id`.
+    pub fn conflict_action(self) -> ConflictAction {
+        match self {
+            // Covers a record inserted by someone else since the check
+            ImportMode::InsertOnly => ConflictAction::Ignore,
+            ImportMode::Upsert | ImportMode::UpdateOnly => ConflictAction::Update,
+        }
+    }
+}
+
+/// Outcome of a batch insert: rows written plus any records the database
+/// rejected.
+#[derive(Debug, Default)]
+pub struct BatchInsertResult {
+    pub inserted: usize,
+    pub errors: Vec<ErrorDetails>,
+}
+
+/// SQLSTATEs for lock conflicts (serialization failure, deadlock, lock not
+/// available). The statement can be retried once rolled back to a savepoint.
+const CONFLICT_SQLSTATES: [&str; 3] = ["40001", "40P01", "55P03"];
+
+/// Most distinct uncoded causes `/api/stats` lists for review.
+const MAX_UNCODED_CAUSES: i64 = 50;
+
+/// How often, and how patiently, transient database errors are retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total attempts, including the first one.
+    pub max_attempts: u32,
+    /// Delay before the first retry; it doubles for each retry after that.
+    pub initial_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(100),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Reads `DB_RETRY_MAX_ATTEMPTS` and `DB_RETRY_BACKOFF_MS`, falling back
+    /// to the defaults for unset variables.
+    pub fn from_env() -> Result<Self, String> {
+        let mut policy = Self::default();
+        
+        if let Some(attempts) = env_number("DB_RETRY_MAX_ATTEMPTS")? {
+            if attempts == 0 {
+                return Err("DB_RETRY_MAX_ATTEMPTS must be at least 1".to_string());
+            }
+            policy.max_attempts = attempts;
+        }
+        if let Some(millis) = env_number("DB_RETRY_BACKOFF_MS")? {
+            policy.initial_backoff = Duration::from_millis(millis);
+        }
+        
+        Ok(policy)
+    }
+    
+    /// Delay before retrying after failed attempt number `attempt` (1-based).
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+    }
+}
+
+/// What a rebuild or update of the map layer did.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FeatureCounts {
+    /// Features inserted or replaced
+    pub written: i32,
+    /// Records left off the map because their geometry is invalid or empty
+    pub skipped_invalid: i32,
+}
+
+/// Connection pool sizing and timeouts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolSettings {
+    pub max_connections: u32,
+    pub min_connections: u32,
+    /// How long a query waits for a free connection before failing.
+    pub acquire_timeout: Duration,
+    /// How long an unused connection is kept open; `None` keeps it forever.
+    pub idle_timeout: Option<Duration>,
+}
+
+impl Default for PoolSettings {
+    fn default() -> Self {
+        Self {
+            max_connections: 10,
+            min_connections: 1,
+            acquire_timeout: Duration::from_secs(30),
+            idle_timeout: Some(Duration::from_secs(600)),
+        }
+    }
+}
+
+impl PoolSettings {
+    /// Reads `DB_MAX_CONNECTIONS`, `DB_MIN_CONNECTIONS`,
+    /// `DB_ACQUIRE_TIMEOUT_SECS` and `DB_IDLE_TIMEOUT_SECS` (0 disables the
+    /// idle timeout), falling back to the defaults for unset variables.
+    pub fn from_env() -> Result<Self, String> {
+        let mut settings = Self::default();
+        
+        if let Some(max) = env_number("DB_MAX_CONNECTIONS")? {
+            settings.max_connections = max;
+        }
+        if let Some(min) = env_number("DB_MIN_CONNECTIONS")? {
+            settings.min_connections = min;
+        }
+        if let Some(secs) = env_number("DB_ACQUIRE_TIMEOUT_SECS")? {
+            settings.acquire_timeout = Duration::from_secs(secs);
+        }
+        if let Some(secs) = env_number("DB_IDLE_TIMEOUT_SECS")? {
+            settings.idle_timeout = (secs > 0).then(|| Duration::from_secs(secs));
+        }
+        
+        if settings.max_connections == 0 {
+            return Err("DB_MAX_CONNECTIONS must be at least 1".to_string());
+        }
+        if settings.min_connections > settings.max_connections {
+            return Err(format!(
+                "DB_MIN_CONNECTIONS ({}) must not exceed DB_MAX_CONNECTIONS ({})",
+                settings.min_connections, settings.max_connections
+            ));
+        }
+        
+        Ok(settings)
+    }
+    
+    pub async fn connect(&self, database_url: &str) -> Result<PgPool, sqlx::Error> {
+        PgPoolOptions::new()
+            .max_connections(self.max_connections)
+            .min_connections(self.min_connections)
+            .acquire_timeout(self.acquire_timeout)
+            .idle_timeout(self.idle_timeout)
+            .connect(database_url)
+            .await
+    }
+}
+
+/// Parses the environment variable `name` as a non-negative number, if set.
+fn env_number<T: std::str::FromStr>(name: &str) -> Result<Option<T>, String> {
+    match std::env::var(name) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("{} must be a non-negative integer, got {:?}", name, value)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Whether `error` is worth retrying: lost connections and lock conflicts.
+/// Constraint violations and other data errors fail the same way every time.
+pub fn is_transient(error: &sqlx::Error) -> bool {
+    match error {
+        sqlx::Error::Io(_) | sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed => true,
+        sqlx::Error::Database(db) => db.code().is_some_and(|code| {
+            // Class 08 is connection exceptions, 57P01-57P03 are shutdowns
+            CONFLICT_SQLSTATES.contains(&code.as_ref())
+                || code.starts_with("08")
+                || matches!(code.as_ref(), "57P01" | "57P02" | "57P03")
+        }),
+        _ => false,
+    }
+}
+
+/// Transient errors that leave the transaction usable, so the statement can
+/// be retried in place. Anything else transient loses the connection and has
+/// to be retried by rerunning the whole transaction.
+fn is_lock_conflict(error: &sqlx::Error) -> bool {
+    match error {
+        sqlx::Error::Database(db) => db
+            .code()
+            .is_some_and(|code| CONFLICT_SQLSTATES.contains(&code.as_ref())),
+        _ => false,
+    }
+}
+
+#[derive(Clone)]
 pub struct Database {
     pool: PgPool,
+    retry: RetryPolicy,
+    batch_size: usize,
+    conflict_key: ConflictKey,
+}
+
+fn grave_feature(geometry: GeoJsonGeometry, properties: Option<serde_json::Value>) -> GeoJsonFeature {
+    GeoJsonFeature {
+        feature_type: "Feature".to_string(),
+        geometry,
+        properties: properties.unwrap_or_else(|| serde_json::json!({})),
+    }
+}
+
+/// A record as stored in the `record_merges` audit columns.
+fn to_json(record: &impl Serialize) -> serde_json::Value {
+    serde_json::to_value(record).unwrap_or_default()
+}
+
+fn coordinates_wkt(record: &DeceasedRecord) -> Option<String> {
+    if let (Some(lat), Some(lon)) = (record.latitude, record.longitude) {
+        Some(format!("POINT({} {})", lon, lat))
+    } else {
+        None
+    }
 }
 
 impl Database {
     pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            retry: RetryPolicy::default(),
+            batch_size: INSERT_CHUNK_SIZE,
+            conflict_key: ConflictKey::default(),
+        }
+    }
+    
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+    
+    /// Inserts up to `size` records per statement; see [`check_batch_size`].
+    pub fn with_batch_size(mut self, size: usize) -> Self {
+        self.batch_size = size;
+        self
+    }
+    
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+    
+    /// Upserts conflict on `key` rather than `record_id`; the table needs a
+    /// unique index on its columns (see [`Database::has_unique_key`]).
+    pub fn with_conflict_key(mut self, key: ConflictKey) -> Self {
+        self.conflict_key = key;
+        self
+    }
+    
+    /// Starts a transaction; writes made through it are only visible once
+    /// it is committed and are rolled back if it is dropped.
+    pub async fn begin(&self) -> Result<Transaction<'static, Postgres>, sqlx::Error> {
+        self.pool.begin().await
+    }
+    
+    /// Runs a trivial query, to check that the database can be reached.
+    pub async fn ping(&self) -> Result<(), sqlx::Error> {
+        sqlx::query("SELECT 1").execute(&self.pool).await.map(|_| ())
     }
     
+    /// Upserts one record on the configured conflict key, returning the id
+    /// of its row.
     pub async fn insert_deceased_record(
         &self,
+        tx: &mut Transaction<'_, Postgres>,
         record: &DeceasedRecord,
         source_file: &str,
-    ) -> Result<i64, sqlx::Error> {
-        let coordinates_wkt = if let (Some(lat), Some(lon)) = (record.latitude, record.longitude) {
-            Some(format!("POINT({} {})", lon, lat))
-        } else {
-            None
+    ) -> Result<i32, sqlx::Error> {
+        let mut query = Self::build_upsert_query_on(
+            std::slice::from_ref(record),
+            source_file,
+            self.conflict_key,
+            ConflictAction::Update,
+        );
+        query.push(" RETURNING id");
+        
+        query.build_query_scalar().fetch_one(&mut **tx).await
+    }
+    
+    /// Which of `record_ids` are already stored, live or soft-deleted. Reads
+    /// through `tx` when given, so rows written earlier in it are seen.
+    pub async fn existing_record_ids(
+        &self,
+        tx: Option<&mut Transaction<'_, Postgres>>,
+        record_ids: &[String],
+    ) -> Result<HashSet<String>, sqlx::Error> {
+        let query = sqlx::query_scalar!(
+            "SELECT record_id FROM deceased_records WHERE record_id = ANY($1)",
+            record_ids
+        );
+        
+        let existing = match tx {
+            Some(tx) => query.fetch_all(&mut **tx).await?,
+            None => query.fetch_all(&self.pool).await?,
         };
         
-        let result = sqlx::query!(
+        Ok(existing.into_iter().collect())
+    }
+    
+    /// The stored record ids of the people in `records`, by their value for
+    /// `key` (see [`ConflictKey::value`]). Reads through `tx` when given.
+    pub async fn record_ids_by_key(
+        &self,
+        tx: Option<&mut Transaction<'_, Postgres>>,
+        key: ConflictKey,
+        records: &[DeceasedRecord],
+    ) -> Result<HashMap<String, String>, sqlx::Error> {
+        match key {
+            ConflictKey::RecordId => Ok(HashMap::new()),
+            ConflictKey::NationalId => {
+                let national_ids: Vec<String> = records.iter().filter_map(|r| r.national_id.clone()).collect();
+                let query = sqlx::query!(
+                    r#"
+                    SELECT national_id as "national_id!", record_id
+                    FROM deceased_records
+                    WHERE national_id = ANY($1)
+                    "#,
+                    &national_ids
+                );
+                
+                let rows = match tx {
+                    Some(tx) => query.fetch_all(&mut **tx).await?,
+                    None => query.fetch_all(&self.pool).await?,
+                };
+                Ok(rows.into_iter().map(|row| (row.national_id, row.record_id)).collect())
+            }
+            ConflictKey::NameAndDeathDate => {
+                let names: Vec<String> = records.iter().map(|r| r.deceased_name.clone()).collect();
+                let death_dates: Vec<NaiveDate> = records.iter().map(|r| r.death_date).collect();
+                let query = sqlx::query!(
+                    r#"
+                    SELECT r.deceased_name, r.death_date, r.record_id
+                    FROM deceased_records r
+                    JOIN UNNEST($1::text[], $2::date[]) AS k(name, death_date)
+                        ON r.deceased_name = k.name AND r.death_date = k.death_date
+                    "#,
+                    &names,
+                    &death_dates
+                );
+                
+                let rows = match tx {
+                    Some(tx) => query.fetch_all(&mut **tx).await?,
+                    None => query.fetch_all(&self.pool).await?,
+                };
+                Ok(rows
+                    .into_iter()
+                    .map(|row| (name_and_death_date(&row.deceased_name, row.death_date), row.record_id))
+                    .collect())
+            }
+        }
+    }
+    
+    /// Whether `deceased_records` has a unique index on exactly the columns
+    /// of `key`, which an upsert conflicting on it needs.
+    pub async fn has_unique_key(&self, key: ConflictKey) -> Result<bool, sqlx::Error> {
+        let mut columns: Vec<String> = key.columns().iter().map(|column| column.to_string()).collect();
+        columns.sort();
+        
+        sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1
+                FROM pg_index i
+                WHERE i.indrelid = 'deceased_records'::regclass
+                    AND i.indisunique
+                    AND i.indpred IS NULL
+                    AND i.indexprs IS NULL
+                    AND ARRAY(
+                        SELECT a.attname::text
+                        FROM pg_attribute a
+                        WHERE a.attrelid = i.indrelid AND a.attnum = ANY(i.indkey)
+                        ORDER BY a.attname
+                    ) = $1
+            ) as "exists!"
+            "#,
+            &columns
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+    
+    /// Builds a single multi-row upsert for `records`, refreshing the
+    /// records whose `record_id` is already stored.
+    pub fn build_upsert_query<'a>(
+        records: &'a [DeceasedRecord],
+        source_file: &'a str,
+    ) -> QueryBuilder<'a, Postgres> {
+        Self::build_upsert_query_with(records, source_file, ConflictAction::Update)
+    }
+    
+    pub fn build_upsert_query_with<'a>(
+        records: &'a [DeceasedRecord],
+        source_file: &'a str,
+        on_conflict: ConflictAction,
+    ) -> QueryBuilder<'a, Postgres> {
+        Self::build_upsert_query_on(records, source_file, ConflictKey::RecordId, on_conflict)
+    }
+    
+    /// Like [`Database::build_upsert_query_with`], conflicting on `key`.
+    pub fn build_upsert_query_on<'a>(
+        records: &'a [DeceasedRecord],
+        source_file: &'a str,
+        key: ConflictKey,
+        on_conflict: ConflictAction,
+    ) -> QueryBuilder<'a, Postgres> {
+        let mut builder = QueryBuilder::new(
             r#"
             INSERT INTO deceased_records (
                 record_id, deceased_name, deceased_name_arabic,
                 father_name, grandfather_name,
                 death_date, death_location, burial_date, burial_location,
                 section, row_number, plot_number, grave_number,
-                coordinates,
-                age_at_death, cause_of_death, national_id, family_contact,
+                coordinates, footprint,
+                birth_date, age_at_death, cause_of_death, cause_of_death_code, national_id, family_contact,
                 additional_data, source_file, processing_status
-            ) VALUES (
-                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13,
-                ST_GeomFromText($14, 4326),
-                $15, $16, $17, $18, $19, $20, $21
-            )
-            ON CONFLICT (record_id) DO UPDATE SET
-                deceased_name = EXCLUDED.deceased_name,
-                burial_date = EXCLUDED.burial_date,
-                coordinates = EXCLUDED.coordinates,
-                updated_at = CURRENT_TIMESTAMP,
-                processing_status = EXCLUDED.processing_status
-            RETURNING id
-            "#,
-            record.record_id,
-            record.deceased_name,
-            record.deceased_name_arabic,
-            record.father_name,
-            record.grandfather_name,
-            record.death_date,
-            record.death_location,
-            record.burial_date,
-            record.burial_location,
-            record.section,
-            record.row_number,
-            record.plot_number,
-            record.grave_number,
-            coordinates_wkt,
-            record.age_at_death,
-            record.cause_of_death,
-            record.national_id,
-            record.family_contact,
-            record.additional_data,
-            source_file,
-            "completed"
-        )
-        .fetch_one(&self.pool)
-        .await?;
+            ) "#,
+        );
+        
+        builder.push_values(records, |mut row, record| {
+            row.push_bind(&record.record_id)
+                .push_bind(&record.deceased_name)
+                .push_bind(&record.deceased_name_arabic)
+                .push_bind(&record.father_name)
+                .push_bind(&record.grandfather_name)
+                .push_bind(record.death_date)
+                .push_bind(&record.death_location)
+                .push_bind(record.burial_date)
+                .push_bind(&record.burial_location)
+                .push_bind(&record.section)
+                .push_bind(record.row_number)
+                .push_bind(record.plot_number)
+                .push_bind(&record.grave_number)
+                .push("ST_GeomFromText(")
+                .push_bind_unseparated(coordinates_wkt(record))
+                .push_unseparated(", 4326)")
+                .push("ST_GeomFromText(")
+                .push_bind_unseparated(record.footprint.as_deref().map(footprint_wkt))
+                .push_unseparated(", 4326)")
+                .push_bind(record.birth_date)
+                .push_bind(record.age_at_death)
+                .push_bind(&record.cause_of_death)
+                .push_bind(&record.cause_of_death_code)
+                .push_bind(&record.national_id)
+                .push_bind(&record.family_contact)
+                .push_bind(&record.additional_data)
+                .push_bind(source_file)
+                .push_bind(record.processing_status.as_str());
+        });
+        
+        builder.push(on_conflict.clause(key));
         
-        Ok(result.id)
+        builder
     }
     
     pub async fn insert_batch(
         &self,
+        tx: &mut Transaction<'_, Postgres>,
         records: &[DeceasedRecord],
         source_file: &str,
-    ) -> Result<usize, sqlx::Error> {
-        let mut inserted = 0;
+    ) -> Result<BatchInsertResult, sqlx::Error> {
+        self.insert_batch_with(tx, records, source_file, ConflictAction::Update).await
+    }
+    
+    /// Like [`Database::insert_batch`], with `on_conflict` deciding what
+    /// happens to records that already exist.
+    pub async fn insert_batch_with(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        records: &[DeceasedRecord],
+        source_file: &str,
+        on_conflict: ConflictAction,
+    ) -> Result<BatchInsertResult, sqlx::Error> {
+        let mut result = BatchInsertResult::default();
+        let mut statements = 0;
         
-        for record in records {
-            match self.insert_deceased_record(record, source_file).await {
-                Ok(_) => inserted += 1,
+        for (chunk_index, chunk) in records.chunks(self.batch_size).enumerate() {
+            statements += 1;
+            
+            match self.upsert_with_retry(tx, chunk, source_file, on_conflict).await? {
+                Ok(rows) => result.inserted += rows as usize,
                 Err(e) => {
-                    error!("Failed to insert record {}: {}", record.record_id, e);
+                    // One bad row fails the whole statement, so retry the chunk
+                    // row by row to land the good records and pinpoint the bad ones
+                    warn!(
+                        "Batch insert of chunk {} failed, retrying row by row: {}",
+                        chunk_index, e
+                    );
+                    
+                    for record in chunk {
+                        statements += 1;
+                        
+                        match self
+                            .upsert_with_retry(tx, std::slice::from_ref(record), source_file, on_conflict)
+                            .await?
+                        {
+                            Ok(rows) => result.inserted += rows as usize,
+                            Err(e) => {
+                                error!("Failed to insert record {} of chunk {}: {}", record.record_id, chunk_index, e);
+                                result.errors.push(ErrorDetails {
+                                    record_id: Some(record.record_id.clone()),
+                                    line_number: None,
+                                    message: format!("Database insert failed in chunk {}: {}", chunk_index, e),
+                                    code: None,
+                                    field: None,
+                                    source_fields: None,
+                                });
+                            }
+                        }
+                    }
                 }
             }
         }
         
-        info!("Inserted {} records into database", inserted);
-        Ok(inserted)
+        info!(
+            "Inserted {} records into database using {} statements",
+            result.inserted, statements
+        );
+        Ok(result)
+    }
+    
+    /// Upserts `records` under a savepoint so a failure does not abort the
+    /// enclosing transaction. Lock conflicts are rolled back and retried with
+    /// exponential backoff; the inner error is the statement's final failure.
+    /// The outer error means the transaction itself is unusable, e.g. because
+    /// the connection was lost.
+    async fn upsert_with_retry(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        records: &[DeceasedRecord],
+        source_file: &str,
+        on_conflict: ConflictAction,
+    ) -> Result<Result<u64, sqlx::Error>, sqlx::Error> {
+        let mut attempt = 1;
+        
+        loop {
+            let mut savepoint = tx.begin().await?;
+            let outcome = Self::build_upsert_query_on(records, source_file, self.conflict_key, on_conflict)
+                .build()
+                .execute(&mut *savepoint)
+                .await;
+            
+            match outcome {
+                Ok(done) => {
+                    savepoint.commit().await?;
+                    return Ok(Ok(done.rows_affected()));
+                }
+                Err(e) => {
+                    savepoint.rollback().await?;
+                    
+                    if !is_lock_conflict(&e) || attempt >= self.retry.max_attempts {
+                        // A lost connection takes the transaction with it, so
+                        // leave it to the caller to rerun the whole thing
+                        return if is_transient(&e) && !is_lock_conflict(&e) {
+                            Err(e)
+                        } else {
+                            Ok(Err(e))
+                        };
+                    }
+                    
+                    let delay = self.retry.backoff(attempt);
+                    warn!(
+                        "Insert attempt {} of {} hit a transient error, retrying in {:?}: {}",
+                        attempt, self.retry.max_attempts, delay, e
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+            }
+        }
     }
     
-    pub async fn create_geojson_features(&self) -> Result<i32, sqlx::Error> {
-        // Clear existing features
+    /// Rebuilds the whole map layer from the live, completed records. Imports
+    /// use [`Database::update_geojson_features`]; this is for maintenance.
+    ///
+    /// A record with a footprint is drawn as that polygon, otherwise as its
+    /// point. With `derive_coordinates`, a record with neither is placed
+    /// inside its plot outline (from an imported shapefile) or, failing that,
+    /// inside its section's polygon in `burial_sections`. Such features carry
+    /// `coordinates_source: "derived"`; records whose section has neither
+    /// are left off the map, as are records whose geometry is invalid or
+    /// empty, which are counted. The records themselves are not changed.
+    pub async fn create_geojson_features(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        derive_coordinates: bool,
+    ) -> Result<FeatureCounts, sqlx::Error> {
+        // Clear existing features; readers keep seeing the old layer until
+        // the enclosing transaction commits
         sqlx::query!("DELETE FROM najaf_cemetery_features")
-            .execute(&self.pool)
+            .execute(&mut **tx)
             .await?;
         
-        // Insert new features from deceased_records
-        let result = sqlx::query!(
+        self.upsert_geojson_features(tx, None, derive_coordinates).await
+    }
+    
+    /// Brings the features of `record_ids` up to date: records that belong
+    /// on the map get their feature inserted or replaced, and the features
+    /// of records that no longer do (deleted, or without coordinates) are
+    /// removed. Other features are left alone.
+    pub async fn update_geojson_features(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        record_ids: &[String],
+        derive_coordinates: bool,
+    ) -> Result<FeatureCounts, sqlx::Error> {
+        self.upsert_geojson_features(tx, Some(record_ids), derive_coordinates).await
+    }
+    
+    /// Rebuilds the map layer outside an import, e.g. after records were
+    /// edited by hand: the whole layer, or only the features of `section`'s
+    /// records and of features still filed under it.
+    pub async fn rebuild_features(
+        &self,
+        section: Option<&str>,
+        derive_coordinates: bool,
+    ) -> Result<FeatureCounts, sqlx::Error> {
+        let mut tx = self.begin().await?;
+        
+        let written = match section {
+            None => self.create_geojson_features(&mut tx, derive_coordinates).await?,
+            Some(section) => {
+                let record_ids: Vec<String> = sqlx::query_scalar!(
+                    r#"
+                    SELECT record_id as "record_id!" FROM deceased_records WHERE section = $1
+                    UNION
+                    SELECT feature_id as "record_id!" FROM najaf_cemetery_features
+                    WHERE properties->>'section' = $1 AND feature_id IS NOT NULL
+                    "#,
+                    section
+                )
+                .fetch_all(&mut *tx)
+                .await?;
+                
+                self.update_geojson_features(&mut tx, &record_ids, derive_coordinates).await?
+            }
+        };
+        
+        tx.commit().await?;
+        Ok(written)
+    }
+    
+    /// Upserts the features of `record_ids`, or of every record for `None`.
+    /// A geometry PostGIS finds invalid or empty would break the layer for
+    /// its readers, so such records get no feature (and lose the one they
+    /// had) and are counted instead.
+    async fn upsert_geojson_features(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        record_ids: Option<&[String]>,
+        derive_coordinates: bool,
+    ) -> Result<FeatureCounts, sqlx::Error> {
+        let counts = sqlx::query!(
             r#"
-            INSERT INTO najaf_cemetery_features (feature_id, geometry, properties)
-            SELECT 
-                record_id as feature_id,
-                coordinates as geometry,
-                jsonb_build_object(
-                    'record_id', record_id,
-                    'name', deceased_name,
-                    'burial_date', burial_date::text,
-                    'burial_location', burial_location,
+            WITH candidates AS (
+                SELECT 
+                    r.record_id as feature_id,
+                    COALESCE(r.footprint, r.coordinates, plot.point, section.point) as geometry,
+                    jsonb_build_object(
+                        'record_id', r.record_id,
+                        'name', r.deceased_name,
+                        'burial_date', r.burial_date::text,
+                        'burial_location', r.burial_location,
+                        'section', r.section,
+                        'row', r.row_number,
+                        'plot', r.plot_number,
+                        'coordinates_source', CASE WHEN COALESCE(r.footprint, r.coordinates) IS NULL THEN 'derived' ELSE 'recorded' END
+                    ) as properties
+                FROM deceased_records r
+                LEFT JOIN LATERAL (
+                    SELECT ST_PointOnSurface(s.geometry) as point
+                    FROM cemetery_sections s
+                    WHERE s.section = r.section AND s.plot_number = r.plot_number
+                    ORDER BY s.layer, s.shape_index, s.part_index
+                    LIMIT 1
+                ) plot ON $1 AND COALESCE(r.footprint, r.coordinates) IS NULL
+                LEFT JOIN LATERAL (
+                    SELECT ST_PointOnSurface(b.geometry) as point
+                    FROM burial_sections b
+                    WHERE b.section_code = r.section AND b.geometry IS NOT NULL
+                ) section ON $1 AND COALESCE(r.footprint, r.coordinates) IS NULL
+                WHERE COALESCE(r.footprint, r.coordinates, plot.point, section.point) IS NOT NULL
+                    AND r.processing_status IN ('completed', 'needs_review', 'geocode_failed')
+                    AND r.deleted_at IS NULL
+                    AND ($2::text[] IS NULL OR r.record_id = ANY($2))
+            ),
+            features AS (
+                SELECT feature_id, geometry, properties
+                FROM candidates
+                WHERE ST_IsValid(geometry) AND NOT ST_IsEmpty(geometry)
+            ),
+            upserted AS (
+                INSERT INTO najaf_cemetery_features (feature_id, geometry, properties)
+                SELECT feature_id, geometry, properties FROM features
+                ON CONFLICT (feature_id) DO UPDATE
+                SET geometry = EXCLUDED.geometry, properties = EXCLUDED.properties
+                RETURNING 1
+            ),
+            removed AS (
+                DELETE FROM najaf_cemetery_features
+                WHERE feature_id = ANY($2)
+                    AND feature_id NOT IN (SELECT feature_id FROM features)
+                RETURNING 1
+            )
+            SELECT
+                (SELECT COUNT(*) FROM upserted) as "upserted!",
+                (SELECT COUNT(*) FROM removed) as "removed!",
+                (SELECT COUNT(*) FROM candidates) - (SELECT COUNT(*) FROM features) as "skipped!"
+            "#,
+            derive_coordinates,
+            record_ids as Option<&[String]>
+        )
+        .fetch_one(&mut **tx)
+        .await?;
+        
+        if counts.removed > 0 {
+            info!("Removed {} features of records no longer on the map", counts.removed);
+        }
+        if counts.skipped > 0 {
+            warn!("Left {} records with invalid or empty geometry off the map", counts.skipped);
+        }
+        
+        Ok(FeatureCounts {
+            written: counts.upserted as i32,
+            skipped_invalid: counts.skipped as i32,
+        })
+    }
+    
+    /// Reads grave features from `najaf_cemetery_features`, points and
+    /// footprint polygons, optionally limited to one section, in a stable
+    /// order for pagination.
+    pub async fn get_features(
+        &self,
+        section: Option<&str>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<GeoJsonFeature>, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                ST_AsGeoJSON(geometry)::jsonb as "geometry!: Json<GeoJsonGeometry>",
+                properties
+            FROM najaf_cemetery_features
+            WHERE geometry IS NOT NULL
+                AND ST_GeometryType(geometry) IN ('ST_Point', 'ST_Polygon')
+                AND ($1::text IS NULL OR properties->>'section' = $1)
+            ORDER BY id
+            LIMIT $2 OFFSET $3
+            "#,
+            section,
+            limit,
+            offset
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        
+        Ok(rows
+            .into_iter()
+            .map(|row| grave_feature(row.geometry.0, row.properties))
+            .collect())
+    }
+    
+    /// Replaces the outlines previously imported from `layer` with `outlines`.
+    pub async fn replace_section_outlines(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        layer: &str,
+        outlines: &[SectionOutline],
+        source_file: &str,
+    ) -> Result<u64, sqlx::Error> {
+        sqlx::query!("DELETE FROM cemetery_sections WHERE layer = $1", layer)
+            .execute(&mut **tx)
+            .await?;
+        
+        let mut written = 0;
+        
+        for outline in outlines {
+            written += sqlx::query!(
+                r#"
+                INSERT INTO cemetery_sections (
+                    layer, shape_index, part_index, section, plot_number,
+                    attributes, geometry, source_file
+                )
+                VALUES ($1, $2, $3, $4, $5, $6, ST_GeomFromText($7, 4326), $8)
+                "#,
+                layer,
+                outline.shape_index,
+                outline.part_index,
+                outline.section,
+                outline.plot_number,
+                outline.attributes,
+                outline.to_wkt(),
+                source_file
+            )
+            .execute(&mut **tx)
+            .await?
+            .rows_affected();
+        }
+        
+        Ok(written)
+    }
+    
+    /// Reads section and plot outlines as GeoJSON polygons, optionally
+    /// limited to one section. The `.dbf` attributes become the properties.
+    pub async fn get_section_outlines(
+        &self,
+        section: Option<&str>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<GeoJsonShapeFeature>, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                ST_AsGeoJSON(geometry)::jsonb as "geometry!",
+                COALESCE(attributes, '{}'::jsonb) || jsonb_build_object(
+                    'layer', layer,
                     'section', section,
-                    'row', row_number,
                     'plot', plot_number
-                ) as properties
-            FROM deceased_records
-            WHERE coordinates IS NOT NULL
-                AND processing_status = 'completed'
+                ) as "properties!"
+            FROM cemetery_sections
+            WHERE ($1::text IS NULL OR section = $1)
+            ORDER BY layer, shape_index, part_index
+            LIMIT $2 OFFSET $3
+            "#,
+            section,
+            limit,
+            offset
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        
+        Ok(rows
+            .into_iter()
+            .map(|row| GeoJsonShapeFeature {
+                feature_type: "Feature".to_string(),
+                geometry: row.geometry,
+                properties: row.properties,
+            })
+            .collect())
+    }
+    
+    /// Counts the live records of each section that has a polygon, for a
+    /// density map. The polygon is the section's `burial_sections` geometry,
+    /// or else the union of its outlines imported from shapefiles. Sections
+    /// without records are included with a count of 0.
+    pub async fn get_section_stats(&self) -> Result<Vec<GeoJsonShapeFeature>, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"
+            WITH outlines AS (
+                SELECT section, ST_Union(geometry) as geometry
+                FROM cemetery_sections
+                WHERE section IS NOT NULL
+                GROUP BY section
+            ),
+            shapes AS (
+                SELECT
+                    COALESCE(b.section_code, o.section) as section,
+                    COALESCE(b.geometry, o.geometry) as geometry,
+                    b.section_name,
+                    b.capacity
+                FROM burial_sections b
+                FULL JOIN outlines o ON o.section = b.section_code
+                WHERE COALESCE(b.geometry, o.geometry) IS NOT NULL
+            ),
+            counts AS (
+                SELECT section, COUNT(*) as records
+                FROM deceased_records
+                WHERE section IS NOT NULL
+                  AND deleted_at IS NULL
+                  AND processing_status IN ('completed', 'needs_review', 'geocode_failed')
+                GROUP BY section
+            )
+            SELECT
+                ST_AsGeoJSON(s.geometry)::jsonb as "geometry!",
+                jsonb_build_object(
+                    'section', s.section,
+                    'section_name', s.section_name,
+                    'record_count', COALESCE(c.records, 0),
+                    'capacity', s.capacity,
+                    'centroid', jsonb_build_array(ST_X(p.point), ST_Y(p.point))
+                ) as "properties!"
+            FROM shapes s
+            CROSS JOIN LATERAL (SELECT ST_PointOnSurface(s.geometry) as point) p
+            LEFT JOIN counts c ON c.section = s.section
+            ORDER BY s.section
             "#
         )
-        .execute(&self.pool)
+        .fetch_all(&self.pool)
         .await?;
         
-        Ok(result.rows_affected() as i32)
+        Ok(rows
+            .into_iter()
+            .map(|row| GeoJsonShapeFeature {
+                feature_type: "Feature".to_string(),
+                geometry: row.geometry,
+                properties: row.properties,
+            })
+            .collect())
     }
     
-    pub async fn log_file_processing(
+    /// Reads grave features whose geometry overlaps a WGS84 bounding box,
+    /// using the spatial index on `najaf_cemetery_features.geometry`.
+    pub async fn get_features_in_bbox(
         &self,
-        filename: &str,
-        file_hash: &str,
-        file_size: i64,
-        records_total: i32,
-        records_processed: i32,
-        records_failed: i32,
-        status: &str,
-        error_message: Option<&str>,
-    ) -> Result<(), sqlx::Error> {
-        sqlx::query!(
+        min_lon: f64,
+        min_lat: f64,
+        max_lon: f64,
+        max_lat: f64,
+        limit: i64,
+    ) -> Result<Vec<GeoJsonFeature>, sqlx::Error> {
+        let rows = sqlx::query!(
             r#"
-            INSERT INTO file_processing_log (
-                filename, file_hash, file_size,
-                records_total, records_processed, records_failed,
-                status, error_message, processing_end_time
-            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, CURRENT_TIMESTAMP)
+            SELECT
+                ST_AsGeoJSON(geometry)::jsonb as "geometry!: Json<GeoJsonGeometry>",
+                properties
+            FROM najaf_cemetery_features
+            WHERE geometry && ST_MakeEnvelope($1, $2, $3, $4, 4326)
+                AND ST_GeometryType(geometry) IN ('ST_Point', 'ST_Polygon')
+            ORDER BY id
+            LIMIT $5
             "#,
-            filename,
-            file_hash,
-            file_size,
-            records_total,
-            records_processed,
-            records_failed,
-            status,
-            error_message
+            min_lon,
+            min_lat,
+            max_lon,
+            max_lat,
+            limit
         )
-        .execute(&self.pool)
+        .fetch_all(&self.pool)
         .await?;
         
-        Ok(())
+        Ok(rows
+            .into_iter()
+            .map(|row| grave_feature(row.geometry.0, row.properties))
+            .collect())
+    }
+    
+    /// Finds records whose Latin or Arabic name resembles `term`, best
+    /// matches first. `term` must already be lowercased and passed through
+    /// `normalize_arabic`; the stored Arabic name is normalized the same way
+    /// in SQL.
+    pub async fn get_record(&self, record_id: &str) -> Result<Option<DeceasedRecordDetail>, sqlx::Error> {
+        sqlx::query_as!(
+            DeceasedRecordDetail,
+            r#"
+            SELECT
+                id, record_id, deceased_name, deceased_name_arabic,
+                father_name, grandfather_name,
+                death_date, death_location, burial_date, burial_location,
+                section, row_number, plot_number, grave_number,
+                ST_Y(coordinates) as latitude,
+                ST_X(coordinates) as longitude,
+                birth_date, age_at_death, cause_of_death, cause_of_death_code, national_id, family_contact,
+                additional_data, source_file,
+                processing_status, processing_error,
+                created_at, updated_at
+            FROM deceased_records
+            WHERE record_id = $1 AND deleted_at IS NULL
+            "#,
+            record_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+    
+    /// A page of the live records matching `filter`, latest burials first and
+    /// by `id` among burials of a day. `after` continues from the last record
+    /// of the previous page, which stays fast however deep the page is;
+    /// `offset` is applied after it.
+    pub async fn list_records(
+        &self,
+        filter: &RecordFilter,
+        after: Option<RecordCursor>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<DbDeceasedRecord>, sqlx::Error> {
+        sqlx::query_as!(
+            DbDeceasedRecord,
+            r#"
+            SELECT
+                id, record_id, deceased_name, burial_date,
+                section, row_number, plot_number,
+                ST_Y(coordinates) as latitude,
+                ST_X(coordinates) as longitude,
+                COALESCE(processing_status, 'pending') as "processing_status!"
+            FROM deceased_records
+            WHERE deleted_at IS NULL
+                AND ($1::text IS NULL OR section = $1)
+                AND ($2::date IS NULL OR burial_date >= $2)
+                AND ($3::date IS NULL OR burial_date <= $3)
+                AND ($4::bool IS NULL OR (coordinates IS NOT NULL) = $4)
+                AND ($5::text IS NULL OR COALESCE(processing_status, 'pending') = $5)
+                AND ($6::date IS NULL OR (burial_date, id) < ($6, $7::int))
+                AND ($10::jsonb IS NULL OR additional_data @> $10)
+            ORDER BY burial_date DESC, id DESC
+            LIMIT $8 OFFSET $9
+            "#,
+            filter.section,
+            filter.burial_date_from,
+            filter.burial_date_to,
+            filter.has_coordinates,
+            filter.processing_status.map(RecordStatus::as_str),
+            after.map(|cursor| cursor.burial_date),
+            after.map(|cursor| cursor.id),
+            limit,
+            offset,
+            filter.extra_containment()
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+    
+    /// How many live records match `filter`, across all pages.
+    pub async fn count_records(&self, filter: &RecordFilter) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) as "count!"
+            FROM deceased_records
+            WHERE deleted_at IS NULL
+                AND ($1::text IS NULL OR section = $1)
+                AND ($2::date IS NULL OR burial_date >= $2)
+                AND ($3::date IS NULL OR burial_date <= $3)
+                AND ($4::bool IS NULL OR (coordinates IS NOT NULL) = $4)
+                AND ($5::text IS NULL OR COALESCE(processing_status, 'pending') = $5)
+                AND ($6::jsonb IS NULL OR additional_data @> $6)
+            "#,
+            filter.section,
+            filter.burial_date_from,
+            filter.burial_date_to,
+            filter.has_coordinates,
+            filter.processing_status.map(RecordStatus::as_str),
+            filter.extra_containment()
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+    
+    /// Streams live records in the CSV import layout, ordered by
+    /// `record_id`. Rows are read as the stream is polled rather than
+    /// loaded up front.
+    pub fn export_records<'a>(
+        &'a self,
+        section: Option<&'a str>,
+        burial_date_from: Option<NaiveDate>,
+        burial_date_to: Option<NaiveDate>,
+    ) -> BoxStream<'a, Result<ExportRecord, sqlx::Error>> {
+        sqlx::query_as!(
+            ExportRecord,
+            r#"
+            SELECT
+                record_id, deceased_name, deceased_name_arabic,
+                death_date, burial_date, burial_location,
+                ST_Y(coordinates) as latitude,
+                ST_X(coordinates) as longitude,
+                section, row_number, plot_number,
+                cause_of_death, national_id, family_contact
+            FROM deceased_records
+            WHERE deleted_at IS NULL
+                AND ($1::text IS NULL OR section = $1)
+                AND ($2::date IS NULL OR burial_date >= $2)
+                AND ($3::date IS NULL OR burial_date <= $3)
+            ORDER BY record_id
+            "#,
+            section,
+            burial_date_from,
+            burial_date_to
+        )
+        .fetch(&self.pool)
+    }
+    
+    /// Merges `duplicates` into the `primary` record: fields the primary is
+    /// missing are copied from the duplicates, the first duplicate listed
+    /// winning, and the duplicates are soft-deleted. The records before and
+    /// after are kept in `record_merges`, and the map features of every
+    /// record involved are updated. All or nothing.
+    pub async fn merge_records(
+        &self,
+        primary: &str,
+        duplicates: &[String],
+        derive_coordinates: bool,
+    ) -> Result<MergeOutcome, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        
+        let mut ids = vec![primary.to_string()];
+        ids.extend_from_slice(duplicates);
+        
+        // Locked in id order, so concurrent merges cannot deadlock
+        let mut before = Self::lock_records(&mut tx, &ids).await?;
+        let missing: Vec<String> = ids
+            .iter()
+            .filter(|id| !before.iter().any(|record| &record.record_id == *id))
+            .cloned()
+            .collect();
+        if !missing.is_empty() {
+            return Ok(MergeOutcome::NotFound(missing));
+        }
+        
+        for duplicate in duplicates {
+            sqlx::query!(
+                r#"
+                UPDATE deceased_records p SET
+                    deceased_name_arabic = COALESCE(NULLIF(p.deceased_name_arabic, ''), d.deceased_name_arabic, p.deceased_name_arabic),
+                    father_name = COALESCE(NULLIF(p.father_name, ''), d.father_name, p.father_name),
+                    grandfather_name = COALESCE(NULLIF(p.grandfather_name, ''), d.grandfather_name, p.grandfather_name),
+                    death_location = COALESCE(NULLIF(p.death_location, ''), d.death_location, p.death_location),
+                    section = COALESCE(NULLIF(p.section, ''), d.section, p.section),
+                    row_number = COALESCE(p.row_number, d.row_number),
+                    plot_number = COALESCE(p.plot_number, d.plot_number),
+                    grave_number = COALESCE(NULLIF(p.grave_number, ''), d.grave_number, p.grave_number),
+                    coordinates = COALESCE(p.coordinates, d.coordinates),
+                    footprint = COALESCE(p.footprint, d.footprint),
+                    birth_date = COALESCE(p.birth_date, d.birth_date),
+                    age_at_death = COALESCE(p.age_at_death, d.age_at_death),
+                    cause_of_death = COALESCE(NULLIF(p.cause_of_death, ''), d.cause_of_death, p.cause_of_death),
+                    cause_of_death_code = CASE
+                        WHEN NULLIF(p.cause_of_death, '') IS NULL AND d.cause_of_death IS NOT NULL THEN d.cause_of_death_code
+                        ELSE p.cause_of_death_code
+                    END,
+                    national_id = COALESCE(NULLIF(p.national_id, ''), d.national_id, p.national_id),
+                    family_contact = COALESCE(NULLIF(p.family_contact, ''), d.family_contact, p.family_contact),
+                    -- jsonb `||` merges the two objects, the primary's keys winning
+                    additional_data = COALESCE(d.additional_data || p.additional_data, p.additional_data, d.additional_data),
+                    updated_at = CURRENT_TIMESTAMP
+                FROM deceased_records d
+                WHERE p.record_id = $1 AND d.record_id = $2
+                "#,
+                primary,
+                duplicate
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        
+        sqlx::query!(
+            r#"
+            UPDATE deceased_records
+            SET deleted_at = CURRENT_TIMESTAMP
+            WHERE record_id = ANY($1)
+            "#,
+            duplicates
+        )
+        .execute(&mut *tx)
+        .await?;
+        
+        let after = Self::lock_records(&mut tx, &ids[..1]).await?;
+        let primary_index = before
+            .iter()
+            .position(|record| record.record_id == primary)
+            .expect("the primary record was locked above");
+        let primary_before = before.remove(primary_index);
+        let (primary_before, primary_after) = (to_json(&primary_before), to_json(&after[0]));
+        
+        let filled_fields = match (&primary_before, &primary_after) {
+            (serde_json::Value::Object(old), serde_json::Value::Object(new)) => new
+                .iter()
+                .filter(|(field, value)| *field != "updated_at" && old.get(*field) != Some(value))
+                .map(|(field, _)| field.clone())
+                .collect(),
+            _ => Vec::new(),
+        };
+        
+        let merge_id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO record_merges (
+                primary_record_id, merged_record_ids, primary_before, primary_after, merged_records
+            ) VALUES ($1, $2, $3, $4, $5)
+            RETURNING id
+            "#,
+            primary,
+            duplicates,
+            primary_before,
+            primary_after,
+            serde_json::Value::Array(before.iter().map(to_json).collect())
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        
+        self.update_geojson_features(&mut tx, &ids, derive_coordinates).await?;
+        tx.commit().await?;
+        
+        Ok(MergeOutcome::Merged(RecordMerge {
+            merge_id,
+            record_id: primary.to_string(),
+            merged_record_ids: duplicates.to_vec(),
+            filled_fields,
+        }))
+    }
+    
+    /// Live records among `record_ids`, locked for update.
+    async fn lock_records(
+        tx: &mut Transaction<'_, Postgres>,
+        record_ids: &[String],
+    ) -> Result<Vec<DeceasedRecordDetail>, sqlx::Error> {
+        sqlx::query_as!(
+            DeceasedRecordDetail,
+            r#"
+            SELECT
+                id, record_id, deceased_name, deceased_name_arabic,
+                father_name, grandfather_name,
+                death_date, death_location, burial_date, burial_location,
+                section, row_number, plot_number, grave_number,
+                ST_Y(coordinates) as latitude,
+                ST_X(coordinates) as longitude,
+                birth_date, age_at_death, cause_of_death, cause_of_death_code, national_id, family_contact,
+                additional_data, source_file,
+                processing_status, processing_error,
+                created_at, updated_at
+            FROM deceased_records
+            WHERE record_id = ANY($1) AND deleted_at IS NULL
+            ORDER BY record_id
+            FOR UPDATE
+            "#,
+            record_ids
+        )
+        .fetch_all(&mut **tx)
+        .await
+    }
+    
+    /// Marks a record deleted and drops its map feature. The row itself is
+    /// kept for auditing. Returns false when no live record has this id.
+    pub async fn soft_delete_record(&self, record_id: &str) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        
+        let result = sqlx::query!(
+            r#"
+            UPDATE deceased_records
+            SET deleted_at = CURRENT_TIMESTAMP
+            WHERE record_id = $1 AND deleted_at IS NULL
+            "#,
+            record_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        
+        Self::delete_feature(&mut tx, record_id).await?;
+        tx.commit().await?;
+        
+        Ok(result.rows_affected() > 0)
+    }
+    
+    /// Permanently removes a record, live or soft-deleted, and its map
+    /// feature. Returns false when no record has this id.
+    pub async fn hard_delete_record(&self, record_id: &str) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        
+        let result = sqlx::query!("DELETE FROM deceased_records WHERE record_id = $1", record_id)
+            .execute(&mut *tx)
+            .await?;
+        
+        Self::delete_feature(&mut tx, record_id).await?;
+        tx.commit().await?;
+        
+        Ok(result.rows_affected() > 0)
+    }
+    
+    /// Removes one record's point from the feature layer without a full rebuild.
+    async fn delete_feature(
+        tx: &mut Transaction<'_, Postgres>,
+        record_id: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!("DELETE FROM najaf_cemetery_features WHERE feature_id = $1", record_id)
+            .execute(&mut **tx)
+            .await?;
+        
+        Ok(())
+    }
+    
+    /// Sets the status of the live records among `record_ids`, returning how
+    /// many there were. Their features are not updated.
+    pub async fn set_processing_status(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        record_ids: &[String],
+        status: RecordStatus,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE deceased_records
+            SET processing_status = $2, updated_at = CURRENT_TIMESTAMP
+            WHERE record_id = ANY($1) AND deleted_at IS NULL
+            "#,
+            record_ids,
+            status.as_str()
+        )
+        .execute(&mut **tx)
+        .await?;
+        
+        Ok(result.rows_affected())
+    }
+    
+    /// Marks the records among `record_ids` that are still off the map for
+    /// want of coordinates as [`RecordStatus::GeocodeFailed`]; run after their
+    /// features were derived. Flagged records are left as they are.
+    pub async fn mark_geocode_failed(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        record_ids: &[String],
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE deceased_records r
+            SET processing_status = 'geocode_failed', updated_at = CURRENT_TIMESTAMP
+            WHERE r.record_id = ANY($1)
+                AND r.deleted_at IS NULL
+                AND r.coordinates IS NULL
+                AND r.footprint IS NULL
+                AND r.processing_status IN ('completed', 'needs_review')
+                AND NOT EXISTS (SELECT 1 FROM najaf_cemetery_features f WHERE f.feature_id = r.record_id)
+            "#,
+            record_ids
+        )
+        .execute(&mut **tx)
+        .await?;
+        
+        Ok(result.rows_affected())
+    }
+    
+    /// Sets one live record's status once a steward has reviewed it, and
+    /// puts it on or takes it off the map to match. Returns false when no
+    /// live record has this id.
+    pub async fn review_record(
+        &self,
+        record_id: &str,
+        status: RecordStatus,
+        derive_coordinates: bool,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        
+        let record_ids = [record_id.to_string()];
+        let updated = self.set_processing_status(&mut tx, &record_ids, status).await?;
+        if updated == 0 {
+            return Ok(false);
+        }
+        
+        self.update_geojson_features(&mut tx, &record_ids, derive_coordinates).await?;
+        tx.commit().await?;
+        
+        Ok(true)
+    }
+    
+    /// Dashboard aggregates over live records and the processing log.
+    pub async fn get_stats(&self) -> Result<CemeteryStats, sqlx::Error> {
+        let totals = sqlx::query!(
+            r#"
+            SELECT
+                COUNT(*) AS "total_records!",
+                COUNT(coordinates) AS "records_with_coordinates!",
+                LOCALTIMESTAMP AS "generated_at!"
+            FROM deceased_records
+            WHERE deleted_at IS NULL
+            "#
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        
+        let records_per_section = sqlx::query_as!(
+            SectionCount,
+            r#"
+            SELECT section, COUNT(*) AS "records!"
+            FROM deceased_records
+            WHERE deleted_at IS NULL
+            GROUP BY section
+            ORDER BY section NULLS LAST
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        
+        let burials_per_year = sqlx::query_as!(
+            YearCount,
+            r#"
+            SELECT
+                EXTRACT(YEAR FROM burial_date)::INTEGER AS "year!",
+                COUNT(*) AS "burials!"
+            FROM deceased_records
+            WHERE deleted_at IS NULL
+            GROUP BY 1
+            ORDER BY 1
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        
+        let causes_of_death = sqlx::query!(
+            r#"
+            SELECT cause_of_death_code AS "code!", COUNT(*) AS "records!"
+            FROM deceased_records
+            WHERE deleted_at IS NULL AND cause_of_death_code IS NOT NULL
+            GROUP BY cause_of_death_code
+            ORDER BY 2 DESC, 1
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|row| CauseCount { code: row.code, label: None, records: row.records })
+        .collect();
+        
+        let uncoded_causes_of_death = sqlx::query_as!(
+            UncodedCauseCount,
+            r#"
+            SELECT cause_of_death AS "cause_of_death!", COUNT(*) AS "records!"
+            FROM deceased_records
+            WHERE deleted_at IS NULL
+                AND cause_of_death_code IS NULL
+                AND NULLIF(TRIM(cause_of_death), '') IS NOT NULL
+            GROUP BY cause_of_death
+            ORDER BY 2 DESC, 1
+            LIMIT $1
+            "#,
+            MAX_UNCODED_CAUSES
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        
+        let processing = sqlx::query_as!(
+            ProcessingTotals,
+            r#"
+            SELECT
+                COUNT(*) FILTER (WHERE status = 'completed') AS "files_completed!",
+                COUNT(*) FILTER (WHERE status = 'failed') AS "files_failed!",
+                COALESCE(SUM(records_total), 0)::BIGINT AS "records_total!",
+                COALESCE(SUM(records_processed), 0)::BIGINT AS "records_processed!",
+                COALESCE(SUM(records_failed), 0)::BIGINT AS "records_failed!",
+                MAX(processing_end_time) AS last_processed_at
+            FROM file_processing_log
+            "#
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        
+        Ok(CemeteryStats {
+            total_records: totals.total_records,
+            records_with_coordinates: totals.records_with_coordinates,
+            records_per_section,
+            burials_per_year,
+            causes_of_death,
+            uncoded_causes_of_death,
+            processing,
+            generated_at: totals.generated_at,
+        })
+    }
+    
+    /// Live records within `radius_m` metres of a point, nearest first.
+    /// Distances are measured on the spheroid, through a geography cast.
+    pub async fn nearby_records(
+        &self,
+        latitude: f64,
+        longitude: f64,
+        radius_m: f64,
+        limit: i64,
+    ) -> Result<Vec<NearbyRecord>, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                id, record_id, deceased_name, burial_date,
+                section, row_number, plot_number,
+                ST_Y(coordinates) as latitude,
+                ST_X(coordinates) as longitude,
+                processing_status as "processing_status!",
+                ST_Distance(
+                    coordinates::geography,
+                    ST_SetSRID(ST_MakePoint($2, $1), 4326)::geography
+                ) as "distance_m!"
+            FROM deceased_records
+            WHERE deleted_at IS NULL
+                AND ST_DWithin(
+                    coordinates::geography,
+                    ST_SetSRID(ST_MakePoint($2, $1), 4326)::geography,
+                    $3
+                )
+            ORDER BY "distance_m!", id
+            LIMIT $4
+            "#,
+            latitude,
+            longitude,
+            radius_m,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        
+        Ok(rows
+            .into_iter()
+            .map(|row| NearbyRecord {
+                record: DbDeceasedRecord {
+                    id: row.id,
+                    record_id: row.record_id,
+                    deceased_name: row.deceased_name,
+                    burial_date: row.burial_date,
+                    section: row.section,
+                    row_number: row.row_number,
+                    plot_number: row.plot_number,
+                    latitude: row.latitude,
+                    longitude: row.longitude,
+                    processing_status: row.processing_status,
+                },
+                distance_m: row.distance_m,
+            })
+            .collect())
+    }
+    
+    pub async fn search_records(
+        &self,
+        term: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<SearchResult>, sqlx::Error> {
+        // Substring fallback for short queries that trigrams score poorly
+        let pattern = format!(
+            "%{}%",
+            term.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+        );
+        
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                id, record_id, deceased_name, burial_date,
+                section, row_number, plot_number,
+                ST_Y(coordinates) as latitude,
+                ST_X(coordinates) as longitude,
+                processing_status as "processing_status!",
+                GREATEST(
+                    similarity(lower(deceased_name), $1),
+                    COALESCE(similarity(translate(deceased_name_arabic, 'آأإٱًٌٍَُِّْٰـ', 'اااا'), $1), 0)
+                ) as "score!"
+            FROM deceased_records
+            WHERE deleted_at IS NULL
+                AND (
+                    lower(deceased_name) % $1
+                    OR lower(deceased_name) LIKE $2
+                    OR translate(deceased_name_arabic, 'آأإٱًٌٍَُِّْٰـ', 'اااا') % $1
+                    OR translate(deceased_name_arabic, 'آأإٱًٌٍَُِّْٰـ', 'اااا') LIKE $2
+                )
+            ORDER BY "score!" DESC, id
+            LIMIT $3 OFFSET $4
+            "#,
+            term,
+            pattern,
+            limit,
+            offset
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        
+        Ok(rows
+            .into_iter()
+            .map(|row| SearchResult {
+                record: DbDeceasedRecord {
+                    id: row.id,
+                    record_id: row.record_id,
+                    deceased_name: row.deceased_name,
+                    burial_date: row.burial_date,
+                    section: row.section,
+                    row_number: row.row_number,
+                    plot_number: row.plot_number,
+                    latitude: row.latitude,
+                    longitude: row.longitude,
+                    processing_status: row.processing_status,
+                },
+                score: row.score,
+            })
+            .collect())
+    }
+    
+    /// `national_id`s shared by several live records, in `national_id`
+    /// order; with `record_ids`, only those some of these records carry.
+    /// `None` for `limit` returns them all. Reads through `tx` when given.
+    pub async fn shared_national_ids(
+        &self,
+        tx: Option<&mut Transaction<'_, Postgres>>,
+        record_ids: Option<&[String]>,
+        limit: Option<i64>,
+        offset: i64,
+    ) -> Result<Vec<SharedNationalId>, sqlx::Error> {
+        let query = sqlx::query_as!(
+            SharedNationalId,
+            r#"
+            SELECT
+                national_id as "national_id!",
+                array_agg(record_id ORDER BY record_id) as "record_ids!"
+            FROM deceased_records
+            WHERE deleted_at IS NULL
+              AND national_id <> ''
+              AND ($1::text[] IS NULL OR national_id IN (
+                  SELECT national_id FROM deceased_records WHERE record_id = ANY($1)
+              ))
+            GROUP BY national_id
+            HAVING COUNT(*) > 1
+            ORDER BY national_id
+            LIMIT $2 OFFSET $3
+            "#,
+            record_ids as Option<&[String]>,
+            limit,
+            offset
+        );
+        
+        match tx {
+            Some(tx) => query.fetch_all(&mut **tx).await,
+            None => query.fetch_all(&self.pool).await,
+        }
+    }
+    
+    /// `(child, father)` pairs of live records where the child's father
+    /// name resembles a word of the father's Latin or Arabic name, one of
+    /// the two being among `record_ids`. Names are compared lowercased and
+    /// normalized as in `search_records`; the pairs still need scoring.
+    pub async fn family_candidates(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        record_ids: &[String],
+    ) -> Result<Vec<(Person, Person)>, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"
+            WITH pairs AS (
+                SELECT c.id AS child_id, p.id AS father_id
+                FROM deceased_records c
+                JOIN deceased_records p
+                    ON p.id <> c.id
+                    AND p.deleted_at IS NULL
+                    AND (
+                        translate(lower(c.father_name), 'آأإٱًٌٍَُِّْٰـ', 'اااا') <% lower(p.deceased_name)
+                        OR translate(lower(c.father_name), 'آأإٱًٌٍَُِّْٰـ', 'اااا')
+                            <% translate(p.deceased_name_arabic, 'آأإٱًٌٍَُِّْٰـ', 'اااا')
+                    )
+                WHERE c.record_id = ANY($1) AND c.deleted_at IS NULL AND c.father_name <> ''
+                UNION
+                SELECT c.id, p.id
+                FROM deceased_records p
+                JOIN deceased_records c
+                    ON c.id <> p.id
+                    AND c.deleted_at IS NULL
+                    AND c.father_name <> ''
+                    AND (
+                        translate(lower(c.father_name), 'آأإٱًٌٍَُِّْٰـ', 'اااا') <% lower(p.deceased_name)
+                        OR translate(lower(c.father_name), 'آأإٱًٌٍَُِّْٰـ', 'اااا')
+                            <% translate(p.deceased_name_arabic, 'آأإٱًٌٍَُِّْٰـ', 'اااا')
+                    )
+                WHERE p.record_id = ANY($1) AND p.deleted_at IS NULL
+            )
+            SELECT
+                c.record_id AS child_record_id,
+                c.deceased_name AS child_name,
+                c.deceased_name_arabic AS child_name_arabic,
+                c.father_name AS child_father_name,
+                c.grandfather_name AS child_grandfather_name,
+                c.birth_date AS child_birth_date,
+                c.death_date AS child_death_date,
+                p.record_id AS father_record_id,
+                p.deceased_name AS father_name,
+                p.deceased_name_arabic AS father_name_arabic,
+                p.father_name AS father_father_name,
+                p.grandfather_name AS father_grandfather_name,
+                p.birth_date AS father_birth_date,
+                p.death_date AS father_death_date
+            FROM pairs
+            JOIN deceased_records c ON c.id = pairs.child_id
+            JOIN deceased_records p ON p.id = pairs.father_id
+            "#,
+            record_ids
+        )
+        .fetch_all(&mut **tx)
+        .await?;
+        
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let child = Person {
+                    record_id: row.child_record_id,
+                    deceased_name: row.child_name,
+                    deceased_name_arabic: row.child_name_arabic,
+                    father_name: row.child_father_name,
+                    grandfather_name: row.child_grandfather_name,
+                    birth_date: row.child_birth_date,
+                    death_date: row.child_death_date,
+                };
+                let father = Person {
+                    record_id: row.father_record_id,
+                    deceased_name: row.father_name,
+                    deceased_name_arabic: row.father_name_arabic,
+                    father_name: row.father_father_name,
+                    grandfather_name: row.father_grandfather_name,
+                    birth_date: row.father_birth_date,
+                    death_date: row.father_death_date,
+                };
+                (child, father)
+            })
+            .collect())
+    }
+    
+    /// Replaces the family links touching `record_ids`, as child or father,
+    /// with `links`.
+    pub async fn replace_family_links(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        record_ids: &[String],
+        links: &[FamilyLink],
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "DELETE FROM family_links WHERE record_id = ANY($1) OR father_record_id = ANY($1)",
+            record_ids
+        )
+        .execute(&mut **tx)
+        .await?;
+        
+        let children: Vec<&str> = links.iter().map(|link| link.record_id.as_str()).collect();
+        let fathers: Vec<&str> = links.iter().map(|link| link.father_record_id.as_str()).collect();
+        let confidences: Vec<f32> = links.iter().map(|link| link.confidence).collect();
+        
+        sqlx::query!(
+            r#"
+            INSERT INTO family_links (record_id, father_record_id, confidence)
+            SELECT * FROM UNNEST($1::text[], $2::text[], $3::real[])
+            ON CONFLICT (record_id, father_record_id) DO UPDATE SET
+                confidence = EXCLUDED.confidence,
+                created_at = CURRENT_TIMESTAMP
+            "#,
+            &children as &[&str],
+            &fathers as &[&str],
+            &confidences
+        )
+        .execute(&mut **tx)
+        .await?;
+        
+        Ok(())
+    }
+    
+    /// The suggested fathers and children of a live record among live
+    /// records, or `None` when no live record has this id.
+    pub async fn family_relations(&self, record_id: &str) -> Result<Option<FamilyRelations>, sqlx::Error> {
+        let exists = sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM deceased_records WHERE record_id = $1 AND deleted_at IS NULL) as "exists!""#,
+            record_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        if !exists {
+            return Ok(None);
+        }
+        
+        let fathers = sqlx::query_as!(
+            RelatedRecord,
+            r#"
+            SELECT r.record_id, r.deceased_name, r.deceased_name_arabic, r.death_date, l.confidence
+            FROM family_links l
+            JOIN deceased_records r ON r.record_id = l.father_record_id AND r.deleted_at IS NULL
+            WHERE l.record_id = $1
+            ORDER BY l.confidence DESC, r.record_id
+            "#,
+            record_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        
+        let children = sqlx::query_as!(
+            RelatedRecord,
+            r#"
+            SELECT r.record_id, r.deceased_name, r.deceased_name_arabic, r.death_date, l.confidence
+            FROM family_links l
+            JOIN deceased_records r ON r.record_id = l.record_id AND r.deleted_at IS NULL
+            WHERE l.father_record_id = $1
+            ORDER BY l.confidence DESC, r.record_id
+            "#,
+            record_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        
+        Ok(Some(FamilyRelations {
+            record_id: record_id.to_string(),
+            fathers,
+            children,
+        }))
+    }
+    
+    /// The previous states kept for a record, newest first, including those
+    /// of a record since deleted. `None` when no record has this id and
+    /// none ever had one that changed.
+    pub async fn record_history(&self, record_id: &str) -> Result<Option<RecordHistory>, sqlx::Error> {
+        let changes = sqlx::query_as!(
+            RecordChange,
+            r#"
+            SELECT id, operation, changed_fields, previous, source_file, changed_at
+            FROM record_history
+            WHERE record_id = $1
+            ORDER BY changed_at DESC, id DESC
+            "#,
+            record_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        
+        if changes.is_empty() {
+            let exists = sqlx::query_scalar!(
+                r#"SELECT EXISTS(SELECT 1 FROM deceased_records WHERE record_id = $1) as "exists!""#,
+                record_id
+            )
+            .fetch_one(&self.pool)
+            .await?;
+            if !exists {
+                return Ok(None);
+            }
+        }
+        
+        Ok(Some(RecordHistory {
+            record_id: record_id.to_string(),
+            changes,
+        }))
+    }
+    
+    /// Logged processing runs, newest first, optionally only those with
+    /// `status`.
+    pub async fn get_processing_log(
+        &self,
+        status: Option<&str>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<ProcessingLogEntry>, sqlx::Error> {
+        sqlx::query_as!(
+            ProcessingLogEntry,
+            r#"
+            SELECT
+                id, filename, file_hash, file_size, download_time,
+                records_total, records_processed, records_failed, records_with_coordinates,
+                status, error_message,
+                processing_start_time, processing_end_time,
+                COALESCE(
+                    processing_duration_seconds,
+                    EXTRACT(EPOCH FROM processing_end_time - processing_start_time)::float8
+                ) as duration_seconds,
+                created_at
+            FROM file_processing_log
+            WHERE ($1::text IS NULL OR status = $1)
+            ORDER BY created_at DESC, id DESC
+            LIMIT $2 OFFSET $3
+            "#,
+            status,
+            limit,
+            offset
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+    
+    /// True when a completed run imported content with this hash, on its own
+    /// or as one file of a directory.
+    pub async fn is_file_already_processed(&self, file_hash: &str) -> Result<bool, sqlx::Error> {
+        let processed = self.processed_file_hashes(&[file_hash.to_string()]).await?;
+        Ok(!processed.is_empty())
+    }
+    
+    /// Of `file_hashes`, those [`Self::is_file_already_processed`] holds for,
+    /// in one query.
+    pub async fn processed_file_hashes(&self, file_hashes: &[String]) -> Result<HashSet<String>, sqlx::Error> {
+        let hashes = sqlx::query_scalar!(
+            r#"
+            SELECT file_hash AS "file_hash!" FROM processed_files
+            WHERE file_hash = ANY($1)
+            UNION
+            SELECT file_hash AS "file_hash!" FROM file_processing_log
+            WHERE file_hash = ANY($1) AND status = 'completed'
+            "#,
+            file_hashes
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        
+        Ok(hashes.into_iter().collect())
+    }
+    
+    /// Notes the content hash of each file, by name, that a run of
+    /// `source_file` imported.
+    pub async fn record_processed_files(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        source_file: &str,
+        file_hashes: &[(String, String)],
+    ) -> Result<(), sqlx::Error> {
+        // Files with the same content are noted once
+        let by_hash: HashMap<&str, &str> = file_hashes.iter()
+            .map(|(name, hash)| (hash.as_str(), name.as_str()))
+            .collect();
+        if by_hash.is_empty() {
+            return Ok(());
+        }
+        let (hashes, names): (Vec<&str>, Vec<&str>) = by_hash.into_iter().unzip();
+        
+        sqlx::query!(
+            r#"
+            INSERT INTO processed_files (file_hash, file_name, source_file)
+            SELECT hash, name, $3 FROM UNNEST($1::text[], $2::text[]) AS f(hash, name)
+            ON CONFLICT (file_hash) DO UPDATE SET
+                file_name = EXCLUDED.file_name,
+                source_file = EXCLUDED.source_file,
+                processed_at = CURRENT_TIMESTAMP
+            "#,
+            &hashes as &[&str],
+            &names as &[&str],
+            source_file
+        )
+        .execute(&mut **tx)
+        .await?;
+        
+        Ok(())
+    }
+    
+    /// Records each file of the content with this hash that an interrupted
+    /// run committed, by file name.
+    pub async fn checkpoints(&self, file_hash: &str) -> Result<HashMap<String, usize>, sqlx::Error> {
+        let rows = sqlx::query!(
+            "SELECT file_name, records_done FROM processing_checkpoints WHERE file_hash = $1",
+            file_hash
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.file_name, row.records_done.max(0) as usize))
+            .collect())
+    }
+    
+    /// Notes that the first `records_done` records of `file_name` are
+    /// written; committed with them.
+    pub async fn save_checkpoint(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        file_hash: &str,
+        file_name: &str,
+        records_done: usize,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO processing_checkpoints (file_hash, file_name, records_done)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (file_hash, file_name) DO UPDATE SET
+                records_done = EXCLUDED.records_done,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+            file_hash,
+            file_name,
+            i32::try_from(records_done).unwrap_or(i32::MAX)
+        )
+        .execute(&mut **tx)
+        .await?;
+        
+        Ok(())
+    }
+    
+    /// Drops the checkpoints of a run once it has finished.
+    pub async fn clear_checkpoints(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        file_hash: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!("DELETE FROM processing_checkpoints WHERE file_hash = $1", file_hash)
+            .execute(&mut **tx)
+            .await?;
+        
+        Ok(())
+    }
+    
+    /// Records a processing run. The unique index on `file_hash` arbitrates
+    /// between concurrent runs of the same content: an earlier completed row
+    /// is only overwritten when `force` is set, and `Ok(false)` means another
+    /// run already claimed the hash. `tx` is the run's own transaction, begun
+    /// when processing started, so its timestamp is the start time.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn log_file_processing(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        filename: &str,
+        file_hash: &str,
+        file_size: i64,
+        download_time: Option<DateTime<Utc>>,
+        records_total: i32,
+        records_processed: i32,
+        records_failed: i32,
+        records_with_coordinates: i32,
+        status: &str,
+        error_message: Option<&str>,
+        force: bool,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO file_processing_log (
+                filename, file_hash, file_size, download_time,
+                records_total, records_processed, records_failed, records_with_coordinates,
+                status, error_message,
+                processing_start_time, processing_end_time, processing_duration_seconds
+            ) VALUES (
+                $1, $2, $3, $10, $4, $5, $6, $11, $7, $8,
+                CURRENT_TIMESTAMP, clock_timestamp(),
+                EXTRACT(EPOCH FROM clock_timestamp() - CURRENT_TIMESTAMP)
+            )
+            ON CONFLICT (file_hash) DO UPDATE SET
+                filename = EXCLUDED.filename,
+                file_size = EXCLUDED.file_size,
+                download_time = EXCLUDED.download_time,
+                records_total = EXCLUDED.records_total,
+                records_processed = EXCLUDED.records_processed,
+                records_failed = EXCLUDED.records_failed,
+                records_with_coordinates = EXCLUDED.records_with_coordinates,
+                status = EXCLUDED.status,
+                error_message = EXCLUDED.error_message,
+                processing_start_time = EXCLUDED.processing_start_time,
+                processing_end_time = EXCLUDED.processing_end_time,
+                processing_duration_seconds = EXCLUDED.processing_duration_seconds
+            WHERE file_processing_log.status IS DISTINCT FROM 'completed' OR $9
+            "#,
+            filename,
+            file_hash,
+            file_size,
+            records_total,
+            records_processed,
+            records_failed,
+            status,
+            error_message,
+            force,
+            download_time,
+            records_with_coordinates
+        )
+        .execute(&mut **tx)
+        .await?;
+        
+        Ok(result.rows_affected() > 0)
+    }
+    
+    /// Logs a run that failed, on a connection of its own so it outlives
+    /// the run's rolled-back transaction. An entry for content already
+    /// imported successfully is left as it is.
+    pub async fn log_failed_processing(
+        &self,
+        filename: &str,
+        file_hash: &str,
+        file_size: i64,
+        download_time: Option<DateTime<Utc>>,
+        error_message: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = self.begin().await?;
+        let logged = self.log_file_processing(
+            &mut tx,
+            filename,
+            file_hash,
+            file_size,
+            download_time,
+            0,
+            0,
+            0,
+            0,
+            "failed",
+            Some(error_message),
+            false,
+        ).await?;
+        tx.commit().await?;
+        
+        Ok(logged)
+    }
+    
+    pub async fn create_job(
+        &self,
+        job_id: Uuid,
+        request: &serde_json::Value,
+        requested_at: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO processing_jobs (id, status, request, requested_at) VALUES ($1, 'queued', $2, $3)",
+            job_id,
+            request,
+            requested_at
+        )
+        .execute(&self.pool)
+        .await?;
+        
+        Ok(())
+    }
+    
+    /// Keeps the records a run of `source_file` rejected in
+    /// `rejected_records`.
+    pub async fn insert_rejected_records(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        source_file: &str,
+        rejected: &[Rejection],
+    ) -> Result<(), sqlx::Error> {
+        // Seven parameters a row keeps a chunk under the bind limit
+        for chunk in rejected.chunks(INSERT_CHUNK_SIZE) {
+            let mut query = QueryBuilder::<Postgres>::new(
+                "INSERT INTO rejected_records (source_file, file_name, record_id, line_number, fields, errors) ",
+            );
+            query.push_values(chunk, |mut row, rejection| {
+                row.push_bind(source_file)
+                    .push_bind(&rejection.file_name)
+                    .push_bind(&rejection.record_id)
+                    .push_bind(rejection.line_number.and_then(|line| i64::try_from(line).ok()))
+                    .push_bind(&rejection.fields)
+                    .push_bind(&rejection.errors);
+            });
+            query.build().execute(&mut **tx).await?;
+        }
+        
+        Ok(())
+    }
+    
+    /// Rejected records in the order they were kept; with `file`, only
+    /// those of that delivery or of that file within one.
+    pub async fn rejected_records(
+        &self,
+        file: Option<&str>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<RejectedRecord>, sqlx::Error> {
+        sqlx::query_as!(
+            RejectedRecord,
+            r#"
+            SELECT id, source_file, file_name, record_id, line_number, fields, errors, rejected_at
+            FROM rejected_records
+            WHERE $1::text IS NULL OR source_file = $1 OR file_name = $1
+            ORDER BY id
+            LIMIT $2 OFFSET $3
+            "#,
+            file,
+            limit,
+            offset
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+    
+    /// Queues job `job_id` as [`Database::create_job`] does, remembering it
+    /// under idempotency `key` until `expires_at`. When `key` already names
+    /// a job that has not expired, nothing is queued and that job is
+    /// returned instead. Expired keys are dropped on the way.
+    pub async fn create_job_once(
+        &self,
+        job_id: Uuid,
+        request: &serde_json::Value,
+        requested_at: DateTime<Utc>,
+        key: &str,
+        request_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Option<KeyedJob>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        
+        sqlx::query!("DELETE FROM idempotency_keys WHERE expires_at <= CURRENT_TIMESTAMP")
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query!(
+            "INSERT INTO processing_jobs (id, status, request, requested_at) VALUES ($1, 'queued', $2, $3)",
+            job_id,
+            request,
+            requested_at
+        )
+        .execute(&mut *tx)
+        .await?;
+        
+        // A concurrent request with the same key waits here until the first
+        // commits, and then finds its job
+        let claimed = sqlx::query!(
+            r#"
+            INSERT INTO idempotency_keys (key, request_hash, job_id, expires_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (key) DO NOTHING
+            "#,
+            key,
+            request_hash,
+            job_id,
+            expires_at
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected() > 0;
+        
+        if claimed {
+            tx.commit().await?;
+            return Ok(None);
+        }
+        
+        let existing = sqlx::query_as!(
+            KeyedJob,
+            r#"
+            SELECT k.job_id, k.request_hash, j.status
+            FROM idempotency_keys k
+            JOIN processing_jobs j ON j.id = k.job_id
+            WHERE k.key = $1
+            "#,
+            key
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.rollback().await?;
+        
+        Ok(Some(existing))
+    }
+    
+    /// Moves a queued job to `running` and returns its request. `None` means
+    /// the job does not exist or has already been picked up.
+    pub async fn start_job(&self, job_id: Uuid) -> Result<Option<serde_json::Value>, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"
+            UPDATE processing_jobs
+            SET status = 'running', started_at = CURRENT_TIMESTAMP
+            WHERE id = $1 AND status = 'queued'
+            RETURNING request
+            "#,
+            job_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+    
+    pub async fn finish_job(
+        &self,
+        job_id: Uuid,
+        status: &str,
+        result: Option<&serde_json::Value>,
+        error_message: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE processing_jobs
+            SET status = $2, result = $3, error_message = $4, finished_at = CURRENT_TIMESTAMP
+            WHERE id = $1
+            "#,
+            job_id,
+            status,
+            result,
+            error_message
+        )
+        .execute(&self.pool)
+        .await?;
+        
+        Ok(())
+    }
+    
+    pub async fn get_job(&self, job_id: Uuid) -> Result<Option<ProcessingJob>, sqlx::Error> {
+        sqlx::query_as!(
+            ProcessingJob,
+            r#"
+            SELECT id, status, result, error_message, requested_at, created_at, started_at, finished_at
+            FROM processing_jobs
+            WHERE id = $1
+            "#,
+            job_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+    
+    /// Jobs left behind by a previous run of the service, oldest first. Jobs
+    /// that were `running` when it stopped are put back in the queue.
+    pub async fn requeue_unfinished_jobs(&self) -> Result<Vec<Uuid>, sqlx::Error> {
+        let interrupted = sqlx::query!(
+            "UPDATE processing_jobs SET status = 'queued', started_at = NULL WHERE status = 'running'"
+        )
+        .execute(&self.pool)
+        .await?;
+        
+        if interrupted.rows_affected() > 0 {
+            warn!("Requeued {} interrupted processing jobs", interrupted.rows_affected());
+        }
+        
+        sqlx::query_scalar!(
+            "SELECT id FROM processing_jobs WHERE status = 'queued' ORDER BY created_at"
+        )
+        .fetch_all(&self.pool)
+        .await
     }
 }
@@ -0,0 +1,81 @@
+use crate::archive::open_decompressed;
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use encoding_rs_io::{DecodeReaderBytes, DecodeReaderBytesBuilder};
+use log::info;
+use std::io::Read;
+use std::path::Path;
+
+/// Bytes read from the start of a file to guess its encoding.
+const SNIFF_LEN: u64 = 64 * 1024;
+
+/// Guesses the text encoding of `sample`, the leading bytes of a file.
+///
+/// A byte order mark wins, then BOM-less UTF-16 (recognised by the NUL
+/// bytes of its ASCII characters), then UTF-8 if the bytes are valid. Other
+/// inputs, typically Windows-1256 Arabic exports, go to `chardetng`.
+pub fn detect_encoding(sample: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(sample) {
+        return encoding;
+    }
+    
+    if let Some(encoding) = detect_bomless_utf16(sample) {
+        return encoding;
+    }
+    
+    // A multi-byte character cut off at the end of the sample is still UTF-8
+    match std::str::from_utf8(sample) {
+        Ok(_) => return UTF_8,
+        Err(e) if e.error_len().is_none() => return UTF_8,
+        Err(_) => {}
+    }
+    
+    let mut detector = EncodingDetector::new();
+    detector.feed(sample, (sample.len() as u64) < SNIFF_LEN);
+    detector.guess(None, true)
+}
+
+/// CSV headers and separators are ASCII, so UTF-16 text has a NUL in every
+/// other byte: the odd ones for little-endian, the even ones for big-endian.
+fn detect_bomless_utf16(sample: &[u8]) -> Option<&'static Encoding> {
+    let pairs = sample.len() / 2;
+    if pairs < 4 {
+        return None;
+    }
+    
+    let even_nuls = sample.iter().step_by(2).filter(|&&b| b == 0).count();
+    let odd_nuls = sample.iter().skip(1).step_by(2).filter(|&&b| b == 0).count();
+    
+    if even_nuls == 0 && odd_nuls >= pairs / 4 {
+        Some(UTF_16LE)
+    } else if odd_nuls == 0 && even_nuls >= pairs / 4 {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+/// Opens a text file as a UTF-8 byte stream, transcoding from `label` (any
+/// WHATWG encoding label, e.g. `windows-1256`) or from the detected encoding.
+/// Gzipped files are decompressed first.
+pub fn open_utf8(
+    path: &Path,
+    label: Option<&str>,
+) -> Result<DecodeReaderBytes<Box<dyn Read + Send>, Vec<u8>>, anyhow::Error> {
+    let encoding = match label {
+        Some(label) => Encoding::for_label(label.trim().as_bytes())
+            .ok_or_else(|| anyhow::anyhow!("Unknown encoding: {}", label))?,
+        None => {
+            let mut sample = Vec::new();
+            open_decompressed(path)?.take(SNIFF_LEN).read_to_end(&mut sample)?;
+            detect_encoding(&sample)
+        }
+    };
+    
+    info!("Reading {:?} as {}", path, encoding.name());
+    
+    Ok(DecodeReaderBytesBuilder::new()
+        .encoding(Some(encoding))
+        .strip_bom(true)
+        .build(open_decompressed(path)?))
+}
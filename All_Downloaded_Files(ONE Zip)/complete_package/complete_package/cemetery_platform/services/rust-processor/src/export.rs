@@ -0,0 +1,242 @@
+use crate::anonymize::Anonymizer;
+use crate::database::Database;
+use crate::models::{ExportRecord, GeoJsonFeature, GeoJsonGeometry};
+use chrono::NaiveDate;
+use futures_util::TryStreamExt;
+use std::fmt::Write;
+use tokio::sync::mpsc;
+
+/// Header of `/api/export/csv`: the documented import layout, so an export
+/// can be processed again as it is, followed by the personal fields that
+/// `anonymize` leaves out. Re-imported without a schema mapping them, those
+/// are kept in `additional_data`.
+pub const CSV_EXPORT_COLUMNS: [&str; 14] = [
+    "record_id", "deceased_name", "deceased_name_arabic", "death_date", "burial_date",
+    "burial_location", "latitude", "longitude", "section", "row", "plot",
+    "cause_of_death", "national_id", "family_contact",
+];
+
+/// Rows per chunk of a streamed CSV export.
+const CSV_CHUNK_ROWS: usize = 500;
+
+/// Lets Excel tell the file is UTF-8, so Arabic names are not garbled.
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Features read per page of a streamed KML export.
+const KML_PAGE_FEATURES: i64 = 1000;
+
+const KML_HEADER: &str = concat!(
+    "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+    "<kml xmlns=\"http://www.opengis.net/kml/2.2\">\n",
+    "<Document>\n",
+    "<name>Najaf Cemetery</name>\n",
+);
+
+const KML_FOOTER: &str = "</Document>\n</kml>\n";
+
+/// Renders grave features as a KML document for Google Earth, one
+/// `<Placemark>` per feature with the name and burial date in its
+/// description. Footprints become polygons, other graves points.
+pub fn features_to_kml(features: &[GeoJsonFeature]) -> String {
+    let mut kml = String::from(KML_HEADER);
+    features.iter().for_each(|feature| write_placemark(&mut kml, feature));
+    kml.push_str(KML_FOOTER);
+    kml
+}
+
+fn write_placemark(kml: &mut String, feature: &GeoJsonFeature) {
+    let geometry = match &feature.geometry {
+        GeoJsonGeometry::Point([longitude, latitude]) => {
+            format!("<Point><coordinates>{},{}</coordinates></Point>", longitude, latitude)
+        }
+        GeoJsonGeometry::Polygon(rings) => {
+            let Some(outline) = rings.first() else { return };
+            let corners: Vec<String> = outline.iter().map(|[lon, lat]| format!("{},{}", lon, lat)).collect();
+            format!(
+                "<Polygon><outerBoundaryIs><LinearRing><coordinates>{}</coordinates></LinearRing></outerBoundaryIs></Polygon>",
+                corners.join(" ")
+            )
+        }
+    };
+    
+    let properties = &feature.properties;
+    let text = |key: &str| match &properties[key] {
+        serde_json::Value::Null => None,
+        serde_json::Value::String(value) => Some(value.clone()),
+        value => Some(value.to_string()),
+    };
+    
+    let name = text("name").or_else(|| text("record_id")).unwrap_or_default();
+    
+    let mut description = Vec::new();
+    if let Some(record_id) = text("record_id") {
+        description.push(format!("Record: {}", record_id));
+    }
+    if let Some(burial_date) = text("burial_date") {
+        description.push(format!("Burial date: {}", burial_date));
+    }
+    let location: Vec<String> = [("Section", "section"), ("Row", "row"), ("Plot", "plot")]
+        .iter()
+        .filter_map(|(label, key)| text(key).map(|value| format!("{} {}", label, value)))
+        .collect();
+    if !location.is_empty() {
+        description.push(location.join(", "));
+    }
+    
+    // Writing to a String cannot fail
+    let _ = write!(
+        kml,
+        "<Placemark>\n<name>{}</name>\n<description>{}</description>\n{}\n</Placemark>\n",
+        escape_xml(&name),
+        escape_xml(&description.join("\n")),
+        geometry
+    );
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Builds a CSV export a chunk at a time, so it can be sent while the
+/// records are still being read.
+pub struct CsvExportWriter {
+    writer: csv::Writer<Vec<u8>>,
+    pending_rows: usize,
+    anonymizer: Option<Anonymizer>,
+}
+
+impl CsvExportWriter {
+    /// Starts the export with the header, preceded by a UTF-8 byte order
+    /// mark if `bom` is set. With an `anonymizer` the stripped columns are
+    /// left out.
+    pub fn new(bom: bool, anonymizer: Option<Anonymizer>) -> Result<Self, anyhow::Error> {
+        let mut writer = Self::chunk_writer(if bom { UTF8_BOM.to_vec() } else { Vec::new() });
+        match &anonymizer {
+            Some(anonymizer) => writer.write_record(anonymizer.csv_columns())?,
+            None => writer.write_record(CSV_EXPORT_COLUMNS)?,
+        }
+        
+        Ok(Self { writer, pending_rows: 0, anonymizer })
+    }
+    
+    /// Adds a record, returning the CSV written so far once a chunk is full.
+    pub fn write(&mut self, record: &ExportRecord) -> Result<Option<Vec<u8>>, anyhow::Error> {
+        match &self.anonymizer {
+            Some(anonymizer) => self.writer.write_record(anonymizer.csv_row(record)?)?,
+            None => self.writer.serialize(record)?,
+        }
+        self.pending_rows += 1;
+        
+        if self.pending_rows < CSV_CHUNK_ROWS {
+            return Ok(None);
+        }
+        
+        self.pending_rows = 0;
+        self.take_chunk().map(Some)
+    }
+    
+    /// The CSV not yet returned by [`CsvExportWriter::write`].
+    pub fn finish(mut self) -> Result<Vec<u8>, anyhow::Error> {
+        self.take_chunk()
+    }
+    
+    fn take_chunk(&mut self) -> Result<Vec<u8>, anyhow::Error> {
+        let writer = std::mem::replace(&mut self.writer, Self::chunk_writer(Vec::new()));
+        writer.into_inner().map_err(|e| anyhow::anyhow!("Failed to write CSV: {}", e.error()))
+    }
+    
+    fn chunk_writer(buffer: Vec<u8>) -> csv::Writer<Vec<u8>> {
+        csv::WriterBuilder::new().has_headers(false).from_writer(buffer)
+    }
+}
+
+/// Streams the live records matching the filters to `chunks` as CSV. A
+/// failure part way through is sent as the last item, since the response
+/// has already started by then. Stops early if the receiver goes away.
+pub async fn stream_records_csv(
+    db: Database,
+    section: Option<String>,
+    burial_date_from: Option<NaiveDate>,
+    burial_date_to: Option<NaiveDate>,
+    bom: bool,
+    anonymizer: Option<Anonymizer>,
+    chunks: mpsc::Sender<Result<Vec<u8>, anyhow::Error>>,
+) {
+    let result = async {
+        let mut writer = CsvExportWriter::new(bom, anonymizer)?;
+        let mut records = db.export_records(section.as_deref(), burial_date_from, burial_date_to);
+        
+        while let Some(record) = records.try_next().await? {
+            if let Some(chunk) = writer.write(&record)? {
+                if chunks.send(Ok(chunk)).await.is_err() {
+                    return Ok(());
+                }
+            }
+        }
+        
+        let _ = chunks.send(Ok(writer.finish()?)).await;
+        Ok::<_, anyhow::Error>(())
+    }
+    .await;
+    
+    if let Err(e) = result {
+        log::error!("CSV export failed: {}", e);
+        let _ = chunks.send(Err(e)).await;
+    }
+}
+
+/// Streams the grave features, optionally of one section, to `chunks` as a
+/// KML document, reading them a page at a time. Failures are sent as in
+/// [`stream_records_csv`].
+pub async fn stream_features_kml(
+    db: Database,
+    section: Option<String>,
+    anonymizer: Option<Anonymizer>,
+    chunks: mpsc::Sender<Result<Vec<u8>, anyhow::Error>>,
+) {
+    let result = async {
+        if chunks.send(Ok(KML_HEADER.as_bytes().to_vec())).await.is_err() {
+            return Ok(());
+        }
+        
+        let mut offset = 0;
+        loop {
+            let mut features = db.get_features(section.as_deref(), KML_PAGE_FEATURES, offset).await?;
+            if let Some(anonymizer) = &anonymizer {
+                features.iter_mut().for_each(|feature| anonymizer.anonymize_feature(feature));
+            }
+            
+            let mut kml = String::new();
+            features.iter().for_each(|feature| write_placemark(&mut kml, feature));
+            if chunks.send(Ok(kml.into_bytes())).await.is_err() {
+                return Ok(());
+            }
+            
+            // A short page is the last one
+            if (features.len() as i64) < KML_PAGE_FEATURES {
+                break;
+            }
+            offset += KML_PAGE_FEATURES;
+        }
+        
+        let _ = chunks.send(Ok(KML_FOOTER.as_bytes().to_vec())).await;
+        Ok::<_, anyhow::Error>(())
+    }
+    .await;
+    
+    if let Err(e) = result {
+        log::error!("KML export failed: {}", e);
+        let _ = chunks.send(Err(e)).await;
+    }
+}
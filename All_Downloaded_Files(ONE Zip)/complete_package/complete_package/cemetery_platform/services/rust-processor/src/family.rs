@@ -0,0 +1,150 @@
+use crate::arabic::normalize_arabic;
+use chrono::{Months, NaiveDate};
+use std::collections::HashSet;
+
+/// Suggested links scoring below this are not stored.
+pub const MIN_LINK_CONFIDENCE: f32 = 0.5;
+
+/// Share of a link's confidence that rests on the father's given name; the
+/// rest comes from the grandfather's name agreeing with the father's own
+/// father, or is withheld when either is unknown.
+const GIVEN_NAME_WEIGHT: f32 = 0.6;
+
+/// Youngest age at which a father is taken to have had a child.
+const MIN_FATHER_AGE_YEARS: u32 = 12;
+
+/// Longest a child can be born after their father's death.
+const POSTHUMOUS_BIRTH_MONTHS: u32 = 12;
+
+/// First words that make a compound given name with the next word, as in
+/// "Abd al-Karim" or "عبد الكريم", compared after [`normalize_name`].
+const COMPOUND_PREFIXES: &[&str] = &["\u{0639}\u{0628}\u{062F}", "abd", "abdul", "abdel"];
+
+/// What linking needs to know of a record.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Person {
+    pub record_id: String,
+    pub deceased_name: String,
+    pub deceased_name_arabic: Option<String>,
+    pub father_name: Option<String>,
+    pub grandfather_name: Option<String>,
+    pub birth_date: Option<NaiveDate>,
+    pub death_date: NaiveDate,
+}
+
+/// A suggestion that `father_record_id` is the father of `record_id`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FamilyLink {
+    pub record_id: String,
+    pub father_record_id: String,
+    /// From 0 to 1; names are often shared, so even 1 is not proof
+    pub confidence: f32,
+}
+
+/// Lowercases `name` and applies [`normalize_arabic`], with single spaces
+/// between words.
+pub fn normalize_name(name: &str) -> String {
+    normalize_arabic(&name.to_lowercase())
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The given name a full name starts with, two words long when it is a
+/// compound such as "Abd al-Karim".
+pub fn given_name(name: &str) -> Option<String> {
+    let name = normalize_name(name);
+    let mut words = name.split(' ').filter(|word| !word.is_empty());
+    let first = words.next()?;
+    
+    match words.next() {
+        Some(second) if COMPOUND_PREFIXES.contains(&first) => Some(format!("{} {}", first, second)),
+        _ => Some(first.to_string()),
+    }
+}
+
+/// Trigram similarity of two names from 0 to 1, scored the way `pg_trgm`
+/// scores them once both are passed through [`normalize_name`].
+pub fn name_similarity(a: &str, b: &str) -> f32 {
+    let (a, b) = (trigrams(&normalize_name(a)), trigrams(&normalize_name(b)));
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    
+    a.intersection(&b).count() as f32 / union as f32
+}
+
+/// Each word padded with two spaces in front and one behind, cut into
+/// overlapping runs of three characters.
+fn trigrams(text: &str) -> HashSet<[char; 3]> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .flat_map(|word| {
+            let padded: Vec<char> = "  ".chars().chain(word.chars()).chain([' ']).collect();
+            padded.windows(3).map(|gram| [gram[0], gram[1], gram[2]]).collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// How likely `father` is the father of `child`: how well `child`'s
+/// father name matches `father`'s given name, raised when `child`'s
+/// grandfather name matches `father`'s father name. `None` when `child` has
+/// no father name or the dates rule the link out.
+pub fn father_confidence(child: &Person, father: &Person) -> Option<f32> {
+    if child.record_id == father.record_id || !plausible_father(child, father) {
+        return None;
+    }
+    let father_name = child.father_name.as_deref().filter(|name| !name.trim().is_empty())?;
+    
+    let given = [Some(father.deceased_name.as_str()), father.deceased_name_arabic.as_deref()]
+        .into_iter()
+        .flatten()
+        .filter_map(given_name)
+        .map(|given| name_similarity(father_name, &given))
+        .fold(0.0, f32::max);
+    
+    let lineage = match (&child.grandfather_name, &father.father_name) {
+        (Some(grandfather), Some(fathers_father)) => name_similarity(grandfather, fathers_father),
+        _ => 0.0,
+    };
+    
+    Some(given * (GIVEN_NAME_WEIGHT + (1.0 - GIVEN_NAME_WEIGHT) * lineage))
+}
+
+/// False when the dates show `father` could not have fathered `child`: born
+/// too shortly before the child, dead too long before the child's birth, or
+/// born after the child died.
+fn plausible_father(child: &Person, father: &Person) -> bool {
+    if let Some(child_birth) = child.birth_date {
+        if let Some(father_birth) = father.birth_date {
+            if father_birth > child_birth - Months::new(MIN_FATHER_AGE_YEARS * 12) {
+                return false;
+            }
+        }
+        if father.death_date < child_birth - Months::new(POSTHUMOUS_BIRTH_MONTHS) {
+            return false;
+        }
+    }
+    
+    father.birth_date.is_none_or(|father_birth| father_birth < child.death_date)
+}
+
+/// Scores `(child, father)` candidate pairs, keeping links of at least
+/// [`MIN_LINK_CONFIDENCE`], most confident first.
+pub fn suggest_links(candidates: &[(Person, Person)]) -> Vec<FamilyLink> {
+    let mut links: Vec<_> = candidates
+        .iter()
+        .filter_map(|(child, father)| {
+            let confidence = father_confidence(child, father)?;
+            (confidence >= MIN_LINK_CONFIDENCE).then(|| FamilyLink {
+                record_id: child.record_id.clone(),
+                father_record_id: father.record_id.clone(),
+                confidence,
+            })
+        })
+        .collect();
+    links.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    
+    links
+}
@@ -0,0 +1,76 @@
+/// Fewest distinct corners a grave outline can have.
+const MIN_VERTICES: usize = 3;
+
+/// Parses a grave's outline, given as a WKT polygon,
+/// `POLYGON((44.3141 32.0174, 44.3143 32.0174, 44.3143 32.0176, 44.3141 32.0174))`,
+/// a JSON array of `[x, y]` pairs, or `x y` pairs separated by commas (or
+/// `x,y` pairs separated by semicolons). The ring is closed if the source
+/// left it open. Coordinates are checked only for being numbers, as they may
+/// still need reprojecting.
+pub fn parse_footprint(text: &str) -> Result<Vec<[f64; 2]>, String> {
+    let text = text.trim();
+    
+    let mut ring = if text.starts_with('[') {
+        serde_json::from_str::<Vec<[f64; 2]>>(text)
+            .map_err(|_| format!("'{}' is not a JSON array of [x, y] pairs", text))?
+    } else if text.get(..7).is_some_and(|keyword| keyword.eq_ignore_ascii_case("POLYGON")) {
+        let rings = text[7..].trim();
+        let inner = rings
+            .strip_prefix('(')
+            .and_then(|rings| rings.strip_suffix(')'))
+            .map(str::trim)
+            .and_then(|ring| ring.strip_prefix('('))
+            .and_then(|ring| ring.strip_suffix(')'))
+            .ok_or_else(|| format!("'{}' is not a WKT polygon", text))?;
+        if inner.contains('(') || inner.contains(')') {
+            return Err("footprints with holes are not supported".to_string());
+        }
+        parse_pairs(inner.split(','), |pair| pair.split_whitespace().collect())?
+    } else if text.contains(';') {
+        parse_pairs(text.split(';'), |pair| pair.split(',').map(str::trim).collect())?
+    } else {
+        parse_pairs(text.split(','), |pair| pair.split_whitespace().collect())?
+    };
+    
+    if ring.iter().flatten().any(|value| !value.is_finite()) {
+        return Err("footprint coordinates must be finite numbers".to_string());
+    }
+    
+    let mut distinct: Vec<[f64; 2]> = Vec::new();
+    for vertex in &ring {
+        if !distinct.contains(vertex) {
+            distinct.push(*vertex);
+        }
+    }
+    if distinct.len() < MIN_VERTICES {
+        return Err(format!("a footprint needs at least {} distinct corners", MIN_VERTICES));
+    }
+    
+    if ring.first() != ring.last() {
+        ring.push(ring[0]);
+    }
+    
+    Ok(ring)
+}
+
+/// Reads each of `pairs` as two numbers, split by `split`.
+fn parse_pairs<'a>(
+    pairs: impl Iterator<Item = &'a str>,
+    split: impl Fn(&'a str) -> Vec<&'a str>,
+) -> Result<Vec<[f64; 2]>, String> {
+    pairs
+        .map(|pair| match split(pair.trim())[..] {
+            [x, y] => match (x.parse(), y.parse()) {
+                (Ok(x), Ok(y)) => Ok([x, y]),
+                _ => Err(format!("'{}' is not a coordinate pair", pair.trim())),
+            },
+            _ => Err(format!("'{}' is not a coordinate pair", pair.trim())),
+        })
+        .collect()
+}
+
+/// The WKT of a closed ring, for `ST_GeomFromText`.
+pub fn footprint_wkt(ring: &[[f64; 2]]) -> String {
+    let vertices: Vec<String> = ring.iter().map(|[x, y]| format!("{} {}", x, y)).collect();
+    format!("POLYGON(({}))", vertices.join(", "))
+}
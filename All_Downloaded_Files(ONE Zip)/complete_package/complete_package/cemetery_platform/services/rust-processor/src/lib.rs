@@ -0,0 +1,26 @@
+pub mod anonymize;
+pub mod arabic;
+pub mod archive;
+pub mod auth;
+pub mod cause_of_death;
+pub mod config;
+pub mod encoding;
+pub mod export;
+pub mod family;
+pub mod footprint;
+pub mod location;
+pub mod models;
+pub mod national_id;
+pub mod parser;
+pub mod database;
+pub mod metrics;
+pub mod migrations;
+pub mod processor;
+pub mod progress;
+pub mod projection;
+pub mod rate_limit;
+pub mod response_cache;
+pub mod timestamps;
+pub mod transform;
+pub mod upload;
+pub mod webhook;
@@ -0,0 +1,126 @@
+use crate::arabic::normalize_digits;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// A part of a grave's place as `burial_location` writes it.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum LocationPart {
+    Section,
+    Row,
+    Plot,
+}
+
+impl LocationPart {
+    fn field(self) -> &'static str {
+        match self {
+            LocationPart::Section => "section",
+            LocationPart::Row => "row_number",
+            LocationPart::Plot => "plot_number",
+        }
+    }
+}
+
+/// How a source writes section, row and plot into `burial_location`, as in
+/// "A/12/45": the parts in order, and the text between them.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, ToSchema)]
+#[serde(default)]
+pub struct LocationLayout {
+    /// Text between the parts; surrounding spaces are optional when reading
+    pub separator: String,
+    pub parts: Vec<LocationPart>,
+}
+
+impl Default for LocationLayout {
+    fn default() -> Self {
+        Self {
+            separator: "/".to_string(),
+            parts: vec![LocationPart::Section, LocationPart::Row, LocationPart::Plot],
+        }
+    }
+}
+
+/// Section, row and plot of a grave, each as far as it is known.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct GravePlace {
+    pub section: Option<String>,
+    pub row_number: Option<i32>,
+    pub plot_number: Option<i32>,
+}
+
+impl LocationLayout {
+    /// Fails on a blank separator, or on no parts or a repeated part.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.separator.trim().is_empty() {
+            return Err("location_layout: the separator must not be blank".to_string());
+        }
+        if self.parts.is_empty() {
+            return Err("location_layout: at least one part is needed".to_string());
+        }
+        if let Some(repeated) = self.parts.iter().enumerate().find(|(i, part)| self.parts[..*i].contains(part)) {
+            return Err(format!("location_layout: {} is listed twice", repeated.1.field()));
+        }
+        Ok(())
+    }
+    
+    /// Reads `location` as laid out, or `None` when it is free text such as
+    /// "Wadi al-Salam": a different number of parts, a blank part, or a row
+    /// or plot that is not a number. Rows and plots may carry a label, as in
+    /// "Row 12"; the last word is the number.
+    pub fn split(&self, location: &str) -> Option<GravePlace> {
+        let values: Vec<&str> = location.split(self.separator.trim()).map(str::trim).collect();
+        if values.len() != self.parts.len() || values.iter().any(|value| value.is_empty()) {
+            return None;
+        }
+        
+        let mut place = GravePlace::default();
+        for (part, value) in self.parts.iter().zip(values) {
+            match part {
+                LocationPart::Section => place.section = Some(value.to_string()),
+                LocationPart::Row => place.row_number = Some(number(value)?),
+                LocationPart::Plot => place.plot_number = Some(number(value)?),
+            }
+        }
+        Some(place)
+    }
+    
+    /// Writes `place` as laid out, or `None` unless every part is known.
+    pub fn compose(&self, place: &GravePlace) -> Option<String> {
+        let values = self.parts.iter().map(|part| match part {
+            LocationPart::Section => place.section.clone().filter(|section| !section.trim().is_empty()),
+            LocationPart::Row => place.row_number.map(|row| row.to_string()),
+            LocationPart::Plot => place.plot_number.map(|plot| plot.to_string()),
+        });
+        Some(values.collect::<Option<Vec<_>>>()?.join(&self.separator))
+    }
+    
+    /// Where `given` disagrees with what `location` says, one message per
+    /// part, e.g. "burial_location 'A/12/45' gives plot_number 45, but the
+    /// column has 46". Parts missing from either side do not disagree.
+    pub fn contradictions(&self, location: &str, given: &GravePlace) -> Vec<String> {
+        let Some(parsed) = self.split(location) else {
+            return Vec::new();
+        };
+        
+        let text = |number: Option<i32>| number.map(|number| number.to_string());
+        let pairs = [
+            (LocationPart::Section, parsed.section, given.section.clone()),
+            (LocationPart::Row, text(parsed.row_number), text(given.row_number)),
+            (LocationPart::Plot, text(parsed.plot_number), text(given.plot_number)),
+        ];
+        
+        pairs
+            .into_iter()
+            .filter_map(|(part, parsed, given)| {
+                let (parsed, given) = (parsed?, given?);
+                (!parsed.eq_ignore_ascii_case(given.trim())).then(|| {
+                    format!("burial_location '{}' gives {} {}, but the column has {}", location, part.field(), parsed, given)
+                })
+            })
+            .collect()
+    }
+}
+
+fn number(value: &str) -> Option<i32> {
+    normalize_digits(value.split_whitespace().last()?).parse().ok()
+}
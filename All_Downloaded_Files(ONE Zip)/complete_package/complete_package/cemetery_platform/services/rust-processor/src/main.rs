@@ -1,109 +1,2293 @@
-use actix_web::{web, App, HttpResponse, HttpServer, Responder};
-use log::{info, error};
+use actix_multipart::Multipart;
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::error::{InternalError, JsonPayloadError};
+use actix_web::http::header::{self, ContentDisposition};
+use actix_web::middleware::{from_fn, Next};
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer, Responder};
+use log::{info, warn, error};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+use futures_util::TryStreamExt;
 use sqlx::postgres::PgPool;
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, watch};
+use utoipa::{OpenApi, ToSchema};
+use utoipa_swagger_ui::SwaggerUi;
+use uuid::Uuid;
 
-mod models;
-mod parser;
-mod database;
-mod processor;
+use najaf_cemetery_processor::anonymize::Anonymizer;
+use najaf_cemetery_processor::arabic::normalize_arabic;
+use najaf_cemetery_processor::config::Config;
+use najaf_cemetery_processor::export;
+use najaf_cemetery_processor::database::{is_transient, Database, RetryPolicy};
+use najaf_cemetery_processor::location::{LocationLayout, LocationPart};
+use najaf_cemetery_processor::metrics::Metrics;
+use najaf_cemetery_processor::migrations;
+use najaf_cemetery_processor::models::*;
+use najaf_cemetery_processor::national_id::NationalIdMode;
+use najaf_cemetery_processor::database::ImportMode;
+use najaf_cemetery_processor::parser::{Calendar, ColumnRef, CsvSchema, FileFormat, JsonSchema, ParseOptions};
+use najaf_cemetery_processor::processor::{self, DataProcessor, RunOptions};
+use najaf_cemetery_processor::progress::{JobProgress, ProgressReporter};
+use najaf_cemetery_processor::response_cache::{CachedResponse, ResponseCache};
+use najaf_cemetery_processor::timestamps::parse_timestamp;
+use najaf_cemetery_processor::transform::{FieldTransforms, Transform};
+use najaf_cemetery_processor::upload::{self, StageError, StagedFile};
+use najaf_cemetery_processor::webhook::{self, WebhookNotifier};
 
-use models::*;
-use processor::DataProcessor;
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+struct ProcessRequest {
+    data_path: String,
+    metadata: FileMetadata,
+    timestamp: String,
+    source: String,
+    /// Where to post the outcome once the job has finished
+    #[serde(default)]
+    callback_url: Option<String>,
+    #[serde(flatten)]
+    run_options: RunOptions,
+    #[serde(flatten)]
+    parse_options: ParseOptions,
+}
+
+/// Largest JSON body accepted; a process request is well under 1 KiB.
+const MAX_JSON_BODY_BYTES: usize = 64 * 1024;
+
+/// The `options` part of an upload: the processing switches of a
+/// [`ProcessRequest`], which has no other way to reach the service.
+#[derive(Debug, Default, Deserialize)]
+struct UploadOptions {
+    callback_url: Option<String>,
+    #[serde(flatten)]
+    run_options: RunOptions,
+    #[serde(flatten)]
+    parse_options: ParseOptions,
+}
+
+/// The parts of an upload, for the API description; the handler reads
+/// them from the multipart stream itself.
+#[derive(ToSchema)]
+#[allow(dead_code)]
+struct UploadForm {
+    /// The data file
+    #[schema(value_type = String, format = Binary)]
+    file: Vec<u8>,
+    /// Who is uploading; `upload` when left out
+    source: Option<String>,
+    /// JSON holding the `callback_url` and the processing switches of a
+    /// process request
+    options: Option<String>,
+}
+
+impl ProcessRequest {
+    /// Checks the metadata against what `file_processing_log` can store,
+    /// reading times without an offset in `zone`, and returns the request's
+    /// `timestamp` in UTC. `data_path` is checked separately by
+    /// [`processor::resolve_data_path`].
+    fn validate(&self, zone: Tz) -> Result<DateTime<Utc>, String> {
+        let metadata = &self.metadata;
+        
+        if metadata.filename.trim().is_empty() || metadata.filename.chars().count() > 255 {
+            return Err("metadata.filename must be 1 to 255 characters".to_string());
+        }
+        // An empty hash is allowed; the service fills in its own
+        if metadata.file_hash.len() > 64 || !metadata.file_hash.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err("metadata.file_hash must be at most 64 letters or digits".to_string());
+        }
+        if metadata.size < 0 {
+            return Err("metadata.size must not be negative".to_string());
+        }
+        parse_timestamp(&metadata.download_time, zone).map_err(|e| format!("metadata.download_time: {}", e))?;
+        let requested_at = parse_timestamp(&self.timestamp, zone).map_err(|e| format!("timestamp: {}", e))?;
+        
+        self.run_options.validate()?;
+        Ok(requested_at)
+    }
+}
+
+/// A `callback_url` can only be accepted when there is a secret to sign the
+/// callback with.
+fn check_callback_url(callback_url: Option<&str>, webhooks_enabled: bool) -> Result<(), String> {
+    let Some(url) = callback_url else {
+        return Ok(());
+    };
+    if !webhooks_enabled {
+        return Err("callback_url requires WEBHOOK_SECRET to be configured".to_string());
+    }
+    
+    webhook::parse_callback_url(url).map(|_| ())
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+struct ValidateRequest {
+    data_path: String,
+    /// Encoding of the CSV files; detected per file when unset
+    #[serde(default)]
+    encoding: Option<String>,
+    #[serde(default)]
+    max_depth: Option<usize>,
+    #[serde(flatten)]
+    parse_options: ParseOptions,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct ProcessResponse {
+    success: bool,
+    records_read: i32,
+    records_processed: i32,
+    records_inserted: i32,
+    records_updated: i32,
+    /// Records left out by the `import_mode`
+    records_skipped: i32,
+    records_failed: i32,
+    /// `records_failed` as a share of `records_read`, from 0 to 1
+    failure_ratio: f64,
+    /// Records that passed validation with a latitude and longitude, and so
+    /// will show up on the map
+    records_with_coordinates: i32,
+    processing_time_seconds: f64,
+    geojson_features_created: i32,
+    /// Records left off the map because their geometry is invalid or empty
+    geojson_features_skipped: i32,
+    already_processed: bool,
+    /// Nothing was written; the counts are what a real run would do
+    dry_run: bool,
+    /// How failed records were treated: `strict` or `lenient`
+    parse_mode: ParseMode,
+    /// Records dropped or merged because their `record_id` was repeated
+    duplicates_collapsed: i32,
+    /// Records under different ids that look like the same person
+    possible_duplicates: Vec<PossibleDuplicate>,
+    /// Records read from each file
+    files: Vec<FileSummary>,
+    /// Section and plot outlines written from shapefiles
+    sections_imported: i32,
+    /// The run picked up where an interrupted run of the same content stopped
+    resumed: bool,
+    /// Records an interrupted run had already committed, and were skipped
+    records_resumed: i32,
+    /// Files of a directory left out because an earlier run imported the
+    /// same content; `force` reads them again
+    files_already_processed: Vec<String>,
+    errors: Vec<ProcessingError>,
+    warnings: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct JobAcceptedResponse {
+    success: bool,
+    job_id: Uuid,
+    status: String,
+}
+
+/// Body of the callback posted to a job's `callback_url`.
+#[derive(Debug, Serialize)]
+struct JobCallback<'a> {
+    job_id: Uuid,
+    status: &'a str,
+    /// The `ProcessResponse` if the job completed
+    result: Option<&'a serde_json::Value>,
+    error: Option<&'a str>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct JobStatusResponse {
+    job_id: Uuid,
+    status: String,
+    requested_at: Option<DateTime<Utc>>,
+    created_at: NaiveDateTime,
+    started_at: Option<NaiveDateTime>,
+    finished_at: Option<NaiveDateTime>,
+    /// The `ProcessResponse` once the job has completed
+    #[schema(value_type = Option<ProcessResponse>)]
+    result: Option<serde_json::Value>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct JobProgressResponse {
+    job_id: Uuid,
+    status: String,
+    #[serde(flatten)]
+    progress: JobProgress,
+    percent: Option<f64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct ProcessingError {
+    record_id: Option<String>,
+    line_number: Option<u64>,
+    error: String,
+    /// Validation failures only: a stable code and the offending field
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    field: Option<&'static str>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct ErrorResponse {
+    success: bool,
+    error: String,
+    details: Option<String>,
+}
+
+/// Most duplicates one merge request may name.
+const MAX_MERGE_DUPLICATES: usize = 100;
+
+#[derive(Debug, Deserialize)]
+struct MergeRequest {
+    /// The record that is kept
+    primary_id: String,
+    /// Records folded into it and then soft-deleted; earlier ones win
+    duplicate_ids: Vec<String>,
+}
+
+impl MergeRequest {
+    fn validate(&self) -> Result<(), String> {
+        if self.duplicate_ids.is_empty() || self.duplicate_ids.len() > MAX_MERGE_DUPLICATES {
+            return Err(format!("duplicate_ids must list 1 to {} records", MAX_MERGE_DUPLICATES));
+        }
+        if self.duplicate_ids.contains(&self.primary_id) {
+            return Err("primary_id cannot also be a duplicate".to_string());
+        }
+        
+        let mut seen = std::collections::HashSet::new();
+        if let Some(repeated) = self.duplicate_ids.iter().find(|id| !seen.insert(*id)) {
+            return Err(format!("duplicate_ids lists {} more than once", repeated));
+        }
+        
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct MergeResponse {
+    success: bool,
+    #[serde(flatten)]
+    merge: RecordMerge,
+}
+
+#[derive(Debug, Deserialize)]
+struct RebuildFeaturesQuery {
+    section: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct RebuildFeaturesResponse {
+    success: bool,
+    section: Option<String>,
+    features_written: i32,
+    /// Records left off the map because their geometry is invalid or empty
+    features_skipped: i32,
+}
+
+#[derive(Debug, Serialize)]
+struct DeleteResponse {
+    success: bool,
+    record_id: String,
+    hard: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReviewRequest {
+    status: RecordStatus,
+}
+
+#[derive(Debug, Serialize)]
+struct ReviewResponse {
+    success: bool,
+    record_id: String,
+    status: RecordStatus,
+}
+
+/// Default and maximum page sizes for `/api/features` and `/api/sections`
+const DEFAULT_FEATURES_LIMIT: i64 = 1000;
+const MAX_FEATURES_LIMIT: i64 = 10_000;
+
+#[derive(Debug, Deserialize)]
+struct FeaturesQuery {
+    section: Option<String>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+    /// Leave out the personal fields in `ANONYMIZE_FIELDS`
+    #[serde(default)]
+    anonymize: bool,
+    /// With `anonymize`, replace names with a pseudonymous token
+    #[serde(default)]
+    pseudonymize_names: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExportQuery {
+    section: Option<String>,
+    #[serde(default)]
+    anonymize: bool,
+    #[serde(default)]
+    pseudonymize_names: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct CsvExportQuery {
+    section: Option<String>,
+    /// Inclusive range on `burial_date`
+    burial_date_from: Option<NaiveDate>,
+    burial_date_to: Option<NaiveDate>,
+    /// Start the file with a UTF-8 byte order mark for Excel
+    #[serde(default)]
+    bom: bool,
+    #[serde(default)]
+    anonymize: bool,
+    #[serde(default)]
+    pseudonymize_names: bool,
+}
+
+/// Default and maximum page sizes for `/api/search`
+const DEFAULT_SEARCH_LIMIT: i64 = 20;
+const MAX_SEARCH_LIMIT: i64 = 100;
+
+#[derive(Debug, Deserialize)]
+struct SearchQuery {
+    q: String,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+/// Default and largest radius, in metres, and default and maximum result
+/// counts for `/api/records/nearby`
+const DEFAULT_NEARBY_RADIUS_M: f64 = 100.0;
+const MAX_NEARBY_RADIUS_M: f64 = 5000.0;
+const DEFAULT_NEARBY_LIMIT: i64 = 50;
+const MAX_NEARBY_LIMIT: i64 = 500;
+
+#[derive(Debug, Deserialize)]
+struct NearbyQuery {
+    lat: f64,
+    lon: f64,
+    radius_m: Option<f64>,
+    limit: Option<i64>,
+}
+
+impl NearbyQuery {
+    fn validate(&self) -> Result<(), String> {
+        if !(-90.0..=90.0).contains(&self.lat) {
+            return Err("lat must be between -90 and 90".to_string());
+        }
+        if !(-180.0..=180.0).contains(&self.lon) {
+            return Err("lon must be between -180 and 180".to_string());
+        }
+        if self.radius_m.is_some_and(|radius| !(radius > 0.0 && radius <= MAX_NEARBY_RADIUS_M)) {
+            return Err(format!("radius_m must be more than 0 and at most {}", MAX_NEARBY_RADIUS_M));
+        }
+        if self.limit.is_some_and(|limit| !(1..=MAX_NEARBY_LIMIT).contains(&limit)) {
+            return Err(format!("limit must be between 1 and {}", MAX_NEARBY_LIMIT));
+        }
+        
+        Ok(())
+    }
+}
+
+/// Default and maximum page sizes for `/api/records`
+const DEFAULT_RECORDS_LIMIT: i64 = 50;
+const MAX_RECORDS_LIMIT: i64 = 500;
+
+/// Header naming a `/api/process` request, so that a retry of it gets the
+/// job the first one queued rather than another run.
+const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// Header set on responses to a repeated `Idempotency-Key`.
+const IDEMPOTENT_REPLAYED_HEADER: &str = "Idempotent-Replayed";
+
+/// Longest `Idempotency-Key` accepted, the size of its column.
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 255;
+
+/// Header carrying how many records match a `/api/records` query in all.
+const TOTAL_COUNT_HEADER: &str = "X-Total-Count";
+
+/// Header carrying the `after` value for the next `/api/records` page.
+const NEXT_CURSOR_HEADER: &str = "X-Next-Cursor";
+
+#[derive(Debug, Deserialize)]
+struct RecordsQuery {
+    section: Option<String>,
+    /// Inclusive range on `burial_date`
+    burial_date_from: Option<NaiveDate>,
+    burial_date_to: Option<NaiveDate>,
+    has_coordinates: Option<bool>,
+    /// `?status=needs_review` lists the review queue
+    #[serde(alias = "status")]
+    processing_status: Option<RecordStatus>,
+    /// `X-Next-Cursor` of the previous page
+    after: Option<String>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+impl RecordsQuery {
+    fn validate(&self) -> Result<(), String> {
+        if let (Some(from), Some(to)) = (self.burial_date_from, self.burial_date_to) {
+            if from > to {
+                return Err("burial_date_from must not be after burial_date_to".to_string());
+            }
+        }
+        
+        if self.after.is_some() && self.offset.is_some() {
+            return Err("Use either after or offset, not both".to_string());
+        }
+        if self.limit.is_some_and(|limit| !(1..=MAX_RECORDS_LIMIT).contains(&limit)) {
+            return Err(format!("limit must be between 1 and {}", MAX_RECORDS_LIMIT));
+        }
+        if self.offset.is_some_and(|offset| offset < 0) {
+            return Err("offset must not be negative".to_string());
+        }
+        
+        Ok(())
+    }
+    
+    /// The filter the query describes, with the `extra.<key>` parameters of
+    /// `query_string`, which the struct cannot name, as `additional_data`
+    /// filters.
+    fn cursor(&self) -> Result<Option<RecordCursor>, String> {
+        self.after.as_deref().map(str::parse).transpose()
+    }
+    
+    fn filter(&self, query_string: &str) -> Result<RecordFilter, String> {
+        let params = web::Query::<Vec<(String, String)>>::from_query(query_string).map_err(|e| e.to_string())?;
+        
+        RecordFilter {
+            section: self.section.clone(),
+            burial_date_from: self.burial_date_from,
+            burial_date_to: self.burial_date_to,
+            has_coordinates: self.has_coordinates,
+            processing_status: self.processing_status,
+            ..RecordFilter::default()
+        }
+        .with_extra(params.iter().map(|(name, value)| (name.as_str(), value.as_str())))
+    }
+}
+
+/// Default and maximum page sizes for `/api/processing-log`
+const DEFAULT_LOG_LIMIT: i64 = 50;
+const MAX_LOG_LIMIT: i64 = 500;
+
+#[derive(Debug, Deserialize)]
+struct ProcessingLogQuery {
+    status: Option<String>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RejectedQuery {
+    /// A delivery, or a file within one
+    file: Option<String>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+/// Default and maximum page sizes for `/api/data-quality/duplicate-ids`
+const DEFAULT_DUPLICATE_IDS_LIMIT: i64 = 100;
+const MAX_DUPLICATE_IDS_LIMIT: i64 = 1000;
+
+#[derive(Debug, Deserialize)]
+struct DuplicateIdsQuery {
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DeleteQuery {
+    /// Remove the row permanently instead of setting `deleted_at`
+    #[serde(default)]
+    hard: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct BboxQuery {
+    min_lon: f64,
+    min_lat: f64,
+    max_lon: f64,
+    max_lat: f64,
+    limit: Option<i64>,
+    #[serde(default)]
+    anonymize: bool,
+    #[serde(default)]
+    pseudonymize_names: bool,
+}
+
+impl BboxQuery {
+    fn validate(&self) -> Result<(), String> {
+        for (name, lon) in [("min_lon", self.min_lon), ("max_lon", self.max_lon)] {
+            if !(-180.0..=180.0).contains(&lon) {
+                return Err(format!("{} must be between -180 and 180", name));
+            }
+        }
+        
+        for (name, lat) in [("min_lat", self.min_lat), ("max_lat", self.max_lat)] {
+            if !(-90.0..=90.0).contains(&lat) {
+                return Err(format!("{} must be between -90 and 90", name));
+            }
+        }
+        
+        if self.min_lon >= self.max_lon {
+            return Err("min_lon must be less than max_lon".to_string());
+        }
+        
+        if self.min_lat >= self.max_lat {
+            return Err("min_lat must be less than max_lat".to_string());
+        }
+        
+        Ok(())
+    }
+}
+
+/// Progress of the jobs being run, by job id.
+type RunningJobs = Arc<std::sync::Mutex<HashMap<Uuid, watch::Receiver<JobProgress>>>>;
+
+#[derive(Clone)]
+struct AppState {
+    db_pool: Arc<PgPool>,
+    job_queue: mpsc::UnboundedSender<Uuid>,
+    running_jobs: RunningJobs,
+    /// Shared with the job worker; `/api/validate` checks records with it
+    processor: Arc<DataProcessor>,
+    metrics: Metrics,
+    /// Settings read at startup
+    config: Arc<Config>,
+}
+
+/// The response for `key`, kept in the response cache or computed by
+/// `compute` when caching is off. `compute` fails with what to report.
+async fn cached<F, Fut>(state: &AppState, key: &str, compute: F) -> Result<Arc<CachedResponse>, String>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<String, String>>,
+{
+    match &state.config.response_cache {
+        Some(cache) => cache.get_or_try_insert(key, compute).await,
+        None => compute().await.map(|body| Arc::new(CachedResponse::new(body))),
+    }
+}
+
+/// Serves `response` with its `ETag`, or as 304 Not Modified when the
+/// request's `If-None-Match` already names it.
+fn respond_cached(req: &HttpRequest, state: &AppState, response: &CachedResponse, content_type: &str) -> HttpResponse {
+    let cache_control = match &state.config.response_cache {
+        Some(cache) => format!("private, max-age={}", cache.ttl().as_secs()),
+        None => "no-cache".to_string(),
+    };
+    let not_modified = req
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|tags| response.matches(tags));
+    
+    let mut builder = if not_modified { HttpResponse::NotModified() } else { HttpResponse::Ok() };
+    builder
+        .insert_header((header::ETAG, response.etag.clone()))
+        .insert_header((header::CACHE_CONTROL, cache_control));
+    
+    if not_modified {
+        builder.finish()
+    } else {
+        builder.content_type(content_type).body(response.body.clone())
+    }
+}
+
+/// Drops cached responses once the records behind them have changed.
+fn forget_cached_responses(state: &AppState) {
+    if let Some(cache) = &state.config.response_cache {
+        cache.invalidate();
+    }
+}
+
+/// The anonymizer for an export's `anonymize` and `pseudonymize_names`
+/// parameters, `None` for a full export.
+fn export_anonymizer(
+    state: &AppState,
+    anonymize: bool,
+    pseudonymize_names: bool,
+) -> Result<Option<Anonymizer>, String> {
+    match (anonymize, pseudonymize_names) {
+        (false, false) => Ok(None),
+        (false, true) => Err("pseudonymize_names needs anonymize=true".to_string()),
+        (true, _) => Ok(Some((*state.config.anonymizer).clone().with_pseudonymized_names(pseudonymize_names))),
+    }
+}
+
+/// How long `/health` waits for the database before reporting it down.
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Serialize)]
+struct HealthResponse {
+    status: &'static str,
+    service: &'static str,
+    version: &'static str,
+    database: DatabaseHealth,
+    pool: PoolHealth,
+}
+
+#[derive(Debug, Serialize)]
+struct DatabaseHealth {
+    /// `ok` or `unavailable`
+    status: &'static str,
+    latency_ms: Option<f64>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct PoolHealth {
+    /// Open connections, idle or in use
+    size: u32,
+    idle: usize,
+    max_connections: u32,
+}
+
+/// Paths served without an API key, for load balancers and orchestrators,
+/// and the API description.
+const PUBLIC_PATHS: &[&str] = &["/health", "/health/live", OPENAPI_PATH];
+
+/// Where the OpenAPI description is served.
+const OPENAPI_PATH: &str = "/api-docs/openapi.json";
+
+/// Prefix of the Swagger UI pages, which are served without an API key too.
+const SWAGGER_UI_PREFIX: &str = "/swagger-ui/";
+
+/// Whether `path` is served without an API key and outside rate limiting.
+fn is_public(path: &str) -> bool {
+    PUBLIC_PATHS.contains(&path) || path.starts_with(SWAGGER_UI_PREFIX)
+}
+
+/// Rejects requests without a configured API key in their `Authorization`
+/// header, except to [`is_public`] paths.
+async fn require_api_key(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    let api_keys = req
+        .app_data::<web::Data<AppState>>()
+        .and_then(|state| state.config.api_keys.clone());
+    
+    if let Some(api_keys) = api_keys {
+        let authorization = req.headers().get(header::AUTHORIZATION).and_then(|value| value.to_str().ok());
+        
+        if !is_public(req.path()) && !api_keys.authorizes(authorization) {
+            warn!("Rejected unauthenticated {} {}", req.method(), req.path());
+            
+            let response = HttpResponse::Unauthorized()
+                .insert_header((header::WWW_AUTHENTICATE, "Bearer"))
+                .json(ErrorResponse {
+                    success: false,
+                    error: "Missing or invalid API key".to_string(),
+                    details: None,
+                });
+            return Ok(req.into_response(response).map_into_right_body());
+        }
+    }
+    
+    Ok(next.call(req).await?.map_into_left_body())
+}
+
+/// Answers 429 once a client has used up its requests, except to
+/// [`is_public`] paths. Clients are told apart by API key when they present a
+/// valid one, and otherwise by IP address.
+async fn rate_limit(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    let Some(state) = req.app_data::<web::Data<AppState>>().cloned() else {
+        return Ok(next.call(req).await?.map_into_left_body());
+    };
+    
+    if let (Some(limiter), false) = (&state.config.rate_limiter, is_public(req.path())) {
+        let authorization = req.headers().get(header::AUTHORIZATION).and_then(|value| value.to_str().ok());
+        
+        // Only a valid key names a client; anyone can make up an invalid one
+        let client = match (&state.config.api_keys, authorization) {
+            (Some(keys), Some(authorization)) if keys.authorizes(Some(authorization)) => {
+                format!("key:{}", authorization.trim())
+            }
+            _ => format!("ip:{}", req.peer_addr().map(|addr| addr.ip().to_string()).unwrap_or_default()),
+        };
+        
+        if let Err(wait) = limiter.check(&client, Instant::now()) {
+            let retry_after = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+            warn!("Rate limited {} {} from {}", req.method(), req.path(), client.split(':').next().unwrap_or_default());
+            
+            let response = HttpResponse::TooManyRequests()
+                .insert_header((header::RETRY_AFTER, retry_after.to_string()))
+                .json(ErrorResponse {
+                    success: false,
+                    error: "Too many requests".to_string(),
+                    details: Some(format!(
+                        "At most {} requests per minute are allowed; retry in {} s",
+                        limiter.requests_per_minute(),
+                        retry_after
+                    )),
+                });
+            return Ok(req.into_response(response).map_into_right_body());
+        }
+    }
+    
+    Ok(next.call(req).await?.map_into_left_body())
+}
+
+/// Answers malformed or oversized JSON bodies in the usual error shape.
+fn json_error_handler(err: JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    let response = match err {
+        JsonPayloadError::OverflowKnownLength { .. } | JsonPayloadError::Overflow { .. } => {
+            HttpResponse::PayloadTooLarge()
+        }
+        _ => HttpResponse::BadRequest(),
+    }
+    .json(ErrorResponse {
+        success: false,
+        error: "Invalid request body".to_string(),
+        details: Some(err.to_string()),
+    });
+    
+    InternalError::from_response(err, response).into()
+}
+
+// Liveness: the process is up and serving, whatever the database's state
+#[utoipa::path(
+    get,
+    path = "/health/live",
+    tag = "health",
+    responses((status = 200, description = "The service is up", body = Object)),
+)]
+async fn liveness_check() -> impl Responder {
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": "healthy",
+        "service": "najaf-cemetery-processor",
+        "version": env!("CARGO_PKG_VERSION")
+    }))
+}
+
+// Readiness: healthy only while the database answers
+async fn health_check(state: web::Data<AppState>) -> impl Responder {
+    let db = Database::new((*state.db_pool).clone());
+    let started = Instant::now();
+    
+    let database = match tokio::time::timeout(HEALTH_CHECK_TIMEOUT, db.ping()).await {
+        Ok(Ok(())) => DatabaseHealth {
+            status: "ok",
+            latency_ms: Some(started.elapsed().as_secs_f64() * 1000.0),
+            error: None,
+        },
+        Ok(Err(e)) => DatabaseHealth { status: "unavailable", latency_ms: None, error: Some(e.to_string()) },
+        Err(_) => DatabaseHealth {
+            status: "unavailable",
+            latency_ms: None,
+            error: Some(format!("no answer within {:?}", HEALTH_CHECK_TIMEOUT)),
+        },
+    };
+    
+    let healthy = database.error.is_none();
+    if let Some(e) = &database.error {
+        warn!("Health check failed: database {}", e);
+    }
+    
+    let body = HealthResponse {
+        status: if healthy { "healthy" } else { "unhealthy" },
+        service: "najaf-cemetery-processor",
+        version: env!("CARGO_PKG_VERSION"),
+        database,
+        pool: PoolHealth {
+            size: state.db_pool.size(),
+            idle: state.db_pool.num_idle(),
+            max_connections: state.db_pool.options().get_max_connections(),
+        },
+    };
+    
+    if healthy {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    }
+}
+
+// Prometheus scrape endpoint
+async fn get_metrics(state: web::Data<AppState>) -> impl Responder {
+    match state.metrics.render(&state.db_pool) {
+        Ok(body) => HttpResponse::Ok()
+            .content_type("text/plain; version=0.0.4")
+            .body(body),
+        Err(e) => {
+            error!("Failed to render metrics: {}", e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                success: false,
+                error: "Failed to render metrics".to_string(),
+                details: Some(e.to_string()),
+            })
+        }
+    }
+}
+
+// Main processing endpoint: queues the run and returns immediately
+#[utoipa::path(
+    post,
+    path = "/api/process",
+    tag = "processing",
+    request_body = ProcessRequest,
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Names the request, so a retry with the same key gets the first job back"),
+    ),
+    responses(
+        (status = 202, description = "The run is queued, or was queued by an earlier request with the same `Idempotency-Key`", body = JobAcceptedResponse),
+        (status = 400, description = "The request is invalid", body = ErrorResponse),
+        (status = 422, description = "The `Idempotency-Key` was sent with a different request", body = ErrorResponse),
+        (status = 500, description = "The job could not be stored", body = ErrorResponse),
+    ),
+)]
+async fn process_data(
+    http_req: HttpRequest,
+    mut req: web::Json<ProcessRequest>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    info!("Received processing request for: {}", req.data_path);
+    info!("Source file: {}", req.metadata.filename);
+    info!("Requested by {} at {}", req.source, req.timestamp);
+    
+    let resolved = req.validate(state.config.timezone).and_then(|requested_at| {
+        let key = idempotency_key(&http_req)?;
+        check_callback_url(req.callback_url.as_deref(), state.config.webhooks.is_some())?;
+        let path = processor::resolve_data_path(&state.config.data_base_dir, &req.data_path)?;
+        path.into_os_string()
+            .into_string()
+            .map(|path| (path, key, requested_at))
+            .map_err(|_| "data_path must be valid UTF-8".to_string())
+    });
+    
+    // The job stores the resolved path, so the worker never sees the
+    // caller's version
+    let (key, requested_at) = match resolved {
+        Ok((path, key, requested_at)) => {
+            req.data_path = path;
+            (key, requested_at)
+        }
+        Err(e) => {
+            warn!("Rejected processing request: {}", e);
+            
+            return HttpResponse::BadRequest().json(ErrorResponse {
+                success: false,
+                error: "Invalid processing request".to_string(),
+                details: Some(e),
+            });
+        }
+    };
+    
+    queue_job(&state, Uuid::new_v4(), &req, requested_at, key.as_deref()).await
+}
+
+/// The `Idempotency-Key` of a request, if it has one: 1 to
+/// [`MAX_IDEMPOTENCY_KEY_LEN`] visible ASCII characters.
+fn idempotency_key(req: &HttpRequest) -> Result<Option<String>, String> {
+    let Some(value) = req.headers().get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(None);
+    };
+    
+    match value.to_str().map(str::trim) {
+        Ok(key) if !key.is_empty() && key.len() <= MAX_IDEMPOTENCY_KEY_LEN => Ok(Some(key.to_string())),
+        _ => Err(format!(
+            "{} must be 1 to {} visible ASCII characters",
+            IDEMPOTENCY_KEY_HEADER, MAX_IDEMPOTENCY_KEY_LEN
+        )),
+    }
+}
+
+/// Persists `req`, made at `requested_at`, as job `job_id` and hands it to
+/// the worker. With an `idempotency_key` that already names a job, that job
+/// is answered with instead, if it was queued for the same request.
+async fn queue_job(
+    state: &AppState,
+    job_id: Uuid,
+    req: &ProcessRequest,
+    requested_at: DateTime<Utc>,
+    idempotency_key: Option<&str>,
+) -> HttpResponse {
+    let db = Database::new((*state.db_pool).clone());
+    
+    let request = match serde_json::to_value(req) {
+        Ok(request) => request,
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                success: false,
+                error: "Failed to queue processing job".to_string(),
+                details: Some(e.to_string()),
+            });
+        }
+    };
+    
+    let request_hash = format!("{:x}", Sha256::digest(request.to_string().as_bytes()));
+    let created = match idempotency_key {
+        Some(key) => {
+            let expires_at = Utc::now() + state.config.idempotency_key_ttl;
+            db.create_job_once(job_id, &request, requested_at, key, &request_hash, expires_at).await
+        }
+        None => db.create_job(job_id, &request, requested_at).await.map(|()| None),
+    };
+    
+    match created {
+        Ok(None) => {}
+        Ok(Some(existing)) if existing.request_hash != request_hash => {
+            warn!("Idempotency key {:?} was reused for a different request", idempotency_key);
+            
+            return HttpResponse::UnprocessableEntity().json(ErrorResponse {
+                success: false,
+                error: "Idempotency key reused".to_string(),
+                details: Some(format!(
+                    "{} was first sent with a different request, which queued job {}",
+                    IDEMPOTENCY_KEY_HEADER, existing.job_id
+                )),
+            });
+        }
+        Ok(Some(existing)) => {
+            info!("Repeated idempotency key; answering with job {}", existing.job_id);
+            
+            return HttpResponse::Accepted()
+                .insert_header((IDEMPOTENT_REPLAYED_HEADER, "true"))
+                .json(JobAcceptedResponse {
+                    success: true,
+                    job_id: existing.job_id,
+                    status: existing.status,
+                });
+        }
+        Err(e) => {
+            error!("Failed to create job: {}", e);
+            
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                success: false,
+                error: "Failed to queue processing job".to_string(),
+                details: Some(e.to_string()),
+            });
+        }
+    }
+    
+    // The job row is already persisted, so a closed queue only delays the
+    // job until the next restart picks it up
+    if state.job_queue.send(job_id).is_err() {
+        error!("Job worker is not running; job {} stays queued", job_id);
+    }
+    
+    info!("Queued processing job {}", job_id);
+    
+    HttpResponse::Accepted().json(JobAcceptedResponse {
+        success: true,
+        job_id,
+        status: "queued".to_string(),
+    })
+}
+
+// Browser upload: stages the `file` part and queues it for processing
+#[utoipa::path(
+    post,
+    path = "/api/upload",
+    tag = "processing",
+    request_body(content = UploadForm, content_type = "multipart/form-data"),
+    responses(
+        (status = 202, description = "The upload is staged and queued", body = JobAcceptedResponse),
+        (status = 400, description = "The upload is invalid", body = ErrorResponse),
+        (status = 413, description = "The file is over `UPLOAD_MAX_MB`", body = ErrorResponse),
+        (status = 500, description = "The upload could not be stored", body = ErrorResponse),
+    ),
+)]
+async fn upload_file(
+    mut payload: Multipart,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    let job_id = Uuid::new_v4();
+    let staging_dir = state.config.upload.dir.join(job_id.to_string());
+    
+    let req = match receive_upload(&mut payload, &staging_dir, &state).await {
+        Ok(req) => req,
+        Err(response) => {
+            if let Err(e) = tokio::fs::remove_dir_all(&staging_dir).await {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    warn!("Failed to remove staged upload {:?}: {}", staging_dir, e);
+                }
+            }
+            return response;
+        }
+    };
+    
+    info!(
+        "Received upload {} ({} bytes) from {}",
+        req.metadata.filename, req.metadata.size, req.source
+    );
+    
+    queue_job(&state, job_id, &req, Utc::now(), None).await
+}
+
+/// Reads the parts of an upload, writing the file into `staging_dir`, and
+/// builds the processing request for it. Parts other than `file`, `source`
+/// and `options` are ignored.
+async fn receive_upload(
+    payload: &mut Multipart,
+    staging_dir: &std::path::Path,
+    state: &AppState,
+) -> Result<ProcessRequest, HttpResponse> {
+    let max_size = state.config.upload.max_size;
+    let bad_request = |details: String| {
+        HttpResponse::BadRequest().json(ErrorResponse {
+            success: false,
+            error: "Invalid upload".to_string(),
+            details: Some(details),
+        })
+    };
+    let write_failed = |e: std::io::Error| {
+        error!("Failed to stage upload: {}", e);
+        HttpResponse::InternalServerError().json(ErrorResponse {
+            success: false,
+            error: "Failed to store upload".to_string(),
+            details: Some(e.to_string()),
+        })
+    };
+    
+    let mut staged = None;
+    let mut source = None;
+    let mut options = UploadOptions::default();
+    
+    while let Some(mut field) = payload.try_next().await.map_err(|e| bad_request(e.to_string()))? {
+        match field.name() {
+            Some("file") => {
+                if staged.is_some() {
+                    return Err(bad_request("Only one file can be uploaded at a time".to_string()));
+                }
+                
+                let client_name = field
+                    .content_disposition()
+                    .and_then(|disposition| disposition.get_filename())
+                    .unwrap_or_default();
+                let filename = upload::upload_file_name(client_name).map_err(bad_request)?;
+                
+                tokio::fs::create_dir_all(staging_dir).await.map_err(write_failed)?;
+                let path = staging_dir.join(&filename);
+                let mut file = StagedFile::create(&path, max_size).await.map_err(write_failed)?;
+                
+                while let Some(chunk) = field.try_next().await.map_err(|e| bad_request(e.to_string()))? {
+                    match file.write(&chunk).await {
+                        Ok(()) => {}
+                        Err(StageError::TooLarge(_)) => {
+                            return Err(HttpResponse::PayloadTooLarge().json(ErrorResponse {
+                                success: false,
+                                error: "Upload too large".to_string(),
+                                details: Some(format!("{}: {}", filename, StageError::TooLarge(max_size))),
+                            }));
+                        }
+                        Err(StageError::Io(e)) => return Err(write_failed(e)),
+                    }
+                }
+                
+                let (file_hash, size) = file.finish().await.map_err(write_failed)?;
+                staged = Some((filename, path, file_hash, size));
+            }
+            Some(name @ ("source" | "options")) => {
+                let name = name.to_string();
+                let mut text = Vec::new();
+                
+                while let Some(chunk) = field.try_next().await.map_err(|e| bad_request(e.to_string()))? {
+                    if text.len() + chunk.len() > MAX_JSON_BODY_BYTES {
+                        return Err(bad_request(format!("The {} part is too large", name)));
+                    }
+                    text.extend_from_slice(&chunk);
+                }
+                
+                if name == "source" {
+                    source = Some(String::from_utf8_lossy(&text).trim().to_string());
+                } else {
+                    options = serde_json::from_slice(&text)
+                        .map_err(|e| bad_request(format!("Invalid options: {}", e)))?;
+                }
+            }
+            _ => {}
+        }
+    }
+    
+    let Some((filename, path, file_hash, size)) = staged else {
+        return Err(bad_request("The upload has no 'file' part".to_string()));
+    };
+    options.run_options.validate().map_err(bad_request)?;
+    check_callback_url(options.callback_url.as_deref(), state.config.webhooks.is_some()).map_err(bad_request)?;
+    
+    let now = Utc::now().to_rfc3339();
+    
+    Ok(ProcessRequest {
+        data_path: path.to_string_lossy().into_owned(),
+        metadata: FileMetadata {
+            filename,
+            file_hash,
+            size: i64::try_from(size).unwrap_or(i64::MAX),
+            download_time: now.clone(),
+            extracted_path: None,
+            encoding: None,
+        },
+        timestamp: now,
+        source: source.filter(|source| !source.is_empty()).unwrap_or_else(|| "upload".to_string()),
+        callback_url: options.callback_url,
+        run_options: options.run_options,
+        parse_options: options.parse_options,
+    })
+}
+
+// Data quality report for a file or directory, without importing it
+#[utoipa::path(
+    post,
+    path = "/api/validate",
+    tag = "processing",
+    request_body = ValidateRequest,
+    responses(
+        (status = 200, description = "How every record fared", body = ValidationReport),
+        (status = 400, description = "The request is invalid", body = ErrorResponse),
+        (status = 500, description = "The data could not be read", body = ErrorResponse),
+    ),
+)]
+async fn validate_data(
+    req: web::Json<ValidateRequest>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    let req = req.into_inner();
+    
+    let path = if req.max_depth == Some(0) {
+        Err("max_depth must be at least 1".to_string())
+    } else {
+        processor::resolve_data_path(&state.config.data_base_dir, &req.data_path)
+    };
+    let path = match path {
+        Ok(path) => path,
+        Err(e) => {
+            warn!("Rejected validation request: {}", e);
+            
+            return HttpResponse::BadRequest().json(ErrorResponse {
+                success: false,
+                error: "Invalid validation request".to_string(),
+                details: Some(e),
+            });
+        }
+    };
+    
+    let options = ParseOptions { encoding: req.encoding, ..req.parse_options };
+    validation_report(&state, &path, &options, req.max_depth).await
+}
+
+// Data quality report for an uploaded file; the upload is not kept
+#[utoipa::path(
+    post,
+    path = "/api/validate/upload",
+    tag = "processing",
+    request_body(content = UploadForm, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "How every record fared", body = ValidationReport),
+        (status = 400, description = "The upload is invalid", body = ErrorResponse),
+        (status = 413, description = "The file is over `UPLOAD_MAX_MB`", body = ErrorResponse),
+    ),
+)]
+async fn validate_upload(
+    mut payload: Multipart,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    let staging_dir = state.config.upload.dir.join(Uuid::new_v4().to_string());
+    
+    let response = match receive_upload(&mut payload, &staging_dir, &state).await {
+        Ok(req) => {
+            let path = PathBuf::from(&req.data_path);
+            validation_report(&state, &path, &req.parse_options, req.run_options.max_depth).await
+        }
+        Err(response) => response,
+    };
+    
+    if let Err(e) = tokio::fs::remove_dir_all(&staging_dir).await {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            warn!("Failed to remove staged upload {:?}: {}", staging_dir, e);
+        }
+    }
+    
+    response
+}
+
+/// Checks the data at `path` with [`DataProcessor::validate_path`] and
+/// answers with the report.
+async fn validation_report(
+    state: &AppState,
+    path: &std::path::Path,
+    options: &ParseOptions,
+    max_depth: Option<usize>,
+) -> HttpResponse {
+    let max_depth = max_depth.unwrap_or(processor::DEFAULT_MAX_DEPTH);
+    
+    match state.processor.validate_path(path, options, max_depth).await {
+        Ok(report) => {
+            info!(
+                "Validated {:?}: {} of {} records valid",
+                path, report.records_valid, report.records_read
+            );
+            HttpResponse::Ok().json(report)
+        }
+        Err(e) => {
+            error!("Failed to validate {:?}: {}", path, e);
+            
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                success: false,
+                error: "Failed to validate data".to_string(),
+                details: Some(e.to_string()),
+            })
+        }
+    }
+}
+
+// Status of a queued processing run
+#[utoipa::path(
+    get,
+    path = "/api/jobs/{id}",
+    tag = "processing",
+    params(("id" = Uuid, Path, description = "The `job_id` the run was queued as")),
+    responses(
+        (status = 200, description = "The job's status, and its result once completed", body = JobStatusResponse),
+        (status = 207, description = "Completed, but more records failed than `PARTIAL_FAILURE_THRESHOLD` allows", body = JobStatusResponse),
+        (status = 404, description = "No such job", body = ErrorResponse),
+    ),
+)]
+async fn get_job(
+    path: web::Path<Uuid>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    let db = Database::new((*state.db_pool).clone());
+    let job_id = path.into_inner();
+    
+    match db.get_job(job_id).await {
+        Ok(Some(job)) => {
+            // A completed job that lost too many records is only a partial success
+            let failure_ratio = job.result.as_ref().and_then(|result| result["failure_ratio"].as_f64());
+            let mut response = if job.status == "completed"
+                && failure_ratio.is_some_and(|ratio| ratio > state.config.partial_failure_threshold)
+            {
+                HttpResponse::MultiStatus()
+            } else {
+                HttpResponse::Ok()
+            };
+            
+            response.json(JobStatusResponse {
+                job_id: job.id,
+                status: job.status,
+                requested_at: job.requested_at,
+                created_at: job.created_at,
+                started_at: job.started_at,
+                finished_at: job.finished_at,
+                result: job.result,
+                error: job.error_message,
+            })
+        }
+        Ok(None) => HttpResponse::NotFound().json(ErrorResponse {
+            success: false,
+            error: "Job not found".to_string(),
+            details: Some(job_id.to_string()),
+        }),
+        Err(e) => {
+            error!("Failed to fetch job {}: {}", job_id, e);
+            
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                success: false,
+                error: "Failed to fetch job".to_string(),
+                details: Some(e.to_string()),
+            })
+        }
+    }
+}
+
+// Progress of a queued processing run, for a progress bar
+#[utoipa::path(
+    get,
+    path = "/api/jobs/{id}/progress",
+    tag = "processing",
+    params(("id" = Uuid, Path, description = "The `job_id` the run was queued as")),
+    responses(
+        (status = 200, description = "How far the run has got", body = JobProgressResponse),
+        (status = 404, description = "No such job", body = ErrorResponse),
+    ),
+)]
+async fn get_job_progress(
+    path: web::Path<Uuid>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    let job_id = path.into_inner();
+    
+    let running = state.running_jobs
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .get(&job_id)
+        .map(|progress| progress.borrow().clone());
+    if let Some(progress) = running {
+        return HttpResponse::Ok().json(JobProgressResponse {
+            job_id,
+            status: "running".to_string(),
+            percent: progress.percent(),
+            progress,
+        });
+    }
+    
+    let db = Database::new((*state.db_pool).clone());
+    match db.get_job(job_id).await {
+        Ok(Some(job)) => {
+            // Only a completed job has a result to report; other jobs have
+            // not started or did not get anywhere that lasted
+            let progress = match &job.result {
+                Some(result) if job.status == "completed" => {
+                    let files = result["files"].as_array().map_or(0, Vec::len);
+                    let records = result["records_read"].as_u64().unwrap_or(0) as usize;
+                    JobProgress {
+                        files_total: files,
+                        files_done: files,
+                        current_file: None,
+                        records_done: records,
+                        records_total: Some(records),
+                    }
+                }
+                _ => JobProgress::default(),
+            };
+            
+            HttpResponse::Ok().json(JobProgressResponse {
+                job_id: job.id,
+                percent: (job.status == "completed").then_some(100.0),
+                status: job.status,
+                progress,
+            })
+        }
+        Ok(None) => HttpResponse::NotFound().json(ErrorResponse {
+            success: false,
+            error: "Job not found".to_string(),
+            details: Some(job_id.to_string()),
+        }),
+        Err(e) => {
+            error!("Failed to fetch job {}: {}", job_id, e);
+            
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                success: false,
+                error: "Failed to fetch job".to_string(),
+                details: Some(e.to_string()),
+            })
+        }
+    }
+}
+
+/// Runs queued processing jobs one at a time, recording each outcome in
+/// `processing_jobs` and posting it to the job's `callback_url`, if any.
+/// While a job runs, its progress is published in `running_jobs`. Cached
+/// responses are dropped once it ends, as even a failed run may have
+/// committed checkpoints.
+async fn run_job_worker(
+    db_pool: Arc<PgPool>,
+    processor: Arc<DataProcessor>,
+    retry: RetryPolicy,
+    webhooks: Option<WebhookNotifier>,
+    response_cache: Option<Arc<ResponseCache>>,
+    running_jobs: RunningJobs,
+    mut queue: mpsc::UnboundedReceiver<Uuid>,
+) {
+    let db = Database::new((*db_pool).clone());
+    
+    while let Some(job_id) = queue.recv().await {
+        let request = match db.start_job(job_id).await {
+            Ok(Some(request)) => request,
+            Ok(None) => continue,
+            Err(e) => {
+                error!("Failed to start job {}: {}", job_id, e);
+                continue;
+            }
+        };
+        
+        let (progress, receiver) = watch::channel(JobProgress::default());
+        let progress = Arc::new(progress);
+        running_jobs.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).insert(job_id, receiver);
+        
+        let mut callback_url = None;
+        let outcome = match serde_json::from_value::<ProcessRequest>(request) {
+            Ok(req) => {
+                callback_url = req.callback_url.clone();
+                run_with_retry(&processor, &req, retry, &progress).await
+            }
+            Err(e) => Err(anyhow::anyhow!("Invalid stored request: {}", e)),
+        };
+        
+        let (status, result, error) = match outcome {
+            Ok(response) => ("completed", serde_json::to_value(&response).ok(), None),
+            Err(e) => {
+                error!("Job {} failed: {}", job_id, e);
+                ("failed", None, Some(e.to_string()))
+            }
+        };
+        
+        if let Err(e) = db.finish_job(job_id, status, result.as_ref(), error.as_deref()).await {
+            error!("Failed to record outcome of job {}: {}", job_id, e);
+        }
+        running_jobs.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).remove(&job_id);
+        if let Some(cache) = &response_cache {
+            cache.invalidate();
+        }
+        
+        let (Some(url), Some(webhooks)) = (callback_url, &webhooks) else {
+            continue;
+        };
+        let callback = JobCallback {
+            job_id,
+            status,
+            result: result.as_ref(),
+            error: error.as_deref(),
+        };
+        match serde_json::to_vec(&callback) {
+            // Retries can take a while, so they do not hold up the next job
+            Ok(body) => {
+                let webhooks = webhooks.clone();
+                actix_web::rt::spawn(async move { webhooks.deliver(&url, body).await });
+            }
+            Err(e) => error!("Failed to serialize callback for job {}: {}", job_id, e),
+        }
+    }
+}
+
+/// Reruns a request whose transaction was lost to a transient database
+/// error, such as a dropped connection. A run commits all or nothing, so
+/// starting over is safe.
+async fn run_with_retry(
+    processor: &DataProcessor,
+    req: &ProcessRequest,
+    retry: RetryPolicy,
+    progress: &Arc<watch::Sender<JobProgress>>,
+) -> Result<ProcessResponse, anyhow::Error> {
+    let mut attempt = 1;
+    
+    loop {
+        match run_process_request(processor, req, ProgressReporter::new(progress.clone())).await {
+            Err(e) if attempt < retry.max_attempts && is_transient_failure(&e) => {
+                let delay = retry.backoff(attempt);
+                warn!(
+                    "Processing attempt {} of {} hit a transient database error, retrying in {:?}: {}",
+                    attempt, retry.max_attempts, delay, e
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            outcome => return outcome,
+        }
+    }
+}
+
+fn is_transient_failure(error: &anyhow::Error) -> bool {
+    error
+        .chain()
+        .any(|cause| cause.downcast_ref::<sqlx::Error>().is_some_and(is_transient))
+}
+
+async fn run_process_request(
+    processor: &DataProcessor,
+    req: &ProcessRequest,
+    progress: ProgressReporter,
+) -> Result<ProcessResponse, anyhow::Error> {
+    let start_time = std::time::Instant::now();
+    
+    // A single file, such as a ZIP delivery, is processed on its own
+    let result = if std::path::Path::new(&req.data_path).is_file() {
+        processor
+            .process_single_file(&req.data_path, &req.metadata, &req.parse_options, req.run_options, progress)
+            .await?
+    } else {
+        processor
+            .process_directory(&req.data_path, &req.metadata, &req.parse_options, req.run_options, progress)
+            .await?
+    };
+    
+    let duration = start_time.elapsed().as_secs_f64();
+    
+    info!(
+        "Processing completed: {} records processed, {} failed in {:.2}s",
+        result.records_processed, result.records_failed, duration
+    );
+    
+    Ok(ProcessResponse {
+        success: true,
+        records_read: result.records_read,
+        failure_ratio: result.failure_ratio(),
+        records_processed: result.records_processed,
+        records_inserted: result.records_inserted,
+        records_updated: result.records_updated,
+        records_skipped: result.records_skipped,
+        records_failed: result.records_failed,
+        records_with_coordinates: result.records_with_coordinates,
+        processing_time_seconds: duration,
+        geojson_features_created: result.geojson_features_created,
+        geojson_features_skipped: result.geojson_features_skipped,
+        already_processed: result.already_processed,
+        dry_run: result.dry_run,
+        parse_mode: result.parse_mode,
+        duplicates_collapsed: result.duplicates_collapsed,
+        possible_duplicates: result.possible_duplicates,
+        files: result.files,
+        sections_imported: result.sections_imported,
+        resumed: result.resumed,
+        records_resumed: result.records_resumed,
+        files_already_processed: result.files_already_processed,
+        errors: result.errors.into_iter().map(|e| ProcessingError {
+            record_id: e.record_id,
+            line_number: e.line_number,
+            error: e.message,
+            code: e.code,
+            field: e.field,
+        }).collect(),
+        warnings: result.warnings,
+    })
+}
+
+// GeoJSON feature layer for the map, served from the response cache
+async fn get_features(
+    req: HttpRequest,
+    query: web::Query<FeaturesQuery>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    let limit = query.limit.unwrap_or(DEFAULT_FEATURES_LIMIT).clamp(1, MAX_FEATURES_LIMIT);
+    let offset = query.offset.unwrap_or(0).max(0);
+    let anonymizer = match export_anonymizer(&state, query.anonymize, query.pseudonymize_names) {
+        Ok(anonymizer) => anonymizer,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(ErrorResponse {
+                success: false,
+                error: e,
+                details: None,
+            });
+        }
+    };
+    
+    let key = format!(
+        "features:{:?}:{}:{}:{}:{}",
+        query.section, limit, offset, query.anonymize, query.pseudonymize_names
+    );
+    let compute = || async {
+        let db = Database::new((*state.db_pool).clone());
+        let mut features = db.get_features(query.section.as_deref(), limit, offset).await.map_err(|e| e.to_string())?;
+        
+        if let Some(anonymizer) = &anonymizer {
+            features.iter_mut().for_each(|feature| anonymizer.anonymize_feature(feature));
+        }
+        
+        serde_json::to_string(&GeoJsonFeatureCollection::new(features)).map_err(|e| e.to_string())
+    };
+    
+    match cached(&state, &key, compute).await {
+        Ok(response) => respond_cached(&req, &state, &response, "application/geo+json"),
+        Err(e) => {
+            error!("Failed to load features: {}", e);
+            
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                success: false,
+                error: "Failed to load features".to_string(),
+                details: Some(e),
+            })
+        }
+    }
+}
+
+// All grave locations as a KML download for Google Earth, streamed as they
+// are read
+async fn export_kml(
+    query: web::Query<ExportQuery>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    let anonymizer = match export_anonymizer(&state, query.anonymize, query.pseudonymize_names) {
+        Ok(anonymizer) => anonymizer,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(ErrorResponse {
+                success: false,
+                error: e,
+                details: None,
+            });
+        }
+    };
+    
+    let db = Database::new((*state.db_pool).clone());
+    let (sender, receiver) = mpsc::channel(4);
+    
+    tokio::spawn(export::stream_features_kml(db, query.into_inner().section, anonymizer, sender));
+    
+    let body = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        let chunk = receiver.recv().await?;
+        Some((chunk.map(web::Bytes::from), receiver))
+    });
+    
+    HttpResponse::Ok()
+        .content_type("application/vnd.google-earth.kml+xml")
+        .insert_header(ContentDisposition::attachment("najaf_cemetery.kml"))
+        .streaming(body)
+}
+
+// Live records as a CSV in the import layout, streamed as they are read
+async fn export_csv(
+    query: web::Query<CsvExportQuery>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    let query = query.into_inner();
+    
+    if let (Some(from), Some(to)) = (query.burial_date_from, query.burial_date_to) {
+        if from > to {
+            return HttpResponse::BadRequest().json(ErrorResponse {
+                success: false,
+                error: "burial_date_from must not be after burial_date_to".to_string(),
+                details: None,
+            });
+        }
+    }
+    
+    let anonymizer = match export_anonymizer(&state, query.anonymize, query.pseudonymize_names) {
+        Ok(anonymizer) => anonymizer,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(ErrorResponse {
+                success: false,
+                error: e,
+                details: None,
+            });
+        }
+    };
+    
+    let db = Database::new((*state.db_pool).clone());
+    let (sender, receiver) = mpsc::channel(4);
+    
+    tokio::spawn(export::stream_records_csv(
+        db,
+        query.section,
+        query.burial_date_from,
+        query.burial_date_to,
+        query.bom,
+        anonymizer,
+        sender,
+    ));
+    
+    let body = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        let chunk = receiver.recv().await?;
+        Some((chunk.map(web::Bytes::from), receiver))
+    });
+    
+    HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .insert_header(ContentDisposition::attachment("najaf_cemetery.csv"))
+        .streaming(body)
+}
+
+// Section and plot outlines imported from shapefiles, drawn under the markers
+async fn get_sections(
+    query: web::Query<FeaturesQuery>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    let limit = query.limit.unwrap_or(DEFAULT_FEATURES_LIMIT).clamp(1, MAX_FEATURES_LIMIT);
+    let offset = query.offset.unwrap_or(0).max(0);
+    
+    let db = Database::new((*state.db_pool).clone());
+    
+    match db.get_section_outlines(query.section.as_deref(), limit, offset).await {
+        Ok(features) => HttpResponse::Ok()
+            .content_type("application/geo+json")
+            .json(GeoJsonFeatureCollection::new(features)),
+        Err(e) => {
+            error!("Failed to load section outlines: {}", e);
+            
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                success: false,
+                error: "Failed to load section outlines".to_string(),
+                details: Some(e.to_string()),
+            })
+        }
+    }
+}
+
+// Rebuilds the map layer from the records, for maintenance
+async fn rebuild_features(
+    query: web::Query<RebuildFeaturesQuery>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    let db = Database::new((*state.db_pool).clone());
+    let section = query.into_inner().section.filter(|section| !section.trim().is_empty());
+    
+    match db.rebuild_features(section.as_deref(), state.config.derive_coordinates).await {
+        Ok(counts) => {
+            forget_cached_responses(&state);
+            info!(
+                "Rebuilt {} features ({})",
+                counts.written,
+                section.as_deref().map_or("all sections".to_string(), |section| format!("section {}", section))
+            );
+            
+            HttpResponse::Ok().json(RebuildFeaturesResponse {
+                success: true,
+                section,
+                features_written: counts.written,
+                features_skipped: counts.skipped_invalid,
+            })
+        }
+        Err(e) => {
+            error!("Failed to rebuild features: {}", e);
+            
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                success: false,
+                error: "Failed to rebuild features".to_string(),
+                details: Some(e.to_string()),
+            })
+        }
+    }
+}
+
+// Section polygons with their record counts, for a density map
+async fn get_section_stats(state: web::Data<AppState>) -> impl Responder {
+    let db = Database::new((*state.db_pool).clone());
+    
+    match db.get_section_stats().await {
+        Ok(features) => HttpResponse::Ok()
+            .content_type("application/geo+json")
+            .json(GeoJsonFeatureCollection::new(features)),
+        Err(e) => {
+            error!("Failed to compute section statistics: {}", e);
+            
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                success: false,
+                error: "Failed to compute section statistics".to_string(),
+                details: Some(e.to_string()),
+            })
+        }
+    }
+}
+
+// Features inside the current map viewport
+async fn get_features_in_bbox(
+    query: web::Query<BboxQuery>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    if let Err(e) = query.validate() {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            success: false,
+            error: "Invalid bounding box".to_string(),
+            details: Some(e),
+        });
+    }
+    
+    let limit = query.limit.unwrap_or(DEFAULT_FEATURES_LIMIT).clamp(1, MAX_FEATURES_LIMIT);
+    let anonymizer = match export_anonymizer(&state, query.anonymize, query.pseudonymize_names) {
+        Ok(anonymizer) => anonymizer,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(ErrorResponse {
+                success: false,
+                error: e,
+                details: None,
+            });
+        }
+    };
+    
+    let db = Database::new((*state.db_pool).clone());
+    
+    match db.get_features_in_bbox(query.min_lon, query.min_lat, query.max_lon, query.max_lat, limit).await {
+        Ok(mut features) => {
+            if let Some(anonymizer) = &anonymizer {
+                features.iter_mut().for_each(|feature| anonymizer.anonymize_feature(feature));
+            }
+            
+            HttpResponse::Ok()
+                .content_type("application/geo+json")
+                .json(GeoJsonFeatureCollection::new(features))
+        }
+        Err(e) => {
+            error!("Failed to load features in bounding box: {}", e);
+            
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                success: false,
+                error: "Failed to load features".to_string(),
+                details: Some(e.to_string()),
+            })
+        }
+    }
+}
 
-#[derive(Debug, Deserialize)]
-struct ProcessRequest {
-    data_path: String,
-    metadata: FileMetadata,
-    timestamp: String,
-    source: String,
+// One page of records for browsing, with the total in X-Total-Count
+async fn list_records(
+    req: HttpRequest,
+    query: web::Query<RecordsQuery>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    let checked = query.validate().and_then(|()| Ok((query.filter(req.query_string())?, query.cursor()?)));
+    let (filter, after) = match checked {
+        Ok(checked) => checked,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(ErrorResponse {
+                success: false,
+                error: "Invalid query".to_string(),
+                details: Some(e),
+            });
+        }
+    };
+    let limit = query.limit.unwrap_or(DEFAULT_RECORDS_LIMIT);
+    let offset = query.offset.unwrap_or(0);
+    
+    let db = Database::new((*state.db_pool).clone());
+    
+    let page = tokio::try_join!(
+        db.list_records(&filter, after, limit, offset),
+        db.count_records(&filter)
+    );
+    
+    match page {
+        Ok((records, total)) => {
+            let mut response = HttpResponse::Ok();
+            response.insert_header((TOTAL_COUNT_HEADER, total.to_string()));
+            
+            // A short page is the last one
+            if records.len() as i64 == limit {
+                if let Some(last) = records.last() {
+                    let cursor = RecordCursor { burial_date: last.burial_date, id: last.id };
+                    response.insert_header((NEXT_CURSOR_HEADER, cursor.to_string()));
+                }
+            }
+            
+            response.json(records)
+        }
+        Err(e) => {
+            error!("Failed to list records: {}", e);
+            
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                success: false,
+                error: "Failed to list records".to_string(),
+                details: Some(e.to_string()),
+            })
+        }
+    }
 }
 
-#[derive(Debug, Serialize)]
-struct ProcessResponse {
-    success: bool,
-    records_processed: i32,
-    records_failed: i32,
-    processing_time_seconds: f64,
-    geojson_features_created: i32,
-    errors: Vec<ProcessingError>,
+// Full details of one record, e.g. for a clicked map marker
+async fn get_record(
+    path: web::Path<String>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    let db = Database::new((*state.db_pool).clone());
+    let record_id = path.into_inner();
+    
+    match db.get_record(&record_id).await {
+        Ok(Some(record)) => HttpResponse::Ok().json(record),
+        Ok(None) => HttpResponse::NotFound().json(ErrorResponse {
+            success: false,
+            error: "Record not found".to_string(),
+            details: Some(record_id),
+        }),
+        Err(e) => {
+            error!("Failed to fetch record {}: {}", record_id, e);
+            
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                success: false,
+                error: "Failed to fetch record".to_string(),
+                details: Some(e.to_string()),
+            })
+        }
+    }
 }
 
-#[derive(Debug, Serialize)]
-struct ProcessingError {
-    record_id: Option<String>,
-    error: String,
+// Audit trail of processed files, newest first
+async fn get_processing_log(
+    query: web::Query<ProcessingLogQuery>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    let limit = query.limit.unwrap_or(DEFAULT_LOG_LIMIT).clamp(1, MAX_LOG_LIMIT);
+    let offset = query.offset.unwrap_or(0).max(0);
+    
+    let db = Database::new((*state.db_pool).clone());
+    
+    match db.get_processing_log(query.status.as_deref(), limit, offset).await {
+        Ok(entries) => HttpResponse::Ok().json(entries),
+        Err(e) => {
+            error!("Failed to load processing log: {}", e);
+            
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                success: false,
+                error: "Failed to load processing log".to_string(),
+                details: Some(e.to_string()),
+            })
+        }
+    }
 }
 
-#[derive(Debug, Serialize)]
-struct ErrorResponse {
-    success: bool,
-    error: String,
-    details: Option<String>,
+// Records runs rejected, with what the source gave for them, oldest first
+async fn get_rejected_records(
+    query: web::Query<RejectedQuery>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    let limit = query.limit.unwrap_or(DEFAULT_LOG_LIMIT).clamp(1, MAX_LOG_LIMIT);
+    let offset = query.offset.unwrap_or(0).max(0);
+    
+    let db = Database::new((*state.db_pool).clone());
+    
+    match db.rejected_records(query.file.as_deref(), limit, offset).await {
+        Ok(rejected) => HttpResponse::Ok().json(rejected),
+        Err(e) => {
+            error!("Failed to load rejected records: {}", e);
+            
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                success: false,
+                error: "Failed to load rejected records".to_string(),
+                details: Some(e.to_string()),
+            })
+        }
+    }
 }
 
-#[derive(Clone)]
-struct AppState {
-    db_pool: Arc<PgPool>,
+// National ids carried by several live records, for data cleansing
+async fn get_duplicate_national_ids(
+    query: web::Query<DuplicateIdsQuery>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    let limit = query.limit.unwrap_or(DEFAULT_DUPLICATE_IDS_LIMIT).clamp(1, MAX_DUPLICATE_IDS_LIMIT);
+    let offset = query.offset.unwrap_or(0).max(0);
+    
+    let db = Database::new((*state.db_pool).clone());
+    
+    match db.shared_national_ids(None, None, Some(limit), offset).await {
+        Ok(shared) => HttpResponse::Ok().json(shared),
+        Err(e) => {
+            error!("Failed to find shared national ids: {}", e);
+            
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                success: false,
+                error: "Failed to find shared national ids".to_string(),
+                details: Some(e.to_string()),
+            })
+        }
+    }
 }
 
-// Health check endpoint
-async fn health_check() -> impl Responder {
-    HttpResponse::Ok().json(serde_json::json!({
-        "status": "healthy",
-        "service": "najaf-cemetery-processor",
-        "version": env!("CARGO_PKG_VERSION")
-    }))
+// Dashboard aggregates, served from the response cache
+async fn get_stats(req: HttpRequest, state: web::Data<AppState>) -> impl Responder {
+    let compute = || async {
+        let db = Database::new((*state.db_pool).clone());
+        let mut stats = db.get_stats().await.map_err(|e| e.to_string())?;
+        
+        if let Some(vocabulary) = state.processor.cause_vocabulary() {
+            for cause in &mut stats.causes_of_death {
+                cause.label = vocabulary.label(&cause.code).map(str::to_string);
+            }
+        }
+        
+        serde_json::to_string(&stats).map_err(|e| e.to_string())
+    };
+    
+    match cached(&state, "stats", compute).await {
+        Ok(response) => respond_cached(&req, &state, &response, "application/json"),
+        Err(e) => {
+            error!("Failed to compute stats: {}", e);
+            
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                success: false,
+                error: "Failed to compute stats".to_string(),
+                details: Some(e),
+            })
+        }
+    }
 }
 
-// Main processing endpoint
-async fn process_data(
-    req: web::Json<ProcessRequest>,
+// Suggested fathers and children of a record, from its family names
+async fn get_record_relations(
+    path: web::Path<String>,
     state: web::Data<AppState>,
 ) -> impl Responder {
-    info!("Received processing request for: {}", req.data_path);
-    info!("Source file: {}", req.metadata.filename);
+    let db = Database::new((*state.db_pool).clone());
+    let record_id = path.into_inner();
     
-    let start_time = std::time::Instant::now();
+    match db.family_relations(&record_id).await {
+        Ok(Some(relations)) => HttpResponse::Ok().json(relations),
+        Ok(None) => HttpResponse::NotFound().json(ErrorResponse {
+            success: false,
+            error: "Record not found".to_string(),
+            details: Some(record_id),
+        }),
+        Err(e) => {
+            error!("Failed to fetch relations of record {}: {}", record_id, e);
+            
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                success: false,
+                error: "Failed to fetch relations".to_string(),
+                details: Some(e.to_string()),
+            })
+        }
+    }
+}
+
+// Earlier states of a record, for auditing corrections
+async fn get_record_history(
+    path: web::Path<String>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    let db = Database::new((*state.db_pool).clone());
+    let record_id = path.into_inner();
+    
+    match db.record_history(&record_id).await {
+        Ok(Some(history)) => HttpResponse::Ok().json(history),
+        Ok(None) => HttpResponse::NotFound().json(ErrorResponse {
+            success: false,
+            error: "Record not found".to_string(),
+            details: Some(record_id),
+        }),
+        Err(e) => {
+            error!("Failed to fetch history of record {}: {}", record_id, e);
+            
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                success: false,
+                error: "Failed to fetch record history".to_string(),
+                details: Some(e.to_string()),
+            })
+        }
+    }
+}
+
+// Delete a record; soft by default so the row stays for auditing
+async fn delete_record(
+    path: web::Path<String>,
+    query: web::Query<DeleteQuery>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    let db = Database::new((*state.db_pool).clone());
+    let record_id = path.into_inner();
     
-    // Create processor instance
-    let processor = DataProcessor::new(state.db_pool.clone());
+    let result = if query.hard {
+        db.hard_delete_record(&record_id).await
+    } else {
+        db.soft_delete_record(&record_id).await
+    };
+    
+    match result {
+        Ok(true) => {
+            forget_cached_responses(&state);
+            info!("Deleted record {} (hard: {})", record_id, query.hard);
+            
+            HttpResponse::Ok().json(DeleteResponse {
+                success: true,
+                record_id,
+                hard: query.hard,
+            })
+        }
+        Ok(false) => HttpResponse::NotFound().json(ErrorResponse {
+            success: false,
+            error: "Record not found".to_string(),
+            details: Some(record_id),
+        }),
+        Err(e) => {
+            error!("Failed to delete record {}: {}", record_id, e);
+            
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                success: false,
+                error: "Failed to delete record".to_string(),
+                details: Some(e.to_string()),
+            })
+        }
+    }
+}
+
+// Records a steward's review of a record, e.g. clearing a flag
+async fn review_record(
+    path: web::Path<String>,
+    req: web::Json<ReviewRequest>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    let db = Database::new((*state.db_pool).clone());
+    let record_id = path.into_inner();
     
-    // Process the data
-    match processor.process_directory(&req.data_path, &req.metadata).await {
-        Ok(result) => {
-            let duration = start_time.elapsed().as_secs_f64();
+    match db.review_record(&record_id, req.status, state.config.derive_coordinates).await {
+        Ok(true) => {
+            forget_cached_responses(&state);
+            info!("Record {} reviewed as {}", record_id, req.status.as_str());
+            
+            HttpResponse::Ok().json(ReviewResponse {
+                success: true,
+                record_id,
+                status: req.status,
+            })
+        }
+        Ok(false) => HttpResponse::NotFound().json(ErrorResponse {
+            success: false,
+            error: "Record not found".to_string(),
+            details: Some(record_id),
+        }),
+        Err(e) => {
+            error!("Failed to update the status of record {}: {}", record_id, e);
             
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                success: false,
+                error: "Failed to update record status".to_string(),
+                details: Some(e.to_string()),
+            })
+        }
+    }
+}
+
+// Folds duplicate records into a primary one
+async fn merge_records(
+    req: web::Json<MergeRequest>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    if let Err(e) = req.validate() {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            success: false,
+            error: "Invalid merge request".to_string(),
+            details: Some(e),
+        });
+    }
+    
+    let db = Database::new((*state.db_pool).clone());
+    
+    match db.merge_records(&req.primary_id, &req.duplicate_ids, state.config.derive_coordinates).await {
+        Ok(MergeOutcome::Merged(merge)) => {
+            forget_cached_responses(&state);
             info!(
-                "Processing completed: {} records processed, {} failed in {:.2}s",
-                result.records_processed, result.records_failed, duration
+                "Merged {} into record {} (merge {})",
+                merge.merged_record_ids.join(", "),
+                merge.record_id,
+                merge.merge_id
             );
             
-            HttpResponse::Ok().json(ProcessResponse {
-                success: true,
-                records_processed: result.records_processed,
-                records_failed: result.records_failed,
-                processing_time_seconds: duration,
-                geojson_features_created: result.geojson_features_created,
-                errors: result.errors.into_iter().map(|e| ProcessingError {
-                    record_id: e.record_id,
-                    error: e.message,
-                }).collect(),
+            HttpResponse::Ok().json(MergeResponse { success: true, merge })
+        }
+        Ok(MergeOutcome::NotFound(missing)) => HttpResponse::NotFound().json(ErrorResponse {
+            success: false,
+            error: "Record not found".to_string(),
+            details: Some(missing.join(", ")),
+        }),
+        Err(e) => {
+            error!("Failed to merge into record {}: {}", req.primary_id, e);
+            
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                success: false,
+                error: "Failed to merge records".to_string(),
+                details: Some(e.to_string()),
+            })
+        }
+    }
+}
+
+// Name search across Latin and Arabic names
+async fn search_records(
+    query: web::Query<SearchQuery>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    let term = normalize_arabic(query.q.trim()).to_lowercase();
+    
+    if term.is_empty() {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            success: false,
+            error: "Invalid search".to_string(),
+            details: Some("q must not be empty".to_string()),
+        });
+    }
+    
+    let limit = query.limit.unwrap_or(DEFAULT_SEARCH_LIMIT).clamp(1, MAX_SEARCH_LIMIT);
+    let offset = query.offset.unwrap_or(0).max(0);
+    
+    let db = Database::new((*state.db_pool).clone());
+    
+    match db.search_records(&term, limit, offset).await {
+        Ok(results) => HttpResponse::Ok().json(results),
+        Err(e) => {
+            error!("Search for '{}' failed: {}", query.q, e);
+            
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                success: false,
+                error: "Search failed".to_string(),
+                details: Some(e.to_string()),
             })
         }
+    }
+}
+
+// Graves near a point, such as one clicked on the map
+async fn nearby_records(
+    query: web::Query<NearbyQuery>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    if let Err(e) = query.validate() {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            success: false,
+            error: "Invalid query".to_string(),
+            details: Some(e),
+        });
+    }
+    
+    let radius_m = query.radius_m.unwrap_or(DEFAULT_NEARBY_RADIUS_M);
+    let limit = query.limit.unwrap_or(DEFAULT_NEARBY_LIMIT);
+    
+    let db = Database::new((*state.db_pool).clone());
+    
+    match db.nearby_records(query.lat, query.lon, radius_m, limit).await {
+        Ok(records) => HttpResponse::Ok().json(records),
         Err(e) => {
-            error!("Processing failed: {}", e);
+            error!("Failed to find records near {}, {}: {}", query.lat, query.lon, e);
             
             HttpResponse::InternalServerError().json(ErrorResponse {
                 success: false,
-                error: "Processing failed".to_string(),
+                error: "Failed to find nearby records".to_string(),
                 details: Some(e.to_string()),
             })
         }
     }
 }
 
+/// OpenAPI description of the processing endpoints, derived from the
+/// handlers and the types they take and return.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Najaf Cemetery Data Processor"),
+    paths(
+        liveness_check,
+        process_data,
+        upload_file,
+        validate_data,
+        validate_upload,
+        get_job,
+        get_job_progress,
+    ),
+    components(schemas(
+        ProcessRequest,
+        FileMetadata,
+        RunOptions,
+        ImportMode,
+        ParseOptions,
+        CsvSchema,
+        ColumnRef,
+        JsonSchema,
+        Calendar,
+        FileFormat,
+        LocationLayout,
+        LocationPart,
+        SwappedCoordinates,
+        DuplicateStrategy,
+        ConflictKey,
+        ParseMode,
+        FieldTransforms,
+        Transform,
+        UploadForm,
+        ValidateRequest,
+        ValidationReport,
+        RecordReport,
+        RecordIssue,
+        JobAcceptedResponse,
+        JobStatusResponse,
+        JobProgressResponse,
+        JobProgress,
+        ProcessResponse,
+        ProcessingError,
+        PossibleDuplicate,
+        FileSummary,
+        ErrorResponse,
+    )),
+    tags(
+        (name = "processing", description = "Importing data and following the runs"),
+        (name = "health", description = "Probes for orchestrators"),
+    ),
+)]
+struct ApiDoc;
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     // Initialize logger
@@ -112,40 +2296,266 @@ async fn main() -> std::io::Result<()> {
     // Load environment variables
     dotenv::dotenv().ok();
     
-    // Get configuration from environment
-    let database_url = std::env::var("DATABASE_URL")
-        .expect("DATABASE_URL must be set");
-    let server_host = std::env::var("SERVER_HOST")
-        .unwrap_or_else(|_| "0.0.0.0".to_string());
-    let server_port = std::env::var("SERVER_PORT")
-        .unwrap_or_else(|_| "8080".to_string())
-        .parse::<u16>()
-        .expect("SERVER_PORT must be a valid port number");
+    // Read and check every setting before doing anything else
+    let config = Config::from_env().unwrap_or_else(|e| panic!("{}", e));
+    
+    info!("Processing requests may only read from {:?}", config.data_base_dir);
+    info!("Reading request times without an offset as {}", config.timezone);
+    info!(
+        "Staging uploads of up to {} MiB in {:?}",
+        config.upload.max_size / (1024 * 1024),
+        config.upload.dir
+    );
+    
+    match &config.api_keys {
+        Some(keys) => info!("API key authentication enabled ({} keys)", keys.len()),
+        None => warn!("API_KEYS is not set; every endpoint is open to anyone who can reach the service"),
+    }
+    
+    match &config.rate_limiter {
+        Some(limiter) => info!("Rate limiting clients to {} requests per minute", limiter.requests_per_minute()),
+        None => info!("Rate limiting is off"),
+    }
+    
+    match &config.response_cache {
+        Some(cache) => info!(
+            "Caching up to {} stats and feature responses for {:?}",
+            cache.max_entries(),
+            cache.ttl()
+        ),
+        None => info!("Response caching is off"),
+    }
+    
+    let stripped: Vec<&str> = config.anonymizer.stripped_fields().collect();
+    info!("Anonymized exports leave out: {}", stripped.join(", "));
+    if !config.anonymizer.pseudonyms_are_stable() {
+        warn!("ANONYMIZE_SECRET is not set; pseudonymized names change whenever the service restarts");
+    }
+    
+    if config.webhooks.is_none() {
+        info!("WEBHOOK_SECRET is not set; requests with a callback_url are rejected");
+    }
+    
+    match config.national_ids.mode() {
+        NationalIdMode::Off => info!("National ids are not checked"),
+        NationalIdMode::Warn => info!("Invalid national ids are reported as warnings"),
+        NationalIdMode::Reject => info!("Records with invalid national ids are rejected"),
+    }
+    
+    match &config.causes {
+        Some(vocabulary) => info!("Coding causes of death against {} causes", vocabulary.causes().len()),
+        None => info!("Causes of death are stored as written"),
+    }
+    
+    match config.max_errors {
+        Some(limit) => info!("Runs are aborted once more than {} records fail", limit),
+        None => info!("Runs try every record however many fail; MAX_ERRORS is not set"),
+    }
+    
+    match config.checkpoint_every {
+        Some(every) => info!("Runs commit a checkpoint every {} records and resume from it", every),
+        None => info!("Runs are committed as a whole; CHECKPOINT_EVERY is not set"),
+    }
+    
+    match &config.cemetery_bounds {
+        Some(bounds) => info!("Validating coordinates against cemetery bounds {:?}", bounds),
+        None => info!("No cemetery bounds configured; only global coordinate ranges are checked"),
+    }
     
-    info!("Connecting to database...");
+    info!(
+        "Connecting to database (pool: {}-{} connections, acquire timeout {:?}, idle timeout {:?})...",
+        config.pool.min_connections,
+        config.pool.max_connections,
+        config.pool.acquire_timeout,
+        config.pool.idle_timeout
+    );
     
     // Create database connection pool
-    let db_pool = PgPool::connect(&database_url)
+    let db_pool = config.pool
+        .connect(&config.database_url)
         .await
         .expect("Failed to connect to database");
     
     info!("Database connection established");
     
+    if config.run_migrations {
+        migrations::run(&db_pool).await.expect("Failed to migrate the database schema");
+    } else {
+        info!("RUN_MIGRATIONS is off; assuming the database schema is up to date");
+    }
+    
+    let db_pool = Arc::new(db_pool);
+    
+    let metrics = Metrics::new();
+    
+    // Start the job worker, resuming anything a previous run left unfinished
+    let (job_queue, job_receiver) = mpsc::unbounded_channel();
+    
+    let pending_jobs = Database::new((*db_pool).clone())
+        .requeue_unfinished_jobs()
+        .await
+        .expect("Failed to load pending processing jobs");
+    
+    for job_id in pending_jobs {
+        job_queue.send(job_id).expect("job queue receiver is alive");
+    }
+    
+    let processor = DataProcessor::from_config(db_pool.clone(), &config).with_metrics(metrics.clone());
+    let processor = Arc::new(processor);
+    
+    let running_jobs = RunningJobs::default();
+    actix_web::rt::spawn(run_job_worker(
+        db_pool.clone(),
+        processor.clone(),
+        config.retry,
+        config.webhooks.clone(),
+        config.response_cache.clone(),
+        running_jobs.clone(),
+        job_receiver,
+    ));
+    
     // Create app state
     let app_state = AppState {
-        db_pool: Arc::new(db_pool),
+        db_pool,
+        job_queue,
+        running_jobs,
+        processor,
+        metrics,
+        config: Arc::new(config),
     };
     
+    let (server_host, server_port) = (app_state.config.server_host.clone(), app_state.config.server_port);
     info!("Starting server at {}:{}", server_host, server_port);
     
     // Start HTTP server
     HttpServer::new(move || {
         App::new()
             .app_data(web::Data::new(app_state.clone()))
+            .wrap(from_fn(require_api_key))
+            .wrap(from_fn(rate_limit))
+            .app_data(
+                web::JsonConfig::default()
+                    .limit(MAX_JSON_BODY_BYTES)
+                    .error_handler(json_error_handler),
+            )
             .route("/health", web::get().to(health_check))
+            .route("/health/live", web::get().to(liveness_check))
+            .route("/metrics", web::get().to(get_metrics))
             .route("/api/process", web::post().to(process_data))
+            .route("/api/upload", web::post().to(upload_file))
+            .route("/api/validate", web::post().to(validate_data))
+            .route("/api/validate/upload", web::post().to(validate_upload))
+            .route("/api/jobs/{id}", web::get().to(get_job))
+            .route("/api/jobs/{id}/progress", web::get().to(get_job_progress))
+            .route("/api/features", web::get().to(get_features))
+            .route("/api/features/rebuild", web::post().to(rebuild_features))
+            .route("/api/sections", web::get().to(get_sections))
+            .route("/api/sections/stats", web::get().to(get_section_stats))
+            .route("/api/features/bbox", web::get().to(get_features_in_bbox))
+            .route("/api/export/kml", web::get().to(export_kml))
+            .route("/api/export/csv", web::get().to(export_csv))
+            .route("/api/search", web::get().to(search_records))
+            .route("/api/stats", web::get().to(get_stats))
+            .route("/api/processing-log", web::get().to(get_processing_log))
+            .route("/api/rejected", web::get().to(get_rejected_records))
+            .route("/api/data-quality/duplicate-ids", web::get().to(get_duplicate_national_ids))
+            .route("/api/records", web::get().to(list_records))
+            .route("/api/records/merge", web::post().to(merge_records))
+            .route("/api/records/nearby", web::get().to(nearby_records))
+            .route("/api/records/{record_id}", web::get().to(get_record))
+            .route("/api/records/{record_id}", web::delete().to(delete_record))
+            .route("/api/records/{record_id}/relations", web::get().to(get_record_relations))
+            .route("/api/records/{record_id}/history", web::get().to(get_record_history))
+            .route("/api/records/{record_id}/status", web::put().to(review_record))
+            .service(
+                SwaggerUi::new(format!("{}{{_:.*}}", SWAGGER_UI_PREFIX))
+                    .url(OPENAPI_PATH, ApiDoc::openapi()),
+            )
     })
     .bind((server_host, server_port))?
     .run()
     .await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test;
+    use sqlx::postgres::PgPoolOptions;
+    use std::sync::OnceLock;
+    
+    /// State for requests turned away before the database is read; the pool
+    /// never connects.
+    fn state() -> AppState {
+        static CONFIG: OnceLock<Arc<Config>> = OnceLock::new();
+        let config = CONFIG
+            .get_or_init(|| {
+                let dir = std::env::temp_dir().join("najaf-handler-tests");
+                std::fs::create_dir_all(&dir).unwrap();
+                std::env::set_var("DATABASE_URL", "postgres://localhost/cemetery");
+                std::env::set_var("DATA_BASE_DIR", &dir);
+                std::env::set_var("UPLOAD_DIR", dir.join("uploads"));
+                Arc::new(Config::from_env().unwrap())
+            })
+            .clone();
+        
+        let db_pool = Arc::new(PgPoolOptions::new().connect_lazy(&config.database_url).unwrap());
+        let (job_queue, _) = mpsc::unbounded_channel();
+        
+        AppState {
+            processor: Arc::new(DataProcessor::from_config(db_pool.clone(), &config)),
+            db_pool,
+            job_queue,
+            running_jobs: RunningJobs::default(),
+            metrics: Metrics::new(),
+            config,
+        }
+    }
+    
+    /// The status and `details` of a GET of `uri`.
+    async fn get<F, R>(uri: &str, path: &str, handler: F) -> (StatusCode, Option<String>)
+    where
+        F: actix_web::Handler<R>,
+        R: actix_web::FromRequest + 'static,
+        F::Output: Responder + 'static,
+    {
+        let app = test::init_service(
+            App::new().app_data(web::Data::new(state())).route(path, web::get().to(handler)),
+        )
+        .await;
+        let response = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+        let status = response.status();
+        let body: serde_json::Value = test::read_body_json(response).await;
+        
+        (status, body["details"].as_str().map(str::to_string))
+    }
+    
+    #[actix_web::test]
+    async fn records_pages_out_of_range_are_rejected() {
+        for (query, details) in [
+            ("limit=0", "limit must be between 1 and 500"),
+            ("limit=501", "limit must be between 1 and 500"),
+            ("offset=-1", "offset must not be negative"),
+            ("after=1262", "Invalid cursor '1262': expected burial_date,id"),
+        ] {
+            let uri = format!("/api/records?{}", query);
+            let (status, found) = get(&uri, "/api/records", list_records).await;
+            assert_eq!((status, found.as_deref()), (StatusCode::BAD_REQUEST, Some(details)), "{}", query);
+        }
+    }
+    
+    #[actix_web::test]
+    async fn nearby_searches_out_of_range_are_rejected() {
+        for (query, details) in [
+            ("lat=91&lon=44.3142", "lat must be between -90 and 90"),
+            ("lat=32.0175&lon=44.3142&radius_m=5001", "radius_m must be more than 0 and at most 5000"),
+            ("lat=32.0175&lon=44.3142&limit=0", "limit must be between 1 and 500"),
+            ("lat=32.0175&lon=44.3142&limit=501", "limit must be between 1 and 500"),
+        ] {
+            let uri = format!("/api/records/nearby?{}", query);
+            let (status, found) = get(&uri, "/api/records/nearby", nearby_records).await;
+            assert_eq!((status, found.as_deref()), (StatusCode::BAD_REQUEST, Some(details)), "{}", query);
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, IntCounter, IntGauge, Registry, TextEncoder,
+};
+use sqlx::PgPool;
+
+/// Buckets in seconds for a whole processing run, from a small CSV to a
+/// large multi-file delivery.
+const RUN_DURATION_BUCKETS: &[f64] = &[0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0, 600.0];
+
+/// Buckets in seconds for one `insert_batch` call of up to
+/// `INSERT_CHUNK_SIZE` records.
+const INSERT_DURATION_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+/// Prometheus counters and histograms for the processor, exposed on
+/// `/metrics`. Cloning shares the underlying metrics.
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    pub files_processed: IntCounter,
+    pub records_inserted: IntCounter,
+    pub records_failed: IntCounter,
+    pub processing_duration: Histogram,
+    pub db_insert_duration: Histogram,
+    pool_size: IntGauge,
+    pool_idle: IntGauge,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new_custom(Some("cemetery_processor".to_string()), None)
+            .expect("metric prefix is valid");
+        
+        let files_processed = IntCounter::new(
+            "files_processed_total",
+            "Files written to the database by committed processing runs",
+        ).expect("valid metric");
+        let records_inserted = IntCounter::new(
+            "records_inserted_total",
+            "Records inserted or updated by committed processing runs",
+        ).expect("valid metric");
+        let records_failed = IntCounter::new(
+            "records_failed_total",
+            "Records rejected by parsing, validation or the database",
+        ).expect("valid metric");
+        let processing_duration = Histogram::with_opts(
+            HistogramOpts::new("processing_duration_seconds", "Time taken by a processing run")
+                .buckets(RUN_DURATION_BUCKETS.to_vec()),
+        ).expect("valid metric");
+        let db_insert_duration = Histogram::with_opts(
+            HistogramOpts::new("db_insert_duration_seconds", "Time taken to insert one batch of records")
+                .buckets(INSERT_DURATION_BUCKETS.to_vec()),
+        ).expect("valid metric");
+        let pool_size = IntGauge::new(
+            "db_pool_connections",
+            "Connections currently open in the database pool",
+        ).expect("valid metric");
+        let pool_idle = IntGauge::new(
+            "db_pool_idle_connections",
+            "Open database connections not in use",
+        ).expect("valid metric");
+        
+        for collector in [
+            Box::new(files_processed.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(records_inserted.clone()),
+            Box::new(records_failed.clone()),
+            Box::new(processing_duration.clone()),
+            Box::new(db_insert_duration.clone()),
+            Box::new(pool_size.clone()),
+            Box::new(pool_idle.clone()),
+        ] {
+            registry.register(collector).expect("metric names are unique");
+        }
+        
+        Self {
+            registry,
+            files_processed,
+            records_inserted,
+            records_failed,
+            processing_duration,
+            db_insert_duration,
+            pool_size,
+            pool_idle,
+        }
+    }
+    
+    /// Renders every metric in the Prometheus text format, sampling the
+    /// pool gauges from `pool` first.
+    pub fn render(&self, pool: &PgPool) -> Result<String, prometheus::Error> {
+        self.pool_size.set(pool.size() as i64);
+        self.pool_idle.set(pool.num_idle() as i64);
+        
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        
+        String::from_utf8(buffer).map_err(|e| prometheus::Error::Msg(e.to_string()))
+    }
+}
@@ -0,0 +1,32 @@
+use log::info;
+use sqlx::migrate::{MigrateError, Migrator};
+use sqlx::PgPool;
+
+/// The versioned schema files in `migrations/`, embedded at build time.
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// Reads whether the service brings the database schema up to date at
+/// startup, from `RUN_MIGRATIONS` (on by default). Turn it off where the
+/// schema is managed separately.
+pub fn run_migrations_from_env() -> Result<bool, String> {
+    match std::env::var("RUN_MIGRATIONS") {
+        Ok(value) => match value.trim().to_ascii_lowercase().as_str() {
+            "1" | "true" | "yes" | "" => Ok(true),
+            "0" | "false" | "no" => Ok(false),
+            _ => Err(format!("RUN_MIGRATIONS must be true or false, got '{}'", value)),
+        },
+        Err(_) => Ok(true),
+    }
+}
+
+/// Applies the migrations the database has not had yet, in version order.
+/// Each runs in its own transaction; a migration already applied whose file
+/// has since changed is an error rather than being run again.
+pub async fn run(pool: &PgPool) -> Result<(), MigrateError> {
+    MIGRATOR.run(pool).await?;
+    
+    if let Some(latest) = MIGRATOR.iter().last() {
+        info!("Database schema is at version {} ({})", latest.version, latest.description);
+    }
+    Ok(())
+}
@@ -1,14 +1,22 @@
 use serde::{Deserialize, Serialize};
-use chrono::{NaiveDate, DateTime, Utc};
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, Utc};
+use log::warn;
+use sha2::{Digest, Sha256};
 use sqlx::FromRow;
+use std::collections::BTreeMap;
+use utoipa::ToSchema;
+use uuid::Uuid;
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
 pub struct FileMetadata {
     pub filename: String,
     pub file_hash: String,
     pub size: i64,
+    /// RFC 3339; without an offset it is local time in `TIMEZONE`
     pub download_time: String,
     pub extracted_path: Option<String>,
+    /// Encoding of the CSV files, e.g. `windows-1256`; detected when unset
+    pub encoding: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -32,18 +40,59 @@ pub struct DeceasedRecord {
     // Coordinates
     pub latitude: Option<f64>,
     pub longitude: Option<f64>,
+    /// Outline of the grave as a closed ring of `[longitude, latitude]`
+    #[serde(default)]
+    pub footprint: Option<Vec<[f64; 2]>>,
     
     // Additional info
+    pub birth_date: Option<NaiveDate>,
     pub age_at_death: Option<i32>,
+    /// As the source wrote it
     pub cause_of_death: Option<String>,
+    /// The cause's code in the vocabulary; `None` while it is unmapped
+    #[serde(default)]
+    pub cause_of_death_code: Option<String>,
     pub national_id: Option<String>,
     pub family_contact: Option<String>,
     
     // Metadata
     pub additional_data: Option<serde_json::Value>,
+    /// Set by the processor from what checking the record turned up
+    #[serde(skip)]
+    pub processing_status: RecordStatus,
 }
 
-#[derive(Debug, FromRow)]
+/// Where a record stands with data stewards, stored as `processing_status`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordStatus {
+    /// Written by something other than the processor; the column default
+    Pending,
+    /// Imported with nothing to check
+    #[default]
+    Completed,
+    /// Imported with warnings, or without coordinates to map it by
+    NeedsReview,
+    /// Without coordinates, and none could be derived from its plot or section
+    GeocodeFailed,
+    /// Shares a national id with another record, has one that fails its
+    /// checks, or may duplicate another record; kept off the map
+    Flagged,
+}
+
+impl RecordStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RecordStatus::Pending => "pending",
+            RecordStatus::Completed => "completed",
+            RecordStatus::NeedsReview => "needs_review",
+            RecordStatus::GeocodeFailed => "geocode_failed",
+            RecordStatus::Flagged => "flagged",
+        }
+    }
+}
+
+#[derive(Debug, FromRow, Serialize)]
 pub struct DbDeceasedRecord {
     pub id: i32,
     pub record_id: String,
@@ -52,9 +101,200 @@ pub struct DbDeceasedRecord {
     pub section: Option<String>,
     pub row_number: Option<i32>,
     pub plot_number: Option<i32>,
+    /// The point geometry split up; null for records without coordinates
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
     pub processing_status: String,
 }
 
+/// Which live records `/api/records` lists; unset filters match everything.
+#[derive(Debug, Default, Clone)]
+pub struct RecordFilter {
+    pub section: Option<String>,
+    /// Inclusive range on `burial_date`
+    pub burial_date_from: Option<NaiveDate>,
+    pub burial_date_to: Option<NaiveDate>,
+    pub has_coordinates: Option<bool>,
+    pub processing_status: Option<RecordStatus>,
+    /// Values `additional_data` must have at top-level keys, compared as text
+    pub extra: BTreeMap<String, String>,
+}
+
+/// Where the next `/api/records` page starts: the `burial_date` and `id` of
+/// the last record of the previous page, written as `2024-11-01,1262`. It
+/// carries both so the page still follows if that record is deleted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordCursor {
+    pub burial_date: NaiveDate,
+    pub id: i32,
+}
+
+impl std::fmt::Display for RecordCursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{}", self.burial_date, self.id)
+    }
+}
+
+impl std::str::FromStr for RecordCursor {
+    type Err = String;
+    
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid cursor '{}': expected burial_date,id", value);
+        let (burial_date, id) = value.split_once(',').ok_or_else(invalid)?;
+        
+        Ok(Self {
+            burial_date: burial_date.trim().parse().map_err(|_| invalid())?,
+            id: id.trim().parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// Prefix of the query parameters that filter on `additional_data`.
+pub const EXTRA_FILTER_PREFIX: &str = "extra.";
+
+/// Most `extra.` filters one listing may combine.
+pub const MAX_EXTRA_FILTERS: usize = 10;
+
+impl RecordFilter {
+    /// Adds the `extra.<key>=<value>` pairs of `params` to the filter; other
+    /// parameters are ignored. Keys are letters, digits, `_` and `-`, up to
+    /// 64 of them, and each may be given once.
+    pub fn with_extra<'a>(mut self, params: impl IntoIterator<Item = (&'a str, &'a str)>) -> Result<Self, String> {
+        for (name, value) in params {
+            let Some(key) = name.strip_prefix(EXTRA_FILTER_PREFIX) else { continue };
+            
+            let valid = !key.is_empty()
+                && key.len() <= 64
+                && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+            if !valid {
+                return Err(format!("{}: keys must be 1 to 64 letters, digits, '_' or '-'", name));
+            }
+            if value.chars().count() > 256 {
+                return Err(format!("{}: values must be at most 256 characters", name));
+            }
+            if self.extra.insert(key.to_string(), value.to_string()).is_some() {
+                return Err(format!("{} is given more than once", name));
+            }
+        }
+        
+        if self.extra.len() > MAX_EXTRA_FILTERS {
+            return Err(format!("At most {} extra. filters may be combined", MAX_EXTRA_FILTERS));
+        }
+        Ok(self)
+    }
+    
+    /// The object `additional_data` must contain, `None` without `extra`
+    /// filters.
+    pub fn extra_containment(&self) -> Option<serde_json::Value> {
+        (!self.extra.is_empty()).then(|| {
+            self.extra
+                .iter()
+                .map(|(key, value)| (key.clone(), serde_json::Value::String(value.clone())))
+                .collect::<serde_json::Map<_, _>>()
+                .into()
+        })
+    }
+}
+
+/// Every stored column of one record, with the point geometry split into
+/// latitude and longitude.
+#[derive(Debug, Serialize)]
+pub struct DeceasedRecordDetail {
+    pub id: i32,
+    pub record_id: String,
+    pub deceased_name: String,
+    pub deceased_name_arabic: Option<String>,
+    pub father_name: Option<String>,
+    pub grandfather_name: Option<String>,
+    pub death_date: NaiveDate,
+    pub death_location: Option<String>,
+    pub burial_date: NaiveDate,
+    pub burial_location: String,
+    pub section: Option<String>,
+    pub row_number: Option<i32>,
+    pub plot_number: Option<i32>,
+    pub grave_number: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub birth_date: Option<NaiveDate>,
+    pub age_at_death: Option<i32>,
+    pub cause_of_death: Option<String>,
+    pub cause_of_death_code: Option<String>,
+    pub national_id: Option<String>,
+    pub family_contact: Option<String>,
+    pub additional_data: Option<serde_json::Value>,
+    pub source_file: Option<String>,
+    pub processing_status: Option<String>,
+    pub processing_error: Option<String>,
+    pub created_at: Option<NaiveDateTime>,
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+/// What `/api/records/merge` did, as stored in `record_merges`.
+#[derive(Debug, Serialize)]
+pub struct RecordMerge {
+    pub merge_id: i32,
+    pub record_id: String,
+    pub merged_record_ids: Vec<String>,
+    /// Fields of the primary record that were filled in from the duplicates
+    pub filled_fields: Vec<String>,
+}
+
+/// The job an `Idempotency-Key` queued, as a repeat of the request finds it.
+#[derive(Debug)]
+pub struct KeyedJob {
+    pub job_id: Uuid,
+    /// SHA-256 of the request the key was first sent with
+    pub request_hash: String,
+    pub status: String,
+}
+
+/// Result of merging duplicates into a primary record.
+#[derive(Debug)]
+pub enum MergeOutcome {
+    Merged(RecordMerge),
+    /// Ids that are not live records; nothing was changed
+    NotFound(Vec<String>),
+}
+
+/// A record in the CSV import layout, as written by `/api/export/csv`.
+#[derive(Debug, Serialize)]
+pub struct ExportRecord {
+    pub record_id: String,
+    pub deceased_name: String,
+    pub deceased_name_arabic: Option<String>,
+    pub death_date: NaiveDate,
+    pub burial_date: NaiveDate,
+    pub burial_location: String,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub section: Option<String>,
+    #[serde(rename = "row")]
+    pub row_number: Option<i32>,
+    #[serde(rename = "plot")]
+    pub plot_number: Option<i32>,
+    pub cause_of_death: Option<String>,
+    pub national_id: Option<String>,
+    pub family_contact: Option<String>,
+}
+
+/// A name search hit with its trigram similarity to the query.
+#[derive(Debug, Serialize)]
+pub struct SearchResult {
+    #[serde(flatten)]
+    pub record: DbDeceasedRecord,
+    pub score: f32,
+}
+
+/// A record found near a point, with its distance from it.
+#[derive(Debug, Serialize)]
+pub struct NearbyRecord {
+    #[serde(flatten)]
+    pub record: DbDeceasedRecord,
+    /// Metres from the point, measured on the WGS 84 spheroid
+    pub distance_m: f64,
+}
+
 #[derive(Debug, Serialize)]
 pub struct GeoJsonFeature {
     #[serde(rename = "type")]
@@ -63,68 +303,814 @@ pub struct GeoJsonFeature {
     pub properties: serde_json::Value,
 }
 
+/// A grave's place on the map: its point, or the outline of its footprint.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "coordinates")]
+pub enum GeoJsonGeometry {
+    /// `[longitude, latitude]`
+    Point([f64; 2]),
+    /// Closed rings of `[longitude, latitude]`, the outline first
+    Polygon(Vec<Vec<[f64; 2]>>),
+}
+
+/// A feature whose geometry was rendered as GeoJSON by PostGIS, such as a
+/// section outline.
+#[derive(Debug, Serialize)]
+pub struct GeoJsonShapeFeature {
+    #[serde(rename = "type")]
+    pub feature_type: String,
+    pub geometry: serde_json::Value,
+    pub properties: serde_json::Value,
+}
+
 #[derive(Debug, Serialize)]
-pub struct GeoJsonGeometry {
+pub struct GeoJsonFeatureCollection<F = GeoJsonFeature> {
     #[serde(rename = "type")]
-    pub geometry_type: String,
-    pub coordinates: Vec<f64>,
+    pub collection_type: String,
+    pub features: Vec<F>,
+}
+
+impl<F> GeoJsonFeatureCollection<F> {
+    pub fn new(features: Vec<F>) -> Self {
+        Self {
+            collection_type: "FeatureCollection".to_string(),
+            features,
+        }
+    }
+}
+
+/// A section or plot outline read from a shapefile, in WGS 84.
+#[derive(Debug, Clone)]
+pub struct SectionOutline {
+    /// Position of the shape in the shapefile, from 0
+    pub shape_index: i32,
+    /// Position among the polygons of a multi-part shape, from 0
+    pub part_index: i32,
+    pub section: Option<String>,
+    pub plot_number: Option<i32>,
+    /// Every attribute of the shape's `.dbf` row
+    pub attributes: serde_json::Value,
+    /// Outer ring followed by any holes, each closed, as (longitude, latitude)
+    pub rings: Vec<Vec<(f64, f64)>>,
+}
+
+impl SectionOutline {
+    /// Well-known text for `ST_GeomFromText`.
+    pub fn to_wkt(&self) -> String {
+        let rings: Vec<String> = self.rings
+            .iter()
+            .map(|ring| {
+                let points: Vec<String> = ring.iter().map(|(lon, lat)| format!("{} {}", lon, lat)).collect();
+                format!("({})", points.join(", "))
+            })
+            .collect();
+        
+        format!("POLYGON({})", rings.join(", "))
+    }
 }
 
 #[derive(Debug)]
 pub struct ProcessingResult {
+    /// Records read from the source files, whatever became of them
+    pub records_read: i32,
+    /// Records written, `records_inserted` plus `records_updated`
     pub records_processed: i32,
+    pub records_inserted: i32,
+    pub records_updated: i32,
+    /// Records left out by the import mode
+    pub records_skipped: i32,
     pub records_failed: i32,
+    /// Records that passed validation with a latitude and longitude, and so
+    /// can be shown on the map
+    pub records_with_coordinates: i32,
     pub geojson_features_created: i32,
+    /// Records left off the map because their geometry is invalid or empty
+    pub geojson_features_skipped: i32,
     pub errors: Vec<ErrorDetails>,
+    pub warnings: Vec<String>,
+    /// Set when the run was skipped because the same content was already processed
+    pub already_processed: bool,
+    /// Set when nothing was written; the counts are what a real run would do
+    pub dry_run: bool,
+    /// Records dropped or merged because their `record_id` was repeated
+    pub duplicates_collapsed: i32,
+    pub possible_duplicates: Vec<PossibleDuplicate>,
+    /// Per-file breakdown, in processing order
+    pub files: Vec<FileSummary>,
+    /// Section and plot outlines written from shapefiles
+    pub sections_imported: i32,
+    /// Set when the run picked up where an interrupted run of the same
+    /// content stopped
+    pub resumed: bool,
+    /// Records skipped because the interrupted run had committed them
+    pub records_resumed: i32,
+    pub parse_mode: ParseMode,
+    /// Files of a directory left out because an earlier run imported the
+    /// same content
+    pub files_already_processed: Vec<String>,
+}
+
+/// Records read from one file of a run.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FileSummary {
+    /// Path relative to the processed directory
+    pub file: String,
+    pub records_read: usize,
+    pub records_failed: usize,
+    /// Records that passed validation with a latitude and longitude
+    pub records_with_coordinates: usize,
+}
+
+impl ProcessingResult {
+    /// Share of the records read that failed, from 0 to 1.
+    pub fn failure_ratio(&self) -> f64 {
+        if self.records_read > 0 {
+            f64::from(self.records_failed) / f64::from(self.records_read)
+        } else {
+            0.0
+        }
+    }
+    
+    /// Result for a run short-circuited by an earlier completed run with the same hash
+    pub fn already_processed(file_hash: &str, warnings: Vec<String>) -> Self {
+        let mut warnings = warnings;
+        warnings.push(format!(
+            "Content with hash {} was already processed; set force to reprocess it",
+            file_hash
+        ));
+        
+        Self {
+            records_read: 0,
+            records_processed: 0,
+            records_inserted: 0,
+            records_updated: 0,
+            records_skipped: 0,
+            records_failed: 0,
+            records_with_coordinates: 0,
+            geojson_features_created: 0,
+            geojson_features_skipped: 0,
+            errors: Vec::new(),
+            warnings,
+            already_processed: true,
+            dry_run: false,
+            duplicates_collapsed: 0,
+            possible_duplicates: Vec::new(),
+            files: Vec::new(),
+            sections_imported: 0,
+            resumed: false,
+            records_resumed: 0,
+            parse_mode: ParseMode::default(),
+            files_already_processed: Vec::new(),
+        }
+    }
+}
+
+/// Every record of some data checked as an import would check it, without
+/// writing anything; the `/api/validate` response.
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct ValidationReport {
+    pub records_read: usize,
+    pub records_valid: usize,
+    pub records_invalid: usize,
+    /// Valid records with a latitude and longitude
+    pub records_with_coordinates: usize,
+    /// In the order the files and their records were read
+    pub records: Vec<RecordReport>,
+    /// Files that could not be read, and shapes rejected from shapefiles
+    pub file_errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl ValidationReport {
+    pub fn push(&mut self, record: RecordReport) {
+        self.records_read += 1;
+        if record.valid {
+            self.records_valid += 1;
+        } else {
+            self.records_invalid += 1;
+        }
+        self.records.push(record);
+    }
+}
+
+/// How one record fared in a [`ValidationReport`].
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RecordReport {
+    pub file: String,
+    /// Missing when the row could not be read far enough to find it
+    pub record_id: Option<String>,
+    /// Known for rows that could not be parsed
+    pub line_number: Option<u64>,
+    pub valid: bool,
+    /// Why the record would fail; empty when it is valid
+    pub errors: Vec<RecordIssue>,
+    /// What the record would be imported with, e.g. an age that disagrees
+    /// with its dates
+    pub warnings: Vec<String>,
+}
+
+/// One reason a record fails, as in a job's `errors`.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RecordIssue {
+    pub message: String,
+    pub code: Option<&'static str>,
+    pub field: Option<&'static str>,
+}
+
+impl From<&ValidationError> for RecordIssue {
+    fn from(error: &ValidationError) -> Self {
+        Self {
+            message: error.to_string(),
+            code: Some(error.code()),
+            field: error.field(),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct ErrorDetails {
     pub record_id: Option<String>,
+    /// Physical line in the source file, counting the header as line 1.
+    pub line_number: Option<u64>,
     pub message: String,
+    /// Machine-readable [`ValidationError::code`] for validation failures
+    pub code: Option<&'static str>,
+    /// The record field a validation failure refers to
+    pub field: Option<&'static str>,
+    /// What the source gave for a record that could not be parsed, kept
+    /// with it in `rejected_records`; boxed, as most errors have none
+    pub source_fields: Option<Box<serde_json::Value>>,
+}
+
+impl ErrorDetails {
+    pub fn from_validation(record_id: &str, error: &ValidationError) -> Self {
+        Self {
+            record_id: Some(record_id.to_string()),
+            line_number: None,
+            message: error.to_string(),
+            code: Some(error.code()),
+            field: error.field(),
+            source_fields: None,
+        }
+    }
+}
+
+/// A problem found by [`DeceasedRecord::validate`]. The messages are the
+/// ones the API has always returned.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum ValidationError {
+    #[error("{0} is required")]
+    MissingField(&'static str),
+    #[error("burial_date cannot be before death_date")]
+    BurialBeforeDeath,
+    #[error("Invalid latitude")]
+    InvalidLatitude(f64),
+    #[error("Invalid longitude")]
+    InvalidLongitude(f64),
+    #[error("coordinates outside cemetery bounds")]
+    OutsideCemeteryBounds { latitude: f64, longitude: f64 },
+    #[error("latitude and longitude appear to be swapped")]
+    CoordinatesSwapped { latitude: f64, longitude: f64 },
+    #[error("age_at_death must be between 0 and {}", MAX_AGE_AT_DEATH)]
+    InvalidAge(i32),
+    #[error("birth_date cannot be after death_date")]
+    BirthAfterDeath,
+    #[error("age_at_death {age} does not match the {expected} years between birth_date and death_date")]
+    AgeMismatch { age: i32, expected: i32 },
+    #[error("national_id '{0}' is not a valid national ID")]
+    InvalidNationalId(String),
+    #[error("{field} {date} is in the future")]
+    DateInFuture { field: &'static str, date: NaiveDate },
+    #[error("{field} {date} is before {earliest_year}")]
+    DateTooEarly { field: &'static str, date: NaiveDate, earliest_year: i32 },
+    #[error("footprint corner ({longitude}, {latitude}) is not a valid longitude and latitude")]
+    InvalidFootprint { longitude: f64, latitude: f64 },
+}
+
+/// Oldest plausible age at death, in years.
+pub const MAX_AGE_AT_DEATH: i32 = 130;
+
+/// Years `age_at_death` may differ from the age worked out from the birth
+/// and death dates before the record is rejected. Smaller differences are
+/// common and only warned about: ages counted in Hijri years run about one
+/// year ahead per 33, and some registers give the age at the next birthday.
+pub const AGE_TOLERANCE_YEARS: i32 = 2;
+
+impl ValidationError {
+    /// Stable identifier for clients, independent of the message wording.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ValidationError::MissingField(_) => "missing_field",
+            ValidationError::BurialBeforeDeath => "burial_before_death",
+            ValidationError::InvalidLatitude(_) => "invalid_latitude",
+            ValidationError::InvalidLongitude(_) => "invalid_longitude",
+            ValidationError::OutsideCemeteryBounds { .. } => "outside_cemetery_bounds",
+            ValidationError::CoordinatesSwapped { .. } => "coordinates_swapped",
+            ValidationError::InvalidAge(_) => "invalid_age",
+            ValidationError::BirthAfterDeath => "birth_after_death",
+            ValidationError::AgeMismatch { .. } => "age_mismatch",
+            ValidationError::InvalidNationalId(_) => "invalid_national_id",
+            ValidationError::DateInFuture { .. } => "date_in_future",
+            ValidationError::DateTooEarly { .. } => "date_too_early",
+            ValidationError::InvalidFootprint { .. } => "invalid_footprint",
+        }
+    }
+    
+    /// The field to highlight for correction.
+    pub fn field(&self) -> Option<&'static str> {
+        match self {
+            ValidationError::MissingField(field) => Some(field),
+            ValidationError::BurialBeforeDeath => Some("burial_date"),
+            ValidationError::InvalidLatitude(_) => Some("latitude"),
+            ValidationError::InvalidLongitude(_) => Some("longitude"),
+            ValidationError::OutsideCemeteryBounds { .. }
+            | ValidationError::CoordinatesSwapped { .. } => Some("coordinates"),
+            ValidationError::InvalidAge(_) | ValidationError::AgeMismatch { .. } => Some("age_at_death"),
+            ValidationError::BirthAfterDeath => Some("birth_date"),
+            ValidationError::InvalidNationalId(_) => Some("national_id"),
+            ValidationError::DateInFuture { field, .. } | ValidationError::DateTooEarly { field, .. } => Some(field),
+            ValidationError::InvalidFootprint { .. } => Some("footprint"),
+        }
+    }
+}
+
+/// Geographic extent of the cemetery, in WGS84 degrees.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct CemeteryBounds {
+    pub min_lon: f64,
+    pub min_lat: f64,
+    pub max_lon: f64,
+    pub max_lat: f64,
+}
+
+impl CemeteryBounds {
+    /// Reads `CEMETERY_BOUNDS` as `min_lon,min_lat,max_lon,max_lat`, the same
+    /// order as the bounding-box query. `None` when the variable is unset.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        match std::env::var("CEMETERY_BOUNDS") {
+            Ok(value) => value.parse().map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+    
+    pub fn contains(&self, lat: f64, lon: f64) -> bool {
+        (self.min_lat..=self.max_lat).contains(&lat) && (self.min_lon..=self.max_lon).contains(&lon)
+    }
+}
+
+impl std::str::FromStr for CemeteryBounds {
+    type Err = String;
+    
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let parts = value.split(',')
+            .map(|part| part.trim().parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Invalid cemetery bounds '{}': {}", value, e))?;
+        
+        let [min_lon, min_lat, max_lon, max_lat] = parts[..] else {
+            return Err(format!(
+                "Invalid cemetery bounds '{}': expected min_lon,min_lat,max_lon,max_lat",
+                value
+            ));
+        };
+        
+        if min_lon >= max_lon || min_lat >= max_lat {
+            return Err(format!("Invalid cemetery bounds '{}': minimum must be below maximum", value));
+        }
+        
+        Ok(Self { min_lon, min_lat, max_lon, max_lat })
+    }
+}
+
+/// Earliest plausible death or burial year when `EARLIEST_DATE_YEAR` is not
+/// set.
+pub const DEFAULT_EARLIEST_DATE_YEAR: i32 = 1800;
+
+/// Days a burial may be dated ahead when `FUTURE_BURIAL_GRACE_DAYS` is not
+/// set, for burials registered before they take place.
+pub const DEFAULT_FUTURE_BURIAL_GRACE_DAYS: u32 = 7;
+
+/// The range of plausible death and burial dates, to catch typos such as a
+/// burial in 2099.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateLimits {
+    pub earliest_year: i32,
+    pub future_burial_grace_days: u32,
+}
+
+impl Default for DateLimits {
+    fn default() -> Self {
+        Self {
+            earliest_year: DEFAULT_EARLIEST_DATE_YEAR,
+            future_burial_grace_days: DEFAULT_FUTURE_BURIAL_GRACE_DAYS,
+        }
+    }
+}
+
+impl DateLimits {
+    /// Reads `EARLIEST_DATE_YEAR` and `FUTURE_BURIAL_GRACE_DAYS`, falling
+    /// back to [`DEFAULT_EARLIEST_DATE_YEAR`] and
+    /// [`DEFAULT_FUTURE_BURIAL_GRACE_DAYS`].
+    pub fn from_env() -> Result<Self, String> {
+        let mut limits = Self::default();
+        
+        if let Ok(value) = std::env::var("EARLIEST_DATE_YEAR") {
+            limits.earliest_year = value
+                .trim()
+                .parse()
+                .map_err(|_| format!("EARLIEST_DATE_YEAR must be a year, got '{}'", value))?;
+        }
+        if let Ok(value) = std::env::var("FUTURE_BURIAL_GRACE_DAYS") {
+            limits.future_burial_grace_days = value
+                .trim()
+                .parse()
+                .map_err(|_| format!("FUTURE_BURIAL_GRACE_DAYS must be a non-negative number, got '{}'", value))?;
+        }
+        
+        Ok(limits)
+    }
+}
+
+/// What to do with a record whose latitude and longitude look exchanged.
+#[derive(Debug, Default, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SwappedCoordinates {
+    /// Fail the record so the source can be corrected
+    #[default]
+    Reject,
+    /// Exchange the values and note the originals in `additional_data`
+    Swap,
+}
+
+/// How a run treats records that cannot be parsed or fail validation.
+#[derive(Debug, Default, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ParseMode {
+    /// Abort the run at the first failed record, writing nothing
+    Strict,
+    /// Report failed records and write the others
+    #[default]
+    Lenient,
+}
+
+/// How repeated `record_id`s within one run are collapsed.
+#[derive(Debug, Default, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateStrategy {
+    /// Keep the first occurrence and drop the rest
+    KeepFirst,
+    /// Keep the last occurrence, as repeated upserts used to
+    #[default]
+    KeepLast,
+    /// Keep the last occurrence but fill its empty fields from earlier ones
+    Merge,
+}
+
+/// What identifies a person across deliveries, and so which stored record
+/// an incoming one updates.
+#[derive(Debug, Default, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictKey {
+    /// The source's own `record_id`
+    #[default]
+    RecordId,
+    /// The national id, for sources whose record ids are not stable
+    NationalId,
+    /// The name together with the death date, for sources with neither
+    NameAndDeathDate,
+}
+
+impl ConflictKey {
+    /// Columns of the unique index an upsert on this key conflicts on.
+    pub fn columns(self) -> &'static [&'static str] {
+        match self {
+            ConflictKey::RecordId => &["record_id"],
+            ConflictKey::NationalId => &["national_id"],
+            ConflictKey::NameAndDeathDate => &["deceased_name", "death_date"],
+        }
+    }
+    
+    /// The record's value for this key, `None` if it has none.
+    pub fn value(self, record: &DeceasedRecord) -> Option<String> {
+        let value = match self {
+            ConflictKey::RecordId => record.record_id.clone(),
+            ConflictKey::NationalId => record.national_id.clone().unwrap_or_default(),
+            ConflictKey::NameAndDeathDate if record.deceased_name.is_empty() => String::new(),
+            ConflictKey::NameAndDeathDate => name_and_death_date(&record.deceased_name, record.death_date),
+        };
+        
+        (!value.trim().is_empty()).then_some(value)
+    }
+    
+    /// The field a record lacks for this key; record ids are checked by
+    /// validation whatever the key.
+    pub fn missing_field(self, record: &DeceasedRecord) -> Option<&'static str> {
+        match self {
+            ConflictKey::RecordId => None,
+            ConflictKey::NationalId => self.value(record).is_none().then_some("national_id"),
+            ConflictKey::NameAndDeathDate => self.value(record).is_none().then_some("deceased_name"),
+        }
+    }
+    
+    /// A record id made from the key, for records that come without one,
+    /// so a repeat delivery of the person gets the same id.
+    pub fn derived_record_id(self, record: &DeceasedRecord) -> Option<String> {
+        let value = self.value(record)?;
+        
+        match self {
+            ConflictKey::RecordId => None,
+            ConflictKey::NationalId => Some(format!("nid-{}", value)),
+            ConflictKey::NameAndDeathDate => {
+                let digest = Sha256::digest(value.as_bytes());
+                let hex: String = digest[..8].iter().map(|b| format!("{:02x}", b)).collect();
+                Some(format!("nd-{}", hex))
+            }
+        }
+    }
+}
+
+/// A person's [`ConflictKey::NameAndDeathDate`] value.
+pub fn name_and_death_date(name: &str, death_date: NaiveDate) -> String {
+    format!("{}|{}", death_date, name)
+}
+
+/// Two records with different ids that look like the same person: same
+/// name, death date and burial location.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq, ToSchema)]
+pub struct PossibleDuplicate {
+    pub record_id: String,
+    pub matches_record_id: String,
 }
 
 impl DeceasedRecord {
-    pub fn validate(&self) -> Result<(), String> {
+    /// Combines this record with a later one that has the same `record_id`,
+    /// according to `strategy`.
+    pub fn collapse(&mut self, later: DeceasedRecord, strategy: DuplicateStrategy) {
+        match strategy {
+            DuplicateStrategy::KeepFirst => {}
+            DuplicateStrategy::KeepLast => *self = later,
+            DuplicateStrategy::Merge => {
+                let earlier = std::mem::replace(self, later);
+                self.fill_from(earlier);
+            }
+        }
+    }
+    
+    /// Fills the empty optional fields of this record from `other`; blank
+    /// CSV cells count as empty. Objects in `additional_data` are merged,
+    /// with this record's keys winning.
+    fn fill_from(&mut self, other: DeceasedRecord) {
+        fn fill<T>(field: &mut Option<T>, other: Option<T>) {
+            if field.is_none() {
+                *field = other;
+            }
+        }
+        
+        fn fill_text(field: &mut Option<String>, other: Option<String>) {
+            if field.as_deref().is_none_or(|text| text.trim().is_empty()) {
+                *field = other.or(field.take());
+            }
+        }
+        
+        fill_text(&mut self.deceased_name_arabic, other.deceased_name_arabic);
+        fill_text(&mut self.father_name, other.father_name);
+        fill_text(&mut self.grandfather_name, other.grandfather_name);
+        fill_text(&mut self.death_location, other.death_location);
+        fill_text(&mut self.section, other.section);
+        fill(&mut self.row_number, other.row_number);
+        fill(&mut self.plot_number, other.plot_number);
+        fill_text(&mut self.grave_number, other.grave_number);
+        fill(&mut self.birth_date, other.birth_date);
+        fill(&mut self.age_at_death, other.age_at_death);
+        // The code belongs to the cause it was read from
+        if self.cause_of_death.as_deref().is_none_or(|text| text.trim().is_empty()) {
+            self.cause_of_death_code = other.cause_of_death_code;
+        }
+        fill_text(&mut self.cause_of_death, other.cause_of_death);
+        fill_text(&mut self.national_id, other.national_id);
+        fill_text(&mut self.family_contact, other.family_contact);
+        
+        // Coordinates only make sense as a pair
+        if self.latitude.is_none() || self.longitude.is_none() {
+            self.latitude = other.latitude;
+            self.longitude = other.longitude;
+        }
+        
+        match (&mut self.additional_data, other.additional_data) {
+            (Some(serde_json::Value::Object(mine)), Some(serde_json::Value::Object(theirs))) => {
+                for (key, value) in theirs {
+                    mine.entry(key).or_insert(value);
+                }
+            }
+            (data @ None, theirs) => *data = theirs,
+            _ => {}
+        }
+    }
+    
+    /// True when the coordinates only make sense with latitude and longitude
+    /// exchanged: the latitude is impossible but the longitude would be a
+    /// valid latitude, or the point misses `bounds` and the swapped one hits.
+    pub fn has_swapped_coordinates(&self, bounds: Option<&CemeteryBounds>) -> bool {
+        let (Some(lat), Some(lon)) = (self.latitude, self.longitude) else {
+            return false;
+        };
+        
+        if !(-90.0..=90.0).contains(&lat) {
+            return (-90.0..=90.0).contains(&lon);
+        }
+        
+        bounds.is_some_and(|bounds| !bounds.contains(lat, lon) && bounds.contains(lon, lat))
+    }
+    
+    /// Applies `mode` to a record with swapped coordinates; records that look
+    /// fine are left untouched.
+    pub fn fix_swapped_coordinates(
+        &mut self,
+        bounds: Option<&CemeteryBounds>,
+        mode: SwappedCoordinates,
+    ) -> Result<(), ValidationError> {
+        let (Some(latitude), Some(longitude)) = (self.latitude, self.longitude) else {
+            return Ok(());
+        };
+        
+        if !self.has_swapped_coordinates(bounds) {
+            return Ok(());
+        }
+        
+        match mode {
+            SwappedCoordinates::Reject => {
+                Err(ValidationError::CoordinatesSwapped { latitude, longitude })
+            }
+            SwappedCoordinates::Swap => {
+                let note = serde_json::json!({
+                    "original_latitude": latitude,
+                    "original_longitude": longitude,
+                });
+                
+                std::mem::swap(&mut self.latitude, &mut self.longitude);
+                
+                match self.additional_data.get_or_insert_with(|| serde_json::json!({})) {
+                    serde_json::Value::Object(data) => {
+                        data.insert("coordinates_swapped".to_string(), note);
+                    }
+                    _ => warn!("Record {} has non-object additional_data; swap not noted", self.record_id),
+                }
+                
+                Ok(())
+            }
+        }
+    }
+    
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        self.validate_with_bounds(None)
+    }
+    
+    /// Like [`DeceasedRecord::validate`], but coordinates must also fall
+    /// inside `bounds` when given. Every problem with the record is reported,
+    /// not just the first.
+    pub fn validate_with_bounds(
+        &self,
+        bounds: Option<&CemeteryBounds>,
+    ) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        
         // Validate required fields
         if self.record_id.is_empty() {
-            return Err("record_id is required".to_string());
+            errors.push(ValidationError::MissingField("record_id"));
         }
         
         if self.deceased_name.is_empty() {
-            return Err("deceased_name is required".to_string());
+            errors.push(ValidationError::MissingField("deceased_name"));
         }
         
         if self.burial_location.is_empty() {
-            return Err("burial_location is required".to_string());
+            errors.push(ValidationError::MissingField("burial_location"));
         }
         
         // Validate dates
         if self.burial_date < self.death_date {
-            return Err("burial_date cannot be before death_date".to_string());
+            errors.push(ValidationError::BurialBeforeDeath);
+        }
+        
+        if let Some(age) = self.age_at_death {
+            if !(0..=MAX_AGE_AT_DEATH).contains(&age) {
+                errors.push(ValidationError::InvalidAge(age));
+            }
+        }
+        
+        match self.age_from_dates() {
+            Some(Err(())) => errors.push(ValidationError::BirthAfterDeath),
+            Some(Ok(expected)) if expected > MAX_AGE_AT_DEATH => {
+                errors.push(ValidationError::InvalidAge(expected));
+            }
+            Some(Ok(expected)) => {
+                let stated = self.age_at_death.filter(|age| (0..=MAX_AGE_AT_DEATH).contains(age));
+                if let Some(age) = stated.filter(|age| (age - expected).abs() > AGE_TOLERANCE_YEARS) {
+                    errors.push(ValidationError::AgeMismatch { age, expected });
+                }
+            }
+            None => {}
         }
         
         // Validate coordinates if present
         if let (Some(lat), Some(lon)) = (self.latitude, self.longitude) {
-            if lat < -90.0 || lat > 90.0 {
-                return Err("Invalid latitude".to_string());
+            let lat_valid = (-90.0..=90.0).contains(&lat);
+            let lon_valid = (-180.0..=180.0).contains(&lon);
+            
+            if !lat_valid {
+                errors.push(ValidationError::InvalidLatitude(lat));
+            }
+            if !lon_valid {
+                errors.push(ValidationError::InvalidLongitude(lon));
             }
-            if lon < -180.0 || lon > 180.0 {
-                return Err("Invalid longitude".to_string());
+            if lat_valid && lon_valid && bounds.is_some_and(|bounds| !bounds.contains(lat, lon)) {
+                errors.push(ValidationError::OutsideCemeteryBounds { latitude: lat, longitude: lon });
             }
         }
         
-        Ok(())
+        let invalid_corner = self.footprint.iter().flatten().find(|[lon, lat]| {
+            !(-180.0..=180.0).contains(lon) || !(-90.0..=90.0).contains(lat)
+        });
+        if let Some(&[longitude, latitude]) = invalid_corner {
+            errors.push(ValidationError::InvalidFootprint { longitude, latitude });
+        }
+        
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+    
+    /// Problems with the death and burial dates given `limits`: a death after
+    /// `today`, a burial more than the grace period after it, or either
+    /// before the earliest year. A day is allowed for the difference
+    /// between the server's date and the cemetery's.
+    pub fn check_date_limits(&self, limits: &DateLimits, today: NaiveDate) -> Vec<ValidationError> {
+        let mut errors = Vec::new();
+        let tomorrow = today + chrono::Duration::days(1);
+        let latest_burial = tomorrow + chrono::Duration::days(i64::from(limits.future_burial_grace_days));
+        
+        for (field, date, latest) in [
+            ("death_date", self.death_date, tomorrow),
+            ("burial_date", self.burial_date, latest_burial),
+        ] {
+            if date > latest {
+                errors.push(ValidationError::DateInFuture { field, date });
+            } else if date.year() < limits.earliest_year {
+                errors.push(ValidationError::DateTooEarly { field, date, earliest_year: limits.earliest_year });
+            }
+        }
+        
+        errors
+    }
+    
+    /// A stated `age_at_death` that differs from the dates by no more than
+    /// [`AGE_TOLERANCE_YEARS`]: worth a look, but not a reason to reject the
+    /// record. Larger differences fail validation instead.
+    pub fn age_discrepancy(&self) -> Option<String> {
+        let (age, Some(Ok(expected))) = (self.age_at_death?, self.age_from_dates()) else {
+            return None;
+        };
+        
+        (age != expected && (age - expected).abs() <= AGE_TOLERANCE_YEARS).then(|| {
+            format!(
+                "age_at_death {} differs from the {} years between birth_date and death_date",
+                age, expected
+            )
+        })
+    }
+    
+    /// Where `burial_location` and the section, row or plot columns
+    /// disagree, as the parser noted it under a `location_layout`.
+    pub fn location_conflict(&self) -> Option<String> {
+        let conflict = self.additional_data.as_ref()?.get("location_conflict")?;
+        conflict.as_str().map(str::to_string)
+    }
+    
+    /// Completed years from `birth_date` to `death_date`, or `Err` if the
+    /// birth is after the death. `None` without a birth date.
+    fn age_from_dates(&self) -> Option<Result<i32, ()>> {
+        let birth_date = self.birth_date?;
+        
+        Some(match self.death_date.years_since(birth_date) {
+            Some(years) => Ok(i32::try_from(years).unwrap_or(i32::MAX)),
+            None => Err(()),
+        })
     }
     
     pub fn has_coordinates(&self) -> bool {
         self.latitude.is_some() && self.longitude.is_some()
     }
     
+    /// The record as a map feature: a polygon when it has a footprint,
+    /// otherwise its point. `None` when it has neither.
     pub fn to_geojson_feature(&self) -> Option<GeoJsonFeature> {
-        if !self.has_coordinates() {
-            return None;
-        }
+        let geometry = match (&self.footprint, self.longitude, self.latitude) {
+            (Some(footprint), _, _) => GeoJsonGeometry::Polygon(vec![footprint.clone()]),
+            (None, Some(longitude), Some(latitude)) => GeoJsonGeometry::Point([longitude, latitude]),
+            _ => return None,
+        };
         
         let mut properties = serde_json::Map::new();
         properties.insert("record_id".to_string(), serde_json::json!(self.record_id));
@@ -144,11 +1130,178 @@ impl DeceasedRecord {
         
         Some(GeoJsonFeature {
             feature_type: "Feature".to_string(),
-            geometry: GeoJsonGeometry {
-                geometry_type: "Point".to_string(),
-                coordinates: vec![self.longitude.unwrap(), self.latitude.unwrap()],
-            },
+            geometry,
             properties: serde_json::Value::Object(properties),
         })
     }
 }
+
+/// A queued `/api/process` run as stored in `processing_jobs`.
+#[derive(Debug, FromRow)]
+pub struct ProcessingJob {
+    pub id: Uuid,
+    /// One of `queued`, `running`, `completed` or `failed`
+    pub status: String,
+    pub result: Option<serde_json::Value>,
+    pub error_message: Option<String>,
+    /// The request's `timestamp`; `None` for jobs queued before it was kept
+    pub requested_at: Option<DateTime<Utc>>,
+    pub created_at: NaiveDateTime,
+    pub started_at: Option<NaiveDateTime>,
+    pub finished_at: Option<NaiveDateTime>,
+}
+
+/// A processing run as recorded in `file_processing_log`.
+#[derive(Debug, Serialize)]
+pub struct ProcessingLogEntry {
+    pub id: i32,
+    pub filename: String,
+    pub file_hash: Option<String>,
+    pub file_size: Option<i64>,
+    pub download_time: Option<DateTime<Utc>>,
+    pub records_total: Option<i32>,
+    pub records_processed: Option<i32>,
+    pub records_failed: Option<i32>,
+    /// `None` for runs logged before coverage was recorded
+    pub records_with_coordinates: Option<i32>,
+    pub status: Option<String>,
+    pub error_message: Option<String>,
+    pub processing_start_time: Option<NaiveDateTime>,
+    pub processing_end_time: Option<NaiveDateTime>,
+    /// `None` for runs logged before start times were recorded
+    pub duration_seconds: Option<f64>,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+/// A `national_id` carried by more than one live record.
+#[derive(Debug, Serialize)]
+pub struct SharedNationalId {
+    pub national_id: String,
+    /// In `record_id` order
+    pub record_ids: Vec<String>,
+}
+
+/// A record suggested as a relative of another, with how confident the
+/// suggestion is.
+#[derive(Debug, Serialize)]
+pub struct RelatedRecord {
+    pub record_id: String,
+    pub deceased_name: String,
+    pub deceased_name_arabic: Option<String>,
+    pub death_date: NaiveDate,
+    /// From 0 to 1
+    pub confidence: f32,
+}
+
+/// A record's suggested family links, most confident first.
+#[derive(Debug, Serialize)]
+pub struct FamilyRelations {
+    pub record_id: String,
+    /// Records whose name matches this record's father name
+    pub fathers: Vec<RelatedRecord>,
+    /// Records whose father name matches this record's name
+    pub children: Vec<RelatedRecord>,
+}
+
+/// One change to a record, as kept in `record_history`.
+#[derive(Debug, Serialize)]
+pub struct RecordChange {
+    pub id: i64,
+    /// `UPDATE`, or `DELETE` when the record was removed for good
+    pub operation: String,
+    /// Columns whose value changed; empty for a delete
+    pub changed_fields: Vec<String>,
+    /// The whole record before the change
+    pub previous: serde_json::Value,
+    /// The record's `source_file` after the change: the delivery that made
+    /// it, when an import did
+    pub source_file: Option<String>,
+    pub changed_at: NaiveDateTime,
+}
+
+/// A record a run rejected, before it is written to `rejected_records`.
+#[derive(Debug, Clone)]
+pub struct Rejection {
+    /// The file within the delivery the record came from
+    pub file_name: String,
+    pub record_id: Option<String>,
+    pub line_number: Option<u64>,
+    /// The source row, or the record as parsed when it failed validation
+    pub fields: Option<serde_json::Value>,
+    /// Every problem found, as `{"message", "code", "field"}`
+    pub errors: serde_json::Value,
+}
+
+/// A rejected record as kept in `rejected_records`, for review.
+#[derive(Debug, Serialize)]
+pub struct RejectedRecord {
+    pub id: i64,
+    /// The delivery, as in `file_processing_log.filename`
+    pub source_file: String,
+    pub file_name: String,
+    pub record_id: Option<String>,
+    pub line_number: Option<i64>,
+    pub fields: Option<serde_json::Value>,
+    pub errors: serde_json::Value,
+    pub rejected_at: NaiveDateTime,
+}
+
+/// The changes kept for a record, newest first.
+#[derive(Debug, Serialize)]
+pub struct RecordHistory {
+    pub record_id: String,
+    pub changes: Vec<RecordChange>,
+}
+
+/// Aggregate figures for the admin dashboard, served by `/api/stats`.
+#[derive(Debug, Clone, Serialize)]
+pub struct CemeteryStats {
+    pub total_records: i64,
+    pub records_with_coordinates: i64,
+    pub records_per_section: Vec<SectionCount>,
+    pub burials_per_year: Vec<YearCount>,
+    /// Records per coded cause of death, most common first
+    pub causes_of_death: Vec<CauseCount>,
+    /// Causes not in the vocabulary, as written, most common first
+    pub uncoded_causes_of_death: Vec<UncodedCauseCount>,
+    pub processing: ProcessingTotals,
+    pub generated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SectionCount {
+    /// `None` groups the records without a section
+    pub section: Option<String>,
+    pub records: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct YearCount {
+    pub year: i32,
+    pub burials: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CauseCount {
+    pub code: String,
+    /// From the vocabulary in use, if it still has the code
+    pub label: Option<String>,
+    pub records: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UncodedCauseCount {
+    pub cause_of_death: String,
+    pub records: i64,
+}
+
+/// Totals over `file_processing_log`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessingTotals {
+    pub files_completed: i64,
+    pub files_failed: i64,
+    pub records_total: i64,
+    pub records_processed: i64,
+    pub records_failed: i64,
+    pub last_processed_at: Option<NaiveDateTime>,
+}
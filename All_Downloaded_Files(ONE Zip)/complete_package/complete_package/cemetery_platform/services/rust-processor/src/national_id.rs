@@ -0,0 +1,131 @@
+use crate::arabic::normalize_digits;
+use crate::models::ValidationError;
+use regex::Regex;
+
+/// The Unified National Card number: 12 digits.
+pub const DEFAULT_NATIONAL_ID_PATTERN: &str = r"^[0-9]{12}$";
+
+/// What happens to a record whose `national_id` does not check out.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NationalIdMode {
+    /// National ids are not checked
+    Off,
+    /// The record is written with a warning; older registers often carry
+    /// ids from before the unified card, or none at all
+    #[default]
+    Warn,
+    /// The record fails validation
+    Reject,
+}
+
+/// Check digit scheme applied after the pattern matches.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NationalIdChecksum {
+    #[default]
+    None,
+    /// The last digit is a Luhn check digit over the others
+    Luhn,
+}
+
+/// How `national_id`s are checked during imports.
+#[derive(Debug, Clone)]
+pub struct NationalIdRules {
+    pattern: Regex,
+    checksum: NationalIdChecksum,
+    mode: NationalIdMode,
+}
+
+impl Default for NationalIdRules {
+    fn default() -> Self {
+        Self::new(DEFAULT_NATIONAL_ID_PATTERN, NationalIdChecksum::None, NationalIdMode::Warn)
+            .expect("the default pattern is valid")
+    }
+}
+
+impl NationalIdRules {
+    pub fn new(pattern: &str, checksum: NationalIdChecksum, mode: NationalIdMode) -> Result<Self, String> {
+        let pattern = Regex::new(pattern).map_err(|e| format!("Invalid national id pattern '{}': {}", pattern, e))?;
+        Ok(Self { pattern, checksum, mode })
+    }
+    
+    /// Reads `NATIONAL_ID_VALIDATION` (`off`, `warn` or `reject`, default
+    /// `warn`), `NATIONAL_ID_PATTERN` (default
+    /// [`DEFAULT_NATIONAL_ID_PATTERN`]) and `NATIONAL_ID_CHECKSUM` (`none`
+    /// or `luhn`, default `none`).
+    pub fn from_env() -> Result<Self, String> {
+        let mode = match std::env::var("NATIONAL_ID_VALIDATION") {
+            Ok(value) => match value.trim().to_ascii_lowercase().as_str() {
+                "off" => NationalIdMode::Off,
+                "warn" | "" => NationalIdMode::Warn,
+                "reject" => NationalIdMode::Reject,
+                _ => return Err(format!("NATIONAL_ID_VALIDATION must be off, warn or reject, got '{}'", value)),
+            },
+            Err(_) => NationalIdMode::default(),
+        };
+        
+        let checksum = match std::env::var("NATIONAL_ID_CHECKSUM") {
+            Ok(value) => match value.trim().to_ascii_lowercase().as_str() {
+                "none" | "" => NationalIdChecksum::None,
+                "luhn" => NationalIdChecksum::Luhn,
+                _ => return Err(format!("NATIONAL_ID_CHECKSUM must be none or luhn, got '{}'", value)),
+            },
+            Err(_) => NationalIdChecksum::default(),
+        };
+        
+        let pattern = std::env::var("NATIONAL_ID_PATTERN").unwrap_or_else(|_| DEFAULT_NATIONAL_ID_PATTERN.to_string());
+        
+        Self::new(&pattern, checksum, mode).map_err(|e| format!("NATIONAL_ID_PATTERN: {}", e))
+    }
+    
+    pub fn mode(&self) -> NationalIdMode {
+        self.mode
+    }
+    
+    /// The problem with `national_id`, if any. Missing ids are fine, and
+    /// nothing is reported when checking is off.
+    pub fn check(&self, national_id: Option<&str>) -> Option<ValidationError> {
+        let national_id = national_id.filter(|id| !id.is_empty())?;
+        if self.mode == NationalIdMode::Off {
+            return None;
+        }
+        
+        let valid = self.pattern.is_match(national_id)
+            && match self.checksum {
+                NationalIdChecksum::None => true,
+                NationalIdChecksum::Luhn => luhn_valid(national_id),
+            };
+        
+        (!valid).then(|| ValidationError::InvalidNationalId(national_id.to_string()))
+    }
+}
+
+/// A national id as stored: trimmed, with Arabic-Indic digits made ASCII
+/// and the spaces and hyphens that printed ids are grouped with removed.
+/// Blank ids become `None`.
+pub fn normalize_national_id(raw: &str) -> Option<String> {
+    let normalized: String = normalize_digits(raw.trim())
+        .chars()
+        .filter(|&c| !c.is_whitespace() && c != '-')
+        .collect();
+    
+    (!normalized.is_empty()).then_some(normalized)
+}
+
+fn luhn_valid(digits: &str) -> bool {
+    let mut sum = 0;
+    
+    for (position, c) in digits.chars().rev().enumerate() {
+        let Some(mut digit) = c.to_digit(10) else {
+            return false;
+        };
+        if position % 2 == 1 {
+            digit *= 2;
+            if digit > 9 {
+                digit -= 9;
+            }
+        }
+        sum += digit;
+    }
+    
+    !digits.is_empty() && sum % 10 == 0
+}
@@ -1,161 +1,2239 @@
-use crate::models::{DeceasedRecord, ErrorDetails};
-use chrono::NaiveDate;
+use crate::arabic::{normalize_digits, split_lineage};
+use crate::archive::{is_gzipped, open_decompressed};
+use crate::encoding;
+use crate::footprint::parse_footprint;
+use crate::location::{GravePlace, LocationLayout};
+use crate::models::{
+    ConflictKey, DeceasedRecord, DuplicateStrategy, ErrorDetails, RecordStatus, SectionOutline, SwappedCoordinates,
+};
+use crate::national_id::normalize_national_id;
+use crate::projection::Projection;
+use crate::transform::FieldTransforms;
+use arrow::array::{Array, ArrayRef, AsArray, RecordBatch};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Date32Type, Float64Type, Int64Type, Schema};
+use arrow::util::display::array_value_to_string;
+use calamine::{Data, Reader};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use encoding_rs_io::DecodeReaderBytes;
+use chrono::format::{Fixed, Item, Numeric, Parsed, StrftimeItems};
+use chrono::{Days, NaiveDate};
 use log::{info, warn, error};
+use serde::{Deserialize, Serialize};
+use shapefile::dbase::FieldValue;
+use shapefile::record::traits::HasXY;
+use shapefile::{PolygonRing, Shape};
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufRead, BufReader, Cursor, Read};
 use std::path::Path;
+use utoipa::ToSchema;
+
+/// Logical fields that must be present in every CSV and JSON source.
+const REQUIRED_FIELDS: &[&str] = &[
+    "record_id",
+    "deceased_name",
+    "death_date",
+    "burial_date",
+    "burial_location",
+];
+
+/// Whether a source must provide `field`: every required field, except that
+/// the record id may be left out when records are matched on another key.
+fn must_map(field: &str, conflict_key: ConflictKey) -> bool {
+    REQUIRED_FIELDS.contains(&field) && (field != "record_id" || conflict_key == ConflictKey::RecordId)
+}
+
+/// Logical fields that are read when a source provides them.
+const OPTIONAL_FIELDS: &[&str] = &[
+    "deceased_name_arabic",
+    "father_name",
+    "grandfather_name",
+    "latitude",
+    "longitude",
+    "section",
+    "row",
+    "plot",
+    "birth_date",
+    "age_at_death",
+    "national_id",
+    "cause_of_death",
+    "family_contact",
+    "footprint",
+];
+
+/// Other header names a field's column is recognised by when no
+/// `csv_schema` is given. Headers are compared lowercased, with spaces and
+/// hyphens read as underscores.
+const FIELD_ALIASES: &[(&str, &[&str])] = &[
+    ("record_id", &["id", "record", "record_no", "record_number"]),
+    ("deceased_name", &["name", "full_name", "deceased"]),
+    ("deceased_name_arabic", &["name_arabic", "arabic_name", "name_ar"]),
+    ("father_name", &["father"]),
+    ("grandfather_name", &["grandfather"]),
+    ("death_date", &["date_of_death", "died", "dod"]),
+    ("burial_date", &["date_of_burial", "buried"]),
+    ("burial_location", &["location", "burial_place"]),
+    ("latitude", &["lat"]),
+    ("longitude", &["lon", "lng", "long"]),
+    ("row", &["row_number", "row_no"]),
+    ("plot", &["plot_number", "plot_no"]),
+    ("birth_date", &["date_of_birth", "dob", "born"]),
+    ("age_at_death", &["age"]),
+    ("cause_of_death", &["cause"]),
+    ("family_contact", &["contact"]),
+];
+
+/// The names `field`'s column is recognised by, its own first.
+fn field_names(field: &str) -> impl Iterator<Item = &str> {
+    std::iter::once(field).chain(
+        FIELD_ALIASES.iter()
+            .filter(move |(name, _)| *name == field)
+            .flat_map(|(_, aliases)| aliases.iter().copied()),
+    )
+}
+
+/// A header as it is compared with field names and aliases.
+fn header_key(header: &str) -> String {
+    header.trim().to_lowercase().replace([' ', '-'], "_")
+}
+
+/// Locates a logical field in a CSV file, either by zero-based column
+/// index or by header name.
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+#[serde(untagged)]
+pub enum ColumnRef {
+    Index(usize),
+    Header(String),
+}
+
+/// Maps logical `DeceasedRecord` field names to CSV columns so sources with
+/// different column orders can be onboarded without recompiling.
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct CsvSchema {
+    pub columns: HashMap<String, ColumnRef>,
+}
+
+impl Default for CsvSchema {
+    /// The documented import layout:
+    /// record_id, deceased_name, deceased_name_arabic, death_date, burial_date,
+    /// burial_location, latitude, longitude, section, row, plot
+    fn default() -> Self {
+        let columns = [
+            "record_id",
+            "deceased_name",
+            "deceased_name_arabic",
+            "death_date",
+            "burial_date",
+            "burial_location",
+            "latitude",
+            "longitude",
+            "section",
+            "row",
+            "plot",
+        ]
+        .iter()
+        .enumerate()
+        .map(|(index, field)| (field.to_string(), ColumnRef::Index(index)))
+        .collect();
+        
+        Self { columns }
+    }
+}
+
+impl CsvSchema {
+    /// Maps each field to the column whose header is its name or one of its
+    /// aliases, so sources may order columns as they like and leave out
+    /// optional ones. A header row naming no field at all is taken to be in
+    /// the documented layout, as `CsvSchema::default()` reads it. Fails
+    /// naming the first required field no header matches.
+    pub fn from_headers(
+        headers: &csv::StringRecord,
+        conflict_key: ConflictKey,
+    ) -> Result<Self, anyhow::Error> {
+        let keys: Vec<String> = headers.iter().map(header_key).collect();
+        
+        let columns: HashMap<String, ColumnRef> = REQUIRED_FIELDS.iter()
+            .chain(OPTIONAL_FIELDS)
+            .filter_map(|field| {
+                field_names(field)
+                    .find_map(|name| keys.iter().position(|key| key == name))
+                    .map(|index| (field.to_string(), ColumnRef::Index(index)))
+            })
+            .collect();
+        
+        if columns.is_empty() {
+            warn!("CSV header names no known field, reading columns in the documented order");
+            return Ok(Self::default());
+        }
+        
+        if let Some(missing) = REQUIRED_FIELDS.iter()
+            .find(|field| must_map(field, conflict_key) && !columns.contains_key(**field))
+        {
+            return Err(anyhow::anyhow!(
+                "CSV file has no column for required field '{}' (looked for: {})",
+                missing,
+                field_names(missing).collect::<Vec<_>>().join(", ")
+            ));
+        }
+        
+        Ok(Self { columns })
+    }
+    
+    /// Resolves every mapped field to a column index using the header row.
+    /// Fails if a required field is unmapped or its column cannot be found;
+    /// `record_id` is optional unless it is the `conflict_key`.
+    pub fn resolve(
+        &self,
+        headers: &csv::StringRecord,
+        conflict_key: ConflictKey,
+    ) -> Result<ResolvedColumns, anyhow::Error> {
+        if let Some(unknown) = self.columns.keys().find(|field| {
+            !REQUIRED_FIELDS.contains(&field.as_str())
+                && !OPTIONAL_FIELDS.contains(&field.as_str())
+        }) {
+            return Err(anyhow::anyhow!("CSV schema maps unknown field '{}'", unknown));
+        }
+        
+        let mut indices = HashMap::new();
+        
+        for field in REQUIRED_FIELDS.iter().chain(OPTIONAL_FIELDS) {
+            let required = must_map(field, conflict_key);
+            
+            let column = match self.columns.get(*field) {
+                Some(column) => column,
+                None if required => {
+                    return Err(anyhow::anyhow!(
+                        "CSV schema does not map required field '{}'", field
+                    ));
+                }
+                None => continue,
+            };
+            
+            let index = match column {
+                ColumnRef::Index(index) if *index < headers.len() => Some(*index),
+                ColumnRef::Index(_) => None,
+                ColumnRef::Header(name) => headers.iter().position(|h| h.trim() == name),
+            };
+            
+            match index {
+                Some(index) => {
+                    indices.insert(*field, index);
+                }
+                None if required => {
+                    let column_name = match column {
+                        ColumnRef::Index(index) => format!("#{}", index),
+                        ColumnRef::Header(name) => name.clone(),
+                    };
+                    return Err(anyhow::anyhow!(
+                        "CSV column '{}' for required field '{}' not found",
+                        column_name, field
+                    ));
+                }
+                None => {
+                    warn!("CSV column for optional field '{}' not found, skipping", field);
+                }
+            }
+        }
+        
+        // Columns no field maps to are kept under their header names
+        let unmapped = headers.iter()
+            .enumerate()
+            .filter(|(index, _)| !indices.values().any(|mapped| mapped == index))
+            .map(|(index, header)| match header.trim() {
+                "" => (index, format!("column_{}", index + 1)),
+                header => (index, header.to_string()),
+            })
+            .collect();
+        
+        let headers = headers.iter()
+            .enumerate()
+            .map(|(index, header)| match header.trim() {
+                "" => format!("column_{}", index + 1),
+                header => header.to_string(),
+            })
+            .collect();
+        
+        Ok(ResolvedColumns { indices, unmapped, headers })
+    }
+}
+
+/// A `CsvSchema` resolved against a concrete header row.
+#[derive(Debug)]
+pub struct ResolvedColumns {
+    indices: HashMap<&'static str, usize>,
+    unmapped: Vec<(usize, String)>,
+    /// Every column's header, blank ones named by position
+    headers: Vec<String>,
+}
+
+impl ResolvedColumns {
+    fn required<'r>(&self, record: &'r csv::StringRecord, field: &str) -> &'r str {
+        self.optional(record, field).unwrap_or("")
+    }
+    
+    fn optional<'r>(&self, record: &'r csv::StringRecord, field: &str) -> Option<&'r str> {
+        self.indices.get(field).and_then(|&index| record.get(index))
+    }
+    
+    fn index(&self, field: &str) -> Option<usize> {
+        self.indices.get(field).copied()
+    }
+    
+    /// The non-empty values of the unmapped columns, by header name.
+    fn unmapped(&self, record: &csv::StringRecord) -> serde_json::Map<String, serde_json::Value> {
+        self.unmapped.iter()
+            .filter_map(|(index, header)| {
+                let value = record.get(*index)?.trim();
+                (!value.is_empty()).then(|| (header.clone(), serde_json::json!(value)))
+            })
+            .collect()
+    }
+    
+    /// Every value of `record` by header name, as the source wrote it.
+    fn source_fields(&self, record: &csv::StringRecord) -> serde_json::Value {
+        self.headers.iter()
+            .zip(record.iter())
+            .map(|(header, value)| (header.clone(), serde_json::json!(value)))
+            .collect::<serde_json::Map<_, _>>()
+            .into()
+    }
+}
+
+/// Maps logical `DeceasedRecord` field names to JSON pointers (RFC 6901)
+/// into each record, so JSON sources with flat or differently named keys can
+/// be onboarded like CSV ones.
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct JsonSchema {
+    /// Pointer to the array of records in the document; `""` when the
+    /// document is the array itself.
+    #[serde(default = "default_records_pointer")]
+    pub records: String,
+    pub fields: HashMap<String, String>,
+}
+
+fn default_records_pointer() -> String {
+    "/records".to_string()
+}
+
+impl Default for JsonSchema {
+    /// The documented JSON layout, with the coordinates under `coordinates`
+    /// and section, row and plot under `location`.
+    fn default() -> Self {
+        let fields = [
+            ("record_id", "/record_id"),
+            ("deceased_name", "/deceased_name"),
+            ("deceased_name_arabic", "/deceased_name_arabic"),
+            ("father_name", "/father_name"),
+            ("grandfather_name", "/grandfather_name"),
+            ("death_date", "/death_date"),
+            ("burial_date", "/burial_date"),
+            ("burial_location", "/burial_location"),
+            ("latitude", "/coordinates/latitude"),
+            ("longitude", "/coordinates/longitude"),
+            ("section", "/location/section"),
+            ("row", "/location/row"),
+            ("plot", "/location/plot"),
+            ("birth_date", "/birth_date"),
+            ("age_at_death", "/age_at_death"),
+            ("national_id", "/national_id"),
+            ("cause_of_death", "/cause_of_death"),
+            ("family_contact", "/family_contact"),
+            ("footprint", "/footprint"),
+        ]
+        .iter()
+        .map(|(field, pointer)| (field.to_string(), pointer.to_string()))
+        .collect();
+        
+        Self { records: default_records_pointer(), fields }
+    }
+}
+
+impl JsonSchema {
+    /// Checks that every field is known, every required field is mapped and
+    /// every pointer is well formed.
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        self.validate_for_key(ConflictKey::RecordId)
+    }
+    
+    /// Like [`JsonSchema::validate`], with `record_id` optional unless it is
+    /// the `conflict_key`.
+    pub fn validate_for_key(&self, conflict_key: ConflictKey) -> Result<(), anyhow::Error> {
+        if let Some(unknown) = self.fields.keys().find(|field| {
+            !REQUIRED_FIELDS.contains(&field.as_str()) && !OPTIONAL_FIELDS.contains(&field.as_str())
+        }) {
+            return Err(anyhow::anyhow!("JSON schema maps unknown field '{}'", unknown));
+        }
+        if let Some(missing) = REQUIRED_FIELDS
+            .iter()
+            .find(|field| must_map(field, conflict_key) && !self.fields.contains_key(**field))
+        {
+            return Err(anyhow::anyhow!("JSON schema does not map required field '{}'", missing));
+        }
+        
+        let pointers = self.fields.values().chain(std::iter::once(&self.records));
+        if let Some(invalid) = pointers.into_iter().find(|p| !p.is_empty() && !p.starts_with('/')) {
+            return Err(anyhow::anyhow!("JSON pointer '{}' must be empty or start with '/'", invalid));
+        }
+        
+        Ok(())
+    }
+    
+    /// Takes the mapped values out of `record`, leaving only the keys the
+    /// schema does not cover.
+    fn extract(&self, record: &mut serde_json::Value) -> HashMap<&str, serde_json::Value> {
+        let values = self.fields.iter()
+            .filter_map(|(field, pointer)| {
+                let value = record.pointer(pointer)?;
+                (!value.is_null()).then(|| (field.as_str(), value.clone()))
+            })
+            .collect();
+        
+        for pointer in self.fields.values() {
+            if let Some(value) = record.pointer_mut(pointer) {
+                value.take();
+            }
+        }
+        prune_empty(record);
+        
+        values
+    }
+}
+
+/// One record of a JSON file, as far as it could be read.
+struct JsonEntry {
+    /// Set for JSON Lines files
+    line_number: Option<u64>,
+    /// A line that is not valid JSON fails with the line as written
+    value: Result<serde_json::Value, (String, serde_json::Error)>,
+}
+
+/// The records of a JSON document and how they were laid out: an array at
+/// `pointer` (`/records` unless the schema says otherwise), the document
+/// itself when it is an array and nothing is at `pointer`, or one object
+/// per line (JSON Lines, also called NDJSON). A document of one line is one
+/// record only when it does not look like a wrapper around the records.
+fn json_records(text: &str, pointer: &str) -> Result<(String, Vec<JsonEntry>), anyhow::Error> {
+    let entries = |records: Vec<serde_json::Value>| {
+        records.into_iter().map(|value| JsonEntry { line_number: None, value: Ok(value) }).collect()
+    };
+    
+    let mut document = match serde_json::from_str::<serde_json::Value>(text) {
+        Ok(document) => document,
+        Err(e) => return json_lines(text).map(|lines| ("JSON Lines".to_string(), lines)).ok_or_else(|| e.into()),
+    };
+    
+    match (document.pointer_mut(pointer).map(serde_json::Value::take), document) {
+        (Some(serde_json::Value::Array(records)), _) => {
+            Ok((format!("an array of records at '{}'", pointer), entries(records)))
+        }
+        (None, serde_json::Value::Array(records)) => Ok(("a bare array of records".to_string(), entries(records))),
+        // A JSON Lines file of one record
+        (None, record @ serde_json::Value::Object(_))
+            if text.trim().lines().count() == 1 && !wraps_records(&record, pointer) =>
+        {
+            let lines = vec![JsonEntry { line_number: Some(1), value: Ok(record) }];
+            Ok(("JSON Lines".to_string(), lines))
+        }
+        _ => Err(anyhow::anyhow!("No array of records at '{}'", pointer)),
+    }
+}
+
+/// Whether a one-line object is a document holding records rather than a
+/// record: the schema points at its records elsewhere, or one of its keys
+/// holds a list of objects, or an empty list.
+fn wraps_records(object: &serde_json::Value, pointer: &str) -> bool {
+    let holds_list = |value: &serde_json::Value| match value {
+        serde_json::Value::Array(items) => items.is_empty() || items[0].is_object(),
+        _ => false,
+    };
+    
+    pointer != default_records_pointer() || object.as_object().is_some_and(|map| map.values().any(holds_list))
+}
+
+/// Reads `text` as one JSON value per line, or `None` unless its first
+/// line is an object on its own. Lines that are not valid JSON are kept as
+/// failed entries; blank lines are skipped.
+fn json_lines(text: &str) -> Option<Vec<JsonEntry>> {
+    let entries: Vec<JsonEntry> = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| JsonEntry {
+            line_number: Some(index as u64 + 1),
+            value: serde_json::from_str(line).map_err(|e| (line.to_string(), e)),
+        })
+        .collect();
+    
+    matches!(entries.first()?.value, Ok(serde_json::Value::Object(_))).then_some(entries)
+}
+
+/// Removes nulls and objects left empty once their mapped keys were taken.
+fn prune_empty(value: &mut serde_json::Value) {
+    if let serde_json::Value::Object(map) = value {
+        for child in map.values_mut() {
+            prune_empty(child);
+        }
+        map.retain(|_, child| match child {
+            serde_json::Value::Null => false,
+            serde_json::Value::Object(child) => !child.is_empty(),
+            _ => true,
+        });
+    }
+}
+
+/// Calendar system a source records its dates in.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Calendar {
+    Gregorian,
+    Hijri,
+}
+
+/// Per-request parsing configuration.
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct ParseOptions {
+    pub csv_schema: Option<CsvSchema>,
+    /// Where the fields of JSON records are found; the documented layout
+    /// when unset.
+    pub json_schema: Option<JsonSchema>,
+    /// Calendar used by the date columns; detected per value when unset.
+    pub calendar: Option<Calendar>,
+    /// Worksheet to read from spreadsheets; the first sheet when unset.
+    pub sheet_name: Option<String>,
+    /// Text encoding of CSV files; detected per file when unset. Taken from
+    /// `FileMetadata::encoding` rather than the request's parse options.
+    #[serde(skip)]
+    pub encoding: Option<String>,
+    /// `strftime`-style formats tried in order for the date columns.
+    #[serde(default = "default_date_formats")]
+    pub date_formats: Vec<String>,
+    /// Handling of records whose latitude and longitude look exchanged.
+    #[serde(default)]
+    pub swapped_coordinates: SwappedCoordinates,
+    /// How records repeating a `record_id` within the run are collapsed.
+    #[serde(default)]
+    pub duplicates: DuplicateStrategy,
+    /// What matches a record to one already stored. With a key other than
+    /// `record_id`, sources may leave the record id out and one is made
+    /// from the key.
+    #[serde(default)]
+    pub conflict_key: ConflictKey,
+    /// Field separator of CSV files. Tab for `.tsv` files and sniffed from
+    /// the header line of others when unset.
+    pub delimiter: Option<char>,
+    /// Fill father and grandfather names from lineage names such as
+    /// "محمد بن علي بن حسين". Off by default, as not every source writes
+    /// names this way.
+    #[serde(default)]
+    pub arabic_lineage: bool,
+    /// Coordinate system of the coordinate columns when it is not WGS 84,
+    /// e.g. UTM zone 38N from a survey. The `longitude` column then holds
+    /// the easting and `latitude` the northing.
+    #[schema(value_type = Option<String>, example = "EPSG:32638")]
+    pub input_crs: Option<Projection>,
+    /// Cleanup applied to the text of each field before it is parsed, such
+    /// as trimming or reading "N/A" as missing.
+    #[serde(default)]
+    pub transforms: FieldTransforms,
+    /// Days after `death_date` that a missing or unreadable `burial_date` is
+    /// taken to be, 0 for the same day. Unset, such records fail.
+    pub burial_date_fallback_days: Option<u32>,
+    /// Format to read files as, whatever they are named. Unset, files are
+    /// read as their extension says, or by their content when it says
+    /// nothing the parser knows.
+    pub format: Option<FileFormat>,
+    /// How `burial_location` writes section, row and plot, e.g. "A/12/45".
+    /// Set, records fill the location columns they leave blank from it, or
+    /// it from them, and note where the two disagree.
+    pub location_layout: Option<LocationLayout>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            csv_schema: None,
+            json_schema: None,
+            calendar: None,
+            sheet_name: None,
+            encoding: None,
+            date_formats: default_date_formats(),
+            swapped_coordinates: SwappedCoordinates::default(),
+            duplicates: DuplicateStrategy::default(),
+            conflict_key: ConflictKey::default(),
+            delimiter: None,
+            arabic_lineage: false,
+            input_crs: None,
+            transforms: FieldTransforms::default(),
+            burial_date_fallback_days: None,
+            format: None,
+            location_layout: None,
+        }
+    }
+}
+
+/// Date layouts seen in government exports. Day-first wins over month-first
+/// for slashed dates, as is the convention in Iraq.
+pub const DEFAULT_DATE_FORMATS: &[&str] = &[
+    "%Y-%m-%d",
+    "%Y/%m/%d",
+    "%Y.%m.%d",
+    "%d/%m/%Y",
+    "%d-%m-%Y",
+    "%d.%m.%Y",
+];
+
+fn default_date_formats() -> Vec<String> {
+    DEFAULT_DATE_FORMATS.iter().map(|format| format.to_string()).collect()
+}
+
+/// Separators recognised when sniffing a CSV header, in order of preference
+/// when their counts tie.
+const CANDIDATE_DELIMITERS: &[u8] = b",;\t|";
+
+/// Picks the field separator of a CSV file from its header line: the
+/// candidate occurring most often outside quotes, or a comma if none does.
+pub fn sniff_delimiter(header: &[u8]) -> u8 {
+    let mut counts = [0usize; CANDIDATE_DELIMITERS.len()];
+    let mut quoted = false;
+    
+    for &byte in header {
+        match byte {
+            b'"' => quoted = !quoted,
+            b'\n' | b'\r' if !quoted => break,
+            _ if !quoted => {
+                if let Some(index) = CANDIDATE_DELIMITERS.iter().position(|&d| d == byte) {
+                    counts[index] += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    
+    // max_by_key keeps the last maximum, so walk the candidates in reverse
+    CANDIDATE_DELIMITERS
+        .iter()
+        .zip(counts)
+        .rev()
+        .filter(|&(_, count)| count > 0)
+        .max_by_key(|&(_, count)| count)
+        .map_or(b',', |(&delimiter, _)| delimiter)
+}
+
+/// Spreadsheet date cells are rendered in this format, so it is always
+/// accepted after the configured ones.
+const ISO_DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// A date parsed from a source value, remembering which calendar it was in.
+#[derive(Debug, Clone, Copy)]
+pub struct ParsedDate {
+    pub date: NaiveDate,
+    pub calendar: Calendar,
+}
+
+/// Tries each `strftime`-style format in order and returns the year, month
+/// and day of the first that matches the whole value.
+///
+/// Components are returned rather than a `NaiveDate` because the value may
+/// be a Hijri date, which is not necessarily a valid Gregorian one.
+pub fn parse_flexible_date(value: &str, formats: &[String]) -> Option<(i32, u32, u32)> {
+    formats.iter()
+        .map(String::as_str)
+        .chain(std::iter::once(ISO_DATE_FORMAT))
+        .find_map(|format| {
+            let mut parsed = Parsed::new();
+            chrono::format::parse(&mut parsed, value, StrftimeItems::new(format)).ok()?;
+            Some((parsed.year()?, parsed.month()?, parsed.day()?))
+        })
+}
+
+/// Parses a date in any of `formats`, converting Hijri dates to Gregorian.
+///
+/// A trailing `H`, `AH` or `هـ`, or a Hijri month name as in "15 Ramadan
+/// 1445" or "١٥ رمضان ١٤٤٥", marks a value as Hijri. Otherwise the given
+/// calendar is used, Gregorian when none is given.
+/// Arabic-Indic digits are read as ASCII ones.
+pub fn parse_date(
+    value: &str,
+    calendar: Option<Calendar>,
+    formats: &[String],
+) -> Result<ParsedDate, anyhow::Error> {
+    let normalized = normalize_digits(value.trim());
+    
+    let (body, suffixed) = match strip_hijri_suffix(&normalized) {
+        Some(body) => (body, true),
+        None => (normalized.as_str(), false),
+    };
+    
+    let named_month = parse_hijri_month_name(body);
+    let (year, month, day) = named_month.or_else(|| parse_flexible_date(body, formats)).ok_or_else(|| {
+        anyhow::anyhow!(
+            "unrecognised date '{}' (expected one of: {}, or a day, Hijri month name and year)",
+            value, formats.join(", ")
+        )
+    })?;
+    
+    let calendar = if suffixed || named_month.is_some() {
+        Calendar::Hijri
+    } else {
+        calendar.unwrap_or(Calendar::Gregorian)
+    };
+    
+    let date = match calendar {
+        Calendar::Gregorian => NaiveDate::from_ymd_opt(year, month, day),
+        Calendar::Hijri => hijri_to_gregorian(year, month, day),
+    };
+    
+    date.map(|date| ParsedDate { date, calendar })
+        .ok_or_else(|| anyhow::anyhow!("invalid {:?} date '{}'", calendar, value))
+}
+
+/// Spellings of the Hijri months, in Arabic and common transliterations,
+/// as `hijri_month_key` writes them.
+const HIJRI_MONTHS: [&[&str]; 12] = [
+    &["محرم", "muharram", "muharam"],
+    &["صفر", "safar"],
+    &["ربيعالاول", "rabialawwal", "rabiulawwal", "rabialawal", "rabiulawal"],
+    &["ربيعالثاني", "ربيعالاخر", "rabialthani", "rabiulthani", "rabiathani", "rabialakhir", "rabiulakhir"],
+    &["جماديالاولي", "جماديالاول", "jumadaalawwal", "jumadaalula", "jumadalawwal", "jumadiulawwal", "jumadalula"],
+    &["جماديالاخره", "جماديالثانيه", "جماديالاخر", "جماديالثاني", "jumadaalthani", "jumadaalakhirah",
+      "jumadaalakhira", "jumadathani", "jumadiulakhir", "jumadalakhirah"],
+    &["رجب", "rajab"],
+    &["شعبان", "shaban", "shaaban"],
+    &["رمضان", "ramadan", "ramadhan", "ramazan"],
+    &["شوال", "shawwal", "shawal"],
+    &["ذوالقعده", "dhualqadah", "dhulqadah", "dhualqidah", "dhulqidah", "dhulqada", "dhualqada"],
+    &["ذوالحجه", "dhualhijjah", "dhulhijjah", "dhualhijja", "dhulhijja"],
+];
+
+/// A month name as it is looked up in `HIJRI_MONTHS`: lowercased letters
+/// only, without Arabic diacritics or tatweel, and with the variant forms
+/// of alif, ta marbuta and alif maqsura written plainly.
+fn hijri_month_key(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphabetic() && !('\u{064B}'..='\u{065F}').contains(c) && *c != '\u{0640}')
+        .map(|c| match c {
+            'أ' | 'إ' | 'آ' => 'ا',
+            'ة' => 'ه',
+            'ى' => 'ي',
+            c => c,
+        })
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Reads "day month-name year" with a Hijri month name, e.g. "15 Ramadan
+/// 1445", "1 Dhu al-Hijjah 1445" or "15 رمضان 1445".
+fn parse_hijri_month_name(value: &str) -> Option<(i32, u32, u32)> {
+    let words: Vec<&str> = value.split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|word| !word.is_empty())
+        .collect();
+    let [day, name @ .., year] = words.as_slice() else {
+        return None;
+    };
+    
+    let key = hijri_month_key(&name.concat());
+    let month = HIJRI_MONTHS.iter().position(|spellings| spellings.contains(&key.as_str()))?;
+    
+    Some((year.parse().ok()?, month as u32 + 1, day.parse().ok()?))
+}
+
+fn strip_hijri_suffix(value: &str) -> Option<&str> {
+    ["هـ", "AH", "ah", "H", "h"]
+        .iter()
+        .find_map(|suffix| value.strip_suffix(suffix))
+        .map(|body| body.trim_end())
+}
+
+/// Converts a date in the tabular (arithmetic) Islamic calendar to Gregorian.
+pub fn hijri_to_gregorian(year: i32, month: u32, day: u32) -> Option<NaiveDate> {
+    if year < 1 || !(1..=12).contains(&month) || day < 1 {
+        return None;
+    }
+    
+    // Odd months have 30 days, even months 29, and the last month gains a day
+    // in the 11 leap years of each 30-year cycle.
+    let is_leap = (14 + 11 * year).rem_euclid(30) < 11;
+    let month_length = if month % 2 == 1 || (month == 12 && is_leap) { 30 } else { 29 };
+    if day > month_length {
+        return None;
+    }
+    
+    let month = month as i32;
+    let days_since_epoch = (59 * (month - 1) + 1) / 2
+        + (year - 1) * 354
+        + (3 + 11 * year).div_euclid(30)
+        + day as i32;
+    
+    // 1 Muharram 1 AH is 19 July 622 in the proleptic Gregorian calendar
+    NaiveDate::from_num_days_from_ce_opt(days_since_epoch + 227_014)
+}
+
+/// Converts an Excel serial day number to a date. Serials count from
+/// 1899-12-30 once Excel's phantom 29 February 1900 is skipped.
+fn excel_serial_to_date(serial: f64) -> Option<NaiveDate> {
+    let epoch = NaiveDate::from_ymd_opt(1899, 12, 30)?;
+    epoch.checked_add_signed(chrono::Duration::days(serial.trunc() as i64))
+}
+
+/// Renders a spreadsheet cell as the text the CSV path would have seen.
+/// Numbers in date columns are treated as Excel serial dates.
+fn cell_to_string(cell: &Data, is_date_column: bool) -> String {
+    let serial_date = |serial: f64| {
+        excel_serial_to_date(serial).map(|d| d.to_string()).unwrap_or_default()
+    };
+    
+    match cell {
+        Data::DateTime(value) => serial_date(value.as_f64()),
+        Data::Float(value) if is_date_column => serial_date(*value),
+        Data::Int(value) if is_date_column => serial_date(*value as f64),
+        Data::DateTimeIso(value) => value.split('T').next().unwrap_or_default().to_string(),
+        Data::Empty | Data::Error(_) => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// Records the raw source value of any date that was converted from Hijri,
+/// so the original is never lost.
+fn raw_hijri_dates(
+    death: (&str, &ParsedDate),
+    burial: (&str, &ParsedDate),
+) -> Option<serde_json::Value> {
+    let mut data = serde_json::Map::new();
+    
+    if death.1.calendar == Calendar::Hijri {
+        data.insert("raw_death_date".to_string(), serde_json::json!(death.0));
+    }
+    if burial.1.calendar == Calendar::Hijri {
+        data.insert("raw_burial_date".to_string(), serde_json::json!(burial.0));
+    }
+    
+    if data.is_empty() {
+        None
+    } else {
+        Some(serde_json::Value::Object(data))
+    }
+}
+
+/// Stands in for a burial date that could not be read for `reason`:
+/// `death_date` plus `options.burial_date_fallback_days`, with `reason`
+/// kept in `additional_data` as `burial_date_estimated`. Without a fallback
+/// the record fails with `reason`.
+fn estimate_burial_date(
+    reason: anyhow::Error,
+    death_date: &ParsedDate,
+    options: &ParseOptions,
+    additional_data: &mut serde_json::Map<String, serde_json::Value>,
+) -> Result<(String, ParsedDate), anyhow::Error> {
+    let Some(days) = options.burial_date_fallback_days else {
+        return Err(reason);
+    };
+    let date = death_date.date
+        .checked_add_days(Days::new(days.into()))
+        .ok_or_else(|| anyhow::anyhow!("burial_date: {} days after death_date is out of range", days))?;
+    
+    additional_data.insert("burial_date_estimated".to_string(), serde_json::json!(reason.to_string()));
+    Ok((String::new(), ParsedDate { date, calendar: Calendar::Gregorian }))
+}
+
+/// Gives a record that came without an id one made from `conflict_key`.
+fn fill_record_id(record: &mut DeceasedRecord, conflict_key: ConflictKey) {
+    if !record.record_id.trim().is_empty() {
+        return;
+    }
+    if let Some(record_id) = conflict_key.derived_record_id(record) {
+        record.record_id = record_id;
+    }
+}
+
+/// Checks that `transforms` only names known fields.
+fn check_transforms(transforms: &FieldTransforms) -> Result<(), anyhow::Error> {
+    match transforms.field_names().find(|field| !REQUIRED_FIELDS.contains(field) && !OPTIONAL_FIELDS.contains(field)) {
+        Some(unknown) => Err(anyhow::anyhow!("Transforms name unknown field '{}'", unknown)),
+        None => Ok(()),
+    }
+}
+
+/// Checks that every date format is a `strftime` pattern naming a year, a
+/// month and a day, since one that does not can never read a date.
+fn check_date_formats(formats: &[String]) -> Result<(), anyhow::Error> {
+    for format in formats {
+        let (mut year, mut month, mut day) = (false, false, false);
+        
+        for item in StrftimeItems::new(format) {
+            match item {
+                Item::Error => {
+                    return Err(anyhow::anyhow!("date format '{}' is not a valid strftime pattern", format));
+                }
+                Item::Numeric(Numeric::Year | Numeric::YearMod100, _) => year = true,
+                Item::Numeric(Numeric::Month, _) | Item::Fixed(Fixed::ShortMonthName | Fixed::LongMonthName) => {
+                    month = true
+                }
+                Item::Numeric(Numeric::Day, _) => day = true,
+                _ => {}
+            }
+        }
+        
+        if !(year && month && day) {
+            return Err(anyhow::anyhow!("date format '{}' must name a year, a month and a day", format));
+        }
+    }
+    Ok(())
+}
+
+/// Checks the options that can be found wrong before any record is read.
+fn check_options(options: &ParseOptions) -> Result<(), anyhow::Error> {
+    check_transforms(&options.transforms)?;
+    check_date_formats(&options.date_formats)?;
+    if let Some(layout) = &options.location_layout {
+        layout.validate().map_err(anyhow::Error::msg)?;
+    }
+    Ok(())
+}
+
+/// Converts the coordinates of `record`, and the corners of its footprint,
+/// from `projection` to WGS 84. The source system and point are kept in
+/// `additional_data` as `original_coordinates`.
+fn reproject_coordinates(record: &mut DeceasedRecord, projection: Projection) {
+    if projection == Projection::Wgs84 {
+        return;
+    }
+    for corner in record.footprint.iter_mut().flatten() {
+        let (longitude, latitude) = projection.to_wgs84(corner[0], corner[1]);
+        *corner = [longitude, latitude];
+    }
+    
+    let (Some(y), Some(x)) = (record.latitude, record.longitude) else {
+        return;
+    };
+    
+    let (longitude, latitude) = projection.to_wgs84(x, y);
+    record.latitude = Some(latitude);
+    record.longitude = Some(longitude);
+    
+    let note = serde_json::json!({ "crs": projection.to_string(), "x": x, "y": y });
+    match record.additional_data.get_or_insert_with(|| serde_json::json!({})) {
+        serde_json::Value::Object(data) => {
+            data.insert("original_coordinates".to_string(), note);
+        }
+        _ => warn!("Record {}: additional_data is not an object; original coordinates not stored", record.record_id),
+    }
+}
+
+/// Fills the father and grandfather names of `record` from a lineage name,
+/// preferring the Arabic name. The full name is left as it is; the given name
+/// alone is kept in `additional_data` as `given_name`.
+fn apply_arabic_lineage(record: &mut DeceasedRecord) {
+    let full_name = record.deceased_name_arabic.as_deref()
+        .filter(|name| !name.trim().is_empty())
+        .unwrap_or(&record.deceased_name);
+    
+    let Some(lineage) = split_lineage(full_name) else {
+        return;
+    };
+    
+    if record.father_name.as_deref().is_none_or(|name| name.trim().is_empty()) {
+        record.father_name = Some(lineage.father_name);
+    }
+    if record.grandfather_name.as_deref().is_none_or(|name| name.trim().is_empty()) {
+        record.grandfather_name = lineage.grandfather_name;
+    }
+    
+    match record.additional_data.get_or_insert_with(|| serde_json::json!({})) {
+        serde_json::Value::Object(data) => {
+            data.insert("given_name".to_string(), serde_json::json!(lineage.given_name));
+        }
+        _ => warn!("Record {}: additional_data is not an object; given name not stored", record.record_id),
+    }
+}
+
+/// Fills the section, row and plot `record` leaves blank from its
+/// `burial_location`, or a blank `burial_location` from them, as `layout`
+/// writes it. Where the two disagree the columns are kept, and the
+/// disagreement is noted in `additional_data` as `location_conflict`.
+fn apply_location_layout(record: &mut DeceasedRecord, layout: &LocationLayout) {
+    let given = GravePlace {
+        section: record.section.clone().filter(|section| !section.trim().is_empty()),
+        row_number: record.row_number,
+        plot_number: record.plot_number,
+    };
+    
+    if record.burial_location.trim().is_empty() {
+        if let Some(location) = layout.compose(&given) {
+            record.burial_location = location;
+        }
+        return;
+    }
+    let Some(parsed) = layout.split(&record.burial_location) else {
+        return;
+    };
+    
+    let conflicts = layout.contradictions(&record.burial_location, &given);
+    record.section = given.section.or(parsed.section);
+    record.row_number = given.row_number.or(parsed.row_number);
+    record.plot_number = given.plot_number.or(parsed.plot_number);
+    if conflicts.is_empty() {
+        return;
+    }
+    
+    match record.additional_data.get_or_insert_with(|| serde_json::json!({})) {
+        serde_json::Value::Object(data) => {
+            data.insert("location_conflict".to_string(), serde_json::json!(conflicts.join("; ")));
+        }
+        _ => warn!("Record {}: additional_data is not an object; location conflict not stored", record.record_id),
+    }
+}
+
+/// Free text, trimmed, or `None` when blank.
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// Parses a numeric column, reading Arabic-Indic digits and the Arabic
+/// decimal separator (٫) as their ASCII counterparts.
+fn parse_number<T: std::str::FromStr>(value: &str) -> Option<T> {
+    normalize_digits(value.trim()).replace('\u{066B}', ".").parse().ok()
+}
+
+fn parse_record_date(
+    field: &str,
+    value: &str,
+    options: &ParseOptions,
+) -> Result<ParsedDate, anyhow::Error> {
+    parse_date(value, options.calendar, &options.date_formats)
+        .map_err(|e| anyhow::anyhow!("{}: {}", field, e))
+}
+
+/// The values a `JsonSchema` found in one record, by logical field, with
+/// the transforms text values go through.
+struct JsonFields<'s> {
+    values: HashMap<&'s str, serde_json::Value>,
+    transforms: &'s FieldTransforms,
+}
+
+impl JsonFields<'_> {
+    fn optional(&self, field: &str) -> Result<Option<String>, anyhow::Error> {
+        let text = match self.values.get(field) {
+            None => return Ok(None),
+            Some(serde_json::Value::String(text)) => text.clone(),
+            Some(value @ (serde_json::Value::Number(_) | serde_json::Value::Bool(_))) => value.to_string(),
+            Some(value) => return Err(anyhow::anyhow!("{}: expected text, got {}", field, value)),
+        };
+        
+        Ok(self.transforms.apply(field, &text).map(Cow::into_owned))
+    }
+    
+    fn required(&self, field: &str) -> Result<String, anyhow::Error> {
+        self.optional(field)?.ok_or_else(|| anyhow::anyhow!("missing field `{}`", field))
+    }
+    
+    /// The outline may also be given as an array of `[x, y]` pairs.
+    fn footprint(&self) -> Result<Option<Vec<[f64; 2]>>, anyhow::Error> {
+        let text = match self.values.get("footprint") {
+            None | Some(serde_json::Value::Null) => return Ok(None),
+            Some(serde_json::Value::String(text)) if text.trim().is_empty() => return Ok(None),
+            Some(serde_json::Value::String(text)) => text.clone(),
+            Some(value @ serde_json::Value::Array(_)) => value.to_string(),
+            Some(value) => return Err(anyhow::anyhow!("footprint: expected text or an array, got {}", value)),
+        };
+        
+        parse_footprint(&text).map(Some).map_err(|e| anyhow::anyhow!("footprint: {}", e))
+    }
+    
+    /// Numbers may also be given as text, as in CSV sources.
+    fn number<T: std::str::FromStr>(&self, field: &str) -> Result<Option<T>, anyhow::Error> {
+        self.optional(field)?
+            .map(|text| {
+                parse_number(&text).ok_or_else(|| anyhow::anyhow!("{}: expected a number, got '{}'", field, text))
+            })
+            .transpose()
+    }
+}
+
+/// Records parsed from one file, plus the rows that could not be parsed.
+#[derive(Debug, Default)]
+pub struct ParsedFile {
+    pub records: Vec<DeceasedRecord>,
+    pub errors: Vec<ErrorDetails>,
+}
+
+impl ParsedFile {
+    fn push(&mut self, row: Result<DeceasedRecord, ErrorDetails>) {
+        match row {
+            Ok(record) => self.records.push(record),
+            Err(error) => self.errors.push(error),
+        }
+    }
+    
+    /// Turns the parsed file back into a record stream, errors first.
+    pub fn into_stream(self) -> RecordStream {
+        Box::new(
+            self.errors.into_iter().map(Err)
+                .chain(self.records.into_iter().map(Ok))
+        )
+    }
+}
+
+impl FromIterator<Result<DeceasedRecord, ErrorDetails>> for ParsedFile {
+    fn from_iter<I: IntoIterator<Item = Result<DeceasedRecord, ErrorDetails>>>(rows: I) -> Self {
+        let mut parsed = ParsedFile::default();
+        for row in rows {
+            parsed.push(row);
+        }
+        parsed
+    }
+}
+
+/// Outlines read from one shapefile, plus the shapes that were rejected.
+#[derive(Debug, Default)]
+pub struct ParsedShapefile {
+    pub outlines: Vec<SectionOutline>,
+    pub errors: Vec<ErrorDetails>,
+}
+
+/// `.dbf` columns holding the section an outline belongs to, compared
+/// case-insensitively. dBase truncates column names to 10 characters.
+const SECTION_ATTRIBUTES: &[&str] = &["section", "section_co", "sec", "block"];
+
+/// `.dbf` columns holding the plot number of a plot outline.
+const PLOT_ATTRIBUTES: &[&str] = &["plot", "plot_no", "plot_numbe", "plot_num"];
+
+fn field_to_json(value: FieldValue) -> serde_json::Value {
+    use serde_json::Value;
+    
+    match value {
+        FieldValue::Character(Some(text)) => Value::String(text.trim().to_string()),
+        FieldValue::Memo(text) => Value::String(text.trim().to_string()),
+        FieldValue::Numeric(Some(number)) | FieldValue::Double(number) | FieldValue::Currency(number) => {
+            serde_json::Number::from_f64(number).map_or(Value::Null, Value::Number)
+        }
+        FieldValue::Float(Some(number)) => {
+            serde_json::Number::from_f64(f64::from(number)).map_or(Value::Null, Value::Number)
+        }
+        FieldValue::Integer(number) => Value::from(number),
+        FieldValue::Logical(Some(flag)) => Value::Bool(flag),
+        FieldValue::Date(Some(date)) => {
+            Value::String(format!("{:04}-{:02}-{:02}", date.year(), date.month(), date.day()))
+        }
+        _ => Value::Null,
+    }
+}
+
+/// The first of `names` present in `attributes`, as text.
+fn attribute_text(attributes: &serde_json::Map<String, serde_json::Value>, names: &[&str]) -> Option<String> {
+    attributes.iter()
+        .find(|(name, _)| names.iter().any(|candidate| name.eq_ignore_ascii_case(candidate)))
+        .and_then(|(_, value)| match value {
+            serde_json::Value::String(text) if !text.is_empty() => Some(text.clone()),
+            serde_json::Value::Number(number) => Some(number.to_string().trim_end_matches(".0").to_string()),
+            _ => None,
+        })
+}
+
+/// Splits a shapefile polygon into polygons of an outer ring and the holes
+/// after it, converted to WGS 84. Shapefiles list each outer ring before its
+/// holes.
+fn polygon_parts<P: HasXY>(rings: &[PolygonRing<P>], projection: Projection) -> Vec<Vec<Vec<(f64, f64)>>> {
+    let mut parts: Vec<Vec<Vec<(f64, f64)>>> = Vec::new();
+    
+    for ring in rings {
+        let points = ring.points().iter().map(|point| projection.to_wgs84(point.x(), point.y())).collect();
+        
+        match (ring, parts.last_mut()) {
+            (PolygonRing::Inner(_), Some(part)) => part.push(points),
+            _ => parts.push(vec![points]),
+        }
+    }
+    
+    parts
+}
+
+/// What a logical field holds, which decides the Parquet column types
+/// accepted for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParquetKind {
+    Text,
+    Date,
+    Integer,
+    Float,
+}
+
+impl ParquetKind {
+    fn of(field: &str) -> Self {
+        match field {
+            "death_date" | "burial_date" | "birth_date" => ParquetKind::Date,
+            "row" | "plot" | "age_at_death" => ParquetKind::Integer,
+            "latitude" | "longitude" => ParquetKind::Float,
+            _ => ParquetKind::Text,
+        }
+    }
+    
+    fn describe(self) -> &'static str {
+        match self {
+            ParquetKind::Text => "text",
+            ParquetKind::Date => "a date",
+            ParquetKind::Integer => "an integer",
+            ParquetKind::Float => "a number",
+        }
+    }
+    
+    /// The type a column of `data_type` is read as for this kind, or `None`
+    /// when it does not fit. Text dates are kept as text and parsed like CSV
+    /// ones, so Hijri dates still work; ids stored as integers are read as
+    /// text.
+    fn read_as(self, data_type: &DataType) -> Option<DataType> {
+        let text = matches!(data_type, DataType::Utf8 | DataType::LargeUtf8);
+        
+        match self {
+            ParquetKind::Text if text || data_type.is_integer() => Some(DataType::Utf8),
+            ParquetKind::Date if text => Some(DataType::Utf8),
+            ParquetKind::Date if matches!(data_type, DataType::Date32 | DataType::Date64 | DataType::Timestamp(_, _)) => {
+                Some(DataType::Date32)
+            }
+            ParquetKind::Integer if data_type.is_integer() => Some(DataType::Int64),
+            ParquetKind::Float if data_type.is_floating() || data_type.is_integer() => Some(DataType::Float64),
+            _ => None,
+        }
+    }
+}
+
+/// The Parquet columns each logical field is read from, checked against the
+/// file's schema.
+struct ParquetColumns {
+    fields: Vec<(&'static str, usize, DataType)>,
+    unmapped: Vec<(usize, String)>,
+}
+
+impl ParquetColumns {
+    /// Finds each field's column by name, or as `csv_schema` maps it, and
+    /// checks its type. Every missing required column and every column of
+    /// the wrong type is listed in the one error.
+    fn resolve(
+        schema: &Schema,
+        csv_schema: Option<&CsvSchema>,
+        conflict_key: ConflictKey,
+    ) -> Result<Self, anyhow::Error> {
+        if let Some(unknown) = csv_schema.and_then(|mapping| {
+            mapping.columns.keys().find(|field| {
+                !REQUIRED_FIELDS.contains(&field.as_str()) && !OPTIONAL_FIELDS.contains(&field.as_str())
+            })
+        }) {
+            return Err(anyhow::anyhow!("CSV schema maps unknown field '{}'", unknown));
+        }
+        
+        let mut fields = Vec::new();
+        let mut mismatches = Vec::new();
+        
+        for field in REQUIRED_FIELDS.iter().chain(OPTIONAL_FIELDS) {
+            let column = match csv_schema {
+                Some(mapping) => mapping.columns.get(*field).cloned(),
+                None => Some(ColumnRef::Header(field.to_string())),
+            };
+            let index = match &column {
+                Some(ColumnRef::Index(index)) => Some(*index).filter(|&index| index < schema.fields().len()),
+                Some(ColumnRef::Header(name)) => schema.index_of(name).ok(),
+                None => None,
+            };
+            
+            let Some(index) = index else {
+                if must_map(field, conflict_key) {
+                    let name = match column {
+                        Some(ColumnRef::Index(index)) => format!("#{}", index),
+                        Some(ColumnRef::Header(name)) => name,
+                        None => field.to_string(),
+                    };
+                    mismatches.push(format!("column '{}' for required field '{}' not found", name, field));
+                }
+                continue;
+            };
+            
+            let column = schema.field(index);
+            let kind = ParquetKind::of(field);
+            match kind.read_as(column.data_type()) {
+                Some(read_as) => fields.push((*field, index, read_as)),
+                None => mismatches.push(format!(
+                    "column '{}' for '{}' is {}, expected {}",
+                    column.name(), field, column.data_type(), kind.describe()
+                )),
+            }
+        }
+        
+        if !mismatches.is_empty() {
+            return Err(anyhow::anyhow!("Parquet schema does not match: {}", mismatches.join("; ")));
+        }
+        
+        // Columns no field maps to are kept as text under their names
+        let unmapped = schema.fields().iter()
+            .enumerate()
+            .filter(|(index, _)| !fields.iter().any(|(_, mapped, _)| mapped == index))
+            .map(|(index, column)| (index, column.name().clone()))
+            .collect();
+        
+        Ok(Self { fields, unmapped })
+    }
+}
+
+/// One batch of a Parquet file, with the mapped columns cast to the types
+/// they are read as and the transforms text values go through.
+struct ParquetBatch<'t> {
+    fields: HashMap<&'static str, ArrayRef>,
+    unmapped: Vec<(String, ArrayRef)>,
+    transforms: &'t FieldTransforms,
+}
+
+impl<'t> ParquetBatch<'t> {
+    fn new(
+        batch: &RecordBatch,
+        columns: &ParquetColumns,
+        transforms: &'t FieldTransforms,
+    ) -> Result<Self, anyhow::Error> {
+        let mut fields = HashMap::new();
+        for (field, index, read_as) in &columns.fields {
+            fields.insert(*field, cast(batch.column(*index), read_as)?);
+        }
+        
+        let unmapped = columns.unmapped.iter()
+            .filter_map(|(index, name)| match cast(batch.column(*index), &DataType::Utf8) {
+                Ok(column) => Some((name.clone(), column)),
+                Err(e) => {
+                    warn!("Parquet column '{}' cannot be kept as text, skipping: {}", name, e);
+                    None
+                }
+            })
+            .collect();
+        
+        Ok(Self { fields, unmapped, transforms })
+    }
+    
+    fn column(&self, field: &str, row: usize) -> Option<&ArrayRef> {
+        self.fields.get(field).filter(|column| column.is_valid(row))
+    }
+    
+    fn text(&self, field: &str, row: usize) -> Option<String> {
+        let column = self.column(field, row)?;
+        self.transforms.apply(field, column.as_string::<i32>().value(row)).map(Cow::into_owned)
+    }
+    
+    fn required(&self, field: &str, row: usize) -> Result<String, anyhow::Error> {
+        self.text(field, row).ok_or_else(|| anyhow::anyhow!("missing field `{}`", field))
+    }
+    
+    /// A date along with the value it came from, parsing text dates as the
+    /// CSV path does.
+    fn date(
+        &self,
+        field: &str,
+        row: usize,
+        options: &ParseOptions,
+    ) -> Result<Option<(String, ParsedDate)>, anyhow::Error> {
+        let Some(column) = self.column(field, row) else {
+            return Ok(None);
+        };
+        
+        if column.data_type() == &DataType::Utf8 {
+            let Some(raw) = self.text(field, row).filter(|raw| !raw.trim().is_empty()) else {
+                return Ok(None);
+            };
+            let date = parse_record_date(field, &raw, options)?;
+            return Ok(Some((raw, date)));
+        }
+        
+        let date = column.as_primitive::<Date32Type>()
+            .value_as_date(row)
+            .ok_or_else(|| anyhow::anyhow!("{}: date out of range", field))?;
+        Ok(Some((date.to_string(), ParsedDate { date, calendar: Calendar::Gregorian })))
+    }
+    
+    fn required_date(
+        &self,
+        field: &str,
+        row: usize,
+        options: &ParseOptions,
+    ) -> Result<(String, ParsedDate), anyhow::Error> {
+        self.date(field, row, options)?.ok_or_else(|| anyhow::anyhow!("missing field `{}`", field))
+    }
+    
+    fn integer(&self, field: &str, row: usize) -> Result<Option<i32>, anyhow::Error> {
+        self.column(field, row)
+            .map(|column| {
+                let value = column.as_primitive::<Int64Type>().value(row);
+                i32::try_from(value).map_err(|_| anyhow::anyhow!("{}: {} is out of range", field, value))
+            })
+            .transpose()
+    }
+    
+    fn float(&self, field: &str, row: usize) -> Option<f64> {
+        self.column(field, row).map(|column| column.as_primitive::<Float64Type>().value(row))
+    }
+    
+    /// The non-empty values of the unmapped columns, by column name.
+    fn unmapped(&self, row: usize) -> serde_json::Map<String, serde_json::Value> {
+        self.unmapped.iter()
+            .filter(|(_, column)| column.is_valid(row))
+            .filter_map(|(name, column)| {
+                let value = column.as_string::<i32>().value(row).trim();
+                (!value.is_empty()).then(|| (name.clone(), serde_json::json!(value)))
+            })
+            .collect()
+    }
+    
+    /// The values of a row as text, the mapped columns by field name and
+    /// the others by column name. Nulls are left out.
+    fn source_fields(&self, row: usize) -> serde_json::Value {
+        let mapped = self.fields.iter().map(|(field, column)| (field.to_string(), column));
+        let unmapped = self.unmapped.iter().map(|(name, column)| (name.clone(), column));
+        
+        mapped.chain(unmapped)
+            .filter(|(_, column)| column.is_valid(row))
+            .filter_map(|(name, column)| {
+                let value = array_value_to_string(column, row).ok()?;
+                Some((name, serde_json::json!(value)))
+            })
+            .collect::<serde_json::Map<_, _>>()
+            .into()
+    }
+}
+
+/// Records from one file, yielded one at a time along with the rows that
+/// could not be parsed.
+pub type RecordStream = Box<dyn Iterator<Item = Result<DeceasedRecord, ErrorDetails>> + Send>;
+
+/// A decoded CSV file, with the header line already read for sniffing put
+/// back in front.
+type CsvSource = std::io::Chain<Cursor<Vec<u8>>, BufReader<DecodeReaderBytes<Box<dyn Read + Send>, Vec<u8>>>>;
+
+/// Parses a CSV file lazily, one row per `next()`, so memory use does not
+/// grow with the size of the file.
+pub struct CsvRecordStream {
+    records: csv::StringRecordsIntoIter<CsvSource>,
+    columns: ResolvedColumns,
+    options: ParseOptions,
+    line_number: u64,
+}
+
+impl Iterator for CsvRecordStream {
+    type Item = Result<DeceasedRecord, ErrorDetails>;
+    
+    fn next(&mut self) -> Option<Self::Item> {
+        let result = self.records.next()?;
+        self.line_number += 1;
+        
+        Some(match result {
+            Ok(record) => {
+                // Quoted fields may span lines, so prefer the reader's position
+                let line = record.position().map_or(self.line_number, |p| p.line());
+                DataParser::parse_row(&record, &self.columns, &self.options, line)
+            }
+            Err(e) => {
+                let line = e.position().map_or(self.line_number, |p| p.line());
+                warn!("Error parsing CSV line {}: {}", line, e);
+                Err(ErrorDetails {
+                    record_id: None,
+                    line_number: Some(line),
+                    message: e.to_string(),
+                    code: None,
+                    field: None,
+                    source_fields: None,
+                })
+            }
+        })
+    }
+}
+
+/// Extensions [`DataParser::detect_and_parse`] knows how to read.
+pub const SUPPORTED_EXTENSIONS: &[&str] = &["csv", "tsv", "json", "jsonl", "ndjson", "xlsx", "xls", "parquet"];
+
+/// Formats that may also come gzip-compressed, as in `burials.csv.gz`.
+pub const GZIP_EXTENSIONS: &[&str] = &["csv", "tsv", "json", "jsonl", "ndjson"];
+
+/// Whether `path` has one of the [`SUPPORTED_EXTENSIONS`], or is a gzipped
+/// file of one of the [`GZIP_EXTENSIONS`].
+pub fn is_supported_file(path: &Path) -> bool {
+    let extensions = if is_gzipped(path) { GZIP_EXTENSIONS } else { SUPPORTED_EXTENSIONS };
+    format_extension(path).is_some_and(|extension| extensions.contains(&extension.as_str()))
+}
+
+/// The lowercased extension that says how `path` is parsed: for a gzipped
+/// file, the one before `.gz`.
+fn format_extension(path: &Path) -> Option<String> {
+    let path = if is_gzipped(path) { Path::new(path.file_stem()?) } else { path };
+    path.extension()
+        .and_then(|s| s.to_str())
+        .map(|s| s.to_lowercase())
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    format_extension(path).is_some_and(|ext| ext == extension)
+}
+
+/// A format [`DataParser::detect_and_parse`] reads, named like its extension.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum FileFormat {
+    Csv,
+    Tsv,
+    Json,
+    Xlsx,
+    Xls,
+    Parquet,
+}
+
+impl FileFormat {
+    fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "csv" => Some(FileFormat::Csv),
+            "tsv" => Some(FileFormat::Tsv),
+            "json" | "jsonl" | "ndjson" => Some(FileFormat::Json),
+            "xlsx" => Some(FileFormat::Xlsx),
+            "xls" => Some(FileFormat::Xls),
+            "parquet" => Some(FileFormat::Parquet),
+            _ => None,
+        }
+    }
+    
+    pub fn name(self) -> &'static str {
+        match self {
+            FileFormat::Csv => "csv",
+            FileFormat::Tsv => "tsv",
+            FileFormat::Json => "json",
+            FileFormat::Xlsx => "xlsx",
+            FileFormat::Xls => "xls",
+            FileFormat::Parquet => "parquet",
+        }
+    }
+}
+
+/// How much of a file [`sniff_format`] looks at.
+const SNIFF_BYTES: u64 = 8192;
+
+/// Lines of a text file that must split alike for it to be taken as CSV.
+const SNIFF_LINES: usize = 5;
+
+/// Signature of the OLE compound files XLS workbooks are stored in.
+const XLS_SIGNATURE: &[u8] = &[0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+
+/// How `path` is read: as `options.format` when set, otherwise as its
+/// extension says, or as [`sniff_format`] finds when it has no extension the
+/// parser knows, like `data.txt` or an upload without one.
+pub fn file_format(path: &Path, options: &ParseOptions) -> Result<FileFormat, anyhow::Error> {
+    if let Some(format) = options.format {
+        return Ok(format);
+    }
+    if let Some(format) = format_extension(path).as_deref().and_then(FileFormat::from_extension) {
+        return Ok(format);
+    }
+    
+    let format = sniff_format(path, options.encoding.as_deref())?;
+    info!("Reading {:?} as {}, going by its content", path, format.name());
+    Ok(format)
+}
+
+/// Tells the format of `path` from its first bytes, decompressed when it is
+/// gzipped: Parquet, XLSX and XLS files by their signatures, JSON by its
+/// first character being `{` or `[`, and CSV or TSV by the first lines
+/// holding the same number of one delimiter. Text is decoded from
+/// `encoding`, or the detected encoding, first.
+pub fn sniff_format(path: &Path, encoding: Option<&str>) -> Result<FileFormat, anyhow::Error> {
+    let undetected = |found: String| anyhow::anyhow!("Could not detect the format of {:?}: {}", path, found);
+    
+    let mut head = Vec::new();
+    open_decompressed(path)?.take(SNIFF_BYTES).read_to_end(&mut head)?;
+    if head.starts_with(b"PAR1") {
+        return Ok(FileFormat::Parquet);
+    }
+    if head.starts_with(b"PK\x03\x04") {
+        return Ok(FileFormat::Xlsx);
+    }
+    if head.starts_with(XLS_SIGNATURE) {
+        return Ok(FileFormat::Xls);
+    }
+    
+    let mut text = Vec::new();
+    encoding::open_utf8(path, encoding)?.take(SNIFF_BYTES).read_to_end(&mut text)?;
+    let cut_short = text.len() as u64 == SNIFF_BYTES;
+    let text = String::from_utf8_lossy(&text);
+    
+    if text.contains('\0') {
+        return Err(undetected("binary content that is not Parquet, XLSX or XLS".to_string()));
+    }
+    match text.trim_start().chars().next() {
+        Some('{') | Some('[') => return Ok(FileFormat::Json),
+        None => return Err(undetected("the file is empty".to_string())),
+        Some(_) => {}
+    }
+    
+    let mut lines: Vec<&str> = text.lines().filter(|line| !line.trim().is_empty()).collect();
+    if cut_short && lines.len() > 1 {
+        lines.pop();
+    }
+    lines.truncate(SNIFF_LINES);
+    
+    match consistent_delimiter(&lines) {
+        Some(b'\t') => Ok(FileFormat::Tsv),
+        Some(_) => Ok(FileFormat::Csv),
+        None => Err(undetected(format!(
+            "text that is not JSON, and whose first {} lines do not hold the same number of commas, \
+             semicolons, tabs or pipes",
+            lines.len()
+        ))),
+    }
+}
+
+/// The candidate delimiter every one of `lines` holds equally often
+/// outside quotes, preferring the most frequent.
+fn consistent_delimiter(lines: &[&str]) -> Option<u8> {
+    CANDIDATE_DELIMITERS
+        .iter()
+        .rev()
+        .filter_map(|&delimiter| {
+            let mut counts = lines.iter().map(|line| count_unquoted(line.as_bytes(), delimiter));
+            let first = counts.next()?;
+            (first > 0 && counts.all(|count| count == first)).then_some((delimiter, first))
+        })
+        .max_by_key(|&(_, count)| count)
+        .map(|(delimiter, _)| delimiter)
+}
+
+fn count_unquoted(line: &[u8], delimiter: u8) -> usize {
+    let mut quoted = false;
+    line.iter()
+        .filter(|&&byte| {
+            if byte == b'"' {
+                quoted = !quoted;
+            }
+            byte == delimiter && !quoted
+        })
+        .count()
+}
+
+/// Opens a workbook by its content rather than its name: as XLS when it has
+/// the OLE signature, otherwise as XLSX.
+fn open_workbook(path: &Path) -> Result<calamine::Sheets<BufReader<File>>, anyhow::Error> {
+    let mut signature = [0; 8];
+    let is_xls = File::open(path)?.read_exact(&mut signature).is_ok() && signature == XLS_SIGNATURE;
+    
+    Ok(if is_xls {
+        calamine::Sheets::Xls(calamine::open_workbook(path)?)
+    } else {
+        calamine::Sheets::Xlsx(calamine::open_workbook(path)?)
+    })
+}
 
 pub struct DataParser;
 
 impl DataParser {
-    pub fn parse_csv_file(file_path: &Path) -> Result<Vec<DeceasedRecord>, anyhow::Error> {
+    pub fn parse_csv_file(
+        file_path: &Path,
+        options: &ParseOptions,
+    ) -> Result<ParsedFile, anyhow::Error> {
+        let parsed: ParsedFile = Self::parse_csv_stream(file_path, options)?.collect();
+        
+        info!(
+            "Successfully parsed {} records from CSV ({} rows failed)",
+            parsed.records.len(), parsed.errors.len()
+        );
+        Ok(parsed)
+    }
+    
+    /// Opens a CSV file and resolves its header, returning an iterator that
+    /// parses the remaining rows on demand.
+    pub fn parse_csv_stream(
+        file_path: &Path,
+        options: &ParseOptions,
+    ) -> Result<CsvRecordStream, anyhow::Error> {
         info!("Parsing CSV file: {:?}", file_path);
+        check_options(options)?;
+        
+        // Arabic exports are often Windows-1256 or UTF-16; the reader only
+        // ever sees UTF-8
+        let mut file = BufReader::new(encoding::open_utf8(file_path, options.encoding.as_deref())?);
+        let mut header = Vec::new();
+        file.read_until(b'\n', &mut header)?;
+        
+        let delimiter = match options.delimiter {
+            Some(delimiter) if delimiter.is_ascii() => delimiter as u8,
+            Some(delimiter) => {
+                return Err(anyhow::anyhow!("CSV delimiter must be an ASCII character, got {:?}", delimiter));
+            }
+            None if options.format == Some(FileFormat::Tsv) || has_extension(file_path, "tsv") => b'\t',
+            None => sniff_delimiter(&header),
+        };
+        info!("Reading {:?} with delimiter {:?}", file_path, delimiter as char);
+        
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(delimiter)
+            .from_reader(Cursor::new(header).chain(file));
+        
+        // Resolve the schema against the header row once, up front
+        let columns = Self::resolve_columns(reader.headers()?, options)?;
+        
+        Ok(CsvRecordStream {
+            records: reader.into_records(),
+            columns,
+            options: options.clone(),
+            line_number: 1, // Header is line 1
+        })
+    }
+    
+    /// Resolves the columns of a header row with the request's `csv_schema`,
+    /// or by header name when it has none.
+    fn resolve_columns(
+        headers: &csv::StringRecord,
+        options: &ParseOptions,
+    ) -> Result<ResolvedColumns, anyhow::Error> {
+        match &options.csv_schema {
+            Some(schema) => schema.resolve(headers, options.conflict_key),
+            None => CsvSchema::from_headers(headers, options.conflict_key)?
+                .resolve(headers, options.conflict_key),
+        }
+    }
+    
+    /// Parses one tabular row into a record or a line-numbered error.
+    fn parse_row(
+        record: &csv::StringRecord,
+        columns: &ResolvedColumns,
+        options: &ParseOptions,
+        line: u64,
+    ) -> Result<DeceasedRecord, ErrorDetails> {
+        Self::parse_csv_record(record, columns, options).map_err(|e| {
+            warn!("Error parsing line {}: {}", line, e);
+            let record_id = columns.required(record, "record_id");
+            ErrorDetails {
+                record_id: (!record_id.is_empty()).then(|| record_id.to_string()),
+                line_number: Some(line),
+                message: e.to_string(),
+                code: None,
+                field: None,
+                source_fields: Some(Box::new(columns.source_fields(record))),
+            }
+        })
+    }
+    
+    fn parse_csv_record(
+        record: &csv::StringRecord,
+        columns: &ResolvedColumns,
+        options: &ParseOptions,
+    ) -> Result<DeceasedRecord, anyhow::Error> {
+        let optional = |field: &str| {
+            columns.optional(record, field).and_then(|raw| options.transforms.apply(field, raw))
+        };
+        let required = |field: &str| optional(field).unwrap_or_default();
+        
+        let record_id = required("record_id");
+        let raw_death_date = required("death_date");
+        let death_date = parse_record_date("death_date", &raw_death_date, options)?;
         
-        let file = File::open(file_path)?;
-        let mut reader = csv::Reader::from_reader(BufReader::new(file));
+        let mut additional_data = columns.unmapped(record);
+        let raw_burial_date = required("burial_date");
+        let (raw_burial_date, burial_date) = match parse_record_date("burial_date", &raw_burial_date, options) {
+            Ok(burial_date) => (raw_burial_date.into_owned(), burial_date),
+            Err(e) => estimate_burial_date(e, &death_date, options, &mut additional_data)?,
+        };
+        if let Some(serde_json::Value::Object(raw_dates)) = raw_hijri_dates(
+            (&raw_death_date, &death_date),
+            (&raw_burial_date, &burial_date),
+        ) {
+            additional_data.extend(raw_dates);
+        }
         
-        let mut records = Vec::new();
-        let mut line_number = 1; // Header is line 0
+        let mut parsed = DeceasedRecord {
+            record_id: record_id.into_owned(),
+            deceased_name: required("deceased_name").into_owned(),
+            deceased_name_arabic: optional("deceased_name_arabic").map(Cow::into_owned),
+            father_name: optional("father_name").as_deref().and_then(non_empty),
+            grandfather_name: optional("grandfather_name").as_deref().and_then(non_empty),
+            death_date: death_date.date,
+            death_location: None,
+            burial_date: burial_date.date,
+            burial_location: required("burial_location").into_owned(),
+            section: optional("section").as_deref().and_then(non_empty),
+            row_number: optional("row").and_then(|raw| parse_number(&raw)),
+            plot_number: optional("plot").and_then(|raw| parse_number(&raw)),
+            grave_number: None,
+            latitude: optional("latitude").and_then(|raw| parse_number(&raw)),
+            longitude: optional("longitude").and_then(|raw| parse_number(&raw)),
+            footprint: optional("footprint")
+                .filter(|raw| !raw.trim().is_empty())
+                .map(|raw| parse_footprint(&raw))
+                .transpose()
+                .map_err(|e| anyhow::anyhow!("footprint: {}", e))?,
+            birth_date: optional("birth_date")
+                .filter(|raw| !raw.trim().is_empty())
+                .map(|raw| parse_record_date("birth_date", &raw, options))
+                .transpose()?
+                .map(|birth_date| birth_date.date),
+            age_at_death: optional("age_at_death").and_then(|raw| parse_number(&raw)),
+            cause_of_death: optional("cause_of_death").as_deref().and_then(non_empty),
+            cause_of_death_code: None,
+            national_id: optional("national_id").as_deref().and_then(normalize_national_id),
+            family_contact: optional("family_contact").as_deref().and_then(non_empty),
+            additional_data: (!additional_data.is_empty()).then_some(serde_json::Value::Object(additional_data)),
+            processing_status: RecordStatus::default(),
+        };
         
-        for result in reader.deserialize() {
-            line_number += 1;
+        if options.arabic_lineage {
+            apply_arabic_lineage(&mut parsed);
+        }
+        if let Some(layout) = &options.location_layout {
+            apply_location_layout(&mut parsed, layout);
+        }
+        if let Some(projection) = options.input_crs {
+            reproject_coordinates(&mut parsed, projection);
+        }
+        fill_record_id(&mut parsed, options.conflict_key);
+        
+        Ok(parsed)
+    }
+    
+    pub fn parse_xlsx_file(
+        file_path: &Path,
+        options: &ParseOptions,
+    ) -> Result<ParsedFile, anyhow::Error> {
+        info!("Parsing spreadsheet: {:?}", file_path);
+        check_options(options)?;
+        
+        let mut workbook = open_workbook(file_path)?;
+        let range = match &options.sheet_name {
+            Some(name) => workbook.worksheet_range(name)?,
+            None => workbook.worksheet_range_at(0)
+                .ok_or_else(|| anyhow::anyhow!("Workbook has no worksheets"))??,
+        };
+        
+        // The range starts at the first non-empty row, which holds the headers
+        let header_line = range.start().map_or(1, |(row, _)| row as u64 + 1);
+        let mut rows = range.rows();
+        
+        let headers: csv::StringRecord = match rows.next() {
+            Some(row) => row.iter().map(|cell| cell_to_string(cell, false)).collect(),
+            None => {
+                warn!("Worksheet in {:?} is empty", file_path);
+                return Ok(ParsedFile::default());
+            }
+        };
+        
+        let columns = Self::resolve_columns(&headers, options)?;
+        let date_columns: Vec<usize> = ["death_date", "burial_date", "birth_date"]
+            .iter()
+            .filter_map(|field| columns.index(field))
+            .collect();
+        
+        let mut parsed = ParsedFile::default();
+        
+        for (offset, row) in rows.enumerate() {
+            let record: csv::StringRecord = row.iter()
+                .enumerate()
+                .map(|(index, cell)| cell_to_string(cell, date_columns.contains(&index)))
+                .collect();
             
-            match result {
-                Ok(record) => {
-                    records.push(Self::parse_csv_record(record)?);
+            // Spreadsheets commonly carry formatted but empty trailing rows
+            if record.iter().all(|field| field.trim().is_empty()) {
+                continue;
+            }
+            
+            let line = header_line + offset as u64 + 1;
+            parsed.push(Self::parse_row(&record, &columns, options, line));
+        }
+        
+        info!(
+            "Successfully parsed {} records from spreadsheet ({} rows failed)",
+            parsed.records.len(), parsed.errors.len()
+        );
+        Ok(parsed)
+    }
+    
+    pub fn parse_json_file(
+        file_path: &Path,
+        options: &ParseOptions,
+    ) -> Result<ParsedFile, anyhow::Error> {
+        info!("Parsing JSON file: {:?}", file_path);
+        check_options(options)?;
+        
+        let mut text = String::new();
+        open_decompressed(file_path)?.read_to_string(&mut text)?;
+        let text = text.trim_start_matches('\u{feff}');
+        
+        let default_schema = JsonSchema::default();
+        let schema = options.json_schema.as_ref().unwrap_or(&default_schema);
+        schema.validate_for_key(options.conflict_key)?;
+        
+        let (layout, records) = json_records(text, &schema.records)?;
+        info!("Reading {:?} as {}", file_path, layout);
+        
+        let mut parsed = ParsedFile::default();
+        
+        // Records are converted one by one so a single malformed entry
+        // does not reject the whole file
+        for (index, entry) in records.into_iter().enumerate() {
+            let mut value = match entry.value {
+                Ok(value) => value,
+                Err((line, e)) => {
+                    warn!("Error parsing JSON record #{}: {}", index + 1, e);
+                    parsed.errors.push(ErrorDetails {
+                        record_id: None,
+                        line_number: entry.line_number,
+                        message: format!("record #{}: {}", index + 1, e),
+                        code: None,
+                        field: None,
+                        source_fields: Some(Box::new(serde_json::Value::String(line))),
+                    });
+                    continue;
                 }
+            };
+            let source = value.clone();
+            let fields = JsonFields { values: schema.extract(&mut value), transforms: &options.transforms };
+            let record_id = fields.optional("record_id").ok().flatten();
+            
+            let result = Self::convert_json_record(&fields, value, options);
+            
+            match result {
+                Ok(record) => parsed.records.push(record),
                 Err(e) => {
-                    warn!("Error parsing CSV line {}: {}", line_number, e);
+                    warn!("Error parsing JSON record #{}: {}", index + 1, e);
+                    parsed.errors.push(ErrorDetails {
+                        record_id,
+                        line_number: entry.line_number,
+                        message: format!("record #{}: {}", index + 1, e),
+                        code: None,
+                        field: None,
+                        source_fields: Some(Box::new(source)),
+                    });
                 }
             }
         }
         
-        info!("Successfully parsed {} records from CSV", records.len());
-        Ok(records)
+        info!(
+            "Successfully parsed {} records from JSON ({} records failed)",
+            parsed.records.len(), parsed.errors.len()
+        );
+        Ok(parsed)
     }
     
-    fn parse_csv_record(record: csv::StringRecord) -> Result<DeceasedRecord, anyhow::Error> {
-        // This is a flexible parser - adjust based on your actual CSV format
-        // Expected columns: record_id, deceased_name, death_date, burial_date, 
-        //                   burial_location, latitude, longitude, section, row, plot
+    /// Builds a record from the mapped `fields`. Whatever is left of the
+    /// source record in `unmapped` is kept in `additional_data`.
+    fn convert_json_record(
+        fields: &JsonFields,
+        unmapped: serde_json::Value,
+        options: &ParseOptions,
+    ) -> Result<DeceasedRecord, anyhow::Error> {
+        let serde_json::Value::Object(mut additional_data) = unmapped else {
+            return Err(anyhow::anyhow!("expected an object, got {}", unmapped));
+        };
+        
+        let raw_death_date = fields.required("death_date")?;
+        let death_date = parse_record_date("death_date", &raw_death_date, options)?;
+        let burial = fields.required("burial_date").and_then(|raw| {
+            let burial_date = parse_record_date("burial_date", &raw, options)?;
+            Ok((raw, burial_date))
+        });
+        let (raw_burial_date, burial_date) = match burial {
+            Ok(burial) => burial,
+            Err(e) => estimate_burial_date(e, &death_date, options, &mut additional_data)?,
+        };
+        let birth_date = fields.optional("birth_date")?
+            .filter(|raw| !raw.trim().is_empty())
+            .map(|raw| parse_record_date("birth_date", &raw, options))
+            .transpose()?;
+        if let Some(serde_json::Value::Object(raw_dates)) = raw_hijri_dates(
+            (&raw_death_date, &death_date),
+            (&raw_burial_date, &burial_date),
+        ) {
+            additional_data.extend(raw_dates);
+        }
         
-        Ok(DeceasedRecord {
-            record_id: record.get(0).unwrap_or("").to_string(),
-            deceased_name: record.get(1).unwrap_or("").to_string(),
-            deceased_name_arabic: record.get(2).map(|s| s.to_string()),
-            father_name: None,
-            grandfather_name: None,
-            death_date: NaiveDate::parse_from_str(
-                record.get(3).unwrap_or(""),
-                "%Y-%m-%d"
-            )?,
+        let mut record = DeceasedRecord {
+            record_id: if must_map("record_id", options.conflict_key) {
+                fields.required("record_id")?
+            } else {
+                fields.optional("record_id")?.unwrap_or_default()
+            },
+            deceased_name: fields.required("deceased_name")?,
+            deceased_name_arabic: fields.optional("deceased_name_arabic")?,
+            father_name: fields.optional("father_name")?.as_deref().and_then(non_empty),
+            grandfather_name: fields.optional("grandfather_name")?.as_deref().and_then(non_empty),
+            death_date: death_date.date,
             death_location: None,
-            burial_date: NaiveDate::parse_from_str(
-                record.get(4).unwrap_or(""),
-                "%Y-%m-%d"
-            )?,
-            burial_location: record.get(5).unwrap_or("").to_string(),
-            section: record.get(8).map(|s| s.to_string()),
-            row_number: record.get(9).and_then(|s| s.parse::<i32>().ok()),
-            plot_number: record.get(10).and_then(|s| s.parse::<i32>().ok()),
+            burial_date: burial_date.date,
+            burial_location: match options.location_layout {
+                Some(_) => fields.optional("burial_location")?.unwrap_or_default(),
+                None => fields.required("burial_location")?,
+            },
+            section: fields.optional("section")?.as_deref().and_then(non_empty),
+            row_number: fields.number("row")?,
+            plot_number: fields.number("plot")?,
             grave_number: None,
-            latitude: record.get(6).and_then(|s| s.parse::<f64>().ok()),
-            longitude: record.get(7).and_then(|s| s.parse::<f64>().ok()),
-            age_at_death: None,
-            cause_of_death: None,
-            national_id: None,
-            family_contact: None,
-            additional_data: None,
-        })
+            latitude: fields.number("latitude")?,
+            longitude: fields.number("longitude")?,
+            footprint: fields.footprint()?,
+            birth_date: birth_date.map(|birth_date| birth_date.date),
+            age_at_death: fields.number("age_at_death")?,
+            cause_of_death: fields.optional("cause_of_death")?.as_deref().and_then(non_empty),
+            cause_of_death_code: None,
+            national_id: fields.optional("national_id")?.as_deref().and_then(normalize_national_id),
+            family_contact: fields.optional("family_contact")?.as_deref().and_then(non_empty),
+            additional_data: (!additional_data.is_empty()).then_some(serde_json::Value::Object(additional_data)),
+            processing_status: RecordStatus::default(),
+        };
+        
+        if options.arabic_lineage {
+            apply_arabic_lineage(&mut record);
+        }
+        if let Some(layout) = &options.location_layout {
+            apply_location_layout(&mut record, layout);
+        }
+        if let Some(projection) = options.input_crs {
+            reproject_coordinates(&mut record, projection);
+        }
+        fill_record_id(&mut record, options.conflict_key);
+        
+        Ok(record)
     }
     
-    pub fn parse_json_file(file_path: &Path) -> Result<Vec<DeceasedRecord>, anyhow::Error> {
-        info!("Parsing JSON file: {:?}", file_path);
+    /// Reads a Parquet file. Columns are found by field name, or as
+    /// `csv_schema` maps them, and the schema is checked before any row is
+    /// read. Date, integer and floating point columns are read as such;
+    /// text dates are parsed as in CSV files.
+    pub fn parse_parquet_file(
+        file_path: &Path,
+        options: &ParseOptions,
+    ) -> Result<ParsedFile, anyhow::Error> {
+        info!("Parsing Parquet file: {:?}", file_path);
+        check_options(options)?;
         
-        let file = File::open(file_path)?;
-        let reader = BufReader::new(file);
-        
-        #[derive(serde::Deserialize)]
-        struct JsonData {
-            records: Vec<JsonRecord>,
-        }
-        
-        #[derive(serde::Deserialize)]
-        struct JsonRecord {
-            record_id: String,
-            deceased_name: String,
-            deceased_name_arabic: Option<String>,
-            death_date: String,
-            burial_date: String,
-            burial_location: String,
-            coordinates: Option<JsonCoordinates>,
-            location: Option<JsonLocation>,
-        }
-        
-        #[derive(serde::Deserialize)]
-        struct JsonCoordinates {
-            latitude: f64,
-            longitude: f64,
-        }
-        
-        #[derive(serde::Deserialize)]
-        struct JsonLocation {
-            section: Option<String>,
-            row: Option<i32>,
-            plot: Option<i32>,
-        }
-        
-        let data: JsonData = serde_json::from_reader(reader)?;
-        
-        let mut records = Vec::new();
-        
-        for json_record in data.records {
-            let record = DeceasedRecord {
-                record_id: json_record.record_id,
-                deceased_name: json_record.deceased_name,
-                deceased_name_arabic: json_record.deceased_name_arabic,
-                father_name: None,
-                grandfather_name: None,
-                death_date: NaiveDate::parse_from_str(&json_record.death_date, "%Y-%m-%d")?,
-                death_location: None,
-                burial_date: NaiveDate::parse_from_str(&json_record.burial_date, "%Y-%m-%d")?,
-                burial_location: json_record.burial_location,
-                section: json_record.location.as_ref().and_then(|l| l.section.clone()),
-                row_number: json_record.location.as_ref().and_then(|l| l.row),
-                plot_number: json_record.location.as_ref().and_then(|l| l.plot),
-                grave_number: None,
-                latitude: json_record.coordinates.as_ref().map(|c| c.latitude),
-                longitude: json_record.coordinates.as_ref().map(|c| c.longitude),
-                age_at_death: None,
-                cause_of_death: None,
-                national_id: None,
-                family_contact: None,
-                additional_data: None,
-            };
+        let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(file_path)?)?;
+        let columns = ParquetColumns::resolve(builder.schema(), options.csv_schema.as_ref(), options.conflict_key)?;
+        
+        let mut parsed = ParsedFile::default();
+        let mut row_number = 0;
+        
+        for batch in builder.build()? {
+            let batch = batch?;
+            let rows = batch.num_rows();
+            let batch = ParquetBatch::new(&batch, &columns, &options.transforms)?;
             
-            records.push(record);
+            for row in 0..rows {
+                row_number += 1;
+                
+                match Self::convert_parquet_row(&batch, row, options) {
+                    Ok(record) => parsed.records.push(record),
+                    Err(e) => {
+                        warn!("Error parsing Parquet row {}: {}", row_number, e);
+                        parsed.errors.push(ErrorDetails {
+                            record_id: batch.text("record_id", row),
+                            line_number: None,
+                            message: format!("row {}: {}", row_number, e),
+                            code: None,
+                            field: None,
+                            source_fields: Some(Box::new(batch.source_fields(row))),
+                        });
+                    }
+                }
+            }
+        }
+        
+        info!(
+            "Successfully parsed {} records from Parquet ({} rows failed)",
+            parsed.records.len(), parsed.errors.len()
+        );
+        Ok(parsed)
+    }
+    
+    fn convert_parquet_row(
+        batch: &ParquetBatch,
+        row: usize,
+        options: &ParseOptions,
+    ) -> Result<DeceasedRecord, anyhow::Error> {
+        let (raw_death_date, death_date) = batch.required_date("death_date", row, options)?;
+        
+        let mut additional_data = batch.unmapped(row);
+        let (raw_burial_date, burial_date) = match batch.required_date("burial_date", row, options) {
+            Ok(burial) => burial,
+            Err(e) => estimate_burial_date(e, &death_date, options, &mut additional_data)?,
+        };
+        if let Some(serde_json::Value::Object(raw_dates)) = raw_hijri_dates(
+            (&raw_death_date, &death_date),
+            (&raw_burial_date, &burial_date),
+        ) {
+            additional_data.extend(raw_dates);
+        }
+        
+        let mut record = DeceasedRecord {
+            record_id: if must_map("record_id", options.conflict_key) {
+                batch.required("record_id", row)?
+            } else {
+                batch.text("record_id", row).unwrap_or_default()
+            },
+            deceased_name: batch.required("deceased_name", row)?,
+            deceased_name_arabic: batch.text("deceased_name_arabic", row),
+            father_name: batch.text("father_name", row).as_deref().and_then(non_empty),
+            grandfather_name: batch.text("grandfather_name", row).as_deref().and_then(non_empty),
+            death_date: death_date.date,
+            death_location: None,
+            burial_date: burial_date.date,
+            burial_location: match options.location_layout {
+                Some(_) => batch.text("burial_location", row).unwrap_or_default(),
+                None => batch.required("burial_location", row)?,
+            },
+            section: batch.text("section", row).as_deref().and_then(non_empty),
+            row_number: batch.integer("row", row)?,
+            plot_number: batch.integer("plot", row)?,
+            grave_number: None,
+            latitude: batch.float("latitude", row),
+            longitude: batch.float("longitude", row),
+            footprint: batch.text("footprint", row)
+                .filter(|raw| !raw.trim().is_empty())
+                .map(|raw| parse_footprint(&raw))
+                .transpose()
+                .map_err(|e| anyhow::anyhow!("footprint: {}", e))?,
+            birth_date: batch.date("birth_date", row, options)?.map(|(_, birth_date)| birth_date.date),
+            age_at_death: batch.integer("age_at_death", row)?,
+            cause_of_death: batch.text("cause_of_death", row).as_deref().and_then(non_empty),
+            cause_of_death_code: None,
+            national_id: batch.text("national_id", row).as_deref().and_then(normalize_national_id),
+            family_contact: batch.text("family_contact", row).as_deref().and_then(non_empty),
+            additional_data: (!additional_data.is_empty()).then_some(serde_json::Value::Object(additional_data)),
+            processing_status: RecordStatus::default(),
+        };
+        
+        if options.arabic_lineage {
+            apply_arabic_lineage(&mut record);
+        }
+        if let Some(layout) = &options.location_layout {
+            apply_location_layout(&mut record, layout);
+        }
+        if let Some(projection) = options.input_crs {
+            reproject_coordinates(&mut record, projection);
         }
+        fill_record_id(&mut record, options.conflict_key);
         
-        info!("Successfully parsed {} records from JSON", records.len());
-        Ok(records)
+        Ok(record)
     }
     
-    pub fn detect_and_parse(file_path: &Path) -> Result<Vec<DeceasedRecord>, anyhow::Error> {
-        let extension = file_path.extension()
-            .and_then(|s| s.to_str())
-            .map(|s| s.to_lowercase());
+    /// Parses `file_path` in the format [`file_format`] gives it.
+    pub fn detect_and_parse(
+        file_path: &Path,
+        options: &ParseOptions,
+    ) -> Result<ParsedFile, anyhow::Error> {
+        let format = file_format(file_path, options).inspect_err(|e| error!("{}", e))?;
+        Self::parse_as(file_path, format, options)
+    }
+    
+    fn parse_as(
+        file_path: &Path,
+        format: FileFormat,
+        options: &ParseOptions,
+    ) -> Result<ParsedFile, anyhow::Error> {
+        // Spreadsheets and Parquet files are compressed already, and their
+        // readers need to seek
+        if is_gzipped(file_path) && !GZIP_EXTENSIONS.contains(&format.name()) {
+            error!("Unsupported compressed file format: {}", format.name());
+            return Err(anyhow::anyhow!(
+                "Unsupported compressed file format: {} (only {} files may be gzipped)",
+                format.name(),
+                GZIP_EXTENSIONS.join(", ")
+            ));
+        }
+        
+        match format {
+            FileFormat::Csv | FileFormat::Tsv => Self::parse_csv_file(file_path, options),
+            FileFormat::Json => Self::parse_json_file(file_path, options),
+            FileFormat::Xlsx | FileFormat::Xls => Self::parse_xlsx_file(file_path, options),
+            FileFormat::Parquet => Self::parse_parquet_file(file_path, options),
+        }
+    }
+    
+    /// Reads section or plot outlines from an ESRI shapefile and the `.dbf`
+    /// next to it. Coordinates are converted to WGS 84 using the `.prj`, and
+    /// taken to be longitude/latitude already when there is none. Each part
+    /// of a multi-part polygon becomes its own outline; shapes that are not
+    /// polygons are reported as errors.
+    pub fn parse_shapefile(file_path: &Path) -> Result<ParsedShapefile, anyhow::Error> {
+        info!("Parsing shapefile: {:?}", file_path);
+        
+        let projection = match std::fs::read_to_string(file_path.with_extension("prj")) {
+            Ok(wkt) => Projection::from_wkt(&wkt)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Projection::Wgs84,
+            Err(e) => return Err(e.into()),
+        };
+        info!("Reading {:?} as {:?}", file_path, projection);
         
-        match extension.as_deref() {
-            Some("csv") => Self::parse_csv_file(file_path),
-            Some("json") => Self::parse_json_file(file_path),
-            Some(ext) => {
-                error!("Unsupported file format: {}", ext);
-                Err(anyhow::anyhow!("Unsupported file format: {}", ext))
+        let mut reader = shapefile::Reader::from_path(file_path)?;
+        let mut parsed = ParsedShapefile::default();
+        
+        for (index, shape_and_record) in reader.iter_shapes_and_records().enumerate() {
+            let (shape, record) = shape_and_record?;
+            let shape_error = |message: String| ErrorDetails {
+                record_id: None,
+                line_number: None,
+                message: format!("Shape {}: {}", index + 1, message),
+                code: None,
+                field: None,
+                source_fields: None,
+            };
+            
+            let parts = match &shape {
+                Shape::Polygon(polygon) => polygon_parts(polygon.rings(), projection),
+                Shape::PolygonM(polygon) => polygon_parts(polygon.rings(), projection),
+                Shape::PolygonZ(polygon) => polygon_parts(polygon.rings(), projection),
+                Shape::NullShape => {
+                    parsed.errors.push(shape_error("has no geometry".to_string()));
+                    continue;
+                }
+                other => {
+                    parsed.errors.push(shape_error(format!("is a {} rather than a polygon", other.shapetype())));
+                    continue;
+                }
+            };
+            
+            let out_of_range = parts.iter().flatten().flatten().any(|&(lon, lat)| {
+                !(-180.0..=180.0).contains(&lon) || !(-90.0..=90.0).contains(&lat)
+            });
+            if out_of_range {
+                parsed.errors.push(shape_error(
+                    "coordinates are not longitude/latitude; is the .prj file missing?".to_string(),
+                ));
+                continue;
             }
-            None => {
-                error!("No file extension found");
-                Err(anyhow::anyhow!("No file extension found"))
+            
+            let attributes: serde_json::Map<String, serde_json::Value> = record
+                .into_iter()
+                .map(|(name, value)| (name, field_to_json(value)))
+                .collect();
+            let section = attribute_text(&attributes, SECTION_ATTRIBUTES);
+            let plot_number = attribute_text(&attributes, PLOT_ATTRIBUTES).and_then(|plot| plot.parse().ok());
+            let attributes = serde_json::Value::Object(attributes);
+            
+            for (part_index, rings) in parts.into_iter().enumerate() {
+                if rings.iter().any(|ring| ring.len() < 4) {
+                    parsed.errors.push(shape_error(format!("part {} has a ring of fewer than 4 points", part_index + 1)));
+                    continue;
+                }
+                
+                parsed.outlines.push(SectionOutline {
+                    shape_index: index as i32,
+                    part_index: part_index as i32,
+                    section: section.clone(),
+                    plot_number,
+                    attributes: attributes.clone(),
+                    rings,
+                });
             }
         }
+        
+        info!("Read {} outlines from {:?}", parsed.outlines.len(), file_path);
+        Ok(parsed)
+    }
+    
+    /// Like [`DataParser::detect_and_parse`], but CSV files are streamed
+    /// rather than read into memory. Other formats are parsed up front.
+    pub fn detect_and_stream(
+        file_path: &Path,
+        options: &ParseOptions,
+    ) -> Result<RecordStream, anyhow::Error> {
+        match file_format(file_path, options).inspect_err(|e| error!("{}", e))? {
+            FileFormat::Csv | FileFormat::Tsv => Ok(Box::new(Self::parse_csv_stream(file_path, options)?)),
+            format => Ok(Self::parse_as(file_path, format, options)?.into_stream()),
+        }
     }
 }
@@ -1,28 +1,965 @@
-use crate::models::{DeceasedRecord, ErrorDetails, FileMetadata, ProcessingResult};
-use crate::parser::DataParser;
-use crate::database::Database;
-use sqlx::PgPool;
-use log::{info, warn, error};
-use std::path::Path;
+use crate::arabic::normalize_arabic;
+use crate::archive::{extract_zip, DEFAULT_MAX_UNCOMPRESSED_SIZE};
+use crate::family::suggest_links;
+use crate::models::{
+    CemeteryBounds, ConflictKey, DateLimits, DeceasedRecord, DuplicateStrategy, ErrorDetails, FileMetadata, FileSummary,
+    ParseMode, PossibleDuplicate, ProcessingResult, RecordIssue, RecordReport, RecordStatus, Rejection, SwappedCoordinates,
+    ValidationError, ValidationReport,
+};
+use crate::parser::{is_supported_file, DataParser, ParseOptions, ParsedShapefile, RecordStream};
+use tempfile::TempDir;
+use crate::database::{check_batch_size, ConflictAction, Database, FeatureCounts, ImportMode, RetryPolicy, INSERT_CHUNK_SIZE};
+use crate::metrics::Metrics;
+use crate::cause_of_death::CauseVocabulary;
+use crate::config::Config;
+use crate::national_id::{NationalIdMode, NationalIdRules};
+use crate::progress::{estimate_records, ProgressReporter};
+use crate::timestamps::{parse_timestamp, DEFAULT_TIMEZONE};
+use chrono::NaiveDate;
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+use log::{error, info, warn};
+use sha2::{Digest, Sha256};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use tokio::sync::{mpsc, Semaphore};
+use utoipa::ToSchema;
+use walkdir::{DirEntry, WalkDir};
+
+/// Directory levels searched for data files when the request does not say;
+/// 1 is the processed directory itself.
+pub const DEFAULT_MAX_DEPTH: usize = 8;
+
+/// Directory that `data_path` in a process request is resolved against when
+/// `DATA_BASE_DIR` is not set; where the Docker image mounts the data.
+pub const DEFAULT_DATA_BASE_DIR: &str = "/app/data";
+
+/// Files parsed at the same time when `PARALLEL_FILES` is not set.
+pub const DEFAULT_PARALLEL_FILES: usize = 4;
+
+/// Files and folders that archivers and operating systems leave next to the
+/// data, matched case-insensitively. Names starting with `.` or `~$` (Office
+/// lock files) are skipped as well.
+const SYSTEM_NAMES: &[&str] = &["__MACOSX", "Thumbs.db", "desktop.ini"];
+
+/// Files that accompany a `.shp` and are read along with it.
+const SHAPEFILE_COMPANIONS: &[&str] = &["shx", "dbf", "prj", "cpg", "sbn", "sbx", "qix"];
+
+/// Hex-encoded SHA-256 of a file's contents.
+pub fn compute_sha256(path: &Path) -> std::io::Result<String> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut reader, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Digest identifying a directory's contents: SHA-256 over each file's name
+/// and content hash, in name order so it does not depend on listing order.
+fn directory_digest(file_hashes: &mut [(String, String)]) -> String {
+    file_hashes.sort();
+    
+    let mut hasher = Sha256::new();
+    for (name, hash) in file_hashes.iter() {
+        hasher.update(name.as_bytes());
+        hasher.update(b"\0");
+        hasher.update(hash.as_bytes());
+        hasher.update(b"\n");
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+fn is_hidden_or_system(entry: &DirEntry) -> bool {
+    let name = entry.file_name().to_string_lossy();
+    
+    name.starts_with('.')
+        || name.starts_with("~$")
+        || SYSTEM_NAMES.iter().any(|system| name.eq_ignore_ascii_case(system))
+}
+
+/// Finds the data files, archives and shapefiles under `dir`, at most
+/// `max_depth` levels down, as (path relative to `dir`, full path) in path
+/// order. Hidden, system and unsupported files are skipped, the latter with a
+/// warning. Symlinks are followed; a link back to an ancestor is reported and
+/// not descended.
+pub fn find_data_files(
+    dir: &Path,
+    max_depth: usize,
+    warnings: &mut Vec<String>,
+) -> Result<Vec<(String, PathBuf)>, anyhow::Error> {
+    let walker = WalkDir::new(dir)
+        .follow_links(true)
+        .max_depth(max_depth)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| entry.depth() == 0 || !is_hidden_or_system(entry));
+    
+    let mut files = Vec::new();
+    
+    for entry in walker {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) if e.loop_ancestor().is_some() => {
+                warn!("Not following symlink loop: {}", e);
+                warnings.push(format!("Skipped symlink loop: {}", e));
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        
+        let relative = entry.path().strip_prefix(dir)?.to_string_lossy().into_owned();
+        
+        if is_supported_file(entry.path()) || is_zip(entry.path()) || is_shapefile(entry.path()) {
+            files.push((relative, entry.into_path()));
+        } else if is_shapefile_companion(entry.path()) {
+            continue;
+        } else {
+            info!("Skipping unsupported file {}", relative);
+            warnings.push(format!("Skipped unsupported file {}", relative));
+        }
+    }
+    
+    Ok(files)
+}
+
+fn is_zip(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("zip"))
+}
+
+fn is_shapefile(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("shp"))
+}
+
+fn is_shapefile_companion(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| {
+        SHAPEFILE_COMPANIONS.iter().any(|companion| ext.eq_ignore_ascii_case(companion))
+    })
+}
+
+/// The layer a shapefile's outlines are stored under: its path inside the
+/// processed directory or archive, so that a later delivery of the same
+/// file replaces them whatever the archive is called.
+fn layer_name(file_name: &str) -> &str {
+    match file_name.to_ascii_lowercase().rfind(".zip/") {
+        Some(end) => &file_name[end + ".zip/".len()..],
+        None => file_name,
+    }
+}
+
+/// How a file found by [`find_data_files`] is read.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FileKind {
+    Records,
+    Archive,
+    Shapefile,
+}
+
+impl FileKind {
+    fn of(path: &Path) -> Self {
+        if is_zip(path) {
+            FileKind::Archive
+        } else if is_shapefile(path) {
+            FileKind::Shapefile
+        } else {
+            FileKind::Records
+        }
+    }
+}
+
+/// Reads how many files may be parsed at once from `PARALLEL_FILES`,
+/// falling back to [`DEFAULT_PARALLEL_FILES`].
+pub fn parallel_files_from_env() -> Result<usize, String> {
+    match std::env::var("PARALLEL_FILES") {
+        Ok(value) => value
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .filter(|&files| files > 0)
+            .ok_or_else(|| format!("PARALLEL_FILES must be a positive number, got '{}'", value)),
+        Err(_) => Ok(DEFAULT_PARALLEL_FILES),
+    }
+}
+
+/// Reads how many records a run reads between checkpoints from
+/// `CHECKPOINT_EVERY`; unset or 0 leaves runs without checkpoints.
+pub fn checkpoint_every_from_env() -> Result<Option<usize>, String> {
+    match std::env::var("CHECKPOINT_EVERY") {
+        Ok(value) => value
+            .trim()
+            .parse::<usize>()
+            .map(|every| Some(every).filter(|&every| every > 0))
+            .map_err(|_| format!("CHECKPOINT_EVERY must be a non-negative number, got '{}'", value)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Reads the limit on failed records that aborts a run from `MAX_ERRORS`, a
+/// count such as `500` or a share such as `5%`; unset means no limit.
+pub fn max_errors_from_env() -> Result<Option<ErrorLimit>, String> {
+    match std::env::var("MAX_ERRORS") {
+        Ok(value) if !value.trim().is_empty() => value.parse().map(Some).map_err(|e| format!("MAX_ERRORS: {}", e)),
+        _ => Ok(None),
+    }
+}
+
+/// Reads whether map features may take their position from the section
+/// layout when a record has no coordinates, from `DERIVE_COORDINATES`
+/// (off by default).
+pub fn derive_coordinates_from_env() -> Result<bool, String> {
+    match std::env::var("DERIVE_COORDINATES") {
+        Ok(value) => match value.trim().to_ascii_lowercase().as_str() {
+            "1" | "true" | "yes" => Ok(true),
+            "0" | "false" | "no" | "" => Ok(false),
+            _ => Err(format!("DERIVE_COORDINATES must be true or false, got '{}'", value)),
+        },
+        Err(_) => Ok(false),
+    }
+}
+
+/// Reads whether records a run rejects are kept in `rejected_records`,
+/// from `KEEP_REJECTED_RECORDS` (on by default).
+pub fn keep_rejected_from_env() -> Result<bool, String> {
+    match std::env::var("KEEP_REJECTED_RECORDS") {
+        Ok(value) => match value.trim().to_ascii_lowercase().as_str() {
+            "1" | "true" | "yes" | "" => Ok(true),
+            "0" | "false" | "no" => Ok(false),
+            _ => Err(format!("KEEP_REJECTED_RECORDS must be true or false, got '{}'", value)),
+        },
+        Err(_) => Ok(true),
+    }
+}
+
+/// Reads the directory requests may process from `DATA_BASE_DIR`, falling
+/// back to [`DEFAULT_DATA_BASE_DIR`]. It must exist, and is returned
+/// canonicalized for [`resolve_data_path`].
+pub fn data_base_dir_from_env() -> Result<PathBuf, String> {
+    let base_dir = std::env::var("DATA_BASE_DIR").unwrap_or_else(|_| DEFAULT_DATA_BASE_DIR.to_string());
+    
+    std::fs::canonicalize(&base_dir)
+        .map_err(|e| format!("DATA_BASE_DIR {:?} is not an accessible directory: {}", base_dir, e))
+}
+
+/// Resolves a request's `data_path`, which is relative to `base_dir`, to the
+/// file or directory to process.
+///
+/// The path comes from the caller, so it may not be absolute or contain
+/// `..`, and after following symlinks it must still lie inside `base_dir`
+/// (which must be canonical). It must also exist.
+pub fn resolve_data_path(base_dir: &Path, data_path: &str) -> Result<PathBuf, String> {
+    let relative = Path::new(data_path);
+    
+    if data_path.trim().is_empty() {
+        return Err("data_path must not be empty".to_string());
+    }
+    if relative.has_root() || relative.is_absolute() {
+        return Err("data_path must be relative to the data directory".to_string());
+    }
+    if relative.components().any(|c| c == std::path::Component::ParentDir) {
+        return Err("data_path must not contain '..'".to_string());
+    }
+    
+    let resolved = std::fs::canonicalize(base_dir.join(relative))
+        .map_err(|_| format!("data_path {:?} does not exist", data_path))?;
+    
+    if !resolved.starts_with(base_dir) {
+        return Err(format!("data_path {:?} is outside the data directory", data_path));
+    }
+    
+    Ok(resolved)
+}
+
+/// A file being parsed in the background by [`parse_files`].
+pub struct ParsedFile {
+    /// Name the file is reported under
+    pub name: String,
+    /// The file's records and row errors, in file order. A file that cannot
+    /// be opened yields a single error.
+    pub rows: mpsc::Receiver<Result<DeceasedRecord, ErrorDetails>>,
+}
+
+/// Parses `files` on blocking threads, at most `parallelism` at a time.
+///
+/// Each file hands its rows over through a channel holding one insert chunk,
+/// so a parser that gets ahead of the writer waits rather than buffering the
+/// whole file. Files are started in the order given, so draining the results
+/// in that order always makes progress and gives the same records, errors
+/// and order as parsing one file after another. Parsing never touches the
+/// database; the run's single transaction stays with the caller.
+pub fn parse_files(
+    files: Vec<(String, PathBuf)>,
+    options: &ParseOptions,
+    parallelism: usize,
+) -> Vec<ParsedFile> {
+    let semaphore = Arc::new(Semaphore::new(parallelism.max(1)));
+    let mut parsed = Vec::with_capacity(files.len());
+    let mut jobs = Vec::with_capacity(files.len());
+    
+    for (name, path) in files {
+        let (sender, rows) = mpsc::channel(INSERT_CHUNK_SIZE);
+        jobs.push((name.clone(), path, sender));
+        parsed.push(ParsedFile { name, rows });
+    }
+    
+    let options = options.clone();
+    tokio::spawn(async move {
+        for (name, path, sender) in jobs {
+            let Ok(permit) = semaphore.clone().acquire_owned().await else {
+                return;
+            };
+            let options = options.clone();
+            
+            tokio::task::spawn_blocking(move || {
+                let _permit = permit;
+                info!("Processing file: {:?}", path);
+                
+                match DataParser::detect_and_stream(&path, &options) {
+                    // Lines are only meaningful alongside the file they came from
+                    Ok(stream) => send_rows(stream.map(|row| row.map_err(|e| ErrorDetails {
+                        message: format!("{}: {}", name, e.message),
+                        ..e
+                    })), &sender),
+                    Err(e) => {
+                        warn!("Failed to parse file {:?}: {}", path, e);
+                        let _ = sender.blocking_send(Err(file_error(format!("Failed to parse file {:?}: {}", name, e))));
+                    }
+                }
+            });
+        }
+    });
+    
+    parsed
+}
+
+/// Reads `stream` on a blocking thread into a channel for the writer.
+fn stream_in_background(stream: RecordStream) -> mpsc::Receiver<Result<DeceasedRecord, ErrorDetails>> {
+    let (sender, rows) = mpsc::channel(INSERT_CHUNK_SIZE);
+    tokio::task::spawn_blocking(move || send_rows(stream, &sender));
+    rows
+}
+
+fn send_rows(
+    stream: impl Iterator<Item = Result<DeceasedRecord, ErrorDetails>>,
+    sender: &mpsc::Sender<Result<DeceasedRecord, ErrorDetails>>,
+) {
+    for row in stream {
+        // The writer is gone when the run failed; stop parsing
+        if sender.blocking_send(row).is_err() {
+            return;
+        }
+    }
+}
+
+/// Picks the hash to record for a run. The computed hash always wins; a
+/// supplied hash that disagrees with it is reported as a warning.
+fn reconcile_hash(supplied: &str, computed: String, warnings: &mut Vec<String>) -> String {
+    if !supplied.is_empty() && !supplied.eq_ignore_ascii_case(&computed) {
+        warn!("Supplied file_hash {} does not match computed {}", supplied, computed);
+        warnings.push(format!(
+            "Supplied file_hash {} does not match computed hash {}; the computed hash was recorded",
+            supplied, computed
+        ));
+    }
+    
+    computed
+}
+
+/// An error about a whole file rather than one of its records.
+fn file_error(message: String) -> ErrorDetails {
+    ErrorDetails {
+        record_id: None,
+        line_number: None,
+        message,
+        code: None,
+        field: None,
+        source_fields: None,
+    }
+}
+
+/// Records a run must have read before a percentage [`ErrorLimit`] can
+/// abort it, so a bad first record does not count as 100% failed.
+pub const ERROR_PERCENT_MIN_RECORDS: usize = 100;
+
+/// How many records may fail before a run is aborted, rolling back what it
+/// wrote. In requests a number is a count and a string such as `"5%"` a
+/// share of the records read.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "ErrorLimitValue", into = "ErrorLimitValue")]
+pub enum ErrorLimit {
+    /// Most records that may fail
+    Count(usize),
+    /// Most records that may fail per hundred read
+    Percent(f64),
+}
+
+/// An [`ErrorLimit`] as written in JSON.
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum ErrorLimitValue {
+    Count(usize),
+    Text(String),
+}
+
+impl ErrorLimit {
+    /// Whether `failed` of `read` records is over the limit. A percentage
+    /// only applies from [`ERROR_PERCENT_MIN_RECORDS`] records on, or once
+    /// the run has read everything (`finished`).
+    pub fn exceeded(&self, failed: usize, read: usize, finished: bool) -> bool {
+        match *self {
+            ErrorLimit::Count(most) => failed > most,
+            ErrorLimit::Percent(percent) => {
+                (finished || read >= ERROR_PERCENT_MIN_RECORDS) && failed as f64 * 100.0 > percent * read as f64
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorLimit::Count(most) => write!(f, "{}", most),
+            ErrorLimit::Percent(percent) => write!(f, "{}%", percent),
+        }
+    }
+}
+
+impl std::str::FromStr for ErrorLimit {
+    type Err = String;
+    
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let trimmed = value.trim();
+        
+        let limit = match trimmed.strip_suffix('%') {
+            Some(percent) => percent
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|percent| (0.0..=100.0).contains(percent))
+                .map(ErrorLimit::Percent),
+            None => trimmed.parse().ok().map(ErrorLimit::Count),
+        };
+        
+        limit.ok_or_else(|| {
+            format!("expected a number of records or a percentage such as '5%', got '{}'", value)
+        })
+    }
+}
+
+impl TryFrom<ErrorLimitValue> for ErrorLimit {
+    type Error = String;
+    
+    fn try_from(value: ErrorLimitValue) -> Result<Self, Self::Error> {
+        match value {
+            ErrorLimitValue::Count(most) => Ok(ErrorLimit::Count(most)),
+            ErrorLimitValue::Text(text) => text.parse(),
+        }
+    }
+}
+
+impl From<ErrorLimit> for ErrorLimitValue {
+    fn from(limit: ErrorLimit) -> Self {
+        match limit {
+            ErrorLimit::Count(most) => ErrorLimitValue::Count(most),
+            ErrorLimit::Percent(_) => ErrorLimitValue::Text(limit.to_string()),
+        }
+    }
+}
+
+/// Per-request switches controlling how a run writes to the database.
+#[derive(Debug, Default, Clone, Copy, Deserialize, Serialize, ToSchema)]
+pub struct RunOptions {
+    /// Reprocess even if the same content has already been processed, and
+    /// keep the files of a directory that earlier runs imported
+    #[serde(default, alias = "force_reprocess")]
+    pub force: bool,
+    /// Parse and validate only; nothing is written
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Directory levels searched for data files, 1 being the top level;
+    /// [`DEFAULT_MAX_DEPTH`] when unset
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+    /// Rebuild every map feature instead of only those of the records this
+    /// run wrote; for maintenance
+    #[serde(default)]
+    pub rebuild_features: bool,
+    /// Whether records already stored are updated, new ones inserted, or both
+    #[serde(default)]
+    pub import_mode: ImportMode,
+    /// Records per INSERT statement, overriding `BATCH_SIZE` for this run
+    #[serde(default)]
+    pub batch_size: Option<usize>,
+    /// Records read between checkpoints, overriding `CHECKPOINT_EVERY` for
+    /// this run; 0 turns them off
+    #[serde(default)]
+    pub checkpoint_every: Option<usize>,
+    /// Failed records that abort the run, overriding `MAX_ERRORS`: a count,
+    /// or a share of the records read such as `"5%"`
+    #[serde(default)]
+    #[schema(value_type = Option<Object>)]
+    pub max_errors: Option<ErrorLimit>,
+    /// `strict` aborts the run, rolling back what it wrote, at the first
+    /// record that fails; `lenient` (the default) writes the others
+    #[serde(default)]
+    pub parse_mode: ParseMode,
+}
+
+impl RunOptions {
+    /// Checks the switches that have a range.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_depth == Some(0) {
+            return Err("max_depth must be at least 1".to_string());
+        }
+        if let Some(size) = self.batch_size {
+            check_batch_size(size)?;
+        }
+        
+        Ok(())
+    }
+}
+
+/// How far a run that commits as it goes has got. Each file's checkpoint is
+/// written with its records, so a restart of the same content skips exactly
+/// what was committed.
+struct Checkpoints {
+    file_hash: String,
+    /// Records read between commits
+    every: usize,
+    /// Records of each file an interrupted run committed
+    resume_from: HashMap<String, usize>,
+    /// Records read since the last commit
+    since_commit: usize,
+    /// Records skipped because an interrupted run committed them
+    resumed: usize,
+}
+
+/// Running totals for one processing run, accumulated chunk by chunk.
+#[derive(Default)]
+struct RunTotals {
+    files: Vec<FileSummary>,
+    records_total: usize,
+    /// Records written as new rows
+    inserted: usize,
+    /// Records written over a stored row with the same id
+    updated: usize,
+    /// Records the import mode left out
+    skipped: usize,
+    /// Records that passed validation with a latitude and longitude
+    with_coordinates: usize,
+    import_mode: ImportMode,
+    errors: Vec<ErrorDetails>,
+    warnings: Vec<String>,
+    dedup: Deduplicator,
+    /// Section and plot outlines read from shapefiles
+    sections: usize,
+    /// Ids of the records sent to the database, whose features need updating
+    touched: Vec<String>,
+    progress: ProgressReporter,
+    /// Records per INSERT statement
+    batch_size: usize,
+    /// Set when the run commits at checkpoints
+    checkpoints: Option<Checkpoints>,
+    /// How much of `touched` has had its features updated at a checkpoint
+    features_done: usize,
+    /// Features written, and records skipped for bad geometry, at checkpoints
+    features: FeatureCounts,
+    /// Failed records that abort the run
+    max_errors: Option<ErrorLimit>,
+    /// Set to strict, any failed record aborts the run
+    parse_mode: ParseMode,
+    /// Set when failed records are kept in `rejected_records`
+    keep_rejected: bool,
+    /// Failed records not yet written to `rejected_records`
+    rejected: Vec<Rejection>,
+    /// Name and content hash of each file of a directory run, noted in
+    /// `processed_files` when the run commits
+    file_hashes: Vec<(String, String)>,
+    /// Files of a directory run left out because earlier runs imported them
+    files_already_processed: Vec<String>,
+}
+
+impl RunTotals {
+    fn new(
+        duplicates: DuplicateStrategy,
+        conflict_key: ConflictKey,
+        import_mode: ImportMode,
+        progress: ProgressReporter,
+    ) -> Self {
+        Self {
+            dedup: Deduplicator { strategy: duplicates, key: conflict_key, ..Deduplicator::default() },
+            import_mode,
+            progress,
+            ..Self::default()
+        }
+    }
+    
+    /// Records written to the database, new or over an existing row.
+    fn written(&self) -> usize {
+        self.inserted + self.updated
+    }
+    
+    /// Records accounted for other than by failing.
+    fn settled(&self) -> usize {
+        self.written() + self.skipped + self.dedup.collapsed
+    }
+    
+    /// Holds a record of `file_name` that failed with `errors` for
+    /// `rejected_records`. Failures of a whole file, with neither a record
+    /// id, a line nor fields, are not records and are left out.
+    fn reject(&mut self, file_name: &str, fields: Option<serde_json::Value>, errors: &[ErrorDetails]) {
+        let Some(first) = errors.first() else { return };
+        if !self.keep_rejected || (first.record_id.is_none() && first.line_number.is_none() && fields.is_none()) {
+            return;
+        }
+        
+        let errors = errors
+            .iter()
+            .map(|error| serde_json::json!({ "message": error.message, "code": error.code, "field": error.field }))
+            .collect();
+        self.rejected.push(Rejection {
+            file_name: file_name.to_string(),
+            record_id: first.record_id.clone(),
+            line_number: first.line_number,
+            fields,
+            errors: serde_json::Value::Array(errors),
+        });
+    }
+    
+    /// Marks the start of a file, for [`RunTotals::finish_file`].
+    fn start_file(&self) -> (usize, usize, usize) {
+        (self.records_total, self.settled(), self.with_coordinates)
+    }
+    
+    /// Records what the file started at `start` contributed. Its last chunk
+    /// must have been written already.
+    fn finish_file(&mut self, file: String, start: (usize, usize, usize)) {
+        let records_read = self.records_total - start.0;
+        let settled = self.settled() - start.1;
+        
+        self.files.push(FileSummary {
+            file,
+            records_read,
+            records_failed: records_read - settled,
+            records_with_coordinates: self.with_coordinates - start.2,
+        });
+    }
+    
+    /// Records that were read but neither written, skipped nor collapsed
+    /// into another. A record can carry several errors, so this is not
+    /// `errors.len()`.
+    fn failed(&self) -> usize {
+        self.records_total - self.settled()
+    }
+    
+    /// Fails the run if `failed` records are over its `max_errors`.
+    /// `finished` is set once every record has been read.
+    fn check_error_limit(&self, failed: usize, finished: bool) -> Result<(), anyhow::Error> {
+        if self.parse_mode == ParseMode::Strict && failed > 0 {
+            let first = self.errors.first().map_or(String::new(), |e| format!(": {}", e.message));
+            return Err(anyhow::anyhow!("Import aborted: parse_mode is strict and a record failed{}", first));
+        }
+        
+        let Some(limit) = self.max_errors else { return Ok(()) };
+        if !limit.exceeded(failed, self.records_total, finished) {
+            return Ok(());
+        }
+        
+        let first = self.errors.first().map_or(String::new(), |e| format!("; the first was: {}", e.message));
+        Err(anyhow::anyhow!(
+            "Import aborted: too many errors ({} of {} records read failed, max_errors is {}){}",
+            failed,
+            self.records_total,
+            limit,
+            first
+        ))
+    }
+}
+
+/// Collapses records that repeat a `record_id` within a run, and spots
+/// records under different ids that look like the same person.
+#[derive(Default)]
+struct Deduplicator {
+    strategy: DuplicateStrategy,
+    /// What identifies a person; records sharing a key are given the id
+    /// first seen for it, or the stored one, and so collapse like repeats
+    key: ConflictKey,
+    /// Id given to each key value, when the key is not `record_id`
+    ids_by_key: HashMap<String, String>,
+    /// Position of each id in the chunk being filled
+    pending: HashMap<String, usize>,
+    /// Ids written by earlier chunks of the run
+    written: HashSet<String>,
+    /// First id seen for each (name, death date, burial location)
+    people: HashMap<(String, NaiveDate, String), String>,
+    collapsed: usize,
+    possible_duplicates: Vec<PossibleDuplicate>,
+}
+
+impl Deduplicator {
+    /// Adds `record` to `chunk`, folding it into an earlier copy in the same
+    /// chunk if there is one.
+    fn push(&mut self, chunk: &mut Vec<DeceasedRecord>, mut record: DeceasedRecord) {
+        if let Some(value) = self.key_value(&record) {
+            match self.ids_by_key.entry(value) {
+                Entry::Occupied(id) => record.record_id = id.get().clone(),
+                Entry::Vacant(slot) => {
+                    slot.insert(record.record_id.clone());
+                }
+            }
+        }
+        
+        if let Some(&index) = self.pending.get(&record.record_id) {
+            self.collapsed += 1;
+            chunk[index].collapse(record, self.strategy);
+            return;
+        }
+        
+        if self.written.contains(&record.record_id) {
+            self.collapsed += 1;
+        } else {
+            self.note_person(&record);
+        }
+        
+        self.pending.insert(record.record_id.clone(), chunk.len());
+        chunk.push(record);
+    }
+    
+    fn note_person(&mut self, record: &DeceasedRecord) {
+        let key = (
+            normalize_arabic(record.deceased_name.trim()).to_lowercase(),
+            record.death_date,
+            record.burial_location.trim().to_lowercase(),
+        );
+        
+        match self.people.entry(key) {
+            Entry::Occupied(first) => self.possible_duplicates.push(PossibleDuplicate {
+                record_id: record.record_id.clone(),
+                matches_record_id: first.get().clone(),
+            }),
+            Entry::Vacant(slot) => {
+                slot.insert(record.record_id.clone());
+            }
+        }
+    }
+    
+    /// The record's value for the key, unless the key is `record_id`.
+    fn key_value(&self, record: &DeceasedRecord) -> Option<String> {
+        (self.key != ConflictKey::RecordId).then(|| self.key.value(record)).flatten()
+    }
+    
+    /// Gives the records of `chunk` the ids already `stored` for their key
+    /// values, and later records with those values too.
+    fn adopt_stored_ids(&mut self, chunk: &mut [DeceasedRecord], stored: &HashMap<String, String>) {
+        for record in chunk {
+            let Some(value) = self.key_value(record) else { continue };
+            if let Some(record_id) = stored.get(&value) {
+                record.record_id = record_id.clone();
+                self.ids_by_key.insert(value, record_id.clone());
+            }
+        }
+    }
+    
+    /// Empties `chunk` into records new to the run and repeats of ids that
+    /// an earlier chunk already wrote.
+    fn take(&mut self, chunk: &mut Vec<DeceasedRecord>) -> (Vec<DeceasedRecord>, Vec<DeceasedRecord>) {
+        self.pending.clear();
+        
+        let (repeats, fresh): (Vec<_>, Vec<_>) = chunk
+            .drain(..)
+            .partition(|record| self.written.contains(&record.record_id));
+        self.written.extend(fresh.iter().map(|record| record.record_id.clone()));
+        
+        (fresh, repeats)
+    }
+    
+    /// Forgets ids that were not written after all, because the database
+    /// rejected them or the import mode skipped them, so a later copy is
+    /// judged afresh.
+    fn forget<'a>(&mut self, record_ids: impl IntoIterator<Item = &'a String>) {
+        for record_id in record_ids {
+            self.written.remove(record_id);
+        }
+    }
+    
+    /// How a repeat is applied to the copy an earlier chunk wrote.
+    fn repeat_action(&self) -> ConflictAction {
+        match self.strategy {
+            DuplicateStrategy::KeepFirst => ConflictAction::Ignore,
+            DuplicateStrategy::KeepLast => ConflictAction::Replace,
+            DuplicateStrategy::Merge => ConflictAction::Fill,
+        }
+    }
+}
 
 pub struct DataProcessor {
     db: Database,
+    bounds: Option<CemeteryBounds>,
+    metrics: Metrics,
+    max_archive_size: u64,
+    parallel_files: usize,
+    derive_coordinates: bool,
+    keep_rejected: bool,
+    national_ids: NationalIdRules,
+    causes: Option<CauseVocabulary>,
+    date_limits: DateLimits,
+    timezone: Tz,
+    checkpoint_every: Option<usize>,
+    max_errors: Option<ErrorLimit>,
 }
 
 impl DataProcessor {
     pub fn new(pool: Arc<PgPool>) -> Self {
         Self {
             db: Database::new((*pool).clone()),
+            bounds: None,
+            metrics: Metrics::new(),
+            max_archive_size: DEFAULT_MAX_UNCOMPRESSED_SIZE,
+            parallel_files: DEFAULT_PARALLEL_FILES,
+            derive_coordinates: false,
+            keep_rejected: true,
+            national_ids: NationalIdRules::default(),
+            causes: None,
+            date_limits: DateLimits::default(),
+            timezone: DEFAULT_TIMEZONE,
+            checkpoint_every: None,
+            max_errors: None,
         }
     }
     
+    /// A processor set up as `config` says; metrics stay private until
+    /// [`DataProcessor::with_metrics`].
+    pub fn from_config(pool: Arc<PgPool>, config: &Config) -> Self {
+        Self::new(pool)
+            .with_bounds(config.cemetery_bounds)
+            .with_retry_policy(config.retry)
+            .with_batch_size(config.batch_size)
+            .with_max_archive_size(config.max_archive_size)
+            .with_parallel_files(config.parallel_files)
+            .with_derive_coordinates(config.derive_coordinates)
+            .with_keep_rejected(config.keep_rejected)
+            .with_national_id_rules(config.national_ids.clone())
+            .with_cause_vocabulary(config.causes.clone())
+            .with_date_limits(config.date_limits)
+            .with_timezone(config.timezone)
+            .with_checkpoint_every(config.checkpoint_every)
+            .with_max_errors(config.max_errors)
+    }
+    
+    /// Rejects records whose coordinates fall outside `bounds`.
+    pub fn with_bounds(mut self, bounds: Option<CemeteryBounds>) -> Self {
+        self.bounds = bounds;
+        self
+    }
+    
+    /// Retries transient database errors during inserts according to `retry`.
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.db = self.db.with_retry_policy(retry);
+        self
+    }
+    
+    /// Inserts up to `size` records per statement unless a run says
+    /// otherwise.
+    pub fn with_batch_size(mut self, size: usize) -> Self {
+        self.db = self.db.with_batch_size(size);
+        self
+    }
+    
+    /// Caps the bytes a ZIP archive may expand to.
+    pub fn with_max_archive_size(mut self, bytes: u64) -> Self {
+        self.max_archive_size = bytes;
+        self
+    }
+    
+    /// Parses up to `files` files of a directory at the same time.
+    pub fn with_parallel_files(mut self, files: usize) -> Self {
+        self.parallel_files = files;
+        self
+    }
+    
+    /// Places records without coordinates on the map at their plot or
+    /// section; see [`Database::create_geojson_features`].
+    pub fn with_derive_coordinates(mut self, derive: bool) -> Self {
+        self.derive_coordinates = derive;
+        self
+    }
+    
+    /// Keeps the records runs reject in `rejected_records`, with what the
+    /// source gave for them, for [`Database::rejected_records`].
+    pub fn with_keep_rejected(mut self, keep: bool) -> Self {
+        self.keep_rejected = keep;
+        self
+    }
+    
+    /// Checks `national_id`s by `rules` instead of the defaults.
+    pub fn with_national_id_rules(mut self, rules: NationalIdRules) -> Self {
+        self.national_ids = rules;
+        self
+    }
+    
+    /// Codes each record's `cause_of_death` by `vocabulary`; causes are
+    /// left uncoded without one.
+    pub fn with_cause_vocabulary(mut self, vocabulary: Option<CauseVocabulary>) -> Self {
+        self.causes = vocabulary;
+        self
+    }
+    
+    pub fn cause_vocabulary(&self) -> Option<&CauseVocabulary> {
+        self.causes.as_ref()
+    }
+    
+    /// Rejects death and burial dates outside `limits` instead of the
+    /// defaults.
+    pub fn with_date_limits(mut self, limits: DateLimits) -> Self {
+        self.date_limits = limits;
+        self
+    }
+    
+    /// Reads download times without an offset as local time in `zone`.
+    pub fn with_timezone(mut self, zone: Tz) -> Self {
+        self.timezone = zone;
+        self
+    }
+    
+    /// Commits runs every `records` records read, so a run that is
+    /// interrupted can resume where it stopped instead of starting over.
+    /// Runs are all or nothing without.
+    pub fn with_checkpoint_every(mut self, records: Option<usize>) -> Self {
+        self.checkpoint_every = records.filter(|&records| records > 0);
+        self
+    }
+    
+    /// Aborts runs, rolling them back, once more records fail than `limit`
+    /// allows, unless a run sets its own. `None` lets every record be tried.
+    pub fn with_max_errors(mut self, limit: Option<ErrorLimit>) -> Self {
+        self.max_errors = limit;
+        self
+    }
+    
+    /// Reports runs to `metrics` instead of a private, unexposed set.
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+    
+    /// Processes the data files under a directory as one run, reporting how
+    /// far it has got to `progress`.
     pub async fn process_directory(
         &self,
         directory_path: &str,
         metadata: &FileMetadata,
+        options: &ParseOptions,
+        run: RunOptions,
+        progress: ProgressReporter,
     ) -> Result<ProcessingResult, anyhow::Error> {
         info!("Processing directory: {}", directory_path);
+        let _timer = self.metrics.processing_duration.start_timer();
         
         let dir = Path::new(directory_path);
         
@@ -30,144 +967,994 @@ impl DataProcessor {
             return Err(anyhow::anyhow!("Directory does not exist or is not a directory"));
         }
         
-        let mut all_records = Vec::new();
-        let mut errors = Vec::new();
+        // An encoding named in the metadata applies to every file
+        let options = &ParseOptions { encoding: metadata.encoding.clone(), ..options.clone() };
+        let mut warnings = Vec::new();
+        let files = find_data_files(dir, run.max_depth.unwrap_or(DEFAULT_MAX_DEPTH), &mut warnings)?;
+        
+        // Hash every file up front so an unchanged upload is skipped before parsing
+        let mut file_hashes = Vec::new();
+        for (file_name, path) in &files {
+            file_hashes.push((file_name.clone(), compute_sha256(path)?));
+        }
+        
+        let file_hash = reconcile_hash(
+            &metadata.file_hash,
+            directory_digest(&mut file_hashes),
+            &mut warnings,
+        );
         
-        // Find and parse all CSV and JSON files in the directory
-        let entries = std::fs::read_dir(dir)?;
+        if self.should_skip(&file_hash, run, &mut warnings).await? {
+            info!("Skipping {}: hash {} already processed", directory_path, file_hash);
+            return Ok(ProcessingResult {
+                parse_mode: run.parse_mode,
+                ..ProcessingResult::already_processed(&file_hash, warnings)
+            });
+        }
         
-        for entry in entries {
-            let entry = entry?;
-            let path = entry.path();
+        let outcome: Result<ProcessingResult, anyhow::Error> = async {
+            let skipped = self.already_processed_files(&files, &file_hashes, run, &mut warnings).await?;
+            let files: Vec<_> = files.into_iter().filter(|(name, _)| !skipped.contains(name)).collect();
             
-            if path.is_file() {
-                info!("Processing file: {:?}", path);
+            let mut tx = self.begin_run(run, options.conflict_key).await?;
+            let mut totals = self.run_totals(options, run, progress);
+            totals.file_hashes = file_hashes.into_iter().filter(|(name, _)| !skipped.contains(name)).collect();
+            totals.files_already_processed = skipped;
+            self.start_checkpoints(&tx, &file_hash, run, &mut totals).await?;
+            totals.progress.start(files.len(), estimate_records(&files));
+            
+            let max_depth = run.max_depth.unwrap_or(DEFAULT_MAX_DEPTH);
+            self.ingest_files(&mut tx, files, options, &metadata.filename, max_depth, &mut totals).await?;
+            
+            self.finish_run(tx, metadata, &file_hash, totals, warnings, run).await
+        }
+        .await;
+        
+        self.log_failed_run(outcome, metadata, &file_hash, run).await
+    }
+    
+    /// Processes one data file, archive or shapefile as a run, reporting how
+    /// far it has got to `progress`.
+    pub async fn process_single_file(
+        &self,
+        file_path: &str,
+        metadata: &FileMetadata,
+        options: &ParseOptions,
+        run: RunOptions,
+        mut progress: ProgressReporter,
+    ) -> Result<ProcessingResult, anyhow::Error> {
+        info!("Processing single file: {}", file_path);
+        let _timer = self.metrics.processing_duration.start_timer();
+        
+        let path = Path::new(file_path);
+        
+        if !path.exists() || !path.is_file() {
+            return Err(anyhow::anyhow!("File does not exist or is not a file"));
+        }
+        
+        let options = &ParseOptions { encoding: metadata.encoding.clone(), ..options.clone() };
+        let mut warnings = Vec::new();
+        let file_hash = reconcile_hash(&metadata.file_hash, compute_sha256(path)?, &mut warnings);
+        
+        if self.should_skip(&file_hash, run, &mut warnings).await? {
+            info!("Skipping {}: hash {} already processed", file_path, file_hash);
+            return Ok(ProcessingResult {
+                parse_mode: run.parse_mode,
+                ..ProcessingResult::already_processed(&file_hash, warnings)
+            });
+        }
+        
+        let file_name = path.file_name().map_or_else(
+            || file_path.to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+        progress.start(1, estimate_records(&[(file_name.clone(), path.to_path_buf())]));
+        
+        let outcome: Result<ProcessingResult, anyhow::Error> = async {
+            if is_zip(path) {
+                let mut totals = self.run_totals(options, run, progress);
+                let max_depth = run.max_depth.unwrap_or(DEFAULT_MAX_DEPTH);
+                let (_extract_dir, files) = self.extract_archive(&file_name, path, max_depth, &mut totals)?;
+                totals.progress.finish_file();
+                
+                let mut tx = self.begin_run(run, options.conflict_key).await?;
+                self.start_checkpoints(&tx, &file_hash, run, &mut totals).await?;
+                self.ingest_files(&mut tx, files, options, &metadata.filename, max_depth, &mut totals).await?;
+                
+                return self.finish_run(tx, metadata, &file_hash, totals, warnings, run).await;
+            }
+            
+            if is_shapefile(path) {
+                let mut tx = self.begin_run(run, options.conflict_key).await?;
+                let mut totals = self.run_totals(options, run, progress);
+                
+                let parsed = DataParser::parse_shapefile(path)?;
+                self.ingest_shapefile(&mut tx, &file_name, parsed, &metadata.filename, &mut totals).await?;
                 
-                match DataParser::detect_and_parse(&path) {
-                    Ok(records) => {
-                        info!("Parsed {} records from {:?}", records.len(), path);
-                        all_records.extend(records);
+                return self.finish_run(tx, metadata, &file_hash, totals, warnings, run).await;
+            }
+            
+            let stream = DataParser::detect_and_stream(path, options)?;
+            
+            let mut tx = self.begin_run(run, options.conflict_key).await?;
+            let mut totals = self.run_totals(options, run, progress);
+            self.start_checkpoints(&tx, &file_hash, run, &mut totals).await?;
+            let file = ParsedFile { name: file_name, rows: stream_in_background(stream) };
+            
+            self.ingest_file(&mut tx, file, &metadata.filename, options.swapped_coordinates, &mut totals).await?;
+            
+            self.finish_run(tx, metadata, &file_hash, totals, warnings, run).await
+        }
+        .await;
+        
+        self.log_failed_run(outcome, metadata, &file_hash, run).await
+    }
+    
+    /// Passes on the outcome of a run, first logging a run that failed as
+    /// `failed` in `file_processing_log`. The run's own transaction has been
+    /// rolled back by then, so the entry is written on a connection of its
+    /// own; it never replaces a `completed` entry. Dry runs are not logged.
+    async fn log_failed_run(
+        &self,
+        outcome: Result<ProcessingResult, anyhow::Error>,
+        metadata: &FileMetadata,
+        file_hash: &str,
+        run: RunOptions,
+    ) -> Result<ProcessingResult, anyhow::Error> {
+        let Err(e) = &outcome else {
+            return outcome;
+        };
+        if run.dry_run {
+            return outcome;
+        }
+        
+        let download_time = parse_timestamp(&metadata.download_time, self.timezone).ok();
+        if let Err(log_error) = self.db
+            .log_failed_processing(&metadata.filename, file_hash, metadata.size, download_time, &e.to_string())
+            .await
+        {
+            error!("Failed to log the failed run for {}: {}", metadata.filename, log_error);
+        }
+        
+        outcome
+    }
+    
+    /// Checks every record of the data at `path`, a directory or a single
+    /// file, as an import would, without touching the database. Archives are
+    /// expanded and shapefiles parsed for their rejected shapes. Repeated
+    /// `record_id`s are reported as warnings, since an import would
+    /// collapse them.
+    pub async fn validate_path(
+        &self,
+        path: &Path,
+        options: &ParseOptions,
+        max_depth: usize,
+    ) -> Result<ValidationReport, anyhow::Error> {
+        let mut report = ValidationReport::default();
+        
+        let files = if path.is_dir() {
+            find_data_files(path, max_depth, &mut report.warnings)?
+        } else {
+            let name = path.file_name().map_or_else(
+                || path.to_string_lossy().into_owned(),
+                |name| name.to_string_lossy().into_owned(),
+            );
+            vec![(name, path.to_path_buf())]
+        };
+        
+        // The extracted files are read from the directories kept here
+        let mut extract_dirs = Vec::new();
+        let mut expanded = Vec::new();
+        for (name, file) in files {
+            if FileKind::of(&file) != FileKind::Archive {
+                expanded.push((name, file));
+                continue;
+            }
+            
+            match self.extract_files(&name, &file, max_depth, &mut report.warnings) {
+                Ok((extract_dir, inner_files)) => {
+                    extract_dirs.push(extract_dir);
+                    expanded.extend(inner_files);
+                }
+                Err(e) => report.file_errors.push(format!("Failed to read archive {:?}: {}", name, e)),
+            }
+        }
+        
+        let (record_files, shapefiles): (Vec<_>, Vec<_>) = expanded
+            .into_iter()
+            .partition(|(_, file)| FileKind::of(file) == FileKind::Records);
+        
+        for (name, file) in shapefiles {
+            match DataParser::parse_shapefile(&file) {
+                Ok(parsed) => report.file_errors.extend(
+                    parsed.errors.into_iter().map(|e| format!("{}: {}", name, e.message))
+                ),
+                Err(e) => report.file_errors.push(format!("Failed to parse file {:?}: {}", name, e)),
+            }
+        }
+        
+        let today = chrono::Utc::now().date_naive();
+        let mut seen = HashSet::new();
+        
+        for mut file in parse_files(record_files, options, self.parallel_files) {
+            while let Some(row) = file.rows.recv().await {
+                let mut record = match row {
+                    Ok(record) => record,
+                    // Neither a record nor a line: the whole file failed
+                    Err(e) if e.record_id.is_none() && e.line_number.is_none() => {
+                        report.file_errors.push(e.message);
+                        continue;
                     }
                     Err(e) => {
-                        warn!("Failed to parse file {:?}: {}", path, e);
-                        errors.push(ErrorDetails {
-                            record_id: None,
-                            message: format!("Failed to parse file {:?}: {}", path, e),
+                        let prefix = format!("{}: ", file.name);
+                        report.push(RecordReport {
+                            file: file.name.clone(),
+                            record_id: e.record_id,
+                            line_number: e.line_number,
+                            valid: false,
+                            errors: vec![RecordIssue {
+                                message: e.message.strip_prefix(&prefix).unwrap_or(&e.message).to_string(),
+                                code: e.code,
+                                field: e.field,
+                            }],
+                            warnings: Vec::new(),
                         });
+                        continue;
                     }
+                };
+                
+                let (problems, mut warnings) =
+                    self.check_record(&mut record, options.swapped_coordinates, options.conflict_key, today);
+                if !seen.insert(record.record_id.clone()) {
+                    warnings.push("record_id is also used by an earlier record".to_string());
                 }
+                
+                if problems.is_empty() && record.has_coordinates() {
+                    report.records_with_coordinates += 1;
+                }
+                report.push(RecordReport {
+                    file: file.name.clone(),
+                    record_id: Some(record.record_id),
+                    line_number: None,
+                    valid: problems.is_empty(),
+                    errors: problems.iter().map(RecordIssue::from).collect(),
+                    warnings,
+                });
             }
         }
         
-        info!("Total records parsed: {}", all_records.len());
-        
-        // Validate and filter records
-        let mut valid_records = Vec::new();
+        Ok(report)
+    }
+    
+    /// Writes `files` into the run in the order given. Record files are
+    /// parsed in the background while earlier ones are written; archives
+    /// are expanded and shapefiles imported in turn. A file that cannot be
+    /// read is recorded as a failed record, but database errors end the run.
+    async fn ingest_files(
+        &self,
+        tx: &mut Option<Transaction<'static, Postgres>>,
+        files: Vec<(String, PathBuf)>,
+        options: &ParseOptions,
+        source_file: &str,
+        max_depth: usize,
+        totals: &mut RunTotals,
+    ) -> Result<(), anyhow::Error> {
+        let kinds: Vec<FileKind> = files.iter().map(|(_, path)| FileKind::of(path)).collect();
+        let (record_files, other_files): (Vec<_>, Vec<_>) = files
+            .into_iter()
+            .partition(|(_, path)| FileKind::of(path) == FileKind::Records);
+        let mut other_files = other_files.into_iter();
+        let mut parsed = parse_files(record_files, options, self.parallel_files).into_iter();
         
-        for record in all_records {
-            match record.validate() {
-                Ok(()) => {
-                    valid_records.push(record);
+        for kind in kinds {
+            if kind == FileKind::Records {
+                let file = parsed.next().expect("one entry per record file");
+                self.ingest_file(tx, file, source_file, options.swapped_coordinates, totals).await?;
+                continue;
+            }
+            
+            let (file_name, path) = other_files.next().expect("one entry per other file");
+            
+            if kind == FileKind::Shapefile {
+                match DataParser::parse_shapefile(&path) {
+                    Ok(parsed) => self.ingest_shapefile(tx, &file_name, parsed, source_file, totals).await?,
+                    Err(e) => {
+                        warn!("Failed to parse shapefile {:?}: {}", path, e);
+                        totals.records_total += 1;
+                        totals.errors.push(file_error(format!("Failed to parse file {:?}: {}", file_name, e)));
+                        totals.progress.finish_file();
+                    }
+                }
+                continue;
+            }
+            
+            totals.progress.start_file(&file_name);
+            let extracted = self.extract_archive(&file_name, &path, max_depth, totals);
+            totals.progress.finish_file();
+            
+            match extracted {
+                Ok((_extract_dir, inner_files)) => {
+                    // Archives inside archives are skipped on extraction, so
+                    // this goes one level deep
+                    Box::pin(self.ingest_files(tx, inner_files, options, source_file, max_depth, totals)).await?;
                 }
                 Err(e) => {
-                    warn!("Validation failed for record {}: {}", record.record_id, e);
-                    errors.push(ErrorDetails {
-                        record_id: Some(record.record_id.clone()),
-                        message: e,
-                    });
+                    warn!("Failed to read archive {:?}: {}", path, e);
+                    totals.records_total += 1;
+                    totals.errors.push(file_error(format!("Failed to read archive {:?}: {}", file_name, e)));
                 }
             }
         }
         
-        info!("Valid records: {}", valid_records.len());
-        info!("Invalid records: {}", errors.len());
+        Ok(())
+    }
+    
+    /// Writes one parsed file into the run and records its summary.
+    async fn ingest_file(
+        &self,
+        tx: &mut Option<Transaction<'static, Postgres>>,
+        file: ParsedFile,
+        source_file: &str,
+        swapped_coordinates: SwappedCoordinates,
+        totals: &mut RunTotals,
+    ) -> Result<(), anyhow::Error> {
+        let start = totals.start_file();
+        totals.progress.start_file(&file.name);
+        self.ingest(tx, &file.name, file.rows, source_file, swapped_coordinates, totals).await?;
+        totals.progress.finish_file();
+        totals.finish_file(file.name, start);
+        Ok(())
+    }
+    
+    /// Extracts a ZIP archive to a temporary directory and lists the files in
+    /// it, named `archive.zip/path/in/archive.csv`. The directory is removed
+    /// when the returned [`TempDir`] is dropped.
+    fn extract_archive(
+        &self,
+        archive_name: &str,
+        path: &Path,
+        max_depth: usize,
+        totals: &mut RunTotals,
+    ) -> Result<(TempDir, Vec<(String, PathBuf)>), anyhow::Error> {
+        let (extract_dir, files) = self.extract_files(archive_name, path, max_depth, &mut totals.warnings)?;
+        totals.progress.add_files(files.len());
         
-        // Insert records into database
-        let inserted = self.db.insert_batch(&valid_records, &metadata.filename).await?;
+        Ok((extract_dir, files))
+    }
+    
+    /// [`Self::extract_archive`] without the run: what is skipped goes to
+    /// `warnings`.
+    fn extract_files(
+        &self,
+        archive_name: &str,
+        path: &Path,
+        max_depth: usize,
+        warnings: &mut Vec<String>,
+    ) -> Result<(TempDir, Vec<(String, PathBuf)>), anyhow::Error> {
+        let extract_dir = tempfile::tempdir()?;
+        let extracted = extract_zip(path, extract_dir.path(), self.max_archive_size)?;
         
-        // Create GeoJSON features
-        let geojson_count = self.db.create_geojson_features().await?;
+        for nested in extracted.skipped {
+            warnings.push(format!("{}: skipped nested archive {}", archive_name, nested));
+        }
         
-        // Log the processing
-        self.db.log_file_processing(
-            &metadata.filename,
-            &metadata.file_hash,
-            metadata.size,
-            valid_records.len() as i32 + errors.len() as i32,
-            inserted as i32,
-            errors.len() as i32,
-            "completed",
-            None,
-        ).await?;
+        let mut found = Vec::new();
+        let files = find_data_files(extract_dir.path(), max_depth, &mut found)?;
+        warnings.extend(found.into_iter().map(|w| format!("{}: {}", archive_name, w)));
         
-        Ok(ProcessingResult {
-            records_processed: inserted as i32,
-            records_failed: errors.len() as i32,
-            geojson_features_created: geojson_count,
-            errors,
-        })
+        let files = files
+            .into_iter()
+            .map(|(file_name, file_path)| (format!("{}/{}", archive_name, file_name), file_path))
+            .collect();
+        
+        Ok((extract_dir, files))
     }
     
-    pub async fn process_single_file(
+    /// Replaces the outlines previously imported from the same layer with
+    /// `parsed`. Rejected shapes are reported as errors; they are not
+    /// records, so they do not count towards `records_failed`.
+    async fn ingest_shapefile(
         &self,
-        file_path: &str,
-        metadata: &FileMetadata,
-    ) -> Result<ProcessingResult, anyhow::Error> {
-        info!("Processing single file: {}", file_path);
+        tx: &mut Option<Transaction<'static, Postgres>>,
+        file_name: &str,
+        parsed: ParsedShapefile,
+        source_file: &str,
+        totals: &mut RunTotals,
+    ) -> Result<(), anyhow::Error> {
+        totals.progress.start_file(file_name);
+        totals.errors.extend(parsed.errors.into_iter().map(|e| ErrorDetails {
+            message: format!("{}: {}", file_name, e.message),
+            ..e
+        }));
         
-        let path = Path::new(file_path);
+        if let Some(tx) = tx {
+            self.db.replace_section_outlines(tx, layer_name(file_name), &parsed.outlines, source_file).await?;
+        }
         
-        if !path.exists() || !path.is_file() {
-            return Err(anyhow::anyhow!("File does not exist or is not a file"));
+        totals.sections += parsed.outlines.len();
+        totals.progress.finish_file();
+        Ok(())
+    }
+    
+    /// Whether content with this hash was already processed and should not be
+    /// again. A dry run is never skipped, but warns that a real run would be.
+    async fn should_skip(
+        &self,
+        file_hash: &str,
+        run: RunOptions,
+        warnings: &mut Vec<String>,
+    ) -> Result<bool, anyhow::Error> {
+        if run.force || !self.db.is_file_already_processed(file_hash).await? {
+            return Ok(false);
         }
         
-        // Parse the file
-        let records = DataParser::detect_and_parse(path)?;
+        if run.dry_run {
+            warnings.push(format!(
+                "Content with hash {} was already processed; a real run would skip it unless forced",
+                file_hash
+            ));
+            return Ok(false);
+        }
+        
+        Ok(true)
+    }
+    
+    /// The files of a directory, by name, whose content a completed run
+    /// already imported, and that this run leaves out unless forced.
+    /// Shapefiles are always read, as their hash does not cover the
+    /// companion files. A dry run reads them all, noting which a real run
+    /// would leave out.
+    async fn already_processed_files(
+        &self,
+        files: &[(String, PathBuf)],
+        file_hashes: &[(String, String)],
+        run: RunOptions,
+        warnings: &mut Vec<String>,
+    ) -> Result<Vec<String>, anyhow::Error> {
+        if run.force {
+            return Ok(Vec::new());
+        }
+        
+        let shapefiles: HashSet<&str> = files.iter()
+            .filter(|(_, path)| is_shapefile(path))
+            .map(|(name, _)| name.as_str())
+            .collect();
+        let candidates: Vec<&(String, String)> = file_hashes.iter()
+            .filter(|(name, _)| !shapefiles.contains(name.as_str()))
+            .collect();
+        if candidates.is_empty() {
+            return Ok(Vec::new());
+        }
         
-        info!("Parsed {} records", records.len());
+        let hashes: Vec<String> = candidates.iter().map(|(_, hash)| hash.clone()).collect();
+        let processed = self.db.processed_file_hashes(&hashes).await?;
+        let names: Vec<String> = candidates.into_iter()
+            .filter(|(_, hash)| processed.contains(hash))
+            .map(|(name, _)| name.clone())
+            .collect();
         
-        // Validate records
-        let mut valid_records = Vec::new();
-        let mut errors = Vec::new();
+        if names.is_empty() {
+            return Ok(names);
+        }
+        if run.dry_run {
+            warnings.push(format!(
+                "Already processed, so a real run would skip them unless forced: {}",
+                names.join(", ")
+            ));
+            return Ok(Vec::new());
+        }
+        
+        info!("Skipping {} files already processed: {}", names.len(), names.join(", "));
+        Ok(names)
+    }
+    
+    /// Totals for a new run, inserting in batches of the run's `batch_size`
+    /// or else the configured one.
+    fn run_totals(&self, options: &ParseOptions, run: RunOptions, progress: ProgressReporter) -> RunTotals {
+        let batch_size = run.batch_size.unwrap_or(self.db.batch_size());
+        info!("Inserting records in batches of {}", batch_size);
+        
+        RunTotals {
+            batch_size,
+            max_errors: run.max_errors.or(self.max_errors),
+            keep_rejected: self.keep_rejected,
+            parse_mode: run.parse_mode,
+            ..RunTotals::new(options.duplicates, options.conflict_key, run.import_mode, progress)
+        }
+    }
+    
+    /// Makes a run that writes commit at checkpoints if the run or the
+    /// processor asks for them, picking up the checkpoints an interrupted
+    /// run of the same content left.
+    async fn start_checkpoints(
+        &self,
+        tx: &Option<Transaction<'static, Postgres>>,
+        file_hash: &str,
+        run: RunOptions,
+        totals: &mut RunTotals,
+    ) -> Result<(), anyhow::Error> {
+        // A strict run must be able to roll back everything it wrote
+        let every = run
+            .checkpoint_every
+            .or(self.checkpoint_every)
+            .filter(|&every| every > 0 && run.parse_mode == ParseMode::Lenient);
+        let (Some(_), Some(every)) = (tx, every) else { return Ok(()) };
+        
+        let resume_from = self.db.checkpoints(file_hash).await?;
+        if !resume_from.is_empty() {
+            info!("Resuming hash {} from checkpoints in {} files", file_hash, resume_from.len());
+        }
+        
+        totals.checkpoints = Some(Checkpoints {
+            file_hash: file_hash.to_string(),
+            every,
+            resume_from,
+            since_commit: 0,
+            resumed: 0,
+        });
+        Ok(())
+    }
+    
+    /// Opens the transaction that makes insert, feature rebuild and log one
+    /// unit (any error drops it and rolls everything back). Dry runs get none.
+    /// Fails up front if the table has no unique index for `conflict_key`.
+    async fn begin_run(
+        &self,
+        run: RunOptions,
+        conflict_key: ConflictKey,
+    ) -> Result<Option<Transaction<'static, Postgres>>, anyhow::Error> {
+        if conflict_key != ConflictKey::RecordId && !self.db.has_unique_key(conflict_key).await? {
+            return Err(anyhow::anyhow!(
+                "conflict_key needs a unique index on deceased_records ({})",
+                conflict_key.columns().join(", ")
+            ));
+        }
+        
+        if run.dry_run {
+            Ok(None)
+        } else {
+            Ok(Some(self.db.begin().await?))
+        }
+    }
+    
+    /// Validates and inserts a record stream in chunks of the run's batch
+    /// size, so at most one chunk is held in memory. Without
+    /// a transaction (a dry run) valid records are only counted. Records of
+    /// `file_name` that an interrupted run committed are skipped.
+    async fn ingest(
+        &self,
+        tx: &mut Option<Transaction<'static, Postgres>>,
+        file_name: &str,
+        mut rows: mpsc::Receiver<Result<DeceasedRecord, ErrorDetails>>,
+        source_file: &str,
+        swapped_coordinates: SwappedCoordinates,
+        totals: &mut RunTotals,
+    ) -> Result<(), anyhow::Error> {
+        let mut chunk = Vec::with_capacity(totals.batch_size);
+        let today = chrono::Utc::now().date_naive();
+        let resume_from = totals
+            .checkpoints
+            .as_ref()
+            .and_then(|checkpoints| checkpoints.resume_from.get(file_name).copied())
+            .unwrap_or(0);
+        let mut position = 0;
         
-        for record in records {
-            match record.validate() {
-                Ok(()) => {
-                    valid_records.push(record);
+        while let Some(row) = rows.recv().await {
+            // Records waiting in the chunk have not failed
+            totals.check_error_limit(totals.failed() - chunk.len(), false)?;
+            position += 1;
+            totals.progress.record_read();
+            
+            if let Some(checkpoints) = totals.checkpoints.as_mut() {
+                if position <= resume_from {
+                    checkpoints.resumed += 1;
+                    continue;
                 }
-                Err(e) => {
-                    warn!("Validation failed for record {}: {}", record.record_id, e);
-                    errors.push(ErrorDetails {
-                        record_id: Some(record.record_id.clone()),
-                        message: e,
-                    });
+                checkpoints.since_commit += 1;
+            }
+            totals.records_total += 1;
+            
+            let mut record = match row {
+                Ok(record) => record,
+                Err(mut e) => {
+                    let fields = e.source_fields.take().map(|fields| *fields);
+                    totals.reject(file_name, fields, std::slice::from_ref(&e));
+                    totals.errors.push(e);
+                    continue;
                 }
+            };
+            
+            let (problems, warnings) = self.check_record(&mut record, swapped_coordinates, totals.dedup.key, today);
+            
+            if !problems.is_empty() {
+                let messages: Vec<String> = problems.iter().map(|e| e.to_string()).collect();
+                warn!("Validation failed for record {}: {}", record.record_id, messages.join("; "));
+                let errors: Vec<ErrorDetails> =
+                    problems.iter().map(|e| ErrorDetails::from_validation(&record.record_id, e)).collect();
+                totals.reject(file_name, serde_json::to_value(&record).ok(), &errors);
+                totals.errors.extend(errors);
+                continue;
+            }
+            
+            totals.warnings.extend(
+                warnings.iter().map(|warning| format!("Record {}: {}", record.record_id, warning))
+            );
+            if record.has_coordinates() {
+                totals.with_coordinates += 1;
+            }
+            
+            totals.dedup.push(&mut chunk, record);
+            
+            if chunk.len() >= totals.batch_size {
+                self.insert_chunk(&mut tx.as_mut(), &mut chunk, file_name, source_file, totals).await?;
+                self.checkpoint(tx, file_name, position, false, totals).await?;
             }
         }
         
-        // Insert into database
-        let inserted = self.db.insert_batch(&valid_records, &metadata.filename).await?;
+        if !chunk.is_empty() {
+            self.insert_chunk(&mut tx.as_mut(), &mut chunk, file_name, source_file, totals).await?;
+        }
+        self.flush_rejected(&mut tx.as_mut(), source_file, totals).await?;
+        if position > resume_from {
+            self.checkpoint(tx, file_name, position, true, totals).await?;
+        }
         
-        // Create GeoJSON features
-        let geojson_count = self.db.create_geojson_features().await?;
+        Ok(())
+    }
+    
+    /// Once a checkpoint's worth of records has been read since the last
+    /// commit, notes that the first `records_done` records of `file_name`
+    /// are written, updates their features and commits. The end of a file
+    /// is always noted, and committed with what follows. Does nothing in
+    /// runs without checkpoints.
+    async fn checkpoint(
+        &self,
+        tx: &mut Option<Transaction<'static, Postgres>>,
+        file_name: &str,
+        records_done: usize,
+        end_of_file: bool,
+        totals: &mut RunTotals,
+    ) -> Result<(), anyhow::Error> {
+        let (Some(open), Some(checkpoints)) = (tx.as_mut(), totals.checkpoints.as_mut()) else {
+            return Ok(());
+        };
+        
+        let due = checkpoints.since_commit >= checkpoints.every;
+        if !due && !end_of_file {
+            return Ok(());
+        }
+        
+        self.db.save_checkpoint(open, &checkpoints.file_hash, file_name, records_done).await?;
+        if !due {
+            return Ok(());
+        }
+        checkpoints.since_commit = 0;
+        
+        let touched = &totals.touched[totals.features_done..];
+        let counts = self.db.update_geojson_features(open, touched, self.derive_coordinates).await?;
+        totals.features.written += counts.written;
+        totals.features.skipped_invalid += counts.skipped_invalid;
+        totals.features_done = totals.touched.len();
         
-        // Log the processing
-        self.db.log_file_processing(
+        if let Some(committed) = tx.take() {
+            committed.commit().await?;
+        }
+        *tx = Some(self.db.begin().await?);
+        
+        info!("Checkpoint: {} records of {} committed", records_done, file_name);
+        Ok(())
+    }
+    
+    /// Fixes swapped coordinates in `record`, codes its cause of death and
+    /// checks it as an import does, including that it has a value for
+    /// `conflict_key`. Returns the
+    /// problems that fail it and the warnings it would be written with.
+    fn check_record(
+        &self,
+        record: &mut DeceasedRecord,
+        swapped_coordinates: SwappedCoordinates,
+        conflict_key: ConflictKey,
+        today: NaiveDate,
+    ) -> (Vec<ValidationError>, Vec<String>) {
+        let mut problems = match record.fix_swapped_coordinates(self.bounds.as_ref(), swapped_coordinates) {
+            Ok(()) => record.validate_with_bounds(self.bounds.as_ref()).err().unwrap_or_default(),
+            Err(e) => vec![e],
+        };
+        problems.extend(conflict_key.missing_field(record).map(ValidationError::MissingField));
+        problems.extend(record.check_date_limits(&self.date_limits, today));
+        
+        let mut national_id = self.national_ids.check(record.national_id.as_deref());
+        if self.national_ids.mode() == NationalIdMode::Reject {
+            problems.extend(national_id.take());
+        }
+        let flagged = national_id.is_some();
+        
+        let mut warnings: Vec<String> = record.age_discrepancy().into_iter()
+            .chain(record.location_conflict())
+            .chain(national_id.map(|problem| problem.to_string()))
+            .collect();
+        
+        // Causes outside the vocabulary are stored as written, for review
+        if let (Some(vocabulary), Some(cause)) = (&self.causes, record.cause_of_death.as_deref()) {
+            record.cause_of_death_code = vocabulary.normalize(cause).map(|entry| entry.code.clone());
+            if record.cause_of_death_code.is_none() {
+                warnings.push(format!("cause_of_death '{}' is not in the vocabulary", cause));
+            }
+        }
+        
+        // Records the map cannot place wait for review, unless their plot
+        // or section may place them once features are built
+        let unplaced = !record.has_coordinates() && record.footprint.is_none() && !self.derive_coordinates;
+        record.processing_status = if flagged {
+            RecordStatus::Flagged
+        } else if !warnings.is_empty() || unplaced {
+            RecordStatus::NeedsReview
+        } else {
+            RecordStatus::Completed
+        };
+        
+        (problems, warnings)
+    }
+    
+    /// Writes the rejected records held so far, in the run's transaction so
+    /// they go with it. A dry run writes none.
+    async fn flush_rejected(
+        &self,
+        tx: &mut Option<&mut Transaction<'static, Postgres>>,
+        source_file: &str,
+        totals: &mut RunTotals,
+    ) -> Result<(), anyhow::Error> {
+        if let (Some(tx), false) = (tx.as_deref_mut(), totals.rejected.is_empty()) {
+            self.db.insert_rejected_records(tx, source_file, &totals.rejected).await?;
+        }
+        totals.rejected.clear();
+        Ok(())
+    }
+    
+    async fn insert_chunk(
+        &self,
+        tx: &mut Option<&mut Transaction<'static, Postgres>>,
+        chunk: &mut Vec<DeceasedRecord>,
+        file_name: &str,
+        source_file: &str,
+        totals: &mut RunTotals,
+    ) -> Result<(), anyhow::Error> {
+        self.flush_rejected(tx, source_file, totals).await?;
+        if totals.dedup.key != ConflictKey::RecordId {
+            let stored = self.db.record_ids_by_key(tx.as_deref_mut(), totals.dedup.key, chunk).await?;
+            totals.dedup.adopt_stored_ids(chunk, &stored);
+        }
+        let (fresh, repeats) = totals.dedup.take(chunk);
+        
+        let ids: Vec<String> = fresh.iter().map(|record| record.record_id.clone()).collect();
+        let existing = self.db.existing_record_ids(tx.as_deref_mut(), &ids).await?;
+        
+        let mode = totals.import_mode;
+        let (fresh, skipped): (Vec<_>, Vec<_>) = fresh
+            .into_iter()
+            .partition(|record| mode.writes(existing.contains(&record.record_id)));
+        totals.skipped += skipped.len();
+        totals.dedup.forget(skipped.iter().map(|record| &record.record_id));
+        
+        let mut updates = fresh.iter().filter(|record| existing.contains(&record.record_id)).count();
+        
+        match tx {
+            Some(tx) => {
+                let db = self.db.clone().with_batch_size(totals.batch_size).with_conflict_key(totals.dedup.key);
+                totals.touched.extend(fresh.iter().chain(&repeats).map(|record| record.record_id.clone()));
+                
+                let _timer = self.metrics.db_insert_duration.start_timer();
+                
+                let batch = db.insert_batch_with(tx, &fresh, source_file, mode.conflict_action()).await?;
+                let rejected: HashSet<&String> = batch.errors.iter().filter_map(|e| e.record_id.as_ref()).collect();
+                updates -= rejected.iter().filter(|&&record_id| existing.contains(record_id)).count();
+                
+                totals.updated += updates;
+                totals.inserted += batch.inserted.saturating_sub(updates);
+                // Rows an insert-only import found in place after all
+                totals.skipped += fresh.len() - rejected.len() - batch.inserted;
+                totals.dedup.forget(rejected);
+                for error in &batch.errors {
+                    let record = fresh.iter().find(|record| error.record_id.as_ref() == Some(&record.record_id));
+                    let fields = record.and_then(|record| serde_json::to_value(record).ok());
+                    totals.reject(file_name, fields, std::slice::from_ref(error));
+                }
+                totals.errors.extend(batch.errors);
+                
+                if !repeats.is_empty() {
+                    let action = totals.dedup.repeat_action();
+                    let batch = db.insert_batch_with(tx, &repeats, source_file, action).await?;
+                    totals.errors.extend(batch.errors);
+                }
+            }
+            None => {
+                totals.updated += updates;
+                totals.inserted += fresh.len() - updates;
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Rebuilds the feature table, logs the run and commits. A dry run just
+    /// reports what it would have written.
+    async fn finish_run(
+        &self,
+        tx: Option<Transaction<'static, Postgres>>,
+        metadata: &FileMetadata,
+        file_hash: &str,
+        mut totals: RunTotals,
+        mut warnings: Vec<String>,
+        run: RunOptions,
+    ) -> Result<ProcessingResult, anyhow::Error> {
+        totals.check_error_limit(totals.failed(), true)?;
+        warnings.append(&mut totals.warnings);
+        let records_resumed = totals.checkpoints.as_ref().map_or(0, |checkpoints| checkpoints.resumed);
+        
+        info!("Total records read: {}", totals.records_total);
+        if records_resumed > 0 {
+            info!("Records committed by an interrupted run: {}", records_resumed);
+        }
+        info!(
+            "Records inserted: {}, updated: {}, skipped: {}",
+            totals.inserted, totals.updated, totals.skipped
+        );
+        info!("Records failed: {}", totals.failed());
+        info!("Records with coordinates: {}", totals.with_coordinates);
+        info!("Duplicate records collapsed: {}", totals.dedup.collapsed);
+        info!("Section outlines imported: {}", totals.sections);
+        
+        let Some(mut tx) = tx else {
+            info!("Dry run for {}: nothing written", metadata.filename);
+            
+            return Ok(ProcessingResult {
+                records_read: totals.records_total as i32,
+                records_processed: totals.written() as i32,
+                records_inserted: totals.inserted as i32,
+                records_updated: totals.updated as i32,
+                records_skipped: totals.skipped as i32,
+                records_failed: totals.failed() as i32,
+                records_with_coordinates: totals.with_coordinates as i32,
+                geojson_features_created: 0,
+                geojson_features_skipped: 0,
+                errors: totals.errors,
+                warnings,
+                already_processed: false,
+                dry_run: true,
+                duplicates_collapsed: totals.dedup.collapsed as i32,
+                possible_duplicates: totals.dedup.possible_duplicates,
+                files: totals.files,
+                sections_imported: totals.sections as i32,
+                resumed: false,
+                records_resumed: 0,
+                parse_mode: run.parse_mode,
+                files_already_processed: totals.files_already_processed,
+            });
+        };
+        
+        // Flag national ids this run shares with other live records, and
+        // records that may duplicate one another, for review
+        let mut flagged: Vec<String> = totals.dedup.possible_duplicates
+            .iter()
+            .flat_map(|possible| [possible.record_id.clone(), possible.matches_record_id.clone()])
+            .collect();
+        if !totals.touched.is_empty() {
+            let shared = self.db.shared_national_ids(Some(&mut tx), Some(&totals.touched), None, 0).await?;
+            warnings.extend(shared.iter().map(|shared| {
+                format!(
+                    "national_id {} is shared by records {}",
+                    shared.national_id,
+                    shared.record_ids.join(", ")
+                )
+            }));
+            flagged.extend(shared.into_iter().flat_map(|shared| shared.record_ids));
+        }
+        if !flagged.is_empty() {
+            let count = self.db.set_processing_status(&mut tx, &flagged, RecordStatus::Flagged).await?;
+            info!("Records flagged for review: {}", count);
+        }
+        
+        // Update the map layer. New outlines can move any derived feature,
+        // so they call for a full rebuild. Flagged records written by
+        // earlier runs come off it
+        let rebuild = run.rebuild_features || (self.derive_coordinates && totals.sections > 0);
+        let features = if rebuild {
+            info!("Rebuilding all map features");
+            self.db.create_geojson_features(&mut tx, self.derive_coordinates).await?
+        } else {
+            let mut record_ids = totals.touched[totals.features_done..].to_vec();
+            record_ids.extend(flagged);
+            let counts = self.db.update_geojson_features(&mut tx, &record_ids, self.derive_coordinates).await?;
+            FeatureCounts {
+                written: totals.features.written + counts.written,
+                skipped_invalid: totals.features.skipped_invalid + counts.skipped_invalid,
+            }
+        };
+        if features.skipped_invalid > 0 {
+            warnings.push(format!(
+                "{} records have invalid or empty geometry and were left off the map",
+                features.skipped_invalid
+            ));
+        }
+        
+        // Records neither their coordinates nor their plot or section could place
+        if self.derive_coordinates && !totals.touched.is_empty() {
+            let unplaced = self.db.mark_geocode_failed(&mut tx, &totals.touched).await?;
+            if unplaced > 0 {
+                warnings.push(format!("{} records could not be placed on the map and need review", unplaced));
+            }
+        }
+        
+        // Suggest fathers and children for the records this run wrote
+        if !totals.touched.is_empty() {
+            let candidates = self.db.family_candidates(&mut tx, &totals.touched).await?;
+            let links = suggest_links(&candidates);
+            self.db.replace_family_links(&mut tx, &totals.touched, &links).await?;
+            info!("Family links suggested: {}", links.len());
+        }
+        
+        let download_time = parse_timestamp(&metadata.download_time, self.timezone)
+            .map_err(|e| warnings.push(format!("download_time not recorded: {}", e)))
+            .ok();
+        
+        // Log the processing. Losing the race on the hash to a concurrent run
+        // rolls this one back (the transaction is dropped uncommitted)
+        let logged = self.db.log_file_processing(
+            &mut tx,
             &metadata.filename,
-            &metadata.file_hash,
+            file_hash,
             metadata.size,
-            valid_records.len() as i32 + errors.len() as i32,
-            inserted as i32,
-            errors.len() as i32,
+            download_time,
+            totals.records_total as i32,
+            totals.written() as i32,
+            totals.failed() as i32,
+            totals.with_coordinates as i32,
             "completed",
             None,
+            run.force,
         ).await?;
         
+        if !logged {
+            info!("Hash {} was processed concurrently; discarding this run", file_hash);
+            return Ok(ProcessingResult {
+                parse_mode: run.parse_mode,
+                ..ProcessingResult::already_processed(file_hash, warnings)
+            });
+        }
+        
+        if totals.checkpoints.is_some() {
+            self.db.clear_checkpoints(&mut tx, file_hash).await?;
+        }
+        self.db.record_processed_files(&mut tx, &metadata.filename, &totals.file_hashes).await?;
+        
+        tx.commit().await?;
+        
+        self.metrics.files_processed.inc_by(totals.files.len() as u64);
+        self.metrics.records_inserted.inc_by(totals.written() as u64);
+        self.metrics.records_failed.inc_by(totals.failed() as u64);
+        
         Ok(ProcessingResult {
-            records_processed: inserted as i32,
-            records_failed: errors.len() as i32,
-            geojson_features_created: geojson_count,
-            errors,
+            records_read: totals.records_total as i32,
+            records_processed: totals.written() as i32,
+            records_inserted: totals.inserted as i32,
+            records_updated: totals.updated as i32,
+            records_skipped: totals.skipped as i32,
+            records_failed: totals.failed() as i32,
+            records_with_coordinates: totals.with_coordinates as i32,
+            geojson_features_created: features.written,
+            geojson_features_skipped: features.skipped_invalid,
+            errors: totals.errors,
+            warnings,
+            already_processed: false,
+            dry_run: false,
+            duplicates_collapsed: totals.dedup.collapsed as i32,
+            possible_duplicates: totals.dedup.possible_duplicates,
+            files: totals.files,
+            sections_imported: totals.sections as i32,
+            resumed: records_resumed > 0,
+            records_resumed: records_resumed as i32,
+            parse_mode: run.parse_mode,
+            files_already_processed: totals.files_already_processed,
         })
     }
 }
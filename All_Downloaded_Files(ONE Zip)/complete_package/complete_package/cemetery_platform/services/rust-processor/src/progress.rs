@@ -0,0 +1,138 @@
+use serde::Serialize;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use utoipa::ToSchema;
+
+/// Shortest time between two progress updates while records are being read.
+/// Starting and finishing a file always publishes.
+pub const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How far a processing run has got.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, ToSchema)]
+pub struct JobProgress {
+    /// Files to read, including archives; an archive's contents are added
+    /// once it is extracted
+    pub files_total: usize,
+    pub files_done: usize,
+    pub current_file: Option<String>,
+    /// Records read so far, whether they were written or failed
+    pub records_done: usize,
+    /// Estimate from the line counts of the run's CSV files; unknown when
+    /// the run includes other kinds of file
+    pub records_total: Option<usize>,
+}
+
+impl JobProgress {
+    /// Share of the run done, from 0 to 100: by records when their total is
+    /// known, otherwise by files.
+    pub fn percent(&self) -> Option<f64> {
+        let (done, total) = match self.records_total {
+            Some(total) if total > 0 => (self.records_done, total),
+            _ => (self.files_done, self.files_total),
+        };
+        
+        (total > 0).then(|| (100.0 * done as f64 / total as f64).min(100.0))
+    }
+}
+
+/// Publishes a run's progress to a `watch` channel as the processor works
+/// through it. A reporter nobody watches costs next to nothing.
+pub struct ProgressReporter {
+    sender: Arc<watch::Sender<JobProgress>>,
+    progress: JobProgress,
+    last_sent: Option<Instant>,
+}
+
+impl Default for ProgressReporter {
+    fn default() -> Self {
+        Self::new(Arc::new(watch::channel(JobProgress::default()).0))
+    }
+}
+
+impl ProgressReporter {
+    pub fn new(sender: Arc<watch::Sender<JobProgress>>) -> Self {
+        Self { sender, progress: JobProgress::default(), last_sent: None }
+    }
+    
+    pub fn progress(&self) -> &JobProgress {
+        &self.progress
+    }
+    
+    /// Starts over for a run of `files_total` files, replacing what an
+    /// earlier attempt reported.
+    pub fn start(&mut self, files_total: usize, records_total: Option<usize>) {
+        self.progress = JobProgress { files_total, records_total, ..JobProgress::default() };
+        self.publish();
+    }
+    
+    pub fn start_file(&mut self, name: &str) {
+        self.progress.current_file = Some(name.to_string());
+        self.publish();
+    }
+    
+    /// Counts a record read, publishing if [`PROGRESS_INTERVAL`] has passed.
+    pub fn record_read(&mut self) {
+        self.progress.records_done += 1;
+        
+        if self.last_sent.is_none_or(|sent| sent.elapsed() >= PROGRESS_INTERVAL) {
+            self.publish();
+        }
+    }
+    
+    /// Adds the files found in an extracted archive. Their records were not
+    /// part of the estimate, so it no longer holds.
+    pub fn add_files(&mut self, files: usize) {
+        self.progress.files_total += files;
+        if files > 0 {
+            self.progress.records_total = None;
+        }
+    }
+    
+    pub fn finish_file(&mut self) {
+        self.progress.files_done += 1;
+        self.progress.current_file = None;
+        self.publish();
+    }
+    
+    fn publish(&mut self) {
+        self.sender.send_replace(self.progress.clone());
+        self.last_sent = Some(Instant::now());
+    }
+}
+
+/// Estimates the records in `files` from the lines of each CSV or TSV file,
+/// less its header. `None` if any other kind of file is included, as their
+/// records cannot be counted without parsing them.
+pub fn estimate_records(files: &[(String, PathBuf)]) -> Option<usize> {
+    files.iter().try_fold(0, |total, (_, path)| {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        if extension != "csv" && extension != "tsv" {
+            return None;
+        }
+        
+        let lines = count_lines(path).ok()?;
+        Some(total + lines.saturating_sub(1))
+    })
+}
+
+/// Lines in a file, counting a last line without a line break.
+fn count_lines(path: &Path) -> std::io::Result<usize> {
+    let mut file = std::fs::File::open(path)?;
+    let mut buffer = vec![0; 64 * 1024];
+    let mut lines = 0;
+    let mut last = b'\n';
+    
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        lines += buffer[..read].iter().filter(|&&byte| byte == b'\n').count();
+        last = buffer[read - 1];
+    }
+    
+    Ok(lines + usize::from(last != b'\n'))
+}
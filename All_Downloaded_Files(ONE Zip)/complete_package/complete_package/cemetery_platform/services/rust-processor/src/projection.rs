@@ -0,0 +1,185 @@
+use serde::{Deserialize, Serialize};
+use std::f64::consts::FRAC_PI_2;
+
+/// WGS 84 semi-major axis, metres.
+const WGS84_A: f64 = 6_378_137.0;
+
+/// WGS 84 flattening.
+const WGS84_F: f64 = 1.0 / 298.257_223_563;
+
+const UTM_SCALE: f64 = 0.9996;
+const UTM_FALSE_EASTING: f64 = 500_000.0;
+const UTM_FALSE_NORTHING_SOUTH: f64 = 10_000_000.0;
+
+/// A coordinate reference system that can be converted to WGS 84
+/// longitude/latitude (EPSG:4326). In requests it is named by its EPSG
+/// code, e.g. `"EPSG:32638"`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Projection {
+    /// Longitude/latitude on WGS 84; nothing to convert
+    Wgs84,
+    /// WGS 84 / UTM, e.g. zone 38N (EPSG:32638), which covers Najaf
+    Utm { zone: u8, north: bool },
+    /// WGS 84 / Pseudo-Mercator (EPSG:3857), used by web maps
+    WebMercator,
+}
+
+impl Projection {
+    /// Recognises the coordinate system in a shapefile's `.prj` (ESRI WKT).
+    ///
+    /// Only WGS 84 based systems are supported: converting from another
+    /// datum, such as Karbala 1979, needs a datum shift this service does
+    /// not implement, and skipping it would misplace outlines by hundreds of
+    /// metres.
+    pub fn from_wkt(wkt: &str) -> Result<Self, anyhow::Error> {
+        let normalized = wkt.trim().to_uppercase().replace([' ', '-'], "_");
+        let name = wkt.split('"').nth(1).unwrap_or(wkt.trim());
+        
+        let is_wgs84 = ["WGS_1984", "WGS_84", "WGS84"].iter().any(|datum| normalized.contains(datum));
+        if !is_wgs84 {
+            return Err(anyhow::anyhow!(
+                "Unsupported coordinate system '{}': only WGS 84 based systems can be converted",
+                name
+            ));
+        }
+        
+        if normalized.starts_with("GEOGCS") {
+            return Ok(Projection::Wgs84);
+        }
+        
+        if let Some(zone) = normalized.split("UTM_ZONE_").nth(1) {
+            let digits: String = zone.chars().take_while(|c| c.is_ascii_digit()).collect();
+            let hemisphere = zone[digits.len()..].chars().next();
+            
+            if let (Ok(zone @ 1..=60), Some(hemisphere @ ('N' | 'S'))) = (digits.parse::<u8>(), hemisphere) {
+                return Ok(Projection::Utm { zone, north: hemisphere == 'N' });
+            }
+        }
+        
+        let web_mercator = ["MERCATOR_AUXILIARY_SPHERE", "PSEUDO_MERCATOR", "WEB_MERCATOR", "POPULAR_VISUALISATION"];
+        if web_mercator.iter().any(|marker| normalized.contains(marker)) {
+            return Ok(Projection::WebMercator);
+        }
+        
+        Err(anyhow::anyhow!(
+            "Unsupported coordinate system '{}': expected WGS 84, WGS 84 / UTM or Web Mercator",
+            name
+        ))
+    }
+    
+    /// The system's EPSG code.
+    pub fn epsg(&self) -> u32 {
+        match *self {
+            Projection::Wgs84 => 4326,
+            Projection::Utm { zone, north: true } => 32600 + u32::from(zone),
+            Projection::Utm { zone, north: false } => 32700 + u32::from(zone),
+            Projection::WebMercator => 3857,
+        }
+    }
+    
+    /// Converts `(x, y)` in this system to `(longitude, latitude)` in degrees.
+    pub fn to_wgs84(&self, x: f64, y: f64) -> (f64, f64) {
+        match *self {
+            Projection::Wgs84 => (x, y),
+            Projection::Utm { zone, north } => utm_to_wgs84(zone, north, x, y),
+            Projection::WebMercator => (
+                (x / WGS84_A).to_degrees(),
+                (2.0 * (y / WGS84_A).exp().atan() - FRAC_PI_2).to_degrees(),
+            ),
+        }
+    }
+}
+
+impl std::str::FromStr for Projection {
+    type Err = String;
+    
+    /// Reads an EPSG code such as `EPSG:32638`: 4326, 3857, or a WGS 84 /
+    /// UTM zone (326xx north, 327xx south).
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let trimmed = value.trim();
+        let code = trimmed
+            .get(..5)
+            .filter(|prefix| prefix.eq_ignore_ascii_case("EPSG:"))
+            .map_or(trimmed, |_| &trimmed[5..]);
+        
+        let projection = match code.trim().parse::<u32>() {
+            Ok(4326) => Some(Projection::Wgs84),
+            Ok(3857) => Some(Projection::WebMercator),
+            Ok(code @ 32601..=32660) => Some(Projection::Utm { zone: (code - 32600) as u8, north: true }),
+            Ok(code @ 32701..=32760) => Some(Projection::Utm { zone: (code - 32700) as u8, north: false }),
+            _ => None,
+        };
+        
+        projection.ok_or_else(|| {
+            format!(
+                "Unsupported coordinate system '{}': expected EPSG:4326, EPSG:3857 or a WGS 84 / UTM zone such as EPSG:32638",
+                value
+            )
+        })
+    }
+}
+
+impl TryFrom<String> for Projection {
+    type Error = String;
+    
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl std::fmt::Display for Projection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "EPSG:{}", self.epsg())
+    }
+}
+
+impl From<Projection> for String {
+    fn from(projection: Projection) -> Self {
+        projection.to_string()
+    }
+}
+
+/// Inverse transverse Mercator on the WGS 84 ellipsoid (Snyder, "Map
+/// Projections: A Working Manual", eqs. 8-18 to 8-25), accurate to well
+/// under a metre within a zone.
+fn utm_to_wgs84(zone: u8, north: bool, easting: f64, northing: f64) -> (f64, f64) {
+    let e2 = WGS84_F * (2.0 - WGS84_F);
+    let ep2 = e2 / (1.0 - e2);
+    let central_meridian = (f64::from(zone) * 6.0 - 183.0).to_radians();
+    
+    let x = easting - UTM_FALSE_EASTING;
+    let y = if north { northing } else { northing - UTM_FALSE_NORTHING_SOUTH };
+    
+    // Footpoint latitude from the meridian distance
+    let m = y / UTM_SCALE;
+    let mu = m / (WGS84_A * (1.0 - e2 / 4.0 - 3.0 * e2.powi(2) / 64.0 - 5.0 * e2.powi(3) / 256.0));
+    let e1 = (1.0 - (1.0 - e2).sqrt()) / (1.0 + (1.0 - e2).sqrt());
+    let phi1 = mu
+        + (3.0 * e1 / 2.0 - 27.0 * e1.powi(3) / 32.0) * (2.0 * mu).sin()
+        + (21.0 * e1.powi(2) / 16.0 - 55.0 * e1.powi(4) / 32.0) * (4.0 * mu).sin()
+        + (151.0 * e1.powi(3) / 96.0) * (6.0 * mu).sin()
+        + (1097.0 * e1.powi(4) / 512.0) * (8.0 * mu).sin();
+    
+    let (sin_phi1, cos_phi1) = phi1.sin_cos();
+    let c1 = ep2 * cos_phi1.powi(2);
+    let t1 = phi1.tan().powi(2);
+    let n1 = WGS84_A / (1.0 - e2 * sin_phi1.powi(2)).sqrt();
+    let r1 = WGS84_A * (1.0 - e2) / (1.0 - e2 * sin_phi1.powi(2)).powf(1.5);
+    let d = x / (n1 * UTM_SCALE);
+    
+    let latitude = phi1
+        - (n1 * phi1.tan() / r1)
+            * (d.powi(2) / 2.0
+                - (5.0 + 3.0 * t1 + 10.0 * c1 - 4.0 * c1.powi(2) - 9.0 * ep2) * d.powi(4) / 24.0
+                + (61.0 + 90.0 * t1 + 298.0 * c1 + 45.0 * t1.powi(2) - 252.0 * ep2 - 3.0 * c1.powi(2))
+                    * d.powi(6)
+                    / 720.0);
+    let longitude = central_meridian
+        + (d - (1.0 + 2.0 * t1 + c1) * d.powi(3) / 6.0
+            + (5.0 - 2.0 * c1 + 28.0 * t1 - 3.0 * c1.powi(2) + 8.0 * ep2 + 24.0 * t1.powi(2)) * d.powi(5)
+                / 120.0)
+            / cos_phi1;
+    
+    (longitude.to_degrees(), latitude.to_degrees())
+}
@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Requests per minute allowed per client when `RATE_LIMIT_PER_MINUTE` is
+/// not set.
+pub const DEFAULT_REQUESTS_PER_MINUTE: u32 = 300;
+
+/// Clients tracked before idle ones are forgotten.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Per-client token buckets. Each client may make up to a minute's worth of
+/// requests in a burst, after which tokens come back at the configured rate.
+pub struct RateLimiter {
+    capacity: f64,
+    tokens_per_second: f64,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_minute: u32) -> Self {
+        Self {
+            capacity: f64::from(requests_per_minute),
+            tokens_per_second: f64::from(requests_per_minute) / 60.0,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+    
+    /// Reads the limit from `RATE_LIMIT_PER_MINUTE`, falling back to
+    /// [`DEFAULT_REQUESTS_PER_MINUTE`]. 0 turns limiting off.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let requests_per_minute = match std::env::var("RATE_LIMIT_PER_MINUTE") {
+            Ok(value) => value.trim().parse::<u32>().map_err(|_| {
+                format!("RATE_LIMIT_PER_MINUTE must be a non-negative integer, got '{}'", value)
+            })?,
+            Err(_) => DEFAULT_REQUESTS_PER_MINUTE,
+        };
+        
+        Ok((requests_per_minute > 0).then(|| Self::new(requests_per_minute)))
+    }
+    
+    pub fn requests_per_minute(&self) -> u32 {
+        self.capacity as u32
+    }
+    
+    /// Takes a token for `client` at `now`. When none is left, returns how
+    /// long until the next one.
+    pub fn check(&self, client: &str, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        
+        if buckets.len() >= PRUNE_THRESHOLD && !buckets.contains_key(client) {
+            // A bucket that has refilled completely is the same as a new one
+            buckets.retain(|_, bucket| self.refilled(bucket, now) < self.capacity);
+        }
+        
+        let bucket = buckets.entry(client.to_string()).or_insert(Bucket {
+            tokens: self.capacity,
+            updated: now,
+        });
+        bucket.tokens = self.refilled(bucket, now);
+        bucket.updated = now;
+        
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.tokens_per_second))
+        }
+    }
+    
+    fn refilled(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        (bucket.tokens + elapsed * self.tokens_per_second).min(self.capacity)
+    }
+}
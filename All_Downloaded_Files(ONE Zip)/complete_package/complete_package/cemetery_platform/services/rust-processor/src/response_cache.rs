@@ -0,0 +1,148 @@
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Seconds a cached response is served when `RESPONSE_CACHE_TTL_SECONDS` is
+/// not set.
+pub const DEFAULT_RESPONSE_CACHE_TTL_SECONDS: u64 = 60;
+
+/// Responses kept when `RESPONSE_CACHE_MAX_ENTRIES` is not set.
+pub const DEFAULT_RESPONSE_CACHE_MAX_ENTRIES: usize = 1000;
+
+/// A response body as it was computed, with the entity tag that names it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedResponse {
+    pub body: String,
+    /// Quoted, as the `ETag` header carries it
+    pub etag: String,
+}
+
+impl CachedResponse {
+    pub fn new(body: String) -> Self {
+        let etag = format!("\"{:x}\"", Sha256::digest(body.as_bytes()));
+        Self { body, etag }
+    }
+    
+    /// Whether an `If-None-Match` header value names this response.
+    pub fn matches(&self, if_none_match: &str) -> bool {
+        if_none_match
+            .split(',')
+            .map(|tag| tag.trim().trim_start_matches("W/"))
+            .any(|tag| tag == "*" || tag == self.etag)
+    }
+}
+
+/// One key's response. Its lock is held while the response is computed, so
+/// concurrent requests for the key wait for one computation rather than
+/// each running it.
+#[derive(Default)]
+struct Slot {
+    value: tokio::sync::Mutex<Option<(Instant, Arc<CachedResponse>)>>,
+}
+
+/// Computed responses of read endpoints by key, each served until it is
+/// `ttl` old or [`ResponseCache::invalidate`] is called.
+pub struct ResponseCache {
+    ttl: Duration,
+    max_entries: usize,
+    slots: Mutex<HashMap<String, (Instant, Arc<Slot>)>>,
+    /// Bumped by every invalidation, so a response computed from data read
+    /// before one is not kept after it
+    generation: AtomicU64,
+}
+
+impl ResponseCache {
+    pub fn new(ttl: Duration, max_entries: usize) -> Self {
+        Self {
+            ttl,
+            max_entries: max_entries.max(1),
+            slots: Mutex::new(HashMap::new()),
+            generation: AtomicU64::new(0),
+        }
+    }
+    
+    /// Reads the lifetime of a response from `RESPONSE_CACHE_TTL_SECONDS`
+    /// and how many are kept from `RESPONSE_CACHE_MAX_ENTRIES`, falling back
+    /// to [`DEFAULT_RESPONSE_CACHE_TTL_SECONDS`] and
+    /// [`DEFAULT_RESPONSE_CACHE_MAX_ENTRIES`]. A lifetime of 0 turns caching
+    /// off.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let ttl = match std::env::var("RESPONSE_CACHE_TTL_SECONDS") {
+            Ok(value) => value.trim().parse::<u64>().map_err(|_| {
+                format!("RESPONSE_CACHE_TTL_SECONDS must be a non-negative integer, got '{}'", value)
+            })?,
+            Err(_) => DEFAULT_RESPONSE_CACHE_TTL_SECONDS,
+        };
+        let max_entries = match std::env::var("RESPONSE_CACHE_MAX_ENTRIES") {
+            Ok(value) => match value.trim().parse::<usize>() {
+                Ok(entries) if entries > 0 => entries,
+                _ => return Err(format!("RESPONSE_CACHE_MAX_ENTRIES must be a positive integer, got '{}'", value)),
+            },
+            Err(_) => DEFAULT_RESPONSE_CACHE_MAX_ENTRIES,
+        };
+        
+        Ok((ttl > 0).then(|| Self::new(Duration::from_secs(ttl), max_entries)))
+    }
+    
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+    
+    pub fn max_entries(&self) -> usize {
+        self.max_entries
+    }
+    
+    /// The response for `key`, computing it with `compute` unless a fresh
+    /// one is kept. Failures are returned and not kept.
+    pub async fn get_or_try_insert<F, Fut, E>(&self, key: &str, compute: F) -> Result<Arc<CachedResponse>, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<String, E>>,
+    {
+        let slot = self.slot(key, Instant::now());
+        let mut value = slot.value.lock().await;
+        
+        if let Some((computed_at, response)) = value.as_ref() {
+            if computed_at.elapsed() < self.ttl {
+                return Ok(response.clone());
+            }
+        }
+        
+        let generation = self.generation.load(Ordering::Acquire);
+        let computed_at = Instant::now();
+        let response = Arc::new(CachedResponse::new(compute().await?));
+        
+        if self.generation.load(Ordering::Acquire) == generation {
+            *value = Some((computed_at, response.clone()));
+        }
+        Ok(response)
+    }
+    
+    /// Drops every kept response, as after an import changes the data.
+    pub fn invalidate(&self) {
+        let mut slots = self.slots.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        slots.clear();
+    }
+    
+    /// The slot for `key`, making room for it by dropping the oldest one
+    /// once `max_entries` are kept.
+    fn slot(&self, key: &str, now: Instant) -> Arc<Slot> {
+        let mut slots = self.slots.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        
+        if slots.len() >= self.max_entries && !slots.contains_key(key) {
+            slots.retain(|_, (created, _)| now.saturating_duration_since(*created) < self.ttl);
+            if slots.len() >= self.max_entries {
+                let oldest = slots.iter().min_by_key(|(_, (created, _))| *created).map(|(key, _)| key.clone());
+                if let Some(oldest) = oldest {
+                    slots.remove(&oldest);
+                }
+            }
+        }
+        
+        slots.entry(key.to_string()).or_insert_with(|| (now, Arc::new(Slot::default()))).1.clone()
+    }
+}
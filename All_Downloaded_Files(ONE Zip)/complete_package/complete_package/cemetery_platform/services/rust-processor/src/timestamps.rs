@@ -0,0 +1,47 @@
+use chrono::{DateTime, LocalResult, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+/// Zone of timestamps given without an offset when `TIMEZONE` is not set.
+pub const DEFAULT_TIMEZONE: Tz = chrono_tz::Asia::Baghdad;
+
+/// Layouts accepted for timestamps without an offset, tried in order.
+const NAIVE_FORMATS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M",
+];
+
+/// Reads the IANA zone name in `TIMEZONE`, e.g. `Asia/Baghdad`, falling
+/// back to [`DEFAULT_TIMEZONE`].
+pub fn timezone_from_env() -> Result<Tz, String> {
+    match std::env::var("TIMEZONE") {
+        Ok(value) if !value.trim().is_empty() => value
+            .trim()
+            .parse()
+            .map_err(|_| format!("TIMEZONE must be an IANA zone name such as Asia/Baghdad, got '{}'", value)),
+        _ => Ok(DEFAULT_TIMEZONE),
+    }
+}
+
+/// Parses an RFC 3339 timestamp, such as `2024-11-01T08:30:00+03:00`, into
+/// UTC. One without an offset, `2024-11-01 08:30:00`, is read as local time
+/// in `zone`; a time repeated when clocks go back is taken the first time
+/// round, and one skipped when they go forward is rejected.
+pub fn parse_timestamp(value: &str, zone: Tz) -> Result<DateTime<Utc>, String> {
+    let value = value.trim();
+    
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(value) {
+        return Ok(timestamp.with_timezone(&Utc));
+    }
+    
+    let naive = NAIVE_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .ok_or_else(|| format!("'{}' is not an RFC 3339 timestamp", value))?;
+    
+    match zone.from_local_datetime(&naive) {
+        LocalResult::Single(local) | LocalResult::Ambiguous(local, _) => Ok(local.with_timezone(&Utc)),
+        LocalResult::None => Err(format!("'{}' does not exist in {}", value, zone)),
+    }
+}
@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+/// Cleanup steps for the text of one field, applied in the order listed
+/// before the value is parsed.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq, Eq, ToSchema)]
+#[serde(default)]
+pub struct Transform {
+    /// Strip leading and trailing whitespace
+    pub trim: bool,
+    /// Turn runs of whitespace into a single space, trimming as well
+    pub collapse_whitespace: bool,
+    /// Whole values replaced by others, e.g. a misspelled location
+    pub replace: HashMap<String, String>,
+    /// Placeholders such as "N/A" or "-" read as a missing value; `""`
+    /// makes blank values missing too
+    pub null_values: Vec<String>,
+    /// Capitalize the first letter of each word and lower-case the rest.
+    /// Scripts without case, such as Arabic, are left as they are.
+    pub title_case: bool,
+}
+
+impl Transform {
+    /// The cleaned value, or `None` when it is one of the `null_values`.
+    pub fn apply<'v>(&self, mut value: Cow<'v, str>) -> Option<Cow<'v, str>> {
+        if self.trim && value.trim().len() != value.len() {
+            value = Cow::Owned(value.trim().to_string());
+        }
+        if self.collapse_whitespace {
+            let collapsed = value.split_whitespace().collect::<Vec<_>>().join(" ");
+            if collapsed != value {
+                value = Cow::Owned(collapsed);
+            }
+        }
+        if let Some(replacement) = self.replace.get(value.as_ref()) {
+            value = Cow::Owned(replacement.clone());
+        }
+        if self.null_values.iter().any(|null| *null == value) {
+            return None;
+        }
+        if self.title_case {
+            value = Cow::Owned(title_case(&value));
+        }
+        
+        Some(value)
+    }
+}
+
+/// "ALI hassan al-najafi" becomes "Ali Hassan Al-Najafi".
+fn title_case(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut in_word = false;
+    
+    for c in value.chars() {
+        if in_word {
+            result.extend(c.to_lowercase());
+        } else {
+            result.extend(c.to_uppercase());
+        }
+        in_word = c.is_alphabetic();
+    }
+    
+    result
+}
+
+/// A source's cleanup rules: `all` applies to every field, then a field's
+/// own entry in `fields`, keyed by logical field name.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq, Eq, ToSchema)]
+#[serde(default)]
+pub struct FieldTransforms {
+    pub all: Option<Transform>,
+    pub fields: HashMap<String, Transform>,
+}
+
+impl FieldTransforms {
+    /// The cleaned text of `field`, or `None` when a transform made it
+    /// missing. Values pass through untouched when there are no rules.
+    pub fn apply<'v>(&self, field: &str, value: &'v str) -> Option<Cow<'v, str>> {
+        let value = match &self.all {
+            Some(transform) => transform.apply(Cow::Borrowed(value))?,
+            None => Cow::Borrowed(value),
+        };
+        
+        match self.fields.get(field) {
+            Some(transform) => transform.apply(value),
+            None => Some(value),
+        }
+    }
+    
+    /// The fields with rules of their own.
+    pub fn field_names(&self) -> impl Iterator<Item = &str> {
+        self.fields.keys().map(String::as_str)
+    }
+}
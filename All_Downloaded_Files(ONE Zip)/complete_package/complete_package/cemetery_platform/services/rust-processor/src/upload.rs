@@ -0,0 +1,135 @@
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+
+/// Staging directory for uploads when `UPLOAD_DIR` is not set.
+pub const DEFAULT_UPLOAD_DIR: &str = "/app/uploads";
+
+/// Largest upload accepted when `UPLOAD_MAX_MB` is not set (200 MiB).
+pub const DEFAULT_MAX_UPLOAD_SIZE: u64 = 200 * 1024 * 1024;
+
+/// File types `/api/upload` accepts. Shapefiles need their `.dbf` and
+/// `.prj` alongside, so they are uploaded inside a ZIP archive.
+pub const UPLOAD_EXTENSIONS: &[&str] = &[
+    "csv", "tsv", "json", "jsonl", "ndjson", "xlsx", "xls", "parquet", "zip", "csv.gz", "tsv.gz", "json.gz",
+    "jsonl.gz", "ndjson.gz",
+];
+
+/// Extensions that say nothing of the format, like names without one. Such
+/// uploads are read as the `format` parse option says, or by their content.
+pub const SNIFFED_UPLOAD_EXTENSIONS: &[&str] = &["txt", "dat"];
+
+/// Where uploads are staged and how large they may be.
+#[derive(Debug, Clone)]
+pub struct UploadSettings {
+    /// Canonical staging directory; each upload gets a subdirectory
+    pub dir: PathBuf,
+    pub max_size: u64,
+}
+
+impl UploadSettings {
+    /// Reads `UPLOAD_DIR` and `UPLOAD_MAX_MB`, falling back to
+    /// [`DEFAULT_UPLOAD_DIR`] and [`DEFAULT_MAX_UPLOAD_SIZE`]. The directory
+    /// is created if missing.
+    pub fn from_env() -> Result<Self, String> {
+        let dir = std::env::var("UPLOAD_DIR").unwrap_or_else(|_| DEFAULT_UPLOAD_DIR.to_string());
+        
+        let max_size = match std::env::var("UPLOAD_MAX_MB") {
+            Ok(value) => value
+                .trim()
+                .parse::<u64>()
+                .ok()
+                .filter(|&mb| mb > 0)
+                .map(|mb| mb * 1024 * 1024)
+                .ok_or_else(|| format!("UPLOAD_MAX_MB must be a positive number, got '{}'", value))?,
+            Err(_) => DEFAULT_MAX_UPLOAD_SIZE,
+        };
+        
+        std::fs::create_dir_all(&dir)
+            .and_then(|()| std::fs::canonicalize(&dir))
+            .map(|dir| Self { dir, max_size })
+            .map_err(|e| format!("UPLOAD_DIR {:?} is not a usable directory: {}", dir, e))
+    }
+}
+
+/// The name an upload is stored under: the last component of the name the
+/// client sent, which must have one of the [`UPLOAD_EXTENSIONS`] or
+/// [`SNIFFED_UPLOAD_EXTENSIONS`], or no extension at all.
+pub fn upload_file_name(client_name: &str) -> Result<String, String> {
+    // Browsers on Windows may send the full path
+    let name = client_name.rsplit(['/', '\\']).next().unwrap_or_default().trim();
+    
+    if name.is_empty() || name == "." || name == ".." {
+        return Err("The uploaded file has no name".to_string());
+    }
+    if name.chars().count() > 255 || name.chars().any(char::is_control) {
+        return Err(format!("Invalid file name '{}'", name.escape_default()));
+    }
+    
+    let lowercase = name.to_lowercase();
+    let has_extension = |extension: &&str| {
+        lowercase.strip_suffix(extension).is_some_and(|stem| stem.len() > 1 && stem.ends_with('.'))
+    };
+    let unnamed_format = match lowercase.rsplit_once('.') {
+        Some((stem, _)) if !stem.is_empty() => SNIFFED_UPLOAD_EXTENSIONS.iter().any(has_extension),
+        _ => true,
+    };
+    if !unnamed_format && !UPLOAD_EXTENSIONS.iter().any(has_extension) {
+        return Err(format!(
+            "Unsupported file type '{}': expected one of {}, or {} or no extension to detect the format",
+            name,
+            UPLOAD_EXTENSIONS.join(", "),
+            SNIFFED_UPLOAD_EXTENSIONS.join(", ")
+        ));
+    }
+    
+    Ok(name.to_string())
+}
+
+/// Writes an upload to disk as it arrives, hashing it on the way and
+/// refusing to grow past the size limit.
+pub struct StagedFile {
+    file: File,
+    hasher: Sha256,
+    size: u64,
+    max_size: u64,
+}
+
+/// Why a chunk could not be staged.
+#[derive(Debug, thiserror::Error)]
+pub enum StageError {
+    #[error("upload exceeds the {} MiB limit", .0 / (1024 * 1024))]
+    TooLarge(u64),
+    #[error("failed to write upload: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl StagedFile {
+    pub async fn create(path: &Path, max_size: u64) -> std::io::Result<Self> {
+        Ok(Self {
+            file: File::create(path).await?,
+            hasher: Sha256::new(),
+            size: 0,
+            max_size,
+        })
+    }
+    
+    pub async fn write(&mut self, chunk: &[u8]) -> Result<(), StageError> {
+        self.size += chunk.len() as u64;
+        if self.size > self.max_size {
+            return Err(StageError::TooLarge(self.max_size));
+        }
+        
+        self.hasher.update(chunk);
+        self.file.write_all(chunk).await?;
+        Ok(())
+    }
+    
+    /// Flushes the file, returning its SHA-256 and size.
+    pub async fn finish(mut self) -> std::io::Result<(String, u64)> {
+        self.file.flush().await?;
+        self.file.sync_all().await?;
+        Ok((format!("{:x}", self.hasher.finalize()), self.size))
+    }
+}
@@ -0,0 +1,134 @@
+use crate::database::RetryPolicy;
+use hmac::{Hmac, Mac};
+use log::{info, warn};
+use sha2::Sha256;
+use std::time::Duration;
+
+/// Header carrying the payload's signature, `sha256=<hex HMAC>`.
+pub const SIGNATURE_HEADER: &str = "X-Signature";
+
+/// How long one delivery attempt may take when `WEBHOOK_TIMEOUT_SECS` is not
+/// set.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Delivery attempts, and the wait before the first retry, when
+/// `WEBHOOK_MAX_ATTEMPTS` and `WEBHOOK_BACKOFF_MS` are not set. The wait
+/// doubles for each retry, so the defaults give up after about 15 seconds.
+pub const DEFAULT_RETRY: RetryPolicy = RetryPolicy {
+    max_attempts: 5,
+    initial_backoff: Duration::from_secs(1),
+};
+
+/// Checks a request's `callback_url`: an absolute `http` or `https` URL.
+pub fn parse_callback_url(url: &str) -> Result<reqwest::Url, String> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| format!("callback_url is not a valid URL: {}", e))?;
+    
+    if !matches!(parsed.scheme(), "http" | "https") || parsed.host().is_none() {
+        return Err("callback_url must be an http or https URL".to_string());
+    }
+    
+    Ok(parsed)
+}
+
+/// The `X-Signature` value for `body`: its HMAC-SHA256 under `secret`, in
+/// hex, prefixed with `sha256=`.
+pub fn sign(secret: &[u8], body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    
+    let digest: String = mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect();
+    format!("sha256={}", digest)
+}
+
+/// Posts job outcomes to the callback URLs given with processing requests.
+#[derive(Clone)]
+pub struct WebhookNotifier {
+    client: reqwest::Client,
+    secret: Vec<u8>,
+    retry: RetryPolicy,
+}
+
+impl WebhookNotifier {
+    pub fn new(secret: impl Into<Vec<u8>>, timeout: Duration, retry: RetryPolicy) -> Result<Self, String> {
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .map_err(|e| format!("Failed to create webhook client: {}", e))?;
+        
+        Ok(Self { client, secret: secret.into(), retry })
+    }
+    
+    /// Reads the shared secret from `WEBHOOK_SECRET`; callbacks are off
+    /// without one. `WEBHOOK_TIMEOUT_SECS`, `WEBHOOK_MAX_ATTEMPTS` and
+    /// `WEBHOOK_BACKOFF_MS` override [`DEFAULT_TIMEOUT`] and
+    /// [`DEFAULT_RETRY`].
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let secret = match std::env::var("WEBHOOK_SECRET") {
+            Ok(secret) if !secret.trim().is_empty() => secret,
+            _ => return Ok(None),
+        };
+        
+        let number = |name: &str| match std::env::var(name) {
+            Ok(value) => value
+                .trim()
+                .parse::<u64>()
+                .map(Some)
+                .map_err(|_| format!("{} must be a non-negative integer, got {:?}", name, value)),
+            Err(_) => Ok(None),
+        };
+        
+        let timeout = number("WEBHOOK_TIMEOUT_SECS")?.map_or(DEFAULT_TIMEOUT, Duration::from_secs);
+        let mut retry = DEFAULT_RETRY;
+        if let Some(attempts) = number("WEBHOOK_MAX_ATTEMPTS")? {
+            retry.max_attempts = u32::try_from(attempts)
+                .ok()
+                .filter(|&attempts| attempts > 0)
+                .ok_or_else(|| "WEBHOOK_MAX_ATTEMPTS must be at least 1".to_string())?;
+        }
+        if let Some(millis) = number("WEBHOOK_BACKOFF_MS")? {
+            retry.initial_backoff = Duration::from_millis(millis);
+        }
+        
+        Self::new(secret, timeout, retry).map(Some)
+    }
+    
+    /// Posts `body` to `url`, signed, retrying failed attempts and non-2xx
+    /// answers with backoff. Returns whether it was accepted in the end.
+    pub async fn deliver(&self, url: &str, body: Vec<u8>) -> bool {
+        let signature = sign(&self.secret, &body);
+        let mut attempt = 1;
+        
+        loop {
+            let outcome = self
+                .client
+                .post(url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(SIGNATURE_HEADER, &signature)
+                .body(body.clone())
+                .send()
+                .await;
+            
+            let failure = match outcome {
+                Ok(response) if response.status().is_success() => {
+                    info!("Delivered webhook to {} (attempt {})", url, attempt);
+                    return true;
+                }
+                Ok(response) => format!("answered {}", response.status()),
+                Err(e) => e.to_string(),
+            };
+            
+            if attempt >= self.retry.max_attempts {
+                warn!("Giving up on webhook to {} after {} attempts: {}", url, attempt, failure);
+                return false;
+            }
+            
+            let delay = self.retry.backoff(attempt);
+            warn!(
+                "Webhook attempt {} of {} to {} failed, retrying in {:?}: {}",
+                attempt, self.retry.max_attempts, url, delay, failure
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
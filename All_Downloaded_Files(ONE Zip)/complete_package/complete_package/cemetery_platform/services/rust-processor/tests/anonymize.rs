@@ -0,0 +1,107 @@
+use chrono::NaiveDate;
+use najaf_cemetery_processor::anonymize::{Anonymizer, DEFAULT_STRIPPED_FIELDS};
+use najaf_cemetery_processor::export::CsvExportWriter;
+use najaf_cemetery_processor::models::{ExportRecord, GeoJsonFeature, GeoJsonGeometry};
+use serde_json::json;
+
+fn export_record() -> ExportRecord {
+    ExportRecord {
+        record_id: "2024001".to_string(),
+        deceased_name: "Ali Hassan".to_string(),
+        deceased_name_arabic: Some("علي حسن".to_string()),
+        death_date: NaiveDate::from_ymd_opt(2024, 10, 31).unwrap(),
+        burial_date: NaiveDate::from_ymd_opt(2024, 11, 1).unwrap(),
+        burial_location: "Wadi al-Salam".to_string(),
+        latitude: Some(32.0175),
+        longitude: Some(44.3142),
+        section: Some("A".to_string()),
+        row_number: Some(12),
+        plot_number: Some(45),
+        cause_of_death: Some("Heart failure".to_string()),
+        national_id: Some("199012345678".to_string()),
+        family_contact: Some("+964 770 000 0000".to_string()),
+    }
+}
+
+fn export_csv(anonymizer: Anonymizer) -> String {
+    let mut writer = CsvExportWriter::new(false, Some(anonymizer)).unwrap();
+    writer.write(&export_record()).unwrap();
+    String::from_utf8(writer.finish().unwrap()).unwrap()
+}
+
+#[test]
+fn anonymized_csv_exports_leave_out_the_stripped_columns() {
+    let anonymizer = Anonymizer::new(&DEFAULT_STRIPPED_FIELDS, "secret").unwrap();
+    
+    assert_eq!(
+        export_csv(anonymizer.clone()),
+        "record_id,deceased_name,deceased_name_arabic,death_date,burial_date,burial_location,latitude,longitude,section,row,plot\n\
+         2024001,Ali Hassan,علي حسن,2024-10-31,2024-11-01,Wadi al-Salam,32.0175,44.3142,A,12,45\n"
+    );
+    
+    // Pseudonymizing names takes the Arabic name out as well
+    let pseudonym = anonymizer.pseudonym("2024001");
+    assert_eq!(
+        export_csv(anonymizer.with_pseudonymized_names(true)),
+        format!(
+            "record_id,deceased_name,death_date,burial_date,burial_location,latitude,longitude,section,row,plot\n\
+             2024001,{},2024-10-31,2024-11-01,Wadi al-Salam,32.0175,44.3142,A,12,45\n",
+            pseudonym
+        )
+    );
+}
+
+#[test]
+fn pseudonyms_are_stable_for_a_key() {
+    let anonymizer = Anonymizer::new(&[], "secret").unwrap();
+    let pseudonym = anonymizer.pseudonym("2024001");
+    
+    assert!(pseudonym.starts_with("anon-"));
+    assert_eq!(pseudonym.len(), 21);
+    assert_eq!(pseudonym, Anonymizer::new(&["national_id"], "secret").unwrap().pseudonym("2024001"));
+    assert_ne!(pseudonym, anonymizer.pseudonym("2024002"));
+    assert_ne!(pseudonym, Anonymizer::new(&[], "another").unwrap().pseudonym("2024001"));
+}
+
+#[test]
+fn the_stripped_fields_are_configurable() {
+    let anonymizer = Anonymizer::new(&["record_id", "burial_location"], "secret").unwrap();
+    assert_eq!(
+        export_csv(anonymizer).lines().next().unwrap(),
+        "deceased_name,deceased_name_arabic,death_date,burial_date,latitude,longitude,section,row,plot,\
+         cause_of_death,national_id,family_contact"
+    );
+    
+    // Dates, section and coordinates are always kept
+    let error = Anonymizer::new(&["burial_date"], "secret").err().unwrap();
+    assert!(error.starts_with("'burial_date' cannot be stripped"), "{}", error);
+}
+
+#[test]
+fn features_lose_the_stripped_properties() {
+    let anonymizer = Anonymizer::new(&["burial_location"], "secret").unwrap().with_pseudonymized_names(true);
+    let mut feature = GeoJsonFeature {
+        feature_type: "Feature".to_string(),
+        geometry: GeoJsonGeometry::Point([44.3142, 32.0175]),
+        properties: json!({
+            "record_id": "2024001",
+            "name": "Ali Hassan",
+            "burial_date": "2024-11-01",
+            "burial_location": "Wadi al-Salam",
+            "section": "A"
+        }),
+    };
+    
+    anonymizer.anonymize_feature(&mut feature);
+    
+    assert_eq!(
+        feature.properties,
+        json!({
+            "record_id": "2024001",
+            "name": anonymizer.pseudonym("2024001"),
+            "burial_date": "2024-11-01",
+            "section": "A"
+        })
+    );
+    assert_eq!(feature.geometry, GeoJsonGeometry::Point([44.3142, 32.0175]));
+}
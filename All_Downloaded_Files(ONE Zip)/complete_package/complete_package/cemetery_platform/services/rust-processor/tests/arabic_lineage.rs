@@ -0,0 +1,64 @@
+use najaf_cemetery_processor::arabic::{split_lineage, NameLineage};
+use najaf_cemetery_processor::parser::{DataParser, ParseOptions};
+
+fn lineage(given: &str, father: &str, grandfather: Option<&str>) -> Option<NameLineage> {
+    Some(NameLineage {
+        given_name: given.to_string(),
+        father_name: father.to_string(),
+        grandfather_name: grandfather.map(str::to_string),
+    })
+}
+
+#[test]
+fn splits_three_generations() {
+    assert_eq!(split_lineage("محمد بن علي بن حسين"), lineage("محمد", "علي", Some("حسين")));
+}
+
+#[test]
+fn keeps_compound_names_and_drops_further_ancestors() {
+    assert_eq!(
+        split_lineage("عبد الله بن عبد الرحمن بن محمد بن جعفر"),
+        lineage("عبد الله", "عبد الرحمن", Some("محمد"))
+    );
+}
+
+#[test]
+fn accepts_ibn_bint_and_hamza_spellings() {
+    assert_eq!(split_lineage("فاطمة بنت حسن"), lineage("فاطمة", "حسن", None));
+    assert_eq!(split_lineage("جعفر ابن محمد إبن علي"), lineage("جعفر", "محمد", Some("علي")));
+}
+
+#[test]
+fn names_without_lineage_pass_through() {
+    assert_eq!(split_lineage("علي حسين الموسوي"), None);
+    assert_eq!(split_lineage("ابن سينا"), None);
+    assert_eq!(split_lineage("John Doe"), None);
+}
+
+#[test]
+fn lineage_is_only_applied_when_requested() {
+    let path = std::env::temp_dir().join(format!("arabic_lineage_{}.csv", std::process::id()));
+    std::fs::write(
+        &path,
+        "record_id,deceased_name,deceased_name_arabic,death_date,burial_date,burial_location,latitude,longitude,section,row,plot\n\
+         2024001,Muhammad ibn Ali,محمد بن علي بن حسين,2024-10-31,2024-11-01,Wadi al-Salam,32.0175,44.3142,A,12,45\n\
+         2024002,Ali al-Musawi,علي حسين الموسوي,2024-10-31,2024-11-01,Wadi al-Salam,32.0175,44.3142,A,12,46\n",
+    ).unwrap();
+    
+    let plain = DataParser::parse_csv_file(&path, &ParseOptions::default()).unwrap();
+    let options = ParseOptions { arabic_lineage: true, ..ParseOptions::default() };
+    let split = DataParser::parse_csv_file(&path, &options).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    
+    assert_eq!(plain.records[0].father_name, None);
+    
+    let with_lineage = &split.records[0];
+    assert_eq!(with_lineage.deceased_name_arabic.as_deref(), Some("محمد بن علي بن حسين"));
+    assert_eq!(with_lineage.father_name.as_deref(), Some("علي"));
+    assert_eq!(with_lineage.grandfather_name.as_deref(), Some("حسين"));
+    assert_eq!(with_lineage.additional_data.as_ref().unwrap()["given_name"], "محمد");
+    
+    let without_lineage = &split.records[1];
+    assert_eq!(without_lineage.father_name, None);
+    assert_eq!(without_lineage.additional_data, None);
+}
@@ -0,0 +1,44 @@
+use chrono::NaiveDate;
+use najaf_cemetery_processor::arabic::normalize_digits;
+use najaf_cemetery_processor::parser::{parse_date, Calendar, DataParser, ParseOptions};
+
+const HEADER: &str = "record_id,deceased_name,deceased_name_arabic,death_date,burial_date,burial_location,latitude,longitude,section,row,plot";
+
+#[test]
+fn arabic_indic_and_eastern_digits_become_ascii() {
+    assert_eq!(normalize_digits("٠١٢٣٤٥٦٧٨٩"), "0123456789");
+    assert_eq!(normalize_digits("۰۱۲۳۴۵۶۷۸۹"), "0123456789");
+    assert_eq!(normalize_digits("قطعة ١٢ - 3"), "قطعة 12 - 3");
+}
+
+#[test]
+fn record_written_in_arabic_indic_numerals_is_parsed() {
+    let path = std::env::temp_dir().join(format!("arabic_numerals_{}.csv", std::process::id()));
+    std::fs::write(
+        &path,
+        format!(
+            "{}\n٢٠٢٤٠٠١,محمد علي,محمد علي,٣١/١٠/٢٠٢٤,٠١/١١/٢٠٢٤,وادي السلام,٣٢٫٠١٧٥,٤٤٫٣١٤٢,أ,١٢,۴۵\n",
+            HEADER
+        ),
+    )
+    .unwrap();
+    
+    let parsed = DataParser::parse_csv_file(&path, &ParseOptions::default()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    
+    assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+    let record = &parsed.records[0];
+    assert_eq!(record.death_date, NaiveDate::from_ymd_opt(2024, 10, 31).unwrap());
+    assert_eq!(record.burial_date, NaiveDate::from_ymd_opt(2024, 11, 1).unwrap());
+    assert_eq!((record.latitude, record.longitude), (Some(32.0175), Some(44.3142)));
+    assert_eq!((record.row_number, record.plot_number), (Some(12), Some(45)));
+}
+
+#[test]
+fn hijri_dates_in_arabic_indic_numerals_are_converted() {
+    let formats = ParseOptions::default().date_formats;
+    let parsed = parse_date("١٤٤٦/٠٤/٢٨ هـ", None, &formats).unwrap();
+    
+    assert_eq!(parsed.calendar, Calendar::Hijri);
+    assert_eq!(parsed.date, parse_date("1446/04/28 AH", None, &formats).unwrap().date);
+}
@@ -0,0 +1,64 @@
+use najaf_cemetery_processor::archive::extract_zip;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+fn write_zip(path: &Path, entries: &[(&str, &[u8])]) {
+    let mut zip = ZipWriter::new(File::create(path).unwrap());
+    for (name, contents) in entries {
+        zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+        zip.write_all(contents).unwrap();
+    }
+    zip.finish().unwrap();
+}
+
+#[test]
+fn extracts_nested_folders_and_skips_inner_archives() {
+    let dir = tempfile::tempdir().unwrap();
+    let archive = dir.path().join("delivery.zip");
+    write_zip(&archive, &[
+        ("burials.csv", b"record_id\nA-1\n"),
+        ("2024/november/burials.json", b"[]"),
+        ("older/backup.zip", b"not expanded"),
+    ]);
+    
+    let destination = dir.path().join("out");
+    let extracted = extract_zip(&archive, &destination, 1024).unwrap();
+    
+    assert_eq!(extracted.files, 2);
+    assert_eq!(extracted.bytes, 16);
+    assert_eq!(extracted.skipped, vec!["older/backup.zip"]);
+    assert_eq!(fs::read_to_string(destination.join("burials.csv")).unwrap(), "record_id\nA-1\n");
+    assert!(destination.join("2024/november/burials.json").is_file());
+    assert!(!destination.join("older/backup.zip").exists());
+}
+
+#[test]
+fn rejects_entries_that_escape_the_destination() {
+    let dir = tempfile::tempdir().unwrap();
+    let archive = dir.path().join("slip.zip");
+    write_zip(&archive, &[("../../escaped.csv", b"record_id\n")]);
+    
+    let destination = dir.path().join("out");
+    let error = extract_zip(&archive, &destination, 1024).unwrap_err();
+    
+    assert!(error.to_string().contains("unsafe path"), "{}", error);
+    assert!(!dir.path().join("escaped.csv").exists());
+}
+
+#[test]
+fn stops_once_the_size_limit_is_exceeded() {
+    let dir = tempfile::tempdir().unwrap();
+    let archive = dir.path().join("bomb.zip");
+    let padding = vec![b'0'; 600];
+    write_zip(&archive, &[("a.csv", &padding), ("b.csv", &padding)]);
+    
+    let destination = dir.path().join("out");
+    let error = extract_zip(&archive, &destination, 1000).unwrap_err();
+    assert!(error.to_string().contains("more than 1000 bytes"), "{}", error);
+    
+    // Exactly at the limit is still allowed
+    assert!(extract_zip(&archive, &dir.path().join("exact"), 1200).is_ok());
+}
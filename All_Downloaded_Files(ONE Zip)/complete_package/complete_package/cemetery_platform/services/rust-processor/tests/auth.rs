@@ -0,0 +1,36 @@
+use najaf_cemetery_processor::auth::ApiKeys;
+
+const OLD_KEY: &str = "old-key-0123456789abcdef";
+const NEW_KEY: &str = "new-key-fedcba9876543210";
+
+#[test]
+fn accepts_any_configured_key_as_a_bearer_token() {
+    let keys = ApiKeys::new([OLD_KEY, NEW_KEY]).unwrap();
+    
+    assert_eq!(keys.len(), 2);
+    assert!(keys.authorizes(Some(&format!("Bearer {}", OLD_KEY))));
+    assert!(keys.authorizes(Some(&format!("Bearer {}", NEW_KEY))));
+    assert!(keys.authorizes(Some(&format!("bearer  {} ", NEW_KEY))));
+}
+
+#[test]
+fn rejects_missing_malformed_and_unknown_keys() {
+    let keys = ApiKeys::new([OLD_KEY]).unwrap();
+    
+    assert!(!keys.authorizes(None));
+    assert!(!keys.authorizes(Some("")));
+    assert!(!keys.authorizes(Some("Bearer")));
+    assert!(!keys.authorizes(Some("Bearer ")));
+    assert!(!keys.authorizes(Some(OLD_KEY)));
+    assert!(!keys.authorizes(Some(&format!("Basic {}", OLD_KEY))));
+    assert!(!keys.authorizes(Some(&format!("Bearer {}x", OLD_KEY))));
+    assert!(!keys.authorizes(Some(&format!("Bearer {}", NEW_KEY))));
+}
+
+#[test]
+fn refuses_short_keys_and_an_empty_list() {
+    let error = ApiKeys::new([OLD_KEY, "short"]).err().unwrap();
+    assert!(error.contains("API key 2"), "{}", error);
+    
+    assert!(ApiKeys::new(Vec::<String>::new()).is_err());
+}
@@ -0,0 +1,104 @@
+use chrono::NaiveDate;
+use najaf_cemetery_processor::database::{
+    check_batch_size, is_transient, Database, RetryPolicy, INSERT_CHUNK_SIZE, MAX_BATCH_SIZE,
+};
+use najaf_cemetery_processor::processor::RunOptions;
+use najaf_cemetery_processor::models::{DeceasedRecord, RecordStatus};
+use std::time::Duration;
+
+fn sample_record(index: usize) -> DeceasedRecord {
+    DeceasedRecord {
+        record_id: format!("2024{:06}", index),
+        deceased_name: format!("Person {}", index),
+        deceased_name_arabic: None,
+        father_name: None,
+        grandfather_name: None,
+        death_date: NaiveDate::from_ymd_opt(2024, 10, 31).unwrap(),
+        death_location: None,
+        burial_date: NaiveDate::from_ymd_opt(2024, 11, 1).unwrap(),
+        burial_location: "Wadi al-Salam".to_string(),
+        section: Some("A".to_string()),
+        row_number: Some(12),
+        plot_number: Some(index as i32),
+        grave_number: None,
+        latitude: Some(32.0175),
+        longitude: Some(44.3142),
+        footprint: None,
+        birth_date: None,
+        age_at_death: None,
+        cause_of_death: None,
+        cause_of_death_code: None,
+        national_id: None,
+        family_contact: None,
+        additional_data: None,
+        processing_status: RecordStatus::default(),
+    }
+}
+
+#[test]
+fn batch_insert_uses_one_statement_per_chunk() {
+    let records: Vec<DeceasedRecord> = (0..10_000).map(sample_record).collect();
+    
+    let statements: Vec<String> = records
+        .chunks(INSERT_CHUNK_SIZE)
+        .map(|chunk| Database::build_upsert_query(chunk, "deceased_2024-11-01.zip").into_sql())
+        .collect();
+    
+    assert_eq!(statements.len(), 10);
+    // Each row has a point and a footprint
+    assert!(statements.iter().all(|sql| sql.matches("ST_GeomFromText(").count() == 2 * INSERT_CHUNK_SIZE));
+}
+
+#[test]
+fn batch_upsert_preserves_conflict_handling() {
+    let records: Vec<DeceasedRecord> = (0..3).map(sample_record).collect();
+    let sql = Database::build_upsert_query(&records, "test.csv").into_sql();
+    
+    assert!(sql.contains("ON CONFLICT (record_id) DO UPDATE SET"));
+    assert!(sql.contains("ST_GeomFromText($14, 4326)"));
+    assert!(sql.contains("ST_GeomFromText($15, 4326)"));
+    // 24 bound parameters per row
+    assert!(sql.contains("$72"));
+    assert!(!sql.contains("$73"));
+}
+
+#[test]
+fn batch_sizes_stay_within_the_bind_parameter_limit() {
+    assert_eq!(MAX_BATCH_SIZE, 2730);
+    assert_eq!(check_batch_size(MAX_BATCH_SIZE), Ok(MAX_BATCH_SIZE));
+    assert!(check_batch_size(MAX_BATCH_SIZE + 1).is_err());
+    assert!(check_batch_size(0).is_err());
+    
+    let records: Vec<DeceasedRecord> = (0..MAX_BATCH_SIZE).map(sample_record).collect();
+    let sql = Database::build_upsert_query(&records, "test.csv").into_sql();
+    assert!(sql.contains(&format!("${}", MAX_BATCH_SIZE * 24)));
+    
+    let options: RunOptions = serde_json::from_str(r#"{"batch_size": 5000}"#).unwrap();
+    assert_eq!(options.validate().unwrap_err(), "batch_size must be between 1 and 2730, got 5000");
+    let options: RunOptions = serde_json::from_str(r#"{"batch_size": 250}"#).unwrap();
+    assert_eq!(options.validate(), Ok(()));
+}
+
+#[test]
+fn retry_backoff_doubles_each_attempt() {
+    let policy = RetryPolicy {
+        max_attempts: 4,
+        initial_backoff: Duration::from_millis(100),
+    };
+    
+    let delays: Vec<Duration> = (1..policy.max_attempts).map(|attempt| policy.backoff(attempt)).collect();
+    assert_eq!(
+        delays,
+        vec![Duration::from_millis(100), Duration::from_millis(200), Duration::from_millis(400)]
+    );
+}
+
+#[test]
+fn only_transient_errors_are_retried() {
+    let reset = std::io::Error::new(std::io::ErrorKind::ConnectionReset, "connection reset");
+    
+    assert!(is_transient(&sqlx::Error::Io(reset)));
+    assert!(is_transient(&sqlx::Error::PoolTimedOut));
+    assert!(!is_transient(&sqlx::Error::RowNotFound));
+    assert!(!is_transient(&sqlx::Error::Protocol("unexpected message".to_string())));
+}
@@ -0,0 +1,79 @@
+use chrono::NaiveDate;
+use najaf_cemetery_processor::parser::{DataParser, ParseOptions};
+
+const CSV: &str = "record_id,deceased_name,deceased_name_arabic,death_date,burial_date,burial_location\n\
+                   B-1,Ali Hassan,,1931-04-02,,Wadi al-Salam\n\
+                   B-2,Zainab Kadhim,,1931-05-10,unknown,Wadi al-Salam\n\
+                   B-3,Hussein Jawad,,1931-06-20,1931-06-21,Wadi al-Salam\n\
+                   B-4,Fatima Ali,,,,Wadi al-Salam\n";
+
+fn ymd(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap()
+}
+
+#[test]
+fn missing_burial_dates_fail_by_default() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("register.csv");
+    std::fs::write(&path, CSV).unwrap();
+    
+    let parsed = DataParser::parse_csv_file(&path, &ParseOptions::default()).unwrap();
+    let ids: Vec<_> = parsed.records.iter().map(|record| record.record_id.as_str()).collect();
+    assert_eq!(ids, ["B-3"]);
+    assert_eq!(parsed.errors.len(), 3);
+    assert!(parsed.errors[0].message.starts_with("burial_date: "), "{}", parsed.errors[0].message);
+}
+
+#[test]
+fn burial_dates_fall_back_to_the_death_date() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("register.csv");
+    std::fs::write(&path, CSV).unwrap();
+    
+    let options = ParseOptions { burial_date_fallback_days: Some(1), ..ParseOptions::default() };
+    let parsed = DataParser::parse_csv_file(&path, &options).unwrap();
+    assert_eq!(parsed.records.len(), 3);
+    
+    let blank = &parsed.records[0];
+    assert_eq!(blank.burial_date, ymd(1931, 4, 3));
+    let reason = blank.additional_data.as_ref().unwrap()["burial_date_estimated"].as_str().unwrap();
+    assert!(reason.starts_with("burial_date: unrecognised date ''"), "{}", reason);
+    
+    let unreadable = &parsed.records[1];
+    assert_eq!(unreadable.burial_date, ymd(1931, 5, 11));
+    let reason = unreadable.additional_data.as_ref().unwrap()["burial_date_estimated"].as_str().unwrap();
+    assert!(reason.contains("'unknown'"), "{}", reason);
+    
+    // Dates the source gives are kept, and are not flagged
+    let given = &parsed.records[2];
+    assert_eq!(given.burial_date, ymd(1931, 6, 21));
+    assert_eq!(given.additional_data, None);
+    
+    // Without a death date there is nothing to fall back on
+    assert_eq!(parsed.errors.len(), 1);
+    assert_eq!(parsed.errors[0].record_id.as_deref(), Some("B-4"));
+    assert!(parsed.errors[0].message.starts_with("death_date: "));
+}
+
+#[test]
+fn json_records_without_a_burial_date_fall_back_too() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("register.json");
+    std::fs::write(
+        &path,
+        r#"[{"record_id": "J-1", "deceased_name": "Ali Hassan", "death_date": "1931-04-02",
+             "burial_location": "Wadi al-Salam"}]"#,
+    )
+    .unwrap();
+    
+    let options = ParseOptions { burial_date_fallback_days: Some(0), ..ParseOptions::default() };
+    let parsed = DataParser::parse_json_file(&path, &options).unwrap();
+    assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+    
+    let record = &parsed.records[0];
+    assert_eq!(record.burial_date, ymd(1931, 4, 2));
+    assert_eq!(
+        record.additional_data,
+        Some(serde_json::json!({ "burial_date_estimated": "missing field `burial_date`" }))
+    );
+}
@@ -0,0 +1,81 @@
+use najaf_cemetery_processor::cause_of_death::{CauseEntry, CauseVocabulary};
+use najaf_cemetery_processor::parser::{ColumnRef, CsvSchema, ParseOptions};
+use najaf_cemetery_processor::processor::{DataProcessor, DEFAULT_MAX_DEPTH};
+use sqlx::postgres::PgPoolOptions;
+use std::sync::Arc;
+
+fn code(vocabulary: &CauseVocabulary, text: &str) -> Option<String> {
+    vocabulary.normalize(text).map(|cause| cause.code.clone())
+}
+
+#[test]
+fn spellings_in_either_language_share_a_code() {
+    let vocabulary = CauseVocabulary::builtin();
+    
+    for text in ["Heart attack", "HEART-ATTACK.", "  myocardial   infarction ", "نوبة قلبية", "نوبه قلبيه", "I21"] {
+        assert_eq!(code(&vocabulary, text).as_deref(), Some("I21"), "{}", text);
+    }
+    assert_eq!(code(&vocabulary, "Covid-19").as_deref(), Some("U07.1"));
+    assert_eq!(code(&vocabulary, "كُورُونا").as_deref(), Some("U07.1"));
+    assert_eq!(code(&vocabulary, "السكري").as_deref(), Some("E14"));
+    assert_eq!(vocabulary.label("I64"), Some("Stroke"));
+    
+    assert_eq!(code(&vocabulary, "fell from a ladder"), None);
+    assert_eq!(code(&vocabulary, ""), None);
+}
+
+#[test]
+fn custom_vocabularies_must_be_unambiguous() {
+    let cause = |code: &str, synonyms: &[&str]| CauseEntry {
+        code: code.to_string(),
+        label: code.to_string(),
+        synonyms: synonyms.iter().map(|synonym| synonym.to_string()).collect(),
+    };
+    
+    let vocabulary = CauseVocabulary::new(vec![cause("HEART", &["heart attack"]), cause("OTHER", &["other"])]).unwrap();
+    assert_eq!(code(&vocabulary, "Heart Attack").as_deref(), Some("HEART"));
+    assert_eq!(code(&vocabulary, "stroke"), None);
+    
+    let error = CauseVocabulary::new(vec![cause("A", &["heart attack"]), cause("B", &["Heart attack"])]).unwrap_err();
+    assert_eq!(error, "'Heart attack' is given for both A and B");
+    assert!(CauseVocabulary::new(vec![cause("A", &[]), cause("A", &["x"])]).is_err());
+    
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("causes.json");
+    std::fs::write(&path, r#"[{"code": "R54", "label": "Old age", "synonyms": ["كبر السن"]}]"#).unwrap();
+    let vocabulary = CauseVocabulary::from_file(&path).unwrap();
+    assert_eq!(code(&vocabulary, "old age").as_deref(), Some("R54"));
+}
+
+#[tokio::test]
+async fn imports_code_causes_and_flag_the_rest() {
+    // Nothing listens here, so any query would fail the test
+    let pool = PgPoolOptions::new()
+        .connect_lazy("postgres://localhost/unused")
+        .unwrap();
+    let processor = DataProcessor::new(Arc::new(pool)).with_cause_vocabulary(Some(CauseVocabulary::builtin()));
+    
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("burials.csv"),
+        "record_id,deceased_name,death_date,burial_date,burial_location,cause\n\
+         C-1,Ali Hassan,2024-10-31,2024-11-01,Wadi al-Salam,جلطة دماغية\n\
+         C-2,Zainab Kadhim,2024-10-31,2024-11-01,Wadi al-Salam,fell from a ladder\n\
+         C-3,Hussein Jawad,2024-10-31,2024-11-01,Wadi al-Salam,\n",
+    )
+    .unwrap();
+    
+    let columns = ["record_id", "deceased_name", "death_date", "burial_date", "burial_location"]
+        .iter()
+        .map(|field| (field.to_string(), ColumnRef::Header(field.to_string())))
+        .chain(std::iter::once(("cause_of_death".to_string(), ColumnRef::Header("cause".to_string()))))
+        .collect();
+    let options = ParseOptions { csv_schema: Some(CsvSchema { columns }), ..ParseOptions::default() };
+    
+    let report = processor.validate_path(dir.path(), &options, DEFAULT_MAX_DEPTH).await.unwrap();
+    
+    assert_eq!(report.records_valid, 3);
+    assert!(report.records[0].warnings.is_empty());
+    assert_eq!(report.records[1].warnings, ["cause_of_death 'fell from a ladder' is not in the vocabulary"]);
+    assert!(report.records[2].warnings.is_empty());
+}
@@ -0,0 +1,26 @@
+use najaf_cemetery_processor::processor::{checkpoint_every_from_env, RunOptions};
+
+#[test]
+fn checkpoints_are_off_unless_configured() {
+    // The only test in this binary that touches the variable
+    std::env::remove_var("CHECKPOINT_EVERY");
+    assert_eq!(checkpoint_every_from_env(), Ok(None));
+    
+    std::env::set_var("CHECKPOINT_EVERY", "50000");
+    assert_eq!(checkpoint_every_from_env(), Ok(Some(50000)));
+    
+    std::env::set_var("CHECKPOINT_EVERY", "0");
+    assert_eq!(checkpoint_every_from_env(), Ok(None));
+    
+    std::env::set_var("CHECKPOINT_EVERY", "often");
+    assert_eq!(
+        checkpoint_every_from_env().unwrap_err(),
+        "CHECKPOINT_EVERY must be a non-negative number, got 'often'"
+    );
+    std::env::remove_var("CHECKPOINT_EVERY");
+    
+    // A run can turn them off again
+    let options: RunOptions = serde_json::from_str(r#"{"checkpoint_every": 0}"#).unwrap();
+    assert_eq!(options.checkpoint_every, Some(0));
+    assert_eq!(RunOptions::default().checkpoint_every, None);
+}
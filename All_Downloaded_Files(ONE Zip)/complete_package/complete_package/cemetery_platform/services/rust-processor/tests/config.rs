@@ -0,0 +1,52 @@
+use najaf_cemetery_processor::config::{Config, DEFAULT_IDEMPOTENCY_KEY_TTL_HOURS, DEFAULT_SERVER_PORT};
+use najaf_cemetery_processor::response_cache::DEFAULT_RESPONSE_CACHE_TTL_SECONDS;
+
+#[test]
+fn settings_are_read_and_checked_together() {
+    // The only test in this binary, so the variables are its own
+    let dir = tempfile::tempdir().unwrap();
+    std::env::set_var("DATA_BASE_DIR", dir.path());
+    std::env::set_var("UPLOAD_DIR", dir.path().join("uploads"));
+    
+    std::env::remove_var("DATABASE_URL");
+    assert_eq!(Config::from_env().err().unwrap(), "DATABASE_URL must be set");
+    
+    std::env::set_var("DATABASE_URL", "postgres://localhost/cemetery");
+    let config = Config::from_env().unwrap();
+    assert_eq!(config.server_port, DEFAULT_SERVER_PORT);
+    assert_eq!(config.data_base_dir, dir.path().canonicalize().unwrap());
+    assert!(config.upload.dir.ends_with("uploads"));
+    assert_eq!(config.checkpoint_every, None);
+    assert!(config.causes.is_none());
+    assert!(config.run_migrations);
+    assert!(config.keep_rejected);
+    assert_eq!(config.response_cache.as_ref().unwrap().ttl().as_secs(), DEFAULT_RESPONSE_CACHE_TTL_SECONDS);
+    assert_eq!(config.idempotency_key_ttl, chrono::Duration::hours(DEFAULT_IDEMPOTENCY_KEY_TTL_HOURS));
+    
+    std::env::set_var("SERVER_PORT", "http");
+    assert_eq!(Config::from_env().err().unwrap(), "SERVER_PORT must be a valid port number, got 'http'");
+    std::env::set_var("SERVER_PORT", "9090");
+    
+    std::env::set_var("PARTIAL_FAILURE_THRESHOLD", "2");
+    assert_eq!(
+        Config::from_env().err().unwrap(),
+        "PARTIAL_FAILURE_THRESHOLD must be between 0 and 1, got '2'"
+    );
+    std::env::set_var("PARTIAL_FAILURE_THRESHOLD", "0.05");
+    
+    std::env::set_var("IDEMPOTENCY_KEY_TTL_HOURS", "0");
+    assert_eq!(
+        Config::from_env().err().unwrap(),
+        "IDEMPOTENCY_KEY_TTL_HOURS must be a number of hours from 1 to 8760, got '0'"
+    );
+    std::env::set_var("IDEMPOTENCY_KEY_TTL_HOURS", "2");
+    std::env::set_var("KEEP_REJECTED_RECORDS", "no");
+    std::env::set_var("RESPONSE_CACHE_TTL_SECONDS", "0");
+    
+    let config = Config::from_env().unwrap();
+    assert_eq!(config.server_port, 9090);
+    assert_eq!(config.partial_failure_threshold, 0.05);
+    assert_eq!(config.idempotency_key_ttl, chrono::Duration::hours(2));
+    assert!(!config.keep_rejected);
+    assert!(config.response_cache.is_none());
+}
@@ -0,0 +1,74 @@
+use najaf_cemetery_processor::database::{ConflictAction, Database};
+use najaf_cemetery_processor::models::ConflictKey;
+use najaf_cemetery_processor::parser::{ColumnRef, CsvSchema, DataParser, JsonSchema, ParseOptions, ParsedFile};
+
+fn parse_csv(contents: &str, conflict_key: ConflictKey) -> Result<ParsedFile, anyhow::Error> {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("burials.csv");
+    std::fs::write(&path, contents).unwrap();
+    
+    let columns = ["deceased_name", "death_date", "burial_date", "burial_location", "national_id"]
+        .iter()
+        .map(|field| (field.to_string(), ColumnRef::Header(field.to_string())))
+        .collect();
+    let options = ParseOptions {
+        csv_schema: Some(CsvSchema { columns }),
+        conflict_key,
+        ..ParseOptions::default()
+    };
+    
+    DataParser::parse_csv_file(&path, &options)
+}
+
+#[test]
+fn sources_without_record_ids_get_ids_from_the_key() {
+    let csv = "deceased_name,death_date,burial_date,burial_location,national_id\n\
+               John Doe,2024-10-31,2024-11-01,Wadi al-Salam,199012345678\n\
+               Jane Doe,2024-10-31,2024-11-01,Wadi al-Salam,\n";
+    
+    // Without another key the record id has to be mapped
+    let error = parse_csv(csv, ConflictKey::RecordId).unwrap_err();
+    assert_eq!(error.to_string(), "CSV schema does not map required field 'record_id'");
+    
+    let parsed = parse_csv(csv, ConflictKey::NationalId).unwrap();
+    assert_eq!(parsed.records[0].record_id, "nid-199012345678");
+    assert_eq!(ConflictKey::NationalId.missing_field(&parsed.records[0]), None);
+    assert_eq!(parsed.records[1].record_id, "");
+    assert_eq!(ConflictKey::NationalId.missing_field(&parsed.records[1]), Some("national_id"));
+    
+    // The same person gets the same id in every delivery
+    let parsed = parse_csv(csv, ConflictKey::NameAndDeathDate).unwrap();
+    let again = parse_csv(csv, ConflictKey::NameAndDeathDate).unwrap();
+    assert!(parsed.records[0].record_id.starts_with("nd-"));
+    assert_eq!(parsed.records[0].record_id.len(), 19);
+    assert_eq!(parsed.records[0].record_id, again.records[0].record_id);
+    assert_ne!(parsed.records[0].record_id, parsed.records[1].record_id);
+}
+
+#[test]
+fn json_schemas_may_leave_the_record_id_out_with_another_key() {
+    let mut schema = JsonSchema::default();
+    schema.fields.remove("record_id");
+    
+    assert!(schema.validate().is_err());
+    assert!(schema.validate_for_key(ConflictKey::NationalId).is_ok());
+}
+
+#[test]
+fn upserts_conflict_on_the_key_columns() {
+    let parsed = parse_csv(
+        "deceased_name,death_date,burial_date,burial_location,national_id\n\
+         John Doe,2024-10-31,2024-11-01,Wadi al-Salam,199012345678\n",
+        ConflictKey::NationalId,
+    )
+    .unwrap();
+    let sql = |key, action| Database::build_upsert_query_on(&parsed.records, "test.csv", key, action).into_sql();
+    
+    assert!(sql(ConflictKey::RecordId, ConflictAction::Update).contains("ON CONFLICT (record_id) DO UPDATE SET"));
+    assert!(sql(ConflictKey::NationalId, ConflictAction::Update).contains("ON CONFLICT (national_id) DO UPDATE SET"));
+    assert!(sql(ConflictKey::NameAndDeathDate, ConflictAction::Ignore)
+        .contains("ON CONFLICT (deceased_name, death_date) DO NOTHING"));
+    
+    let key: ConflictKey = serde_json::from_str("\"name_and_death_date\"").unwrap();
+    assert_eq!(key, ConflictKey::NameAndDeathDate);
+}
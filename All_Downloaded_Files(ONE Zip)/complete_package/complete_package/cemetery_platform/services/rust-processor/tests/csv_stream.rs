@@ -0,0 +1,36 @@
+use najaf_cemetery_processor::parser::{DataParser, ParseOptions};
+use std::io::Write;
+
+#[test]
+fn csv_stream_yields_rows_lazily_with_line_numbers() {
+    let path = std::env::temp_dir().join(format!("csv_stream_{}.csv", std::process::id()));
+    let mut file = std::fs::File::create(&path).unwrap();
+    
+    writeln!(file, "record_id,deceased_name,deceased_name_arabic,death_date,burial_date,burial_location,latitude,longitude,section,row,plot").unwrap();
+    for index in 0..2500 {
+        // Row 1000 (line 1001) carries an unparseable date
+        let death_date = if index == 999 { "not-a-date" } else { "2024-10-31" };
+        writeln!(
+            file,
+            "2024{:06},Person {},,{},2024-11-01,Wadi al-Salam,32.0175,44.3142,A,12,{}",
+            index, index, death_date, index
+        ).unwrap();
+    }
+    drop(file);
+    
+    let mut stream = DataParser::parse_csv_stream(&path, &ParseOptions::default()).unwrap();
+    
+    // The first row is available without reading the rest of the file
+    let first = stream.next().unwrap().unwrap();
+    assert_eq!(first.record_id, "2024000000");
+    
+    let rest: Vec<_> = stream.collect();
+    std::fs::remove_file(&path).unwrap();
+    
+    assert_eq!(rest.len(), 2499);
+    
+    let errors: Vec<_> = rest.iter().filter_map(|row| row.as_ref().err()).collect();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].record_id.as_deref(), Some("2024000999"));
+    assert_eq!(errors[0].line_number, Some(1001));
+}
@@ -0,0 +1,54 @@
+use najaf_cemetery_processor::processor::resolve_data_path;
+use std::fs;
+
+#[test]
+fn resolves_paths_inside_the_base_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    let base = fs::canonicalize(dir.path()).unwrap();
+    fs::create_dir_all(base.join("deliveries/2024-11-01")).unwrap();
+    fs::write(base.join("deliveries/batch.zip"), b"").unwrap();
+    
+    assert_eq!(
+        resolve_data_path(&base, "deliveries/2024-11-01").unwrap(),
+        base.join("deliveries/2024-11-01")
+    );
+    assert_eq!(resolve_data_path(&base, "./deliveries/batch.zip").unwrap(), base.join("deliveries/batch.zip"));
+    assert_eq!(resolve_data_path(&base, ".").unwrap(), base);
+}
+
+#[test]
+fn rejects_paths_that_could_leave_the_base_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    let base = fs::canonicalize(dir.path()).unwrap().join("data");
+    fs::create_dir_all(base.join("inner")).unwrap();
+    fs::create_dir_all(dir.path().join("secret")).unwrap();
+    
+    let rejected = |path: &str| resolve_data_path(&base, path).unwrap_err();
+    
+    assert!(rejected("").contains("empty"));
+    assert!(rejected("/etc").contains("relative"));
+    assert!(rejected(base.to_str().unwrap()).contains("relative"));
+    assert!(rejected("../secret").contains(".."));
+    assert!(rejected("inner/../../secret").contains(".."));
+    assert!(rejected("missing").contains("does not exist"));
+}
+
+#[cfg(unix)]
+#[test]
+fn rejects_symlinks_that_point_outside() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = fs::canonicalize(dir.path()).unwrap();
+    let base = root.join("data");
+    fs::create_dir_all(&base).unwrap();
+    fs::create_dir_all(root.join("secret")).unwrap();
+    fs::create_dir_all(base.join("real")).unwrap();
+    
+    std::os::unix::fs::symlink(root.join("secret"), base.join("escape")).unwrap();
+    std::os::unix::fs::symlink(base.join("real"), base.join("alias")).unwrap();
+    
+    let error = resolve_data_path(&base, "escape").unwrap_err();
+    assert!(error.contains("outside"), "{}", error);
+    
+    // A link that stays inside is fine
+    assert_eq!(resolve_data_path(&base, "alias").unwrap(), base.join("real"));
+}
@@ -0,0 +1,97 @@
+use chrono::NaiveDate;
+use najaf_cemetery_processor::parser::{
+    parse_date, parse_flexible_date, Calendar, DataParser, ParseOptions, DEFAULT_DATE_FORMATS,
+};
+
+fn ymd(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap()
+}
+
+fn formats(list: &[&str]) -> Vec<String> {
+    list.iter().map(|format| format.to_string()).collect()
+}
+
+#[test]
+fn default_formats_accept_common_layouts() {
+    let defaults = formats(DEFAULT_DATE_FORMATS);
+    
+    for value in ["2024-11-01", "2024/11/01", "2024.11.01", "01/11/2024", "1/11/2024", "01-11-2024", "01.11.2024"] {
+        let parsed = parse_date(value, None, &defaults).unwrap();
+        assert_eq!(parsed.date, ymd(2024, 11, 1), "{}", value);
+        assert_eq!(parsed.calendar, Calendar::Gregorian);
+    }
+}
+
+#[test]
+fn formats_are_tried_in_order() {
+    let month_first = formats(&["%m-%d-%Y"]);
+    assert_eq!(parse_flexible_date("11-01-2024", &month_first), Some((2024, 11, 1)));
+    
+    let day_first = formats(&["%d-%m-%Y", "%m-%d-%Y"]);
+    assert_eq!(parse_flexible_date("11-01-2024", &day_first), Some((2024, 1, 11)));
+    
+    // Only the second format can read a day above 12 in the middle position
+    assert_eq!(parse_flexible_date("11-25-2024", &day_first), Some((2024, 11, 25)));
+}
+
+#[test]
+fn iso_dates_are_always_accepted() {
+    // Spreadsheet date cells arrive as ISO whatever the configured formats
+    let parsed = parse_date("2024-11-01", None, &formats(&["%d/%m/%Y"])).unwrap();
+    assert_eq!(parsed.date, ymd(2024, 11, 1));
+}
+
+#[test]
+fn hijri_dates_use_the_configured_formats() {
+    // 29 Safar is not a Gregorian date in 1445, so components must not be
+    // validated as Gregorian before the calendar is known
+    let parsed = parse_date("29/02/1445 AH", None, &formats(DEFAULT_DATE_FORMATS)).unwrap();
+    assert_eq!(parsed.calendar, Calendar::Hijri);
+    assert_eq!(parsed.date, ymd(2023, 9, 15));
+}
+
+#[test]
+fn unrecognised_dates_name_the_field_and_value() {
+    let path = std::env::temp_dir().join(format!("date_formats_{}.csv", std::process::id()));
+    std::fs::write(
+        &path,
+        "record_id,deceased_name,deceased_name_arabic,death_date,burial_date,burial_location,latitude,longitude,section,row,plot\n\
+         2024001,John Doe,,31 Oct 2024,2024-11-01,Wadi al-Salam,32.0175,44.3142,A,12,45\n",
+    ).unwrap();
+    
+    let parsed = DataParser::parse_csv_file(&path, &ParseOptions::default()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    
+    assert!(parsed.records.is_empty());
+    assert_eq!(parsed.errors.len(), 1);
+    assert_eq!(parsed.errors[0].record_id.as_deref(), Some("2024001"));
+    assert!(parsed.errors[0].message.starts_with("death_date: unrecognised date '31 Oct 2024'"));
+    
+    let options: ParseOptions = serde_json::from_str(r#"{"date_formats": ["%d %b %Y"]}"#).unwrap();
+    assert_eq!(parse_date("31 Oct 2024", None, &options.date_formats).unwrap().date, ymd(2024, 10, 31));
+}
+
+#[test]
+fn formats_without_a_full_date_fail_the_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("burials.csv");
+    std::fs::write(
+        &path,
+        "record_id,deceased_name,death_date,burial_date,burial_location\n\
+         2024001,John Doe,2024-10-31,2024-11-01,Wadi al-Salam\n",
+    )
+    .unwrap();
+    
+    for (date_formats, expected) in [
+        (formats(&["%d/%m"]), "date format '%d/%m' must name a year, a month and a day"),
+        (formats(&["%Y-%m-%d", "%d/%m/%Q"]), "date format '%d/%m/%Q' is not a valid strftime pattern"),
+    ] {
+        let options = ParseOptions { date_formats, ..ParseOptions::default() };
+        let error = DataParser::parse_csv_file(&path, &options).unwrap_err();
+        assert_eq!(error.to_string(), expected);
+    }
+    
+    // Month names count as the month
+    let options = ParseOptions { date_formats: formats(&["%d %b %Y"]), ..ParseOptions::default() };
+    assert!(DataParser::parse_csv_file(&path, &options).is_ok());
+}
@@ -0,0 +1,67 @@
+use najaf_cemetery_processor::parser::{sniff_delimiter, DataParser, ParseOptions};
+
+const HEADER: &str = "record_id,deceased_name,deceased_name_arabic,death_date,burial_date,burial_location,latitude,longitude,section,row,plot";
+
+fn write_fixture(name: &str, contents: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("delimiters_{}_{}", std::process::id(), name));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn sniffs_the_most_common_separator_outside_quotes() {
+    assert_eq!(sniff_delimiter(b"a,b,c\n1,2,3"), b',');
+    assert_eq!(sniff_delimiter(b"a;b;c\n1,5;2,5;3"), b';');
+    assert_eq!(sniff_delimiter(b"a\tb\tc"), b'\t');
+    assert_eq!(sniff_delimiter(b"\"a;b\",c,d"), b',');
+    assert_eq!(sniff_delimiter(b"record_id"), b',');
+}
+
+#[test]
+fn semicolon_delimited_arabic_file_is_sniffed() {
+    let header = HEADER.replace(',', ";");
+    let path = write_fixture(
+        "arabic.csv",
+        &format!(
+            "{}\n2024001;محمد علي;محمد علي حسن;31/10/2024;01/11/2024;وادي السلام;32.0175;44.3142;أ;12;45\n",
+            header
+        ),
+    );
+    
+    let parsed = DataParser::parse_csv_file(&path, &ParseOptions::default()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    
+    assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+    assert_eq!(parsed.records.len(), 1);
+    let record = &parsed.records[0];
+    assert_eq!(record.deceased_name, "محمد علي");
+    assert_eq!(record.deceased_name_arabic.as_deref(), Some("محمد علي حسن"));
+    assert_eq!(record.burial_location, "وادي السلام");
+    assert_eq!(record.section.as_deref(), Some("أ"));
+    assert_eq!(record.plot_number, Some(45));
+    assert_eq!(record.latitude, Some(32.0175));
+}
+
+#[test]
+fn tsv_files_are_tab_delimited_and_explicit_delimiters_win() {
+    let row = "2024001,John Doe,,2024-10-31,2024-11-01,Wadi al-Salam,32.0175,44.3142,A,12,45";
+    let tsv = write_fixture(
+        "export.tsv",
+        &format!("{}\n{}\n", HEADER.replace(',', "\t"), row.replace(',', "\t")),
+    );
+    let piped = write_fixture(
+        "export.csv",
+        &format!("{}\n{}\n", HEADER.replace(',', "|"), row.replace(',', "|")),
+    );
+    
+    let from_tsv = DataParser::detect_and_parse(&tsv, &ParseOptions::default()).unwrap();
+    let options = ParseOptions { delimiter: Some('|'), ..ParseOptions::default() };
+    let from_piped = DataParser::detect_and_parse(&piped, &options).unwrap();
+    std::fs::remove_file(&tsv).unwrap();
+    std::fs::remove_file(&piped).unwrap();
+    
+    assert_eq!(from_tsv.records.len(), 1);
+    assert_eq!(from_tsv.records[0].latitude, Some(32.0175));
+    assert_eq!(from_piped.records.len(), 1);
+    assert_eq!(from_piped.records[0].burial_location, "Wadi al-Salam");
+}
@@ -0,0 +1,75 @@
+use najaf_cemetery_processor::processor::find_data_files;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn fixture_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("directory_walk_{}_{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn touch(dir: &Path, relative: &str) {
+    let path = dir.join(relative);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, "record_id\n").unwrap();
+}
+
+fn relative_paths(files: &[(String, PathBuf)]) -> Vec<&str> {
+    files.iter().map(|(relative, _)| relative.as_str()).collect()
+}
+
+#[test]
+fn finds_nested_data_files_and_skips_hidden_and_system_ones() {
+    let dir = fixture_dir("nested");
+    touch(&dir, "top.csv");
+    touch(&dir, "2024/november/burials.json");
+    touch(&dir, "2024/november/sections.xlsx");
+    touch(&dir, "2024/notes.txt");
+    touch(&dir, ".hidden.csv");
+    touch(&dir, ".cache/old.csv");
+    touch(&dir, "__MACOSX/2024/._burials.json");
+    touch(&dir, "2024/~$sections.xlsx");
+    
+    let mut warnings = Vec::new();
+    let files = find_data_files(&dir, 8, &mut warnings).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    
+    assert_eq!(
+        relative_paths(&files),
+        vec!["2024/november/burials.json", "2024/november/sections.xlsx", "top.csv"]
+    );
+    assert_eq!(warnings, vec!["Skipped unsupported file 2024/notes.txt"]);
+}
+
+#[test]
+fn max_depth_limits_how_far_down_files_are_found() {
+    let dir = fixture_dir("depth");
+    touch(&dir, "top.csv");
+    touch(&dir, "a/one.csv");
+    touch(&dir, "a/b/two.csv");
+    
+    let mut warnings = Vec::new();
+    let top_only = find_data_files(&dir, 1, &mut warnings).unwrap();
+    let two_levels = find_data_files(&dir, 2, &mut warnings).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    
+    assert_eq!(relative_paths(&top_only), vec!["top.csv"]);
+    assert_eq!(relative_paths(&two_levels), vec!["a/one.csv", "top.csv"]);
+}
+
+#[cfg(unix)]
+#[test]
+fn symlink_loops_are_reported_not_followed() {
+    let dir = fixture_dir("loop");
+    touch(&dir, "data/burials.csv");
+    std::os::unix::fs::symlink(&dir, dir.join("data/back_to_top")).unwrap();
+    
+    let mut warnings = Vec::new();
+    let files = find_data_files(&dir, 8, &mut warnings).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    
+    assert_eq!(relative_paths(&files), vec!["data/burials.csv"]);
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].starts_with("Skipped symlink loop"), "{:?}", warnings);
+}
@@ -0,0 +1,95 @@
+use chrono::NaiveDate;
+use najaf_cemetery_processor::database::{ConflictAction, Database, ImportMode};
+use najaf_cemetery_processor::models::{DeceasedRecord, DuplicateStrategy, RecordStatus};
+use serde_json::json;
+
+fn record(name: &str) -> DeceasedRecord {
+    DeceasedRecord {
+        record_id: "2024001".to_string(),
+        deceased_name: name.to_string(),
+        deceased_name_arabic: None,
+        father_name: None,
+        grandfather_name: None,
+        death_date: NaiveDate::from_ymd_opt(2024, 10, 31).unwrap(),
+        death_location: None,
+        burial_date: NaiveDate::from_ymd_opt(2024, 11, 1).unwrap(),
+        burial_location: "Wadi al-Salam".to_string(),
+        section: None,
+        row_number: None,
+        plot_number: None,
+        grave_number: None,
+        latitude: None,
+        longitude: None,
+        footprint: None,
+        birth_date: None,
+        age_at_death: None,
+        cause_of_death: None,
+        cause_of_death_code: None,
+        national_id: None,
+        family_contact: None,
+        additional_data: None,
+        processing_status: RecordStatus::default(),
+    }
+}
+
+#[test]
+fn keep_first_and_keep_last_pick_one_copy() {
+    let mut kept = record("First");
+    kept.collapse(record("Last"), DuplicateStrategy::KeepFirst);
+    assert_eq!(kept.deceased_name, "First");
+    
+    let mut kept = record("First");
+    kept.collapse(record("Last"), DuplicateStrategy::KeepLast);
+    assert_eq!(kept.deceased_name, "Last");
+}
+
+#[test]
+fn merge_fills_blanks_from_earlier_copies() {
+    let mut earlier = record("First");
+    earlier.section = Some("A".to_string());
+    earlier.plot_number = Some(45);
+    earlier.latitude = Some(32.0175);
+    earlier.longitude = Some(44.3142);
+    earlier.additional_data = Some(json!({ "sheet": "2023", "notes": "old" }));
+    
+    let mut later = record("Last");
+    later.section = Some(String::new());
+    later.plot_number = Some(46);
+    later.father_name = Some("Hassan".to_string());
+    later.additional_data = Some(json!({ "notes": "new" }));
+    
+    earlier.collapse(later, DuplicateStrategy::Merge);
+    
+    assert_eq!(earlier.deceased_name, "Last");
+    assert_eq!(earlier.section.as_deref(), Some("A"));
+    assert_eq!(earlier.plot_number, Some(46));
+    assert_eq!(earlier.father_name.as_deref(), Some("Hassan"));
+    assert_eq!((earlier.latitude, earlier.longitude), (Some(32.0175), Some(44.3142)));
+    assert_eq!(earlier.additional_data, Some(json!({ "sheet": "2023", "notes": "new" })));
+}
+
+#[test]
+fn repeats_of_written_records_use_the_strategy_on_conflict() {
+    let records = [record("Last")];
+    let sql = |action| Database::build_upsert_query_with(&records, "test.csv", action).into_sql();
+    
+    assert!(sql(ConflictAction::Ignore).contains("ON CONFLICT (record_id) DO NOTHING"));
+    assert!(sql(ConflictAction::Replace).contains("father_name = EXCLUDED.father_name"));
+    assert!(sql(ConflictAction::Fill)
+        .contains("father_name = COALESCE(NULLIF(EXCLUDED.father_name, ''), deceased_records.father_name)"));
+    assert!(!sql(ConflictAction::Update).contains("father_name ="));
+}
+
+#[test]
+fn import_modes_choose_which_records_are_written() {
+    assert!(ImportMode::Upsert.writes(true) && ImportMode::Upsert.writes(false));
+    assert!(!ImportMode::InsertOnly.writes(true) && ImportMode::InsertOnly.writes(false));
+    assert!(ImportMode::UpdateOnly.writes(true) && !ImportMode::UpdateOnly.writes(false));
+    
+    assert_eq!(ImportMode::InsertOnly.conflict_action(), ConflictAction::Ignore);
+    assert_eq!(ImportMode::UpdateOnly.conflict_action(), ConflictAction::Update);
+    
+    let mode: ImportMode = serde_json::from_str("\"insert_only\"").unwrap();
+    assert_eq!(mode, ImportMode::InsertOnly);
+    assert_eq!(ImportMode::default(), ImportMode::Upsert);
+}
@@ -0,0 +1,94 @@
+use encoding_rs::{Encoding, UTF_16LE, WINDOWS_1256};
+use najaf_cemetery_processor::encoding::detect_encoding;
+use najaf_cemetery_processor::parser::{DataParser, ParseOptions};
+use std::path::PathBuf;
+
+const HEADER: &str = "record_id,deceased_name,deceased_name_arabic,death_date,burial_date,burial_location,latitude,longitude,section,row,plot";
+
+const ARABIC_NAMES: &[&str] = &[
+    "علي حسين الموسوي",
+    "فاطمة عبد الله",
+    "محمد جعفر الحكيم",
+];
+
+fn sample_csv() -> String {
+    let mut csv = format!("{}\n", HEADER);
+    for (index, name) in ARABIC_NAMES.iter().enumerate() {
+        csv.push_str(&format!(
+            "2024{:06},Person {},{},2024-10-31,2024-11-01,وادي السلام,32.0175,44.3142,A,12,{}\n",
+            index, index, name, index
+        ));
+    }
+    csv
+}
+
+fn write_sample(name: &str, bytes: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("{}_{}.csv", name, std::process::id()));
+    std::fs::write(&path, bytes).unwrap();
+    path
+}
+
+fn arabic_names(path: &PathBuf, options: &ParseOptions) -> Vec<String> {
+    let parsed = DataParser::parse_csv_file(path, options).unwrap();
+    std::fs::remove_file(path).unwrap();
+    
+    assert!(parsed.errors.is_empty(), "unexpected errors: {:?}", parsed.errors);
+    assert_eq!(parsed.records[0].burial_location, "وادي السلام");
+    parsed.records.into_iter().map(|r| r.deceased_name_arabic.unwrap()).collect()
+}
+
+fn encode(encoding: &'static Encoding, text: &str) -> Vec<u8> {
+    let (bytes, _, unmappable) = encoding.encode(text);
+    assert!(!unmappable);
+    bytes.into_owned()
+}
+
+#[test]
+fn windows_1256_round_trips_to_arabic_text() {
+    let bytes = encode(WINDOWS_1256, &sample_csv());
+    assert_eq!(detect_encoding(&bytes), WINDOWS_1256);
+    
+    let path = write_sample("windows_1256", &bytes);
+    assert_eq!(arabic_names(&path, &ParseOptions::default()), ARABIC_NAMES);
+}
+
+#[test]
+fn utf16_is_detected_with_and_without_bom() {
+    // encoding_rs only encodes to UTF-8, so build UTF-16LE by hand
+    let utf16: Vec<u8> = sample_csv().encode_utf16().flat_map(|unit| unit.to_le_bytes()).collect();
+    assert_eq!(detect_encoding(&utf16), UTF_16LE);
+    
+    let mut with_bom = vec![0xFF, 0xFE];
+    with_bom.extend_from_slice(&utf16);
+    
+    let path = write_sample("utf16_bom", &with_bom);
+    assert_eq!(arabic_names(&path, &ParseOptions::default()), ARABIC_NAMES);
+    
+    let path = write_sample("utf16", &utf16);
+    assert_eq!(arabic_names(&path, &ParseOptions::default()), ARABIC_NAMES);
+}
+
+#[test]
+fn explicit_encoding_overrides_detection() {
+    let bytes = encode(WINDOWS_1256, &sample_csv());
+    let with_label = |label: &str| ParseOptions {
+        encoding: Some(label.to_string()),
+        ..ParseOptions::default()
+    };
+    
+    let path = write_sample("override_1256", &bytes);
+    let parsed = DataParser::parse_csv_file(&path, &with_label("cp1256")).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(parsed.records[0].deceased_name_arabic.as_deref(), Some(ARABIC_NAMES[0]));
+    
+    // A wrong label is honoured too, yielding Latin-1 mojibake
+    let path = write_sample("override_1252", &bytes);
+    let parsed = DataParser::parse_csv_file(&path, &with_label("windows-1252")).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_ne!(parsed.records[0].deceased_name_arabic.as_deref(), Some(ARABIC_NAMES[0]));
+    
+    let path = write_sample("override_unknown", &bytes);
+    let result = DataParser::parse_csv_file(&path, &with_label("no-such-encoding"));
+    std::fs::remove_file(&path).unwrap();
+    assert!(result.is_err());
+}
@@ -0,0 +1,114 @@
+use chrono::NaiveDate;
+use najaf_cemetery_processor::export::{features_to_kml, CsvExportWriter};
+use najaf_cemetery_processor::models::{ExportRecord, GeoJsonFeature, GeoJsonGeometry};
+use najaf_cemetery_processor::parser::{DataParser, ParseOptions};
+use serde_json::json;
+
+fn feature(longitude: f64, latitude: f64, properties: serde_json::Value) -> GeoJsonFeature {
+    GeoJsonFeature {
+        feature_type: "Feature".to_string(),
+        geometry: GeoJsonGeometry::Point([longitude, latitude]),
+        properties,
+    }
+}
+
+#[test]
+fn writes_one_placemark_per_feature() {
+    let kml = features_to_kml(&[
+        feature(44.3142, 32.0175, json!({
+            "record_id": "2024001",
+            "name": "Ali & Sons <Trading>",
+            "burial_date": "2024-11-01",
+            "section": "A",
+            "row": 12,
+            "plot": 45
+        })),
+        feature(44.32, 32.02, json!({ "record_id": "2024002", "name": null, "burial_date": null })),
+    ]);
+    
+    assert!(kml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<kml xmlns=\"http://www.opengis.net/kml/2.2\">"));
+    assert!(kml.ends_with("</Document>\n</kml>\n"));
+    assert_eq!(kml.matches("<Placemark>").count(), 2);
+    
+    assert!(kml.contains("<name>Ali &amp; Sons &lt;Trading&gt;</name>"), "{}", kml);
+    assert!(kml.contains(
+        "<description>Record: 2024001\nBurial date: 2024-11-01\nSection A, Row 12, Plot 45</description>"
+    ), "{}", kml);
+    assert!(kml.contains("<coordinates>44.3142,32.0175</coordinates>"));
+    
+    // Without a name the record id labels the placemark
+    assert!(kml.contains("<name>2024002</name>\n<description>Record: 2024002</description>"), "{}", kml);
+}
+
+#[test]
+fn keeps_arabic_names_as_utf8() {
+    let kml = features_to_kml(&[feature(44.3, 32.0, json!({ "name": "محمد علي" }))]);
+    
+    assert!(kml.contains("<name>محمد علي</name>"));
+}
+
+fn export_record(record_id: &str) -> ExportRecord {
+    ExportRecord {
+        record_id: record_id.to_string(),
+        deceased_name: "Ali, son of Hassan".to_string(),
+        deceased_name_arabic: Some("علي بن حسن".to_string()),
+        death_date: NaiveDate::from_ymd_opt(2024, 10, 31).unwrap(),
+        burial_date: NaiveDate::from_ymd_opt(2024, 11, 1).unwrap(),
+        burial_location: "Wadi al-Salam".to_string(),
+        latitude: Some(32.0175),
+        longitude: Some(44.3142),
+        section: Some("A".to_string()),
+        row_number: Some(12),
+        plot_number: None,
+        cause_of_death: None,
+        national_id: Some("199012345678".to_string()),
+        family_contact: None,
+    }
+}
+
+#[test]
+fn csv_export_uses_the_import_layout() {
+    let mut writer = CsvExportWriter::new(true, None).unwrap();
+    assert!(writer.write(&export_record("2024001")).unwrap().is_none());
+    let csv = writer.finish().unwrap();
+    
+    assert!(csv.starts_with(b"\xEF\xBB\xBF"));
+    assert_eq!(
+        std::str::from_utf8(&csv[3..]).unwrap(),
+        "record_id,deceased_name,deceased_name_arabic,death_date,burial_date,burial_location,latitude,longitude,section,row,plot,\
+         cause_of_death,national_id,family_contact\n\
+         2024001,\"Ali, son of Hassan\",علي بن حسن,2024-10-31,2024-11-01,Wadi al-Salam,32.0175,44.3142,A,12,,,199012345678,\n"
+    );
+    
+    // The export can be processed again as it is
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("export.csv");
+    std::fs::write(&path, &csv).unwrap();
+    
+    let parsed = DataParser::parse_csv_file(&path, &ParseOptions::default()).unwrap();
+    assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+    assert_eq!(parsed.records.len(), 1);
+    assert_eq!(parsed.records[0].deceased_name_arabic.as_deref(), Some("علي بن حسن"));
+    assert_eq!(parsed.records[0].row_number, Some(12));
+    assert_eq!(parsed.records[0].national_id.as_deref(), Some("199012345678"));
+    assert_eq!(parsed.records[0].additional_data, None);
+}
+
+#[test]
+fn csv_export_is_returned_in_chunks() {
+    let mut writer = CsvExportWriter::new(false, None).unwrap();
+    let mut chunks = Vec::new();
+    
+    for row in 0..1200 {
+        if let Some(chunk) = writer.write(&export_record(&format!("{:06}", row))).unwrap() {
+            chunks.push(chunk);
+        }
+    }
+    chunks.push(writer.finish().unwrap());
+    
+    assert_eq!(chunks.len(), 3);
+    let csv = String::from_utf8(chunks.concat()).unwrap();
+    assert_eq!(csv.lines().count(), 1201);
+    assert!(csv.starts_with("record_id,"));
+    assert!(chunks[1].starts_with(b"000500,"));
+}
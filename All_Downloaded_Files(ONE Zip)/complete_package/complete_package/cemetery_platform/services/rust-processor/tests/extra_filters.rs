@@ -0,0 +1,34 @@
+use najaf_cemetery_processor::models::RecordFilter;
+
+#[test]
+fn extra_parameters_become_a_containment_object() {
+    let filter = RecordFilter::default()
+        .with_extra([
+            ("section", "A"),
+            ("extra.registrar", "Kadhim"),
+            ("extra.cemetery_name", "Wadi al-Salam"),
+        ])
+        .unwrap();
+    
+    assert_eq!(
+        filter.extra_containment(),
+        Some(serde_json::json!({ "cemetery_name": "Wadi al-Salam", "registrar": "Kadhim" }))
+    );
+    assert_eq!(RecordFilter::default().extra_containment(), None);
+}
+
+#[test]
+fn extra_keys_are_checked() {
+    let error = RecordFilter::default().with_extra([("extra.name'); DROP TABLE x; --", "1")]).unwrap_err();
+    assert!(error.contains("keys must be 1 to 64 letters"), "{}", error);
+    
+    assert!(RecordFilter::default().with_extra([("extra.", "1")]).is_err());
+    assert!(RecordFilter::default().with_extra([("extra.registrar", "a"), ("extra.registrar", "b")]).is_err());
+    
+    let long = "x".repeat(257);
+    assert!(RecordFilter::default().with_extra([("extra.registrar", long.as_str())]).is_err());
+    
+    let keys: Vec<String> = (0..11).map(|n| format!("extra.field_{}", n)).collect();
+    let error = RecordFilter::default().with_extra(keys.iter().map(|key| (key.as_str(), "1"))).unwrap_err();
+    assert_eq!(error, "At most 10 extra. filters may be combined");
+}
@@ -0,0 +1,86 @@
+use chrono::NaiveDate;
+use najaf_cemetery_processor::family::{father_confidence, given_name, name_similarity, suggest_links, Person};
+
+fn ymd(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap()
+}
+
+fn child(father_name: &str, grandfather_name: Option<&str>) -> Person {
+    Person {
+        record_id: "C-1".to_string(),
+        deceased_name: "Ali Hassan".to_string(),
+        father_name: Some(father_name.to_string()),
+        grandfather_name: grandfather_name.map(str::to_string),
+        death_date: ymd(2024, 1, 10),
+        ..Person::default()
+    }
+}
+
+fn father(record_id: &str, name: &str, father_name: Option<&str>) -> Person {
+    Person {
+        record_id: record_id.to_string(),
+        deceased_name: name.to_string(),
+        father_name: father_name.map(str::to_string),
+        death_date: ymd(1987, 3, 14),
+        ..Person::default()
+    }
+}
+
+#[test]
+fn grandfather_names_raise_confidence() {
+    let hassan = father("F-1", "Hassan Kadhim", Some("Kadhim"));
+    
+    assert_eq!(father_confidence(&child("Hassan", Some("Kadhim")), &hassan), Some(1.0));
+    assert_eq!(father_confidence(&child("Hassan", None), &hassan), Some(0.6));
+    assert_eq!(father_confidence(&child("Hassan", Some("Jawad")), &hassan), Some(0.6));
+    
+    // A spelling variant still matches, less confidently
+    assert_eq!(name_similarity("Hasan", "HASSAN"), 0.625);
+    assert_eq!(father_confidence(&child("Hasan", Some("Kadhim")), &hassan), Some(0.625));
+}
+
+#[test]
+fn arabic_names_are_normalized() {
+    let hassan = Person {
+        deceased_name_arabic: Some("حسن كاظم".to_string()),
+        ..father("F-1", "H. Kadhim", None)
+    };
+    
+    assert_eq!(father_confidence(&child("حَسَن", None), &hassan), Some(0.6));
+    assert_eq!(given_name("Abd al-Karim Hassan").as_deref(), Some("abd al-karim"));
+    assert_eq!(given_name("عبد الكريم حسن").as_deref(), Some("عبد الكريم"));
+    assert_eq!(given_name("  "), None);
+}
+
+#[test]
+fn dates_rule_out_fathers() {
+    let born_2005 = Person { birth_date: Some(ymd(2005, 6, 1)), ..child("Hassan", None) };
+    
+    let too_young = Person { birth_date: Some(ymd(2000, 1, 1)), ..father("F-1", "Hassan Kadhim", None) };
+    assert_eq!(father_confidence(&born_2005, &too_young), None);
+    
+    let died_long_before = father("F-2", "Hassan Kadhim", None);
+    assert_eq!(father_confidence(&born_2005, &died_long_before), None);
+    
+    let posthumous = Person { death_date: ymd(2005, 1, 20), ..father("F-3", "Hassan Kadhim", None) };
+    assert_eq!(father_confidence(&born_2005, &posthumous), Some(0.6));
+    
+    let born_after_death = Person { birth_date: Some(ymd(2024, 5, 1)), ..father("F-4", "Hassan Kadhim", None) };
+    assert_eq!(father_confidence(&child("Hassan", None), &born_after_death), None);
+}
+
+#[test]
+fn weak_links_are_dropped() {
+    let child = child("Hassan", Some("Kadhim"));
+    let candidates = [
+        (child.clone(), father("F-1", "Hussein Ali", None)),
+        (child.clone(), father("F-2", "Hassan Jawad", None)),
+        (child.clone(), father("F-3", "Hassan Kadhim", Some("Kadhim"))),
+        (child.clone(), child.clone()),
+    ];
+    
+    let links = suggest_links(&candidates);
+    let fathers: Vec<_> = links.iter().map(|link| (link.father_record_id.as_str(), link.confidence)).collect();
+    assert_eq!(fathers, [("F-3", 1.0), ("F-2", 0.6)]);
+    assert!(links.iter().all(|link| link.record_id == "C-1"));
+}
@@ -0,0 +1,95 @@
+use najaf_cemetery_processor::export::features_to_kml;
+use najaf_cemetery_processor::footprint::{footprint_wkt, parse_footprint};
+use najaf_cemetery_processor::models::{DeceasedRecord, GeoJsonGeometry, ValidationError};
+use najaf_cemetery_processor::parser::{ColumnRef, CsvSchema, DataParser, ParseOptions};
+use serde_json::json;
+
+const SQUARE: [[f64; 2]; 5] = [[44.1, 32.1], [44.2, 32.1], [44.2, 32.2], [44.1, 32.2], [44.1, 32.1]];
+
+#[test]
+fn footprints_are_read_in_every_accepted_form() {
+    for text in [
+        "POLYGON((44.1 32.1, 44.2 32.1, 44.2 32.2, 44.1 32.2, 44.1 32.1))",
+        "polygon ((44.1 32.1,44.2 32.1,44.2 32.2,44.1 32.2))",
+        "[[44.1, 32.1], [44.2, 32.1], [44.2, 32.2], [44.1, 32.2]]",
+        "44.1 32.1, 44.2 32.1, 44.2 32.2, 44.1 32.2",
+        "44.1,32.1; 44.2,32.1; 44.2,32.2; 44.1,32.2",
+    ] {
+        assert_eq!(parse_footprint(text), Ok(SQUARE.to_vec()), "{}", text);
+    }
+    
+    assert_eq!(
+        footprint_wkt(&SQUARE),
+        "POLYGON((44.1 32.1, 44.2 32.1, 44.2 32.2, 44.1 32.2, 44.1 32.1))"
+    );
+}
+
+#[test]
+fn footprints_that_are_not_an_area_are_rejected() {
+    assert!(parse_footprint("44.1 32.1, 44.2 32.1, 44.1 32.1").unwrap_err().contains("at least 3"));
+    assert!(parse_footprint("44.1 32.1, 44.2").unwrap_err().contains("'44.2' is not a coordinate pair"));
+    assert!(parse_footprint("POLYGON((0 0, 4 0, 4 4, 0 0), (1 1, 2 1, 2 2, 1 1))")
+        .unwrap_err()
+        .contains("holes"));
+    assert!(parse_footprint("[[44.1, 32.1]").is_err());
+}
+
+fn parse_with_footprint(footprint: &str) -> Result<DeceasedRecord, String> {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("burials.csv");
+    std::fs::write(
+        &path,
+        format!(
+            "record_id,deceased_name,death_date,burial_date,burial_location,latitude,longitude,outline\n\
+             2024001,John Doe,2024-10-31,2024-11-01,Wadi al-Salam,32.15,44.15,\"{}\"\n",
+            footprint
+        ),
+    )
+    .unwrap();
+    
+    let columns = [
+        "record_id", "deceased_name", "death_date", "burial_date", "burial_location", "latitude", "longitude",
+    ]
+    .iter()
+    .map(|field| (field.to_string(), ColumnRef::Header(field.to_string())))
+    .chain(std::iter::once(("footprint".to_string(), ColumnRef::Header("outline".to_string()))))
+    .collect();
+    let options = ParseOptions { csv_schema: Some(CsvSchema { columns }), ..ParseOptions::default() };
+    
+    let mut parsed = DataParser::parse_csv_file(&path, &options).unwrap();
+    match parsed.errors.pop() {
+        Some(error) => Err(error.message),
+        None => Ok(parsed.records.remove(0)),
+    }
+}
+
+#[test]
+fn a_footprint_is_drawn_instead_of_the_point() {
+    let record = parse_with_footprint("44.1 32.1, 44.2 32.1, 44.2 32.2, 44.1 32.2").unwrap();
+    let feature = record.to_geojson_feature().unwrap();
+    
+    assert_eq!(feature.geometry, GeoJsonGeometry::Polygon(vec![SQUARE.to_vec()]));
+    assert_eq!(
+        serde_json::to_value(&feature.geometry).unwrap(),
+        json!({ "type": "Polygon", "coordinates": [SQUARE] })
+    );
+    assert!(features_to_kml(&[feature]).contains(
+        "<Polygon><outerBoundaryIs><LinearRing><coordinates>44.1,32.1 44.2,32.1 44.2,32.2 44.1,32.2 44.1,32.1"
+    ));
+    
+    // Without one the grave stays a point
+    let record = parse_with_footprint("").unwrap();
+    assert_eq!(record.footprint, None);
+    assert_eq!(record.to_geojson_feature().unwrap().geometry, GeoJsonGeometry::Point([44.15, 32.15]));
+}
+
+#[test]
+fn footprint_problems_are_reported_against_the_record() {
+    assert!(parse_with_footprint("44.1 32.1, 44.2 32.1").unwrap_err().starts_with("footprint: "));
+    
+    let record = parse_with_footprint("44.1 32.1, 44.2 32.1, 244.2 32.2").unwrap();
+    assert_eq!(
+        record.validate(),
+        Err(vec![ValidationError::InvalidFootprint { longitude: 244.2, latitude: 32.2 }])
+    );
+}
@@ -0,0 +1,71 @@
+use najaf_cemetery_processor::parser::{file_format, sniff_format, DataParser, FileFormat, ParseOptions};
+use std::path::Path;
+
+const CSV: &str = "record_id,deceased_name,death_date,burial_date,burial_location\n\
+                   S-1,Ali Hassan,2024-10-31,2024-11-01,Wadi al-Salam\n\
+                   S-2,\"Kadhim, Zainab\",2024-11-02,2024-11-03,Wadi al-Salam\n";
+
+fn write(dir: &Path, name: &str, content: &str) -> std::path::PathBuf {
+    let path = dir.join(name);
+    std::fs::write(&path, content).unwrap();
+    path
+}
+
+#[test]
+fn text_files_are_read_by_their_content() {
+    let dir = tempfile::tempdir().unwrap();
+    
+    let csv = write(dir.path(), "export.txt", CSV);
+    assert_eq!(sniff_format(&csv, None).unwrap(), FileFormat::Csv);
+    let parsed = DataParser::detect_and_parse(&csv, &ParseOptions::default()).unwrap();
+    assert_eq!(parsed.records[1].deceased_name, "Kadhim, Zainab");
+    
+    let tsv = write(dir.path(), "export.dat", &CSV.replace(',', "\t"));
+    assert_eq!(sniff_format(&tsv, None).unwrap(), FileFormat::Tsv);
+    let ids: Vec<_> = DataParser::detect_and_stream(&tsv, &ParseOptions::default())
+        .unwrap()
+        .map(|record| record.unwrap().record_id)
+        .collect();
+    assert_eq!(ids, ["S-1", "S-2"]);
+    
+    let json = write(
+        dir.path(),
+        "burials",
+        r#"  [{"record_id": "J-1", "deceased_name": "Ali Hassan", "death_date": "2024-10-31",
+              "burial_date": "2024-11-01", "burial_location": "Wadi al-Salam"}]"#,
+    );
+    assert_eq!(sniff_format(&json, None).unwrap(), FileFormat::Json);
+    let parsed = DataParser::detect_and_parse(&json, &ParseOptions::default()).unwrap();
+    assert_eq!(parsed.records[0].record_id, "J-1");
+}
+
+#[test]
+fn the_format_option_overrides_the_extension() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write(dir.path(), "burials.json", &CSV.replace(',', ";"));
+    
+    assert_eq!(file_format(&path, &ParseOptions::default()).unwrap(), FileFormat::Json);
+    assert!(DataParser::detect_and_parse(&path, &ParseOptions::default()).is_err());
+    
+    let options = ParseOptions { format: Some(FileFormat::Csv), ..ParseOptions::default() };
+    let parsed = DataParser::detect_and_parse(&path, &options).unwrap();
+    assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+    assert_eq!(parsed.records.len(), 2);
+}
+
+#[test]
+fn undetectable_files_say_what_was_found() {
+    let dir = tempfile::tempdir().unwrap();
+    
+    let prose = write(dir.path(), "notes", "Deliveries from the north gate\nresume next week, as agreed.\n");
+    let error = DataParser::detect_and_parse(&prose, &ParseOptions::default()).unwrap_err();
+    assert!(error.to_string().starts_with("Could not detect the format of"), "{}", error);
+    assert!(error.to_string().contains("first 2 lines"), "{}", error);
+    
+    let empty = write(dir.path(), "empty", "");
+    assert!(sniff_format(&empty, None).unwrap_err().to_string().contains("empty"));
+    
+    let binary = dir.path().join("image");
+    std::fs::write(&binary, b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+    assert!(sniff_format(&binary, None).unwrap_err().to_string().contains("binary"));
+}
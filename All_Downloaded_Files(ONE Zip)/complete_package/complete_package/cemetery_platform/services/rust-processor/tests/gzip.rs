@@ -0,0 +1,76 @@
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use najaf_cemetery_processor::parser::{is_supported_file, DataParser, ParseOptions};
+use najaf_cemetery_processor::upload::upload_file_name;
+use std::io::Write;
+use std::path::Path;
+
+fn write_gzipped(path: &Path, content: &str) {
+    let mut encoder = GzEncoder::new(std::fs::File::create(path).unwrap(), Compression::default());
+    encoder.write_all(content.as_bytes()).unwrap();
+    encoder.finish().unwrap();
+}
+
+#[test]
+fn gzipped_csv_is_parsed_as_csv() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("burials.CSV.gz");
+    write_gzipped(
+        &path,
+        "record_id,deceased_name,deceased_name_arabic,death_date,burial_date,burial_location\n\
+         G-1,Ali Hassan,علي حسن,2024-10-31,2024-11-01,Wadi al-Salam\n\
+         G-2,Zainab Kadhim,,2024-11-02,2024-11-03,Wadi al-Salam\n",
+    );
+    
+    assert!(is_supported_file(&path));
+    
+    let parsed = DataParser::detect_and_parse(&path, &ParseOptions::default()).unwrap();
+    assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+    assert_eq!(parsed.records.len(), 2);
+    assert_eq!(parsed.records[0].deceased_name_arabic.as_deref(), Some("علي حسن"));
+    
+    let streamed: Vec<_> = DataParser::detect_and_stream(&path, &ParseOptions::default())
+        .unwrap()
+        .map(|record| record.unwrap().record_id)
+        .collect();
+    assert_eq!(streamed, ["G-1", "G-2"]);
+}
+
+#[test]
+fn gzipped_tsv_and_json_keep_their_format() {
+    let dir = tempfile::tempdir().unwrap();
+    
+    let tsv = dir.path().join("burials.tsv.gz");
+    write_gzipped(
+        &tsv,
+        "record_id\tdeceased_name\tdeath_date\tburial_date\tburial_location\n\
+         T-1\tAli, son of Hassan\t2024-10-31\t2024-11-01\tWadi al-Salam\n",
+    );
+    let parsed = DataParser::detect_and_parse(&tsv, &ParseOptions::default()).unwrap();
+    assert_eq!(parsed.records[0].deceased_name, "Ali, son of Hassan");
+    
+    let json = dir.path().join("burials.json.gz");
+    write_gzipped(
+        &json,
+        r#"[{"record_id": "J-1", "deceased_name": "Ali Hassan", "death_date": "2024-10-31",
+             "burial_date": "2024-11-01", "burial_location": "Wadi al-Salam"}]"#,
+    );
+    let parsed = DataParser::detect_and_parse(&json, &ParseOptions::default()).unwrap();
+    assert_eq!(parsed.records[0].record_id, "J-1");
+}
+
+#[test]
+fn only_text_formats_may_be_gzipped() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("burials.xlsx.gz");
+    write_gzipped(&path, "not a workbook");
+    
+    assert!(!is_supported_file(&path));
+    assert!(!is_supported_file(Path::new("burials.gz")));
+    
+    let error = DataParser::detect_and_parse(&path, &ParseOptions::default()).unwrap_err();
+    assert!(error.to_string().starts_with("Unsupported compressed file format: xlsx"), "{}", error);
+    
+    assert_eq!(upload_file_name("burials.CSV.GZ").unwrap(), "burials.CSV.GZ");
+    assert!(upload_file_name("burials.xlsx.gz").unwrap_err().contains("Unsupported"));
+}
@@ -0,0 +1,102 @@
+use chrono::NaiveDate;
+use najaf_cemetery_processor::parser::{DataParser, ParseOptions, ParsedFile};
+
+fn parse(contents: &str) -> Result<ParsedFile, anyhow::Error> {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("burials.csv");
+    std::fs::write(&path, contents).unwrap();
+    DataParser::parse_csv_file(&path, &ParseOptions::default())
+}
+
+#[test]
+fn reordered_columns_are_found_by_header_and_alias() {
+    let parsed = parse(
+        "Burial Location,Lon,Lat,Date-Of-Burial,Died,Name,ID,Section,Age\n\
+         Wadi al-Salam,44.3142,32.0175,2024-11-01,2024-10-31,Ali Hassan,H-1,A,71\n",
+    )
+    .unwrap();
+    
+    assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+    let record = &parsed.records[0];
+    assert_eq!((record.record_id.as_str(), record.deceased_name.as_str()), ("H-1", "Ali Hassan"));
+    assert_eq!(record.death_date, NaiveDate::from_ymd_opt(2024, 10, 31).unwrap());
+    assert_eq!(record.burial_date, NaiveDate::from_ymd_opt(2024, 11, 1).unwrap());
+    assert_eq!(record.burial_location, "Wadi al-Salam");
+    assert_eq!((record.latitude, record.longitude), (Some(32.0175), Some(44.3142)));
+    assert_eq!(record.section.as_deref(), Some("A"));
+    assert_eq!(record.age_at_death, Some(71));
+    assert_eq!(record.additional_data, None);
+}
+
+#[test]
+fn optional_columns_may_be_left_out() {
+    let parsed = parse(
+        "record_id,deceased_name,death_date,burial_date,burial_location,plot\n\
+         H-2,Zainab Kadhim,2024-10-31,2024-11-01,Wadi al-Salam,9\n",
+    )
+    .unwrap();
+    
+    assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+    let record = &parsed.records[0];
+    assert_eq!(record.burial_location, "Wadi al-Salam");
+    assert_eq!(record.plot_number, Some(9));
+    assert_eq!((record.latitude, record.section.as_deref(), record.row_number), (None, None, None));
+}
+
+#[test]
+fn missing_required_column_is_named() {
+    let error = parse(
+        "record_id,deceased_name,death_date,burial_location\n\
+         H-3,Hussein Jawad,2024-10-31,Wadi al-Salam\n",
+    )
+    .unwrap_err();
+    
+    assert!(error.to_string().contains("'burial_date'"), "{}", error);
+}
+
+#[test]
+fn unrecognised_headers_are_read_in_the_documented_order() {
+    let parsed = parse(
+        "رقم,الاسم,الاسم بالعربية,تاريخ الوفاة,تاريخ الدفن,المقبرة\n\
+         H-4,Ali Hassan,علي حسن,2024-10-31,2024-11-01,Wadi al-Salam\n",
+    )
+    .unwrap();
+    
+    assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+    assert_eq!(parsed.records[0].record_id, "H-4");
+    assert_eq!(parsed.records[0].deceased_name_arabic.as_deref(), Some("علي حسن"));
+    assert_eq!(parsed.records[0].burial_location, "Wadi al-Salam");
+}
+
+#[test]
+fn every_optional_column_is_read() {
+    let parsed = parse(
+        "record_id,deceased_name,deceased_name_arabic,father_name,grandfather_name,death_date,burial_date,\
+         burial_location,latitude,longitude,section,row,plot,birth_date,age_at_death,national_id,\
+         cause_of_death,family_contact\n\
+         H-5,Ali Hassan,علي حسن,Hassan,Jawad,2024-10-31,2024-11-01,Wadi al-Salam,32.0175,44.3142,A,12,45,\
+         1950-11-15,73,199012345678,Heart failure,+964 770 000 0000\n\
+         H-6,Zainab Kadhim,,,,2024-10-31,2024-11-01,Wadi al-Salam,,,,,,,about 70,,,\n",
+    )
+    .unwrap();
+    
+    assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+    let record = &parsed.records[0];
+    assert_eq!(record.deceased_name_arabic.as_deref(), Some("علي حسن"));
+    assert_eq!(record.father_name.as_deref(), Some("Hassan"));
+    assert_eq!(record.grandfather_name.as_deref(), Some("Jawad"));
+    assert_eq!((record.latitude, record.longitude), (Some(32.0175), Some(44.3142)));
+    assert_eq!((record.section.as_deref(), record.row_number, record.plot_number), (Some("A"), Some(12), Some(45)));
+    assert_eq!(record.birth_date, NaiveDate::from_ymd_opt(1950, 11, 15));
+    assert_eq!(record.age_at_death, Some(73));
+    assert_eq!(record.national_id.as_deref(), Some("199012345678"));
+    assert_eq!(record.cause_of_death.as_deref(), Some("Heart failure"));
+    assert_eq!(record.family_contact.as_deref(), Some("+964 770 000 0000"));
+    assert_eq!(record.additional_data, None);
+    
+    // Blank columns are missing, and an age that is not a number is dropped
+    let sparse = &parsed.records[1];
+    assert_eq!((sparse.father_name.as_deref(), sparse.grandfather_name.as_deref()), (None, None));
+    assert_eq!(sparse.age_at_death, None);
+    assert_eq!(sparse.national_id, None);
+}
@@ -0,0 +1,104 @@
+use chrono::NaiveDate;
+use najaf_cemetery_processor::parser::{
+    hijri_to_gregorian, parse_date, Calendar, DataParser, ParseOptions, DEFAULT_DATE_FORMATS,
+};
+
+fn ymd(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap()
+}
+
+fn parse(value: &str) -> Result<NaiveDate, anyhow::Error> {
+    let formats: Vec<String> = DEFAULT_DATE_FORMATS.iter().map(|format| format.to_string()).collect();
+    let parsed = parse_date(value, Some(Calendar::Hijri), &formats)?;
+    assert_eq!(parsed.calendar, Calendar::Hijri, "{}", value);
+    Ok(parsed.date)
+}
+
+#[test]
+fn known_dates_convert_to_gregorian() {
+    // Tabular Islamic calendar dates
+    let pairs = [
+        ((1400, 1, 1), ymd(1979, 11, 21)),
+        ((1445, 3, 12), ymd(2023, 9, 27)),
+        ((1445, 9, 15), ymd(2024, 3, 25)),
+        ((1445, 12, 10), ymd(2024, 6, 17)),
+        ((1446, 1, 1), ymd(2024, 7, 8)),
+    ];
+    
+    for ((year, month, day), gregorian) in pairs {
+        assert_eq!(hijri_to_gregorian(year, month, day), Some(gregorian), "{}-{}-{}", year, month, day);
+        assert_eq!(parse(&format!("{}-{:02}-{:02}", year, month, day)).unwrap(), gregorian);
+    }
+    
+    // Unmarked dates are Gregorian unless the request says otherwise
+    let unmarked = parse_date("1445-03-12", None, &[]).unwrap();
+    assert_eq!((unmarked.calendar, unmarked.date), (Calendar::Gregorian, ymd(1445, 3, 12)));
+    let suffixed = parse_date("1445-03-12 AH", None, &[]).unwrap();
+    assert_eq!((suffixed.calendar, suffixed.date), (Calendar::Hijri, ymd(2023, 9, 27)));
+}
+
+#[test]
+fn consecutive_hijri_days_are_consecutive_gregorian_days() {
+    let mut previous = hijri_to_gregorian(1399, 12, 29).unwrap();
+    
+    for year in 1400..=1460 {
+        for month in 1..=12 {
+            for day in 1..=30 {
+                let Some(date) = hijri_to_gregorian(year, month, day) else {
+                    assert!(day >= 30, "{}-{}-{} has no date", year, month, day);
+                    continue;
+                };
+                assert_eq!(previous.succ_opt(), Some(date), "{}-{}-{}", year, month, day);
+                previous = date;
+            }
+        }
+    }
+}
+
+#[test]
+fn month_names_are_read_in_arabic_and_transliterated() {
+    let ramadan = ymd(2024, 3, 25);
+    for value in ["15 Ramadan 1445", "15 ramadhan 1445 AH", "15 رمضان 1445", "١٥ رمضان ١٤٤٥هـ", "15 Ramadan, 1445"] {
+        assert_eq!(parse(value).unwrap(), ramadan, "{}", value);
+    }
+    
+    let hijjah = ymd(2024, 6, 17);
+    for value in ["10 Dhu al-Hijjah 1445", "10 Dhul-Hijjah 1445", "10 ذو الحجة 1445", "10 ذو الحجّة 1445"] {
+        assert_eq!(parse(value).unwrap(), hijjah, "{}", value);
+    }
+    
+    assert_eq!(parse("12 Rabi' al-Awwal 1445").unwrap(), ymd(2023, 9, 27));
+    assert_eq!(parse("12 ربيع الأول 1445").unwrap(), ymd(2023, 9, 27));
+    
+    // A month name is Hijri whatever calendar the request names
+    let gregorian = parse_date("1 Muharram 1446", Some(Calendar::Gregorian), &[]).unwrap();
+    assert_eq!((gregorian.calendar, gregorian.date), (Calendar::Hijri, ymd(2024, 7, 8)));
+}
+
+#[test]
+fn malformed_hijri_dates_name_the_record() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("burials.csv");
+    std::fs::write(
+        &path,
+        "record_id,deceased_name,death_date,burial_date,burial_location\n\
+         N-1,Ali Hassan,15 Ramadan 1445,16 Ramadan 1445,Wadi al-Salam\n\
+         N-2,Zainab Kadhim,30 Safar 1445,1 Rabi al-Awwal 1445,Wadi al-Salam\n\
+         N-3,Hussein Jawad,15 Ramadhaan 1445,16 Ramadan 1445,Wadi al-Salam\n",
+    )
+    .unwrap();
+    
+    let parsed = DataParser::parse_csv_file(&path, &ParseOptions::default()).unwrap();
+    
+    assert_eq!(parsed.records.len(), 1);
+    assert_eq!(parsed.records[0].death_date, ymd(2024, 3, 25));
+    
+    // Safar has 29 days
+    let short_month = &parsed.errors[0];
+    assert_eq!(short_month.record_id.as_deref(), Some("N-2"));
+    assert!(short_month.message.contains("death_date: invalid Hijri date '30 Safar 1445'"), "{}", short_month.message);
+    
+    let misspelt = &parsed.errors[1];
+    assert_eq!(misspelt.record_id.as_deref(), Some("N-3"));
+    assert!(misspelt.message.contains("unrecognised date '15 Ramadhaan 1445'"), "{}", misspelt.message);
+}
@@ -0,0 +1,62 @@
+use najaf_cemetery_processor::parser::{is_supported_file, DataParser, ParseOptions, ParsedFile};
+use std::path::Path;
+
+const RECORD: &str = r#""deceased_name": "Ali Hassan", "death_date": "2024-10-31", "burial_date": "2024-11-01", "burial_location": "Wadi al-Salam""#;
+
+fn parse(name: &str, content: &str) -> ParsedFile {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(name);
+    std::fs::write(&path, content).unwrap();
+    DataParser::detect_and_parse(&path, &ParseOptions::default()).unwrap()
+}
+
+fn ids(parsed: &ParsedFile) -> Vec<&str> {
+    parsed.records.iter().map(|record| record.record_id.as_str()).collect()
+}
+
+#[test]
+fn wrapped_and_bare_arrays_are_read_alike() {
+    let wrapped = parse("wrapped.json", &format!(r#"{{"records": [{{"record_id": "W-1", {}}}]}}"#, RECORD));
+    assert_eq!(ids(&wrapped), ["W-1"]);
+    
+    let bare = parse("bare.json", &format!(r#"[{{"record_id": "B-1", {0}}}, {{"record_id": "B-2", {0}}}]"#, RECORD));
+    assert_eq!(ids(&bare), ["B-1", "B-2"]);
+}
+
+#[test]
+fn json_lines_are_read_line_by_line() {
+    let content = format!(
+        "{{\"record_id\": \"L-1\", {0}}}\n\n{{\"record_id\": \"L-2\", {0}\n{{\"record_id\": \"L-3\", {0}}}\n",
+        RECORD
+    );
+    let parsed = parse("burials.ndjson", &content);
+    assert_eq!(ids(&parsed), ["L-1", "L-3"]);
+    
+    // The broken line fails on its own, and is kept as written
+    assert_eq!(parsed.errors.len(), 1);
+    assert_eq!(parsed.errors[0].line_number, Some(3));
+    let kept = parsed.errors[0].source_fields.as_ref().unwrap().as_str().unwrap();
+    assert!(kept.starts_with("{\"record_id\": \"L-2\""), "{}", kept);
+    
+    // By content, whatever the extension
+    let single = parse("burials.json", &format!("{{\"record_id\": \"L-9\", {}}}\n", RECORD));
+    assert_eq!(ids(&single), ["L-9"]);
+}
+
+#[test]
+fn documents_without_records_still_fail() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("burials.json");
+    std::fs::write(&path, "{\"data\": {\"count\": 2},\n \"source\": \"registry\"}").unwrap();
+    
+    let error = DataParser::detect_and_parse(&path, &ParseOptions::default()).unwrap_err();
+    assert_eq!(error.to_string(), "No array of records at '/records'");
+    
+    // Nor is a one-line document with its records under another key
+    std::fs::write(&path, format!("{{\"data\": [{{\"record_id\": \"D-1\", {}}}]}}", RECORD)).unwrap();
+    let error = DataParser::detect_and_parse(&path, &ParseOptions::default()).unwrap_err();
+    assert_eq!(error.to_string(), "No array of records at '/records'");
+    
+    assert!(is_supported_file(Path::new("burials.jsonl")));
+    assert!(is_supported_file(Path::new("burials.ndjson.gz")));
+}
@@ -0,0 +1,108 @@
+use najaf_cemetery_processor::parser::{DataParser, JsonSchema, ParseOptions, ParsedFile};
+use serde_json::json;
+
+fn parse(document: serde_json::Value, options: &ParseOptions) -> Result<ParsedFile, anyhow::Error> {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("burials.json");
+    std::fs::write(&path, document.to_string()).unwrap();
+    
+    DataParser::parse_json_file(&path, options)
+}
+
+#[test]
+fn the_default_schema_reads_the_documented_layout() {
+    let parsed = parse(
+        json!({ "records": [{
+            "record_id": "2024001",
+            "deceased_name": "John Doe",
+            "death_date": "2024-10-31",
+            "burial_date": "2024-11-01",
+            "burial_location": "Wadi al-Salam",
+            "coordinates": { "latitude": 32.0175, "longitude": 44.3142 },
+            "location": { "section": "A", "row": 12, "plot": 45, "block": "north" },
+            "imam": "Sayyid Hassan"
+        }] }),
+        &ParseOptions::default(),
+    )
+    .unwrap();
+    
+    assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+    let record = &parsed.records[0];
+    assert_eq!((record.latitude, record.longitude), (Some(32.0175), Some(44.3142)));
+    assert_eq!((record.section.as_deref(), record.row_number, record.plot_number), (Some("A"), Some(12), Some(45)));
+    assert_eq!(
+        record.additional_data,
+        Some(json!({ "location": { "block": "north" }, "imam": "Sayyid Hassan" }))
+    );
+}
+
+#[test]
+fn a_schema_maps_flat_and_renamed_keys() {
+    let schema: JsonSchema = serde_json::from_value(json!({
+        "records": "/data/burials",
+        "fields": {
+            "record_id": "/id",
+            "deceased_name": "/name",
+            "death_date": "/died",
+            "burial_date": "/buried",
+            "burial_location": "/cemetery",
+            "latitude": "/lat",
+            "longitude": "/lon",
+            "plot": "/grave/plot"
+        }
+    }))
+    .unwrap();
+    let options = ParseOptions { json_schema: Some(schema), ..ParseOptions::default() };
+    
+    let parsed = parse(
+        json!({ "data": { "burials": [
+            {
+                "id": 2024001, "name": "John Doe", "died": "2024-10-31", "buried": "2024-11-01",
+                "cemetery": "Wadi al-Salam", "lat": "32.0175", "lon": 44.3142, "grave": { "plot": 45 }
+            },
+            { "id": "2024002", "name": "Jane Doe", "died": "2024-10-31", "buried": "2024-11-01" },
+            { "id": "2024003", "name": "Ali", "died": "2024-10-31", "buried": "2024-11-01", "cemetery": "Wadi al-Salam", "lat": "north" }
+        ] } }),
+        &options,
+    )
+    .unwrap();
+    
+    assert_eq!(parsed.records.len(), 1);
+    let record = &parsed.records[0];
+    assert_eq!(record.record_id, "2024001");
+    assert_eq!((record.latitude, record.longitude, record.plot_number), (Some(32.0175), Some(44.3142), Some(45)));
+    assert_eq!(record.additional_data, None);
+    
+    let messages: Vec<_> = parsed.errors.iter().map(|e| (e.record_id.as_deref(), e.message.as_str())).collect();
+    assert_eq!(
+        messages,
+        vec![
+            (Some("2024002"), "record #2: missing field `burial_location`"),
+            (Some("2024003"), "record #3: latitude: expected a number, got 'north'"),
+        ]
+    );
+}
+
+#[test]
+fn schemas_missing_required_fields_are_rejected() {
+    let schema = JsonSchema {
+        fields: [("record_id", "/id"), ("deceased_name", "/name"), ("grave", "/grave")]
+            .iter()
+            .map(|(field, pointer)| (field.to_string(), pointer.to_string()))
+            .collect(),
+        ..JsonSchema::default()
+    };
+    assert_eq!(schema.validate().unwrap_err().to_string(), "JSON schema maps unknown field 'grave'");
+    
+    let mut schema = JsonSchema::default();
+    schema.fields.remove("burial_date");
+    assert_eq!(
+        schema.validate().unwrap_err().to_string(),
+        "JSON schema does not map required field 'burial_date'"
+    );
+    
+    let schema = JsonSchema { records: "/burials".to_string(), ..JsonSchema::default() };
+    let options = ParseOptions { json_schema: Some(schema), ..ParseOptions::default() };
+    let error = parse(json!({ "records": [] }), &options).unwrap_err();
+    assert_eq!(error.to_string(), "No array of records at '/burials'");
+}
@@ -0,0 +1,108 @@
+use najaf_cemetery_processor::location::{GravePlace, LocationLayout, LocationPart};
+use najaf_cemetery_processor::parser::{DataParser, ParseOptions};
+
+fn place(section: Option<&str>, row_number: Option<i32>, plot_number: Option<i32>) -> GravePlace {
+    GravePlace { section: section.map(str::to_string), row_number, plot_number }
+}
+
+#[test]
+fn locations_split_and_compose_as_laid_out() {
+    let layout = LocationLayout::default();
+    assert_eq!(layout.split("A/12/45"), Some(place(Some("A"), Some(12), Some(45))));
+    assert_eq!(layout.split(" B / Row ١٢ / Plot 7 "), Some(place(Some("B"), Some(12), Some(7))));
+    assert_eq!(layout.compose(&place(Some("A"), Some(12), Some(45))).as_deref(), Some("A/12/45"));
+    assert_eq!(layout.compose(&place(Some("A"), None, Some(45))), None);
+    
+    // Free text is not a structured location
+    assert_eq!(layout.split("Wadi al-Salam"), None);
+    assert_eq!(layout.split("A/12"), None);
+    assert_eq!(layout.split("A/twelve/45"), None);
+    
+    let plot_first = LocationLayout {
+        separator: " - ".to_string(),
+        parts: vec![LocationPart::Plot, LocationPart::Section],
+    };
+    assert_eq!(plot_first.split("45-C"), Some(place(Some("C"), None, Some(45))));
+    assert_eq!(plot_first.compose(&place(Some("C"), None, Some(45))).as_deref(), Some("45 - C"));
+}
+
+#[test]
+fn layouts_are_checked() {
+    assert!(LocationLayout::default().validate().is_ok());
+    
+    let blank = LocationLayout { separator: " ".to_string(), ..LocationLayout::default() };
+    assert!(blank.validate().unwrap_err().contains("separator"));
+    let repeated = LocationLayout {
+        parts: vec![LocationPart::Row, LocationPart::Row],
+        ..LocationLayout::default()
+    };
+    assert_eq!(repeated.validate().unwrap_err(), "location_layout: row_number is listed twice");
+    
+    let options: ParseOptions = serde_json::from_str(r#"{"location_layout": {"parts": ["plot"]}}"#).unwrap();
+    assert_eq!(options.location_layout.unwrap().separator, "/");
+}
+
+#[test]
+fn records_fill_in_and_flag_their_locations() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("register.csv");
+    std::fs::write(
+        &path,
+        "record_id,deceased_name,death_date,burial_date,burial_location,section,row,plot\n\
+         L-1,Ali Hassan,2024-10-31,2024-11-01,A/12/45,,,\n\
+         L-2,Zainab Kadhim,2024-10-31,2024-11-01,,B,3,9\n\
+         L-3,Hussein Jawad,2024-10-31,2024-11-01,A/12/45,a,12,46\n\
+         L-4,Fatima Ali,2024-10-31,2024-11-01,Wadi al-Salam,C,,\n",
+    )
+    .unwrap();
+    
+    let options = ParseOptions { location_layout: Some(LocationLayout::default()), ..ParseOptions::default() };
+    let parsed = DataParser::parse_csv_file(&path, &options).unwrap();
+    assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+    let records = &parsed.records;
+    
+    let filled = &records[0];
+    assert_eq!((filled.section.as_deref(), filled.row_number, filled.plot_number), (Some("A"), Some(12), Some(45)));
+    assert_eq!(records[1].burial_location, "B/3/9");
+    
+    // The columns win, and the disagreement is kept for review
+    assert_eq!(records[2].plot_number, Some(46));
+    assert_eq!(
+        records[2].location_conflict().as_deref(),
+        Some("burial_location 'A/12/45' gives plot_number 45, but the column has 46")
+    );
+    assert_eq!(records[0].location_conflict(), None);
+    
+    assert_eq!((records[3].burial_location.as_str(), records[3].row_number), ("Wadi al-Salam", None));
+    
+    // Without a layout nothing changes
+    let parsed = DataParser::parse_csv_file(&path, &ParseOptions::default()).unwrap();
+    assert_eq!(parsed.records[0].section, None);
+    assert_eq!(parsed.records[1].burial_location, "");
+}
+
+#[test]
+fn json_records_may_leave_the_location_out() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("register.json");
+    std::fs::write(
+        &path,
+        r#"[{"record_id": "J-1", "deceased_name": "Ali Hassan", "death_date": "2024-10-31",
+             "burial_date": "2024-11-01", "location": {"section": "D", "row": 4, "plot": 18}},
+            {"record_id": "J-2", "deceased_name": "Zainab Kadhim", "death_date": "2024-10-31",
+             "burial_date": "2024-11-01", "burial_location": "E/5/20", "location": {"section": " "}}]"#,
+    )
+    .unwrap();
+    
+    let parsed = DataParser::parse_json_file(&path, &ParseOptions::default()).unwrap();
+    assert_eq!(parsed.records.iter().map(|record| record.record_id.as_str()).collect::<Vec<_>>(), ["J-2"]);
+    
+    let options = ParseOptions { location_layout: Some(LocationLayout::default()), ..ParseOptions::default() };
+    let parsed = DataParser::parse_json_file(&path, &options).unwrap();
+    assert_eq!(parsed.records[0].burial_location, "D/4/18");
+    
+    // A blank section is no section, so the location fills it in
+    let filled = &parsed.records[1];
+    assert_eq!((filled.section.as_deref(), filled.row_number, filled.plot_number), (Some("E"), Some(5), Some(20)));
+    assert_eq!(filled.location_conflict(), None);
+}
@@ -0,0 +1,45 @@
+use najaf_cemetery_processor::models::ParseMode;
+use najaf_cemetery_processor::processor::{ErrorLimit, RunOptions, ERROR_PERCENT_MIN_RECORDS};
+
+#[test]
+fn limits_are_counts_or_percentages() {
+    let options: RunOptions = serde_json::from_str(r#"{"max_errors": 500}"#).unwrap();
+    assert_eq!(options.max_errors, Some(ErrorLimit::Count(500)));
+    
+    let options: RunOptions = serde_json::from_str(r#"{"max_errors": "2.5%"}"#).unwrap();
+    assert_eq!(options.max_errors, Some(ErrorLimit::Percent(2.5)));
+    assert_eq!(serde_json::to_value(options).unwrap()["max_errors"], "2.5%");
+    
+    assert_eq!("40".parse(), Ok(ErrorLimit::Count(40)));
+    assert_eq!(
+        "150%".parse::<ErrorLimit>().unwrap_err(),
+        "expected a number of records or a percentage such as '5%', got '150%'"
+    );
+    assert!(serde_json::from_str::<RunOptions>(r#"{"max_errors": "many"}"#).is_err());
+    assert_eq!(RunOptions::default().max_errors, None);
+}
+
+#[test]
+fn percentages_wait_for_enough_records() {
+    let count = ErrorLimit::Count(10);
+    assert!(!count.exceeded(10, 12, false));
+    assert!(count.exceeded(11, 12, false));
+    
+    // One bad record out of the first few is not yet 10% of the file
+    let percent = ErrorLimit::Percent(10.0);
+    assert!(!percent.exceeded(3, 5, false));
+    assert!(percent.exceeded(3, 5, true));
+    assert!(!percent.exceeded(10, ERROR_PERCENT_MIN_RECORDS, false));
+    assert!(percent.exceeded(11, ERROR_PERCENT_MIN_RECORDS, false));
+}
+
+#[test]
+fn runs_are_lenient_unless_asked_to_be_strict() {
+    assert_eq!(RunOptions::default().parse_mode, ParseMode::Lenient);
+    
+    let options: RunOptions = serde_json::from_str(r#"{"parse_mode": "strict"}"#).unwrap();
+    assert_eq!(options.parse_mode, ParseMode::Strict);
+    assert_eq!(serde_json::to_value(options).unwrap()["parse_mode"], "strict");
+    
+    assert!(serde_json::from_str::<RunOptions>(r#"{"parse_mode": "forgiving"}"#).is_err());
+}
@@ -0,0 +1,20 @@
+use najaf_cemetery_processor::metrics::Metrics;
+use sqlx::postgres::PgPoolOptions;
+
+#[tokio::test]
+async fn metrics_render_in_prometheus_text_format() {
+    let pool = PgPoolOptions::new()
+        .connect_lazy("postgres://localhost/unused")
+        .unwrap();
+    let metrics = Metrics::new();
+    
+    metrics.records_inserted.inc_by(42);
+    metrics.db_insert_duration.observe(0.02);
+    
+    let body = metrics.render(&pool).unwrap();
+    
+    assert!(body.contains("# TYPE cemetery_processor_records_inserted_total counter"));
+    assert!(body.contains("cemetery_processor_records_inserted_total 42"));
+    assert!(body.contains("cemetery_processor_db_insert_duration_seconds_bucket{le=\"0.025\"} 1"));
+    assert!(body.contains("cemetery_processor_db_pool_connections 0"));
+}
@@ -0,0 +1,33 @@
+use najaf_cemetery_processor::models::ValidationError;
+use najaf_cemetery_processor::national_id::{
+    normalize_national_id, NationalIdChecksum, NationalIdMode, NationalIdRules,
+};
+
+#[test]
+fn national_ids_are_normalized_before_checking() {
+    assert_eq!(normalize_national_id(" ١٩٨٥-٠١٢٣ ٤٥٦٧ "), Some("198501234567".to_string()));
+    assert_eq!(normalize_national_id("۱۲۳"), Some("123".to_string()));
+    assert_eq!(normalize_national_id("  "), None);
+    
+    let rules = NationalIdRules::default();
+    assert_eq!(rules.check(Some("198501234567")), None);
+    assert_eq!(rules.check(None), None);
+    
+    let problem = rules.check(Some("12345")).unwrap();
+    assert_eq!(problem, ValidationError::InvalidNationalId("12345".to_string()));
+    assert_eq!((problem.code(), problem.field()), ("invalid_national_id", Some("national_id")));
+}
+
+#[test]
+fn pattern_and_checksum_are_configurable() {
+    let rules = NationalIdRules::new(r"^[0-9]{8}$", NationalIdChecksum::Luhn, NationalIdMode::Reject).unwrap();
+    
+    assert_eq!(rules.check(Some("1234567")), Some(ValidationError::InvalidNationalId("1234567".to_string())));
+    assert_eq!(rules.check(Some("12345674")), None);
+    assert_eq!(rules.check(Some("12345678")), Some(ValidationError::InvalidNationalId("12345678".to_string())));
+    
+    let off = NationalIdRules::new(r"^[0-9]{8}$", NationalIdChecksum::None, NationalIdMode::Off).unwrap();
+    assert_eq!(off.check(Some("not an id")), None);
+    
+    assert!(NationalIdRules::new("[0-9", NationalIdChecksum::None, NationalIdMode::Warn).is_err());
+}
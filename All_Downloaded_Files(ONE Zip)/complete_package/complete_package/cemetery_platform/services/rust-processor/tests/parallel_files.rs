@@ -0,0 +1,77 @@
+use najaf_cemetery_processor::models::ErrorDetails;
+use najaf_cemetery_processor::parser::{DataParser, ParseOptions};
+use najaf_cemetery_processor::processor::parse_files;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+const HEADER: &str = "record_id,deceased_name,death_date,burial_date,burial_location,section";
+
+/// Files of different sizes, so parsers finish out of order, with a bad date
+/// in some and one file that cannot be parsed at all.
+fn write_fixtures(dir: &Path) -> Vec<(String, PathBuf)> {
+    let mut files = Vec::new();
+    
+    for file in 0..12 {
+        let mut contents = format!("{}\n", HEADER);
+        for row in 0..(12 - file) * 400 {
+            let death_date = if row % 997 == 5 { "not-a-date" } else { "2024-10-31" };
+            writeln!(
+                contents,
+                "{:02}{:06},Person {},{},2024-11-01,Wadi al-Salam,{}",
+                file, row, row, death_date, file
+            ).unwrap();
+        }
+        
+        let name = format!("batch_{:02}.csv", file);
+        std::fs::write(dir.join(&name), contents).unwrap();
+        files.push((name.clone(), dir.join(name)));
+    }
+    
+    std::fs::write(dir.join("broken.json"), "{ not json").unwrap();
+    files.insert(5, ("broken.json".to_string(), dir.join("broken.json")));
+    
+    files
+}
+
+/// What processing the files one after another reads.
+fn parse_sequentially(files: &[(String, PathBuf)], options: &ParseOptions) -> Vec<String> {
+    let mut rows = Vec::new();
+    
+    for (name, path) in files {
+        match DataParser::detect_and_stream(path, options) {
+            Ok(stream) => rows.extend(stream.map(|row| match row {
+                Ok(record) => format!("{:?}", record),
+                Err(e) => format!("{:?}", ErrorDetails { message: format!("{}: {}", name, e.message), ..e }),
+            })),
+            Err(e) => rows.push(format!("Failed to parse file {:?}: {}", name, e)),
+        }
+    }
+    
+    rows
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn parallel_parsing_matches_sequential_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let files = write_fixtures(dir.path());
+    let options = ParseOptions::default();
+    
+    let expected = parse_sequentially(&files, &options);
+    
+    for parallelism in [1, 3, 8] {
+        let mut rows = Vec::new();
+        
+        for mut file in parse_files(files.clone(), &options, parallelism) {
+            while let Some(row) = file.rows.recv().await {
+                rows.push(match row {
+                    Ok(record) => format!("{:?}", record),
+                    Err(e) if e.line_number.is_none() && e.record_id.is_none() => e.message,
+                    Err(e) => format!("{:?}", e),
+                });
+            }
+        }
+        
+        assert_eq!(rows.len(), expected.len(), "parallelism {}", parallelism);
+        assert!(rows == expected, "parallelism {} changed the output", parallelism);
+    }
+}
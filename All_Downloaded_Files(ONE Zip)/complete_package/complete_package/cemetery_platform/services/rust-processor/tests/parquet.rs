@@ -0,0 +1,125 @@
+use arrow::array::{ArrayRef, Date32Array, Float64Array, Int32Array, Int64Array, RecordBatch, StringArray};
+use chrono::NaiveDate;
+use najaf_cemetery_processor::parser::{ColumnRef, CsvSchema, DataParser, ParseOptions};
+use parquet::arrow::ArrowWriter;
+use std::path::Path;
+use std::sync::Arc;
+
+fn ymd(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap()
+}
+
+fn days(date: NaiveDate) -> i32 {
+    (date - ymd(1970, 1, 1)).num_days() as i32
+}
+
+fn write_parquet(path: &Path, columns: Vec<(&str, ArrayRef)>) {
+    let batch = RecordBatch::try_from_iter(columns).unwrap();
+    let mut writer = ArrowWriter::try_new(std::fs::File::create(path).unwrap(), batch.schema(), None).unwrap();
+    writer.write(&batch).unwrap();
+    writer.close().unwrap();
+}
+
+fn text(values: &[Option<&str>]) -> ArrayRef {
+    Arc::new(StringArray::from(values.to_vec()))
+}
+
+#[test]
+fn reads_native_dates_and_numbers() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("burials.parquet");
+    
+    write_parquet(&path, vec![
+        ("record_id", Arc::new(Int64Array::from(vec![1001, 1002])) as ArrayRef),
+        ("deceased_name", text(&[Some("Ali Hassan"), Some("Zainab Kadhim")])),
+        ("death_date", Arc::new(Date32Array::from(vec![days(ymd(2024, 1, 10)), days(ymd(2024, 2, 1))]))),
+        ("burial_date", text(&[Some("2024-01-11"), Some("1445-07-22H")])),
+        ("burial_location", text(&[Some("Wadi al-Salam"), Some("Wadi al-Salam")])),
+        ("birth_date", text(&[Some(" "), Some("1950-03-02")])),
+        ("latitude", Arc::new(Float64Array::from(vec![Some(32.0123), None]))),
+        ("longitude", Arc::new(Float64Array::from(vec![Some(44.3312), None]))),
+        ("row", Arc::new(Int32Array::from(vec![Some(7), None]))),
+        ("plot", Arc::new(Int32Array::from(vec![Some(12), None]))),
+        ("source_batch", text(&[Some("1998-scan"), None])),
+    ]);
+    
+    let parsed = DataParser::detect_and_parse(&path, &ParseOptions::default()).unwrap();
+    assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+    assert_eq!(parsed.records.len(), 2);
+    
+    let first = &parsed.records[0];
+    assert_eq!(first.record_id, "1001");
+    assert_eq!(first.death_date, ymd(2024, 1, 10));
+    assert_eq!(first.burial_date, ymd(2024, 1, 11));
+    assert_eq!(first.latitude, Some(32.0123));
+    assert_eq!(first.row_number, Some(7));
+    assert_eq!(first.plot_number, Some(12));
+    assert_eq!(first.birth_date, None);
+    assert_eq!(first.additional_data, Some(serde_json::json!({ "source_batch": "1998-scan" })));
+    
+    // Text dates are parsed as in CSV files, Hijri included
+    let second = &parsed.records[1];
+    assert_eq!(second.burial_date, ymd(2024, 2, 2));
+    assert_eq!(second.birth_date, Some(ymd(1950, 3, 2)));
+    assert_eq!(second.latitude, None);
+    assert_eq!(second.additional_data, Some(serde_json::json!({ "raw_burial_date": "1445-07-22H" })));
+}
+
+#[test]
+fn schema_mismatches_are_listed_up_front() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("burials.parquet");
+    
+    write_parquet(&path, vec![
+        ("record_id", text(&[Some("R1")])),
+        ("deceased_name", text(&[Some("Ali Hassan")])),
+        ("death_date", Arc::new(Float64Array::from(vec![45300.0])) as ArrayRef),
+        ("burial_date", text(&[Some("2024-01-11")])),
+        ("latitude", text(&[Some("32.0123")])),
+    ]);
+    
+    let error = DataParser::detect_and_parse(&path, &ParseOptions::default()).unwrap_err().to_string();
+    assert!(error.starts_with("Parquet schema does not match"), "{}", error);
+    assert!(error.contains("column 'death_date' for 'death_date' is Float64, expected a date"), "{}", error);
+    assert!(error.contains("column 'burial_location' for required field 'burial_location' not found"), "{}", error);
+    assert!(error.contains("column 'latitude' for 'latitude' is Utf8, expected a number"), "{}", error);
+}
+
+#[test]
+fn columns_follow_the_csv_schema() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("burials.parquet");
+    
+    write_parquet(&path, vec![
+        ("ID", text(&[Some("R1"), Some("R2")])),
+        ("NAME", text(&[Some("Ali Hassan"), None])),
+        ("DOD", Arc::new(Date32Array::from(vec![days(ymd(2024, 1, 10)); 2])) as ArrayRef),
+        ("DOB", Arc::new(Date32Array::from(vec![days(ymd(2024, 1, 11)); 2])) as ArrayRef),
+        ("CEMETERY", text(&[Some("Wadi al-Salam"), Some("Wadi al-Salam")])),
+    ]);
+    
+    let mapping = [
+        ("record_id", "ID"),
+        ("deceased_name", "NAME"),
+        ("death_date", "DOD"),
+        ("burial_date", "DOB"),
+        ("burial_location", "CEMETERY"),
+    ];
+    let options = ParseOptions {
+        csv_schema: Some(CsvSchema {
+            columns: mapping.iter()
+                .map(|(field, column)| (field.to_string(), ColumnRef::Header(column.to_string())))
+                .collect(),
+        }),
+        ..ParseOptions::default()
+    };
+    
+    let parsed = DataParser::parse_parquet_file(&path, &options).unwrap();
+    assert_eq!(parsed.records.len(), 1);
+    assert_eq!(parsed.records[0].burial_date, ymd(2024, 1, 11));
+    
+    // A null in a required column fails only that row
+    assert_eq!(parsed.errors.len(), 1);
+    assert_eq!(parsed.errors[0].record_id.as_deref(), Some("R2"));
+    assert_eq!(parsed.errors[0].message, "row 2: missing field `deceased_name`");
+}
@@ -0,0 +1,515 @@
+use chrono::{Days, NaiveDate};
+use najaf_cemetery_processor::database::Database;
+use najaf_cemetery_processor::export::{features_to_kml, stream_features_kml};
+use najaf_cemetery_processor::models::{
+    DbDeceasedRecord, DeceasedRecord, FileMetadata, GeoJsonGeometry, ParseMode, RecordCursor, RecordFilter, RecordStatus, Rejection,
+};
+use najaf_cemetery_processor::parser::ParseOptions;
+use najaf_cemetery_processor::processor::{compute_sha256, DataProcessor, RunOptions};
+use najaf_cemetery_processor::progress::ProgressReporter;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+const SOURCE_FILE: &str = "deceased_2024-11-01.zip";
+
+fn record(record_id: &str, coordinates: Option<(f64, f64)>) -> DeceasedRecord {
+    DeceasedRecord {
+        record_id: record_id.to_string(),
+        deceased_name: format!("Person {}", record_id),
+        deceased_name_arabic: None,
+        father_name: None,
+        grandfather_name: None,
+        death_date: NaiveDate::from_ymd_opt(2024, 10, 31).unwrap(),
+        death_location: None,
+        burial_date: NaiveDate::from_ymd_opt(2024, 11, 1).unwrap(),
+        burial_location: "Wadi al-Salam".to_string(),
+        section: Some("A".to_string()),
+        row_number: Some(12),
+        plot_number: Some(45),
+        grave_number: None,
+        latitude: coordinates.map(|(latitude, _)| latitude),
+        longitude: coordinates.map(|(_, longitude)| longitude),
+        footprint: None,
+        birth_date: None,
+        age_at_death: None,
+        cause_of_death: None,
+        cause_of_death_code: None,
+        national_id: None,
+        family_contact: None,
+        additional_data: None,
+        processing_status: RecordStatus::default(),
+    }
+}
+
+#[sqlx::test]
+#[ignore = "needs a PostGIS server at DATABASE_URL"]
+async fn coordinates_round_trip(pool: PgPool) {
+    let db = Database::new(pool);
+    
+    let mut tx = db.begin().await.unwrap();
+    db.insert_deceased_record(&mut tx, &record("R-1", Some((32.017512, 44.314287))), SOURCE_FILE)
+        .await
+        .unwrap();
+    db.insert_deceased_record(&mut tx, &record("R-2", None), SOURCE_FILE).await.unwrap();
+    tx.commit().await.unwrap();
+    
+    let mapped = db.get_record("R-1").await.unwrap().unwrap();
+    assert_eq!((mapped.latitude, mapped.longitude), (Some(32.017512), Some(44.314287)));
+    assert_eq!(mapped.source_file.as_deref(), Some(SOURCE_FILE));
+    
+    let unmapped = db.get_record("R-2").await.unwrap().unwrap();
+    assert_eq!((unmapped.latitude, unmapped.longitude), (None, None));
+    
+    // Listings, R-2 first as the later of one day's burials, and searches
+    // split the point up too
+    let listed = db.list_records(&RecordFilter::default(), None, 10, 0).await.unwrap();
+    let points: Vec<_> = listed.iter().map(|record| (record.latitude, record.longitude)).collect();
+    assert_eq!(points, [(None, None), (Some(32.017512), Some(44.314287))]);
+    
+    let found = db.search_records("person r-1", 10, 0).await.unwrap();
+    assert_eq!((found[0].record.latitude, found[0].record.longitude), (Some(32.017512), Some(44.314287)));
+}
+
+#[sqlx::test]
+#[ignore = "needs a PostGIS server at DATABASE_URL"]
+async fn batches_update_records_that_exist(pool: PgPool) {
+    let db = Database::new(pool);
+    
+    let first: Vec<_> = ["B-1", "B-2", "B-3"].iter().map(|id| record(id, Some((32.0175, 44.3142)))).collect();
+    let mut tx = db.begin().await.unwrap();
+    let result = db.insert_batch(&mut tx, &first, SOURCE_FILE).await.unwrap();
+    tx.commit().await.unwrap();
+    assert_eq!((result.inserted, result.errors.len()), (3, 0));
+    
+    // A redelivery corrects one record and adds another
+    let corrected = DeceasedRecord {
+        deceased_name: "Ali Hassan".to_string(),
+        latitude: Some(32.0181),
+        ..record("B-2", Some((32.0175, 44.3142)))
+    };
+    let second = vec![corrected, record("B-4", None)];
+    let mut tx = db.begin().await.unwrap();
+    let result = db.insert_batch(&mut tx, &second, "deceased_2024-11-02.zip").await.unwrap();
+    tx.commit().await.unwrap();
+    assert_eq!((result.inserted, result.errors.len()), (2, 0));
+    
+    let updated = db.get_record("B-2").await.unwrap().unwrap();
+    assert_eq!(updated.deceased_name, "Ali Hassan");
+    assert_eq!(updated.latitude, Some(32.0181));
+    assert_eq!(updated.source_file.as_deref(), Some("deceased_2024-11-02.zip"));
+    assert_eq!(db.count_records(&RecordFilter::default()).await.unwrap(), 4);
+}
+
+#[sqlx::test]
+#[ignore = "needs a PostGIS server at DATABASE_URL"]
+async fn a_bad_row_fails_only_itself_in_a_large_batch(pool: PgPool) {
+    let db = Database::new(pool);
+    
+    let mut records: Vec<_> = (0..10_000)
+        .map(|index| record(&format!("L-{}", index), Some((32.0175, 44.3142))))
+        .collect();
+    // Longer than section's VARCHAR(50), so chunk 4 falls back to row by row
+    records[4321].section = Some("x".repeat(51));
+    
+    let mut tx = db.begin().await.unwrap();
+    let result = db.insert_batch(&mut tx, &records, SOURCE_FILE).await.unwrap();
+    tx.commit().await.unwrap();
+    
+    assert_eq!(result.inserted, 9_999);
+    assert_eq!(result.errors.len(), 1);
+    assert_eq!(result.errors[0].record_id.as_deref(), Some("L-4321"));
+    assert!(result.errors[0].message.starts_with("Database insert failed in chunk 4: "), "{}", result.errors[0].message);
+    
+    // Every other row is stored, the rest of chunk 4 included
+    let ids: Vec<String> = records.iter().map(|record| record.record_id.clone()).collect();
+    let stored = db.existing_record_ids(None, &ids).await.unwrap();
+    let missing: Vec<_> = ids.iter().filter(|id| !stored.contains(*id)).collect();
+    assert_eq!(missing, ["L-4321"]);
+}
+
+#[sqlx::test]
+#[ignore = "needs a PostGIS server at DATABASE_URL"]
+async fn a_failed_run_leaves_no_records_and_is_logged(pool: PgPool) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("burials.csv");
+    let mut csv = String::from("record_id,deceased_name,death_date,burial_date,burial_location,section\n");
+    for index in 0..2_500 {
+        // Longer than section's VARCHAR(50), so its insert violates the column
+        let section = if index == 1_500 { "x".repeat(51) } else { "A".to_string() };
+        csv.push_str(&format!("T-{},Person {},2024-10-31,2024-11-01,Wadi al-Salam,{}\n", index, index, section));
+    }
+    std::fs::write(&path, csv).unwrap();
+    
+    let processor = DataProcessor::new(Arc::new(pool.clone())).with_batch_size(1_000);
+    let metadata = FileMetadata {
+        filename: "burials.csv".to_string(),
+        file_hash: String::new(),
+        size: 0,
+        download_time: "2024-11-01T08:00:00Z".to_string(),
+        extracted_path: None,
+        encoding: None,
+    };
+    // Strict runs stop at the first failed record, with a chunk written
+    let run = RunOptions { parse_mode: ParseMode::Strict, ..RunOptions::default() };
+    
+    let error = processor
+        .process_single_file(path.to_str().unwrap(), &metadata, &ParseOptions::default(), run, ProgressReporter::default())
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("parse_mode is strict"), "{}", error);
+    
+    let db = Database::new(pool);
+    assert_eq!(db.count_records(&RecordFilter::default()).await.unwrap(), 0);
+    
+    let entries = db.get_processing_log(Some("failed"), 10, 0).await.unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].filename, "burials.csv");
+    assert_eq!(entries[0].error_message.as_deref(), Some(error.to_string().as_str()));
+}
+
+#[sqlx::test]
+#[ignore = "needs a PostGIS server at DATABASE_URL"]
+async fn directory_runs_leave_out_files_already_imported(pool: PgPool) {
+    let dir = tempfile::tempdir().unwrap();
+    let write = |name: &str, record_id: &str| {
+        std::fs::write(
+            dir.path().join(name),
+            format!(
+                "record_id,deceased_name,death_date,burial_date,burial_location\n\
+                 {},Ali Hassan,2024-10-31,2024-11-01,Wadi al-Salam\n",
+                record_id
+            ),
+        )
+        .unwrap();
+    };
+    let processor = DataProcessor::new(Arc::new(pool.clone()));
+    let metadata = FileMetadata {
+        filename: "november".to_string(),
+        file_hash: String::new(),
+        size: 0,
+        download_time: "2024-11-01T08:00:00Z".to_string(),
+        extracted_path: None,
+        encoding: None,
+    };
+    let process = |run: RunOptions| {
+        let (processor, metadata, dir) = (&processor, &metadata, dir.path().to_str().unwrap());
+        async move {
+            processor
+                .process_directory(dir, metadata, &ParseOptions::default(), run, ProgressReporter::default())
+                .await
+                .unwrap()
+        }
+    };
+    
+    write("a.csv", "P-1");
+    write("b.csv", "P-2");
+    let first = process(RunOptions::default()).await;
+    assert_eq!((first.records_read, first.files_already_processed.len()), (2, 0));
+    
+    let db = Database::new(pool);
+    let a_hash = compute_sha256(&dir.path().join("a.csv")).unwrap();
+    assert!(db.is_file_already_processed(&a_hash).await.unwrap());
+    
+    // A new file changes the directory's hash, but only it is read
+    write("c.csv", "P-3");
+    let second = process(RunOptions::default()).await;
+    assert!(!second.already_processed);
+    assert_eq!(second.records_read, 1);
+    assert_eq!(second.files_already_processed, ["a.csv", "b.csv"]);
+    
+    let forced: RunOptions = serde_json::from_str(r#"{"force_reprocess": true}"#).unwrap();
+    let third = process(forced).await;
+    assert_eq!((third.records_read, third.files_already_processed.len()), (3, 0));
+    assert_eq!(db.count_records(&RecordFilter::default()).await.unwrap(), 3);
+}
+
+#[sqlx::test]
+#[ignore = "needs a PostGIS server at DATABASE_URL"]
+async fn records_are_listed_a_page_at_a_time(pool: PgPool) {
+    let db = Database::new(pool);
+    // Three burials a day over 40 days, the days not in insertion order
+    let first_day = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+    let records: Vec<_> = (1..=120)
+        .map(|index| DeceasedRecord {
+            burial_date: first_day + Days::new(index % 40),
+            ..record(&format!("G-{:03}", index), None)
+        })
+        .collect();
+    let mut tx = db.begin().await.unwrap();
+    db.insert_batch(&mut tx, &records, SOURCE_FILE).await.unwrap();
+    tx.commit().await.unwrap();
+    
+    // Latest burials first, and the last inserted first within a day
+    let mut expected: Vec<_> = records.iter().map(|record| (record.burial_date, record.record_id.clone())).collect();
+    expected.sort_by(|a, b| b.cmp(a));
+    let expected: Vec<String> = expected.into_iter().map(|(_, record_id)| record_id).collect();
+    
+    let filter = RecordFilter::default();
+    let ids = |page: &[DbDeceasedRecord]| page.iter().map(|record| record.record_id.clone()).collect::<Vec<_>>();
+    let after = |page: &[DbDeceasedRecord]| {
+        page.last().map(|last| RecordCursor { burial_date: last.burial_date, id: last.id })
+    };
+    assert_eq!(db.count_records(&filter).await.unwrap(), 120);
+    
+    let first = db.list_records(&filter, None, 50, 0).await.unwrap();
+    assert_eq!(ids(&first), expected[..50]);
+    assert_eq!((first[0].record_id.as_str(), first[1].record_id.as_str()), ("G-119", "G-079"));
+    
+    // The last page is short, by offset or by cursor alike
+    let last = db.list_records(&filter, None, 50, 100).await.unwrap();
+    assert_eq!(ids(&last), expected[100..]);
+    
+    let second = db.list_records(&filter, after(&first), 50, 0).await.unwrap();
+    assert_eq!(ids(&second), expected[50..100]);
+    
+    // The cursor does not need its record to still be there
+    assert!(db.hard_delete_record(&second[49].record_id).await.unwrap());
+    let third = db.list_records(&filter, after(&second), 50, 0).await.unwrap();
+    assert_eq!(ids(&third), ids(&last));
+    assert!(db.list_records(&filter, after(&third), 50, 0).await.unwrap().is_empty());
+}
+
+#[sqlx::test]
+#[ignore = "needs a PostGIS server at DATABASE_URL"]
+async fn records_are_found_near_a_point(pool: PgPool) {
+    let db = Database::new(pool);
+    
+    // About 44 m, 300 m and 2.2 km north of the search point
+    let records = vec![
+        record("N-far", Some((32.0375, 44.3142))),
+        record("N-mid", Some((32.0202, 44.3142))),
+        record("N-near", Some((32.0179, 44.3142))),
+        record("N-none", None),
+    ];
+    let mut tx = db.begin().await.unwrap();
+    db.insert_batch(&mut tx, &records, SOURCE_FILE).await.unwrap();
+    tx.commit().await.unwrap();
+    
+    let nearby = db.nearby_records(32.0175, 44.3142, 500.0, 50).await.unwrap();
+    let ids: Vec<_> = nearby.iter().map(|found| found.record.record_id.as_str()).collect();
+    assert_eq!(ids, ["N-near", "N-mid"]);
+    assert!((nearby[0].distance_m - 44.4).abs() < 2.0, "{}", nearby[0].distance_m);
+    assert!((nearby[1].distance_m - 299.4).abs() < 5.0, "{}", nearby[1].distance_m);
+    
+    assert_eq!(db.nearby_records(32.0175, 44.3142, 500.0, 1).await.unwrap().len(), 1);
+}
+
+#[sqlx::test]
+#[ignore = "needs a PostGIS server at DATABASE_URL"]
+async fn features_are_built_from_record_points(pool: PgPool) {
+    let db = Database::new(pool);
+    
+    let records = vec![
+        record("F-1", Some((32.0175, 44.3142))),
+        record("F-2", Some((32.0177, 44.3145))),
+        record("F-3", None),
+    ];
+    let mut tx = db.begin().await.unwrap();
+    db.insert_batch(&mut tx, &records, SOURCE_FILE).await.unwrap();
+    let counts = db.create_geojson_features(&mut tx, false).await.unwrap();
+    tx.commit().await.unwrap();
+    assert_eq!((counts.written, counts.skipped_invalid), (2, 0));
+    
+    let features = db.get_features(None, 10, 0).await.unwrap();
+    let points: Vec<_> = features.iter().map(|feature| &feature.geometry).collect();
+    assert_eq!(
+        points,
+        [&GeoJsonGeometry::Point([44.3142, 32.0175]), &GeoJsonGeometry::Point([44.3145, 32.0177])]
+    );
+    
+    // The streamed KML export is the same document
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(4);
+    stream_features_kml(db.clone(), None, None, sender).await;
+    let mut kml = Vec::new();
+    while let Some(chunk) = receiver.recv().await {
+        kml.extend(chunk.unwrap());
+    }
+    assert_eq!(String::from_utf8(kml).unwrap(), features_to_kml(&features));
+}
+
+#[sqlx::test]
+#[ignore = "needs a PostGIS server at DATABASE_URL"]
+async fn content_is_logged_once_unless_forced(pool: PgPool) {
+    let db = Database::new(pool);
+    let log = |force: bool| {
+        let db = &db;
+        async move {
+            let mut tx = db.begin().await.unwrap();
+            let logged = db
+                .log_file_processing(&mut tx, SOURCE_FILE, "9f86d081", 1024, None, 3, 2, 1, 2, "completed", None, force)
+                .await
+                .unwrap();
+            tx.commit().await.unwrap();
+            logged
+        }
+    };
+    
+    assert!(!db.is_file_already_processed("9f86d081").await.unwrap());
+    assert!(log(false).await);
+    assert!(db.is_file_already_processed("9f86d081").await.unwrap());
+    assert!(!log(false).await);
+    assert!(log(true).await);
+    
+    let entries = db.get_processing_log(Some("completed"), 10, 0).await.unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].records_processed, Some(2));
+    assert_eq!(entries[0].records_with_coordinates, Some(2));
+}
+
+#[sqlx::test]
+#[ignore = "needs a PostGIS server at DATABASE_URL"]
+async fn reviewed_records_leave_the_queue(pool: PgPool) {
+    let db = Database::new(pool);
+    
+    let flagged = DeceasedRecord {
+        processing_status: RecordStatus::Flagged,
+        ..record("Q-1", Some((32.0175, 44.3142)))
+    };
+    let unmapped = DeceasedRecord { processing_status: RecordStatus::NeedsReview, ..record("Q-2", None) };
+    let mut tx = db.begin().await.unwrap();
+    db.insert_batch(&mut tx, &[flagged, unmapped, record("Q-3", Some((32.0177, 44.3145)))], SOURCE_FILE)
+        .await
+        .unwrap();
+    db.create_geojson_features(&mut tx, false).await.unwrap();
+    tx.commit().await.unwrap();
+    
+    // Flagged records stay off the map
+    let features = db.get_features(None, 10, 0).await.unwrap();
+    let mapped: Vec<_> = features.iter().map(|feature| &feature.properties["record_id"]).collect();
+    assert_eq!(mapped, [&serde_json::json!("Q-3")]);
+    
+    let queue = RecordFilter { processing_status: Some(RecordStatus::NeedsReview), ..RecordFilter::default() };
+    assert_eq!(db.count_records(&queue).await.unwrap(), 1);
+    
+    assert!(db.review_record("Q-1", RecordStatus::Completed, false).await.unwrap());
+    assert!(db.review_record("Q-2", RecordStatus::Completed, false).await.unwrap());
+    assert!(!db.review_record("Q-9", RecordStatus::Completed, false).await.unwrap());
+    
+    assert_eq!(db.count_records(&queue).await.unwrap(), 0);
+    assert_eq!(db.get_features(None, 10, 0).await.unwrap().len(), 2);
+}
+
+#[sqlx::test]
+#[ignore = "needs a PostGIS server at DATABASE_URL"]
+async fn records_are_filtered_on_additional_data(pool: PgPool) {
+    let db = Database::new(pool);
+    
+    let extra = |registrar: &str| Some(serde_json::json!({ "registrar": registrar, "cemetery_name": "Wadi al-Salam" }));
+    let records = vec![
+        DeceasedRecord { additional_data: extra("Kadhim"), ..record("E-1", None) },
+        DeceasedRecord { additional_data: extra("Jawad"), ..record("E-2", None) },
+        record("E-3", None),
+    ];
+    let mut tx = db.begin().await.unwrap();
+    db.insert_batch(&mut tx, &records, SOURCE_FILE).await.unwrap();
+    tx.commit().await.unwrap();
+    
+    let filter = RecordFilter::default()
+        .with_extra([("extra.cemetery_name", "Wadi al-Salam"), ("extra.registrar", "Kadhim")])
+        .unwrap();
+    let listed = db.list_records(&filter, None, 10, 0).await.unwrap();
+    let ids: Vec<_> = listed.iter().map(|record| record.record_id.as_str()).collect();
+    assert_eq!(ids, ["E-1"]);
+    assert_eq!(db.count_records(&filter).await.unwrap(), 1);
+    
+    let everywhere = RecordFilter::default().with_extra([("extra.cemetery_name", "Wadi al-Salam")]).unwrap();
+    assert_eq!(db.count_records(&everywhere).await.unwrap(), 2);
+}
+
+#[sqlx::test]
+#[ignore = "needs a PostGIS server at DATABASE_URL"]
+async fn corrections_keep_the_previous_record(pool: PgPool) {
+    let db = Database::new(pool);
+    
+    let mut tx = db.begin().await.unwrap();
+    db.insert_batch(&mut tx, &[record("H-1", Some((32.0175, 44.3142)))], SOURCE_FILE).await.unwrap();
+    tx.commit().await.unwrap();
+    assert!(db.record_history("H-1").await.unwrap().unwrap().changes.is_empty());
+    assert!(db.record_history("H-9").await.unwrap().is_none());
+    
+    // The same delivery again changes nothing
+    let mut tx = db.begin().await.unwrap();
+    db.insert_batch(&mut tx, &[record("H-1", Some((32.0175, 44.3142)))], SOURCE_FILE).await.unwrap();
+    tx.commit().await.unwrap();
+    assert!(db.record_history("H-1").await.unwrap().unwrap().changes.is_empty());
+    
+    let corrected = DeceasedRecord {
+        deceased_name: "Ali Hassan".to_string(),
+        ..record("H-1", Some((32.0175, 44.3142)))
+    };
+    let mut tx = db.begin().await.unwrap();
+    db.insert_batch(&mut tx, &[corrected], "deceased_2024-11-02.zip").await.unwrap();
+    tx.commit().await.unwrap();
+    assert!(db.hard_delete_record("H-1").await.unwrap());
+    
+    let history = db.record_history("H-1").await.unwrap().unwrap();
+    let operations: Vec<_> = history.changes.iter().map(|change| change.operation.as_str()).collect();
+    assert_eq!(operations, ["DELETE", "UPDATE"]);
+    
+    let correction = &history.changes[1];
+    assert_eq!(correction.changed_fields, ["deceased_name", "source_file"]);
+    assert_eq!(correction.previous["deceased_name"], "Person H-1");
+    assert_eq!(correction.source_file.as_deref(), Some("deceased_2024-11-02.zip"));
+    assert_eq!(history.changes[0].previous["deceased_name"], "Ali Hassan");
+}
+
+#[sqlx::test]
+#[ignore = "needs a PostGIS server at DATABASE_URL"]
+async fn idempotency_keys_return_the_first_job(pool: PgPool) {
+    let db = Database::new(pool.clone());
+    let request = serde_json::json!({ "data_path": "deceased_2024-11-01" });
+    let hash = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+    let now = chrono::Utc::now();
+    let later = now + chrono::Duration::hours(1);
+    
+    let first = uuid::Uuid::new_v4();
+    assert!(db.create_job_once(first, &request, now, "retry-1", hash, later).await.unwrap().is_none());
+    
+    // The retry queues nothing and finds the first job
+    let retry = uuid::Uuid::new_v4();
+    let existing = db.create_job_once(retry, &request, now, "retry-1", hash, later).await.unwrap().unwrap();
+    assert_eq!((existing.job_id, existing.status.as_str(), existing.request_hash.as_str()), (first, "queued", hash));
+    assert!(db.get_job(retry).await.unwrap().is_none());
+    
+    // Once the key has expired it queues a new job
+    sqlx::query("UPDATE idempotency_keys SET expires_at = now() - interval '1 minute'")
+        .execute(&pool)
+        .await
+        .unwrap();
+    assert!(db.create_job_once(retry, &request, now, "retry-1", hash, later).await.unwrap().is_none());
+    assert_eq!(db.get_job(retry).await.unwrap().unwrap().status, "queued");
+}
+
+#[sqlx::test]
+#[ignore = "needs a PostGIS server at DATABASE_URL"]
+async fn rejected_records_are_listed_by_file(pool: PgPool) {
+    let db = Database::new(pool);
+    let rejection = |file_name: &str, record_id: &str| Rejection {
+        file_name: file_name.to_string(),
+        record_id: Some(record_id.to_string()),
+        line_number: Some(18),
+        fields: Some(serde_json::json!({ "record_id": record_id, "death_date": "31/13/2024" })),
+        errors: serde_json::json!([{ "message": "death_date: unrecognised date '31/13/2024'", "code": null, "field": null }]),
+    };
+    
+    let mut tx = db.begin().await.unwrap();
+    db.insert_rejected_records(&mut tx, SOURCE_FILE, &[rejection("burials.csv", "X-1"), rejection("extra.csv", "X-2")])
+        .await
+        .unwrap();
+    db.insert_rejected_records(&mut tx, "deceased_2024-11-02.zip", &[rejection("burials.csv", "X-3")])
+        .await
+        .unwrap();
+    tx.commit().await.unwrap();
+    
+    let ids = |rejected: Vec<najaf_cemetery_processor::models::RejectedRecord>| {
+        rejected.into_iter().map(|record| record.record_id.unwrap()).collect::<Vec<_>>()
+    };
+    assert_eq!(ids(db.rejected_records(Some(SOURCE_FILE), 10, 0).await.unwrap()), ["X-1", "X-2"]);
+    assert_eq!(ids(db.rejected_records(Some("burials.csv"), 10, 0).await.unwrap()), ["X-1", "X-3"]);
+    assert_eq!(ids(db.rejected_records(None, 2, 1).await.unwrap()), ["X-2", "X-3"]);
+    
+    let kept = &db.rejected_records(Some("extra.csv"), 10, 0).await.unwrap()[0];
+    assert_eq!(kept.line_number, Some(18));
+    assert_eq!(kept.fields.as_ref().unwrap()["death_date"], "31/13/2024");
+    assert_eq!(kept.errors[0]["message"], "death_date: unrecognised date '31/13/2024'");
+}
@@ -0,0 +1,56 @@
+use najaf_cemetery_processor::progress::{estimate_records, JobProgress, ProgressReporter};
+use std::sync::Arc;
+use tokio::sync::watch;
+
+#[test]
+fn progress_is_published_at_file_boundaries_and_throttled_between() {
+    let (sender, receiver) = watch::channel(JobProgress::default());
+    let mut reporter = ProgressReporter::new(Arc::new(sender));
+    
+    reporter.start(2, Some(3));
+    reporter.start_file("a.csv");
+    assert_eq!(receiver.borrow().current_file.as_deref(), Some("a.csv"));
+    
+    reporter.record_read();
+    reporter.record_read();
+    assert_eq!(reporter.progress().records_done, 2);
+    assert_eq!(receiver.borrow().records_done, 0, "updates within the interval are held back");
+    
+    reporter.finish_file();
+    assert_eq!(
+        *receiver.borrow(),
+        JobProgress {
+            files_total: 2,
+            files_done: 1,
+            current_file: None,
+            records_done: 2,
+            records_total: Some(3),
+        }
+    );
+    assert_eq!(receiver.borrow().percent().map(f64::round), Some(67.0));
+    
+    // An archive's contents make the line estimate meaningless
+    reporter.add_files(3);
+    assert_eq!(reporter.progress().records_total, None);
+    assert_eq!(reporter.progress().percent(), Some(20.0));
+}
+
+#[test]
+fn records_are_estimated_from_csv_line_counts() {
+    let dir = tempfile::tempdir().unwrap();
+    let csv = dir.path().join("a.csv");
+    let tsv = dir.path().join("b.TSV");
+    let json = dir.path().join("c.json");
+    std::fs::write(&csv, "record_id\n1\n2\n").unwrap();
+    std::fs::write(&tsv, "record_id\n1\n2\n3").unwrap();
+    std::fs::write(&json, "{\"records\": []}").unwrap();
+    
+    let files = vec![("a.csv".to_string(), csv), ("b.TSV".to_string(), tsv)];
+    assert_eq!(estimate_records(&files), Some(5));
+    
+    let mut files = files;
+    files.push(("c.json".to_string(), json));
+    assert_eq!(estimate_records(&files), None);
+    
+    assert_eq!(JobProgress::default().percent(), None);
+}
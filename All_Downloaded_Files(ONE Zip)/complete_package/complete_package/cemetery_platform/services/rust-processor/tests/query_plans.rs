@@ -0,0 +1,145 @@
+//! The plans PostgreSQL picks for the map and search queries once a table
+//! holds more than a few pages, so a dropped or mismatched index shows up
+//! as a failing test rather than as a table scan on the full register.
+
+use sqlx::postgres::PgArguments;
+use sqlx::query::Query;
+use sqlx::{PgConnection, PgPool, Postgres, Row};
+
+/// What `Database::get_features_in_bbox` runs. Expected plan: a bitmap scan
+/// of `idx_cemetery_features_geometry` for the `&&` overlap, then a sort by
+/// `id` under the limit:
+///
+/// ```text
+/// Limit
+///   ->  Sort  (Sort Key: id)
+///         ->  Bitmap Heap Scan on najaf_cemetery_features
+///               Recheck Cond: (geometry && '...'::geometry)
+///               Filter: (st_geometrytype(geometry) = ANY (...))
+///               ->  Bitmap Index Scan on idx_cemetery_features_geometry
+/// ```
+const BBOX_QUERY: &str = "
+    SELECT ST_AsGeoJSON(geometry)::jsonb, properties
+    FROM najaf_cemetery_features
+    WHERE geometry && ST_MakeEnvelope($1, $2, $3, $4, 4326)
+        AND ST_GeometryType(geometry) IN ('ST_Point', 'ST_Polygon')
+    ORDER BY id
+    LIMIT $5";
+
+/// What `Database::search_records` runs. Expected plan: the four name
+/// conditions OR'd together from the two trigram indexes, each of which
+/// answers both `%` and `LIKE`:
+///
+/// ```text
+/// Limit
+///   ->  Sort  (Sort Key: (GREATEST(...)) DESC, id)
+///         ->  Bitmap Heap Scan on deceased_records
+///               Recheck Cond: ((lower(deceased_name) % 'hassan') OR ...)
+///               Filter: (deleted_at IS NULL)
+///               ->  BitmapOr
+///                     ->  Bitmap Index Scan on idx_deceased_name_trgm
+///                     ->  Bitmap Index Scan on idx_deceased_name_trgm
+///                     ->  Bitmap Index Scan on idx_deceased_name_arabic_trgm
+///                     ->  Bitmap Index Scan on idx_deceased_name_arabic_trgm
+/// ```
+const SEARCH_QUERY: &str = "
+    SELECT
+        id, record_id, deceased_name,
+        ST_Y(coordinates) as latitude,
+        ST_X(coordinates) as longitude,
+        GREATEST(
+            similarity(lower(deceased_name), $1),
+            COALESCE(similarity(translate(deceased_name_arabic, 'آأإٱًٌٍَُِّْٰـ', 'اااا'), $1), 0)
+        ) as score
+    FROM deceased_records
+    WHERE deleted_at IS NULL
+        AND (
+            lower(deceased_name) % $1
+            OR lower(deceased_name) LIKE $2
+            OR translate(deceased_name_arabic, 'آأإٱًٌٍَُِّْٰـ', 'اااا') % $1
+            OR translate(deceased_name_arabic, 'آأإٱًٌٍَُِّْٰـ', 'اااا') LIKE $2
+        )
+    ORDER BY score DESC, id
+    LIMIT $3 OFFSET $4";
+
+/// The plan for a query, one node per line as `EXPLAIN` prints it.
+async fn plan(conn: &mut PgConnection, explain: Query<'_, Postgres, PgArguments>) -> String {
+    let rows = explain.fetch_all(&mut *conn).await.expect("EXPLAIN should succeed");
+    rows.iter().map(|row| row.get::<String, _>(0)).collect::<Vec<_>>().join("\n")
+}
+
+#[sqlx::test]
+#[ignore = "needs a PostGIS server at DATABASE_URL"]
+async fn bbox_queries_use_the_spatial_index(pool: PgPool) {
+    let mut conn = pool.acquire().await.unwrap();
+    
+    // 10,000 graves on a 100 x 100 grid across the cemetery
+    sqlx::query(
+        "INSERT INTO najaf_cemetery_features (feature_id, geometry, properties)
+         SELECT 'F-' || n,
+                ST_SetSRID(ST_MakePoint(44.28 + (n % 100) * 0.0008, 31.98 + (n / 100) * 0.0007), 4326),
+                '{}'::jsonb
+         FROM generate_series(0, 9999) AS n",
+    )
+    .execute(&mut *conn)
+    .await
+    .unwrap();
+    sqlx::query("ANALYZE najaf_cemetery_features").execute(&mut *conn).await.unwrap();
+    
+    // A street's worth of graves, about nine of them
+    let explain = format!("EXPLAIN {}", BBOX_QUERY);
+    let query = sqlx::query(&explain)
+        .bind(44.300_f64)
+        .bind(31.990_f64)
+        .bind(44.302_f64)
+        .bind(31.992_f64)
+        .bind(1000_i64);
+    let plan = plan(&mut conn, query).await;
+    
+    assert!(plan.contains("idx_cemetery_features_geometry"), "{}", plan);
+    assert!(!plan.contains("Seq Scan"), "{}", plan);
+}
+
+#[sqlx::test]
+#[ignore = "needs a PostGIS server at DATABASE_URL"]
+async fn name_search_uses_the_trigram_indexes(pool: PgPool) {
+    let mut conn = pool.acquire().await.unwrap();
+    
+    // 10,000 unrelated names, and a handful the search should find
+    sqlx::query(
+        "INSERT INTO deceased_records
+             (record_id, deceased_name, deceased_name_arabic, death_date, burial_date, burial_location)
+         SELECT 'R-' || n,
+                CASE WHEN n % 1000 = 0 THEN 'Hassan Kadhim ' || n ELSE 'Name ' || md5(n::text) END,
+                CASE WHEN n % 1000 = 0 THEN 'حسن كاظم' ELSE NULL END,
+                DATE '2024-10-31', DATE '2024-11-01', 'Wadi al-Salam'
+         FROM generate_series(0, 9999) AS n",
+    )
+    .execute(&mut *conn)
+    .await
+    .unwrap();
+    sqlx::query("ANALYZE deceased_records").execute(&mut *conn).await.unwrap();
+    
+    // At this size the planner costs four GIN lookups above one scan of the
+    // short rows, so rule the scan out: a mismatched index still shows one
+    sqlx::query("SET enable_seqscan = off").execute(&mut *conn).await.unwrap();
+    
+    let explain = format!("EXPLAIN {}", SEARCH_QUERY);
+    let query = sqlx::query(&explain).bind("hassan").bind("%hassan%").bind(20_i64).bind(0_i64);
+    let plan = plan(&mut conn, query).await;
+    
+    assert!(plan.contains("idx_deceased_name_trgm"), "{}", plan);
+    assert!(plan.contains("idx_deceased_name_arabic_trgm"), "{}", plan);
+    assert!(!plan.contains("Seq Scan"), "{}", plan);
+    
+    // And the plan finds what the search is for
+    let found = sqlx::query(SEARCH_QUERY)
+        .bind("hassan")
+        .bind("%hassan%")
+        .bind(20_i64)
+        .bind(0_i64)
+        .fetch_all(&mut *conn)
+        .await
+        .unwrap();
+    assert_eq!(found.len(), 10);
+}
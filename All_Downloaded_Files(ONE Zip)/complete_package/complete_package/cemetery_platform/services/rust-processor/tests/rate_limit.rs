@@ -0,0 +1,40 @@
+use najaf_cemetery_processor::rate_limit::RateLimiter;
+use std::time::{Duration, Instant};
+
+#[test]
+fn allows_a_burst_then_refills_at_the_configured_rate() {
+    let limiter = RateLimiter::new(60);
+    let start = Instant::now();
+    
+    for _ in 0..60 {
+        assert!(limiter.check("ip:10.0.0.1", start).is_ok());
+    }
+    
+    let wait = limiter.check("ip:10.0.0.1", start).unwrap_err();
+    assert_eq!(wait, Duration::from_secs(1));
+    
+    // One request a second comes back
+    assert!(limiter.check("ip:10.0.0.1", start + Duration::from_millis(500)).is_err());
+    assert!(limiter.check("ip:10.0.0.1", start + Duration::from_secs(1)).is_ok());
+    assert!(limiter.check("ip:10.0.0.1", start + Duration::from_secs(1)).is_err());
+    
+    // Never more than a full bucket, however long the client was idle
+    let later = start + Duration::from_secs(3600);
+    for _ in 0..60 {
+        assert!(limiter.check("ip:10.0.0.1", later).is_ok());
+    }
+    assert!(limiter.check("ip:10.0.0.1", later).is_err());
+}
+
+#[test]
+fn clients_have_separate_budgets() {
+    let limiter = RateLimiter::new(2);
+    let now = Instant::now();
+    
+    assert!(limiter.check("key:a", now).is_ok());
+    assert!(limiter.check("key:a", now).is_ok());
+    assert!(limiter.check("key:a", now).is_err());
+    
+    assert!(limiter.check("key:b", now).is_ok());
+    assert!(limiter.check("ip:10.0.0.2", now).is_ok());
+}
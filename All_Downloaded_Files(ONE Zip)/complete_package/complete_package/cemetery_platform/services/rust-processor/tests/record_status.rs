@@ -0,0 +1,41 @@
+use najaf_cemetery_processor::models::RecordStatus;
+use najaf_cemetery_processor::parser::{DataParser, ParseOptions};
+use serde_json::json;
+
+const STATUSES: [RecordStatus; 5] = [
+    RecordStatus::Pending,
+    RecordStatus::Completed,
+    RecordStatus::NeedsReview,
+    RecordStatus::GeocodeFailed,
+    RecordStatus::Flagged,
+];
+
+#[test]
+fn statuses_are_stored_as_the_api_names_them() {
+    for status in STATUSES {
+        assert_eq!(serde_json::to_value(status).unwrap(), json!(status.as_str()));
+        assert_eq!(serde_json::from_value::<RecordStatus>(json!(status.as_str())).unwrap(), status);
+    }
+    
+    assert_eq!(RecordStatus::NeedsReview.as_str(), "needs_review");
+    assert!(serde_json::from_value::<RecordStatus>(json!("NeedsReview")).is_err());
+}
+
+#[test]
+fn parsed_records_start_completed() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("register.csv");
+    std::fs::write(
+        &path,
+        "record_id,deceased_name,death_date,burial_date,burial_location\n\
+         S-1,Ali Hassan,2024-10-31,2024-11-01,Wadi al-Salam\n",
+    )
+    .unwrap();
+    
+    let parsed = DataParser::parse_csv_file(&path, &ParseOptions::default()).unwrap();
+    assert_eq!(parsed.records[0].processing_status, RecordStatus::Completed);
+    
+    // Input cannot set it
+    let record: serde_json::Value = serde_json::to_value(&parsed.records[0]).unwrap();
+    assert!(record.get("processing_status").is_none());
+}
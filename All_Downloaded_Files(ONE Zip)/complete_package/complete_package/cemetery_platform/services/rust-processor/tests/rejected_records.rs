@@ -0,0 +1,39 @@
+use najaf_cemetery_processor::parser::{DataParser, ParseOptions};
+
+#[test]
+fn unparsed_csv_rows_keep_their_columns() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("register.csv");
+    std::fs::write(
+        &path,
+        "record_id,deceased_name,death_date,burial_date,burial_location,\n\
+         R-1,Ali Hassan,31/13/2024,2024-11-01,Wadi al-Salam,plot 7\n",
+    )
+    .unwrap();
+    
+    let parsed = DataParser::parse_csv_file(&path, &ParseOptions::default()).unwrap();
+    assert_eq!(parsed.errors.len(), 1);
+    
+    // Blank headers are numbered from one
+    let fields = parsed.errors[0].source_fields.as_ref().unwrap();
+    assert_eq!(fields["death_date"], "31/13/2024");
+    assert_eq!(fields["deceased_name"], "Ali Hassan");
+    assert_eq!(fields["column_6"], "plot 7");
+}
+
+#[test]
+fn unparsed_json_records_keep_the_object() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("register.json");
+    std::fs::write(
+        &path,
+        r#"[{"record_id": "J-1", "deceased_name": "Ali Hassan", "death_date": "2024-10-31",
+             "burial_location": "Wadi al-Salam", "notes": "no burial date"}]"#,
+    )
+    .unwrap();
+    
+    let parsed = DataParser::parse_json_file(&path, &ParseOptions::default()).unwrap();
+    let fields = parsed.errors[0].source_fields.as_ref().unwrap();
+    assert_eq!(fields["record_id"], "J-1");
+    assert_eq!(fields["notes"], "no burial date");
+}
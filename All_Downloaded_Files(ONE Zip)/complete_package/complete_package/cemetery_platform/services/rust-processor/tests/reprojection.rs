@@ -0,0 +1,45 @@
+use najaf_cemetery_processor::parser::{DataParser, ParseOptions};
+use najaf_cemetery_processor::projection::Projection;
+use serde_json::json;
+
+const HEADER: &str = "record_id,deceased_name,deceased_name_arabic,death_date,burial_date,burial_location,latitude,longitude,section,row,plot";
+
+#[test]
+fn coordinate_systems_are_named_by_epsg_code() {
+    assert_eq!("EPSG:32638".parse::<Projection>().unwrap(), Projection::Utm { zone: 38, north: true });
+    assert_eq!("epsg:32721".parse::<Projection>().unwrap(), Projection::Utm { zone: 21, north: false });
+    assert_eq!("4326".parse::<Projection>().unwrap(), Projection::Wgs84);
+    assert_eq!("EPSG:3857".parse::<Projection>().unwrap(), Projection::WebMercator);
+    assert!("EPSG:3893".parse::<Projection>().is_err());
+    assert!("EPSG:32661".parse::<Projection>().is_err());
+    
+    let options: ParseOptions = serde_json::from_value(json!({ "input_crs": "EPSG:32638" })).unwrap();
+    assert_eq!(options.input_crs, Some(Projection::Utm { zone: 38, north: true }));
+    assert_eq!(Projection::Utm { zone: 38, north: true }.to_string(), "EPSG:32638");
+}
+
+#[test]
+fn utm_coordinates_are_reprojected_and_the_originals_kept() {
+    let path = std::env::temp_dir().join(format!("reprojection_{}.csv", std::process::id()));
+    std::fs::write(
+        &path,
+        format!(
+            "{}\n2024001,John Doe,,2024-10-31,2024-11-01,Wadi al-Salam,3542580.945,435234.149,A,12,45\n",
+            HEADER
+        ),
+    )
+    .unwrap();
+    
+    let options = ParseOptions { input_crs: Some("EPSG:32638".parse().unwrap()), ..ParseOptions::default() };
+    let parsed = DataParser::parse_csv_file(&path, &options).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    
+    assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+    let record = &parsed.records[0];
+    assert!((record.latitude.unwrap() - 32.0175).abs() < 1e-6);
+    assert!((record.longitude.unwrap() - 44.3142).abs() < 1e-6);
+    assert_eq!(
+        record.additional_data,
+        Some(json!({ "original_coordinates": { "crs": "EPSG:32638", "x": 435234.149, "y": 3542580.945 } }))
+    );
+}
@@ -0,0 +1,92 @@
+use najaf_cemetery_processor::response_cache::{CachedResponse, ResponseCache};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+async fn compute(calls: &AtomicUsize, body: &str) -> Result<String, String> {
+    calls.fetch_add(1, Ordering::SeqCst);
+    Ok(body.to_string())
+}
+
+#[tokio::test]
+async fn responses_are_kept_until_invalidated() {
+    let cache = ResponseCache::new(Duration::from_secs(60), 10);
+    let calls = AtomicUsize::new(0);
+    
+    let first = cache.get_or_try_insert("stats", || compute(&calls, "{\"total\":1}")).await.unwrap();
+    let again = cache.get_or_try_insert("stats", || compute(&calls, "{\"total\":2}")).await.unwrap();
+    assert_eq!(again.body, "{\"total\":1}");
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    
+    // Keys are kept apart
+    cache.get_or_try_insert("features:A", || compute(&calls, "[]")).await.unwrap();
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+    
+    cache.invalidate();
+    let fresh = cache.get_or_try_insert("stats", || compute(&calls, "{\"total\":2}")).await.unwrap();
+    assert_eq!(fresh.body, "{\"total\":2}");
+    assert_ne!(fresh.etag, first.etag);
+}
+
+#[tokio::test]
+async fn failures_are_not_kept() {
+    let cache = ResponseCache::new(Duration::from_secs(60), 10);
+    
+    let failed = cache.get_or_try_insert("stats", || async { Err::<String, _>("connection refused") }).await;
+    assert_eq!(failed.unwrap_err(), "connection refused");
+    
+    let kept = cache.get_or_try_insert("stats", || async { Ok::<_, &str>("{}".to_string()) }).await.unwrap();
+    assert_eq!(kept.body, "{}");
+}
+
+#[tokio::test]
+async fn concurrent_requests_share_one_computation() {
+    let cache = Arc::new(ResponseCache::new(Duration::from_secs(60), 10));
+    let calls = Arc::new(AtomicUsize::new(0));
+    
+    let requests: Vec<_> = (0..8)
+        .map(|_| {
+            let (cache, calls) = (cache.clone(), calls.clone());
+            tokio::spawn(async move {
+                cache
+                    .get_or_try_insert("stats", || async {
+                        tokio::time::sleep(Duration::from_millis(20)).await;
+                        compute(&calls, "{}").await
+                    })
+                    .await
+                    .unwrap()
+            })
+        })
+        .collect();
+    for request in requests {
+        request.await.unwrap();
+    }
+    
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn the_oldest_response_makes_room() {
+    let cache = ResponseCache::new(Duration::from_secs(60), 2);
+    let calls = AtomicUsize::new(0);
+    
+    for key in ["a", "b", "c"] {
+        cache.get_or_try_insert(key, || compute(&calls, key)).await.unwrap();
+    }
+    cache.get_or_try_insert("c", || compute(&calls, "c")).await.unwrap();
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+    
+    cache.get_or_try_insert("a", || compute(&calls, "a")).await.unwrap();
+    assert_eq!(calls.load(Ordering::SeqCst), 4);
+}
+
+#[test]
+fn etags_answer_if_none_match() {
+    let response = CachedResponse::new("{}".to_string());
+    assert!(response.etag.starts_with('"') && response.etag.ends_with('"'));
+    
+    assert!(response.matches(&response.etag));
+    assert!(response.matches(&format!("\"other\", W/{}", response.etag)));
+    assert!(response.matches("*"));
+    assert!(!response.matches("\"other\""));
+}
@@ -0,0 +1,125 @@
+use najaf_cemetery_processor::parser::DataParser;
+use najaf_cemetery_processor::projection::Projection;
+use shapefile::dbase::{FieldValue, Record, TableWriterBuilder};
+use shapefile::{Point, Polygon, PolygonRing, Writer};
+use std::path::Path;
+
+const UTM_38N_PRJ: &str = r#"PROJCS["WGS_1984_UTM_Zone_38N",GEOGCS["GCS_WGS_1984",DATUM["D_WGS_1984",SPHEROID["WGS_1984",6378137.0,298.257223563]],PRIMEM["Greenwich",0.0],UNIT["Degree",0.0174532925199433]],PROJECTION["Transverse_Mercator"],PARAMETER["False_Easting",500000.0],PARAMETER["False_Northing",0.0],PARAMETER["Central_Meridian",45.0],PARAMETER["Scale_Factor",0.9996],PARAMETER["Latitude_Of_Origin",0.0],UNIT["Meter",1.0]]"#;
+
+fn assert_close(actual: (f64, f64), expected: (f64, f64)) {
+    // 1e-6 degrees is about 10 cm
+    assert!(
+        (actual.0 - expected.0).abs() < 1e-6 && (actual.1 - expected.1).abs() < 1e-6,
+        "{:?} != {:?}",
+        actual,
+        expected
+    );
+}
+
+fn square(x: f64, y: f64, size: f64) -> Vec<Point> {
+    vec![
+        Point::new(x, y),
+        Point::new(x, y + size),
+        Point::new(x + size, y + size),
+        Point::new(x + size, y),
+        Point::new(x, y),
+    ]
+}
+
+fn write_shapefile(path: &Path, shapes: Vec<(Polygon, &str, f64)>) {
+    let table = TableWriterBuilder::new()
+        .add_character_field("SECTION".try_into().unwrap(), 10)
+        .add_numeric_field("PLOT_NO".try_into().unwrap(), 6, 0);
+    let mut writer = Writer::from_path(path, table).unwrap();
+    
+    for (polygon, section, plot) in shapes {
+        let mut record = Record::default();
+        record.insert("SECTION".to_string(), FieldValue::Character(Some(section.to_string())));
+        record.insert("PLOT_NO".to_string(), FieldValue::Numeric(Some(plot)));
+        writer.write_shape_and_record(&polygon, &record).unwrap();
+    }
+}
+
+#[test]
+fn recognises_prj_coordinate_systems() {
+    assert_eq!(Projection::from_wkt(UTM_38N_PRJ).unwrap(), Projection::Utm { zone: 38, north: true });
+    assert_eq!(
+        Projection::from_wkt(r#"PROJCS["WGS 84 / UTM zone 21S",GEOGCS["WGS 84",DATUM["WGS_1984"]]]"#).unwrap(),
+        Projection::Utm { zone: 21, north: false }
+    );
+    assert_eq!(
+        Projection::from_wkt(r#"GEOGCS["GCS_WGS_1984",DATUM["D_WGS_1984",SPHEROID["WGS_1984",6378137.0,298.257223563]]]"#).unwrap(),
+        Projection::Wgs84
+    );
+    assert_eq!(
+        Projection::from_wkt(r#"PROJCS["WGS_1984_Web_Mercator_Auxiliary_Sphere",GEOGCS["GCS_WGS_1984",DATUM["D_WGS_1984"]],PROJECTION["Mercator_Auxiliary_Sphere"]]"#).unwrap(),
+        Projection::WebMercator
+    );
+    
+    // A different datum would need a shift, so it is refused
+    let karbala = r#"PROJCS["Karbala_1979_Polservice_UTM_Zone_38N",GEOGCS["GCS_Karbala_1979_Polservice",DATUM["D_Karbala_1979_Polservice",SPHEROID["Clarke_1880_RGS",6378249.145,293.465]]]]"#;
+    let error = Projection::from_wkt(karbala).unwrap_err();
+    assert!(error.to_string().contains("Karbala_1979_Polservice_UTM_Zone_38N"), "{}", error);
+}
+
+#[test]
+fn converts_projected_coordinates_to_wgs84() {
+    // Reference values from an independent Krüger-series forward projection
+    let zone_38n = Projection::Utm { zone: 38, north: true };
+    assert_close(zone_38n.to_wgs84(435_234.149, 3_542_580.945), (44.3142, 32.0175));
+    assert_close(zone_38n.to_wgs84(439_580.855, 3_546_156.910), (44.36, 32.05));
+    
+    let zone_21s = Projection::Utm { zone: 21, north: false };
+    assert_close(zone_21s.to_wgs84(373_458.607, 6_170_448.511), (-58.38, -34.6));
+    
+    assert_close(Projection::WebMercator.to_wgs84(4_933_034.179, 3_765_607.993), (44.3142, 32.0175));
+}
+
+#[test]
+fn reads_utm_outlines_with_holes_and_multiple_parts() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("plots.shp");
+    
+    let with_hole = Polygon::with_rings(vec![
+        PolygonRing::Outer(square(435_234.149, 3_542_580.945, 100.0)),
+        PolygonRing::Inner(square(435_260.0, 3_542_600.0, 20.0).into_iter().rev().collect()),
+    ]);
+    let two_parts = Polygon::with_rings(vec![
+        PolygonRing::Outer(square(435_400.0, 3_542_580.0, 50.0)),
+        PolygonRing::Outer(square(435_500.0, 3_542_580.0, 50.0)),
+    ]);
+    write_shapefile(&path, vec![(with_hole, "A", 45.0), (two_parts, "B", 7.0)]);
+    std::fs::write(path.with_extension("prj"), UTM_38N_PRJ).unwrap();
+    
+    let parsed = DataParser::parse_shapefile(&path).unwrap();
+    
+    assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+    assert_eq!(parsed.outlines.len(), 3);
+    
+    let first = &parsed.outlines[0];
+    assert_eq!(first.section.as_deref(), Some("A"));
+    assert_eq!(first.plot_number, Some(45));
+    assert_eq!(first.attributes["SECTION"], "A");
+    assert_eq!(first.rings.len(), 2);
+    assert_close(first.rings[0][0], (44.3142, 32.0175));
+    assert!(first.to_wkt().starts_with("POLYGON((44.3142"));
+    
+    let parts: Vec<_> = parsed.outlines[1..].iter().map(|o| (o.shape_index, o.part_index, o.rings.len())).collect();
+    assert_eq!(parts, vec![(1, 0, 1), (1, 1, 1)]);
+    assert_eq!(parsed.outlines[2].section.as_deref(), Some("B"));
+}
+
+#[test]
+fn rejects_projected_coordinates_without_a_prj() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("plots.shp");
+    
+    let polygon = Polygon::new(PolygonRing::Outer(square(435_234.0, 3_542_580.0, 100.0)));
+    write_shapefile(&path, vec![(polygon, "A", 1.0)]);
+    
+    let parsed = DataParser::parse_shapefile(&path).unwrap();
+    
+    assert!(parsed.outlines.is_empty());
+    assert_eq!(parsed.errors.len(), 1);
+    assert!(parsed.errors[0].message.contains(".prj"), "{}", parsed.errors[0].message);
+}
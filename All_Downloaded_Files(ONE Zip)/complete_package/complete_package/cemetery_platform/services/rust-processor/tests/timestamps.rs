@@ -0,0 +1,30 @@
+use chrono::{TimeZone, Utc};
+use najaf_cemetery_processor::timestamps::{parse_timestamp, DEFAULT_TIMEZONE};
+
+#[test]
+fn timestamps_with_an_offset_are_converted_to_utc() {
+    let expected = Utc.with_ymd_and_hms(2024, 11, 1, 5, 30, 0).unwrap();
+    
+    assert_eq!(parse_timestamp("2024-11-01T08:30:00+03:00", DEFAULT_TIMEZONE), Ok(expected));
+    assert_eq!(parse_timestamp("2024-11-01T05:30:00Z", chrono_tz::America::New_York), Ok(expected));
+    assert_eq!(parse_timestamp(" 2024-11-01 05:30:00z ", DEFAULT_TIMEZONE), Ok(expected));
+}
+
+#[test]
+fn timestamps_without_an_offset_are_read_in_the_configured_zone() {
+    let baghdad = Utc.with_ymd_and_hms(2024, 11, 1, 5, 30, 0).unwrap();
+    assert_eq!(parse_timestamp("2024-11-01T08:30:00", DEFAULT_TIMEZONE), Ok(baghdad));
+    assert_eq!(parse_timestamp("2024-11-01 08:30", DEFAULT_TIMEZONE), Ok(baghdad));
+    
+    let berlin = chrono_tz::Europe::Berlin;
+    // Repeated when clocks go back: the first, summer-time occurrence
+    assert_eq!(
+        parse_timestamp("2024-10-27 02:30:00", berlin),
+        Ok(Utc.with_ymd_and_hms(2024, 10, 27, 0, 30, 0).unwrap())
+    );
+    // Skipped when they go forward
+    assert!(parse_timestamp("2024-03-31 02:30:00", berlin).unwrap_err().contains("does not exist"));
+    
+    assert!(parse_timestamp("yesterday", DEFAULT_TIMEZONE).is_err());
+    assert!(parse_timestamp("2024-11-01", DEFAULT_TIMEZONE).is_err());
+}
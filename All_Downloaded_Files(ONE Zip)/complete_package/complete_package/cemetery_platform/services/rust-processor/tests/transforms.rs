@@ -0,0 +1,101 @@
+use najaf_cemetery_processor::parser::{DataParser, ParseOptions};
+use najaf_cemetery_processor::transform::{FieldTransforms, Transform};
+use std::borrow::Cow;
+
+const HEADER: &str = "record_id,deceased_name,deceased_name_arabic,death_date,burial_date,burial_location,latitude,longitude,section,row,plot";
+
+fn cleanup() -> Transform {
+    Transform {
+        trim: true,
+        collapse_whitespace: true,
+        null_values: vec!["N/A".to_string(), "-".to_string(), String::new()],
+        ..Transform::default()
+    }
+}
+
+#[test]
+fn placeholders_become_missing() {
+    let transforms = FieldTransforms { all: Some(cleanup()), ..FieldTransforms::default() };
+    
+    for placeholder in ["N/A", " - ", "", "   "] {
+        assert_eq!(transforms.apply("section", placeholder), None, "{:?}", placeholder);
+    }
+    assert_eq!(transforms.apply("section", "  B   North "), Some(Cow::Borrowed("B North")));
+    
+    // Without rules values are passed on as they are
+    assert_eq!(FieldTransforms::default().apply("section", " N/A "), Some(Cow::Borrowed(" N/A ")));
+}
+
+#[test]
+fn latin_names_are_title_cased() {
+    let transforms = FieldTransforms {
+        all: Some(cleanup()),
+        fields: [("deceased_name".to_string(), Transform { title_case: true, ..Transform::default() })].into(),
+    };
+    
+    assert_eq!(transforms.apply("deceased_name", "ALI  hassan al-najafi").unwrap(), "Ali Hassan Al-Najafi");
+    assert_eq!(transforms.apply("deceased_name", "محمد علي").unwrap(), "محمد علي");
+    assert_eq!(transforms.apply("burial_location", "WADI AL-SALAM").unwrap(), "WADI AL-SALAM");
+}
+
+#[test]
+fn csv_fields_are_cleaned_before_parsing() {
+    let path = std::env::temp_dir().join(format!("transforms_{}.csv", std::process::id()));
+    std::fs::write(
+        &path,
+        format!(
+            "{}\n\
+             R1,  ali   HASSAN ,  محمد   علي  , 2024-10-31 ,2024-11-01,W. Salam,N/A,-,,N/A,12\n",
+            HEADER
+        ),
+    )
+    .unwrap();
+    
+    let options = ParseOptions {
+        transforms: FieldTransforms {
+            all: Some(cleanup()),
+            fields: [
+                ("deceased_name".to_string(), Transform { title_case: true, ..Transform::default() }),
+                (
+                    "burial_location".to_string(),
+                    Transform {
+                        replace: [("W. Salam".to_string(), "Wadi al-Salam".to_string())].into(),
+                        ..Transform::default()
+                    },
+                ),
+            ]
+            .into(),
+        },
+        ..ParseOptions::default()
+    };
+    let parsed = DataParser::parse_csv_file(&path, &options).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    
+    assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+    let record = &parsed.records[0];
+    assert_eq!(record.deceased_name, "Ali Hassan");
+    assert_eq!(record.deceased_name_arabic.as_deref(), Some("محمد علي"));
+    assert_eq!(record.burial_location, "Wadi al-Salam");
+    assert_eq!(record.section, None);
+    assert_eq!(record.latitude, None);
+    assert_eq!(record.row_number, None);
+    assert_eq!(record.plot_number, Some(12));
+}
+
+#[test]
+fn unknown_fields_are_refused() {
+    let path = std::env::temp_dir().join(format!("transforms_unknown_{}.csv", std::process::id()));
+    std::fs::write(&path, format!("{}\n", HEADER)).unwrap();
+    
+    let options = ParseOptions {
+        transforms: FieldTransforms {
+            fields: [("surname".to_string(), cleanup())].into(),
+            ..FieldTransforms::default()
+        },
+        ..ParseOptions::default()
+    };
+    let error = DataParser::parse_csv_file(&path, &options).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+    
+    assert_eq!(error.to_string(), "Transforms name unknown field 'surname'");
+}
@@ -0,0 +1,52 @@
+use najaf_cemetery_processor::parser::{ColumnRef, CsvSchema, DataParser, ParseOptions};
+use serde_json::json;
+
+#[test]
+fn unmapped_columns_are_kept_in_additional_data() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("burials.csv");
+    std::fs::write(
+        &path,
+        "record_id,deceased_name,deceased_name_arabic,death_date,burial_date,burial_location,\
+         latitude,longitude,section,row,plot,cemetery_name,registrar,\n\
+         2024001,John Doe,,2024-10-31,2024-11-01,Wadi al-Salam,32.0175,44.3142,A,12,45,Wadi al-Salam,,x\n\
+         2024002,Jane Doe,,1445-04-16H,2024-11-01,Wadi al-Salam,,,,,,,,\n",
+    )
+    .unwrap();
+    
+    let parsed = DataParser::parse_csv_file(&path, &ParseOptions::default()).unwrap();
+    
+    assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+    assert_eq!(
+        parsed.records[0].additional_data,
+        Some(json!({ "cemetery_name": "Wadi al-Salam", "column_14": "x" }))
+    );
+    assert_eq!(parsed.records[1].additional_data, Some(json!({ "raw_death_date": "1445-04-16H" })));
+}
+
+#[test]
+fn columns_a_schema_maps_are_not_repeated() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("burials.csv");
+    std::fs::write(
+        &path,
+        "ID,Name,Died,Buried,Cemetery,Registrar\n2024001,John Doe,2024-10-31,2024-11-01,Wadi al-Salam,Ali\n",
+    )
+    .unwrap();
+    
+    let columns = [
+        ("record_id", "ID"),
+        ("deceased_name", "Name"),
+        ("death_date", "Died"),
+        ("burial_date", "Buried"),
+        ("burial_location", "Cemetery"),
+    ]
+    .iter()
+    .map(|(field, header)| (field.to_string(), ColumnRef::Header(header.to_string())))
+    .collect();
+    let options = ParseOptions { csv_schema: Some(CsvSchema { columns }), ..ParseOptions::default() };
+    
+    let parsed = DataParser::parse_csv_file(&path, &options).unwrap();
+    
+    assert_eq!(parsed.records[0].additional_data, Some(json!({ "Registrar": "Ali" })));
+}
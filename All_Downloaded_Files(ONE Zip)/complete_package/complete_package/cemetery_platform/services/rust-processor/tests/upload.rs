@@ -0,0 +1,37 @@
+use najaf_cemetery_processor::upload::{upload_file_name, StageError, StagedFile};
+use std::fs;
+
+#[test]
+fn upload_names_keep_only_the_last_component() {
+    assert_eq!(upload_file_name("burials.csv").unwrap(), "burials.csv");
+    assert_eq!(upload_file_name("C:\\Users\\clerk\\Desktop\\burials.XLSX").unwrap(), "burials.XLSX");
+    assert_eq!(upload_file_name("../../etc/delivery.zip").unwrap(), "delivery.zip");
+    
+    assert!(upload_file_name("").unwrap_err().contains("no name"));
+    assert!(upload_file_name("deliveries/..").unwrap_err().contains("no name"));
+    assert!(upload_file_name("plots.shp").unwrap_err().contains("Unsupported"));
+    
+    // Formats the name does not give are detected from the content
+    assert_eq!(upload_file_name("burials").unwrap(), "burials");
+    assert_eq!(upload_file_name("export.TXT").unwrap(), "export.TXT");
+}
+
+#[tokio::test]
+async fn staged_files_are_hashed_and_capped() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("burials.csv");
+    
+    let mut file = StagedFile::create(&path, 11).await.unwrap();
+    file.write(b"hello ").await.unwrap();
+    file.write(b"world").await.unwrap();
+    let (hash, size) = file.finish().await.unwrap();
+    
+    assert_eq!(hash, "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9");
+    assert_eq!(size, 11);
+    assert_eq!(fs::read(&path).unwrap(), b"hello world");
+    
+    // One byte over the limit
+    let mut file = StagedFile::create(&path, 10).await.unwrap();
+    file.write(b"hello ").await.unwrap();
+    assert!(matches!(file.write(b"world").await, Err(StageError::TooLarge(10))));
+}
@@ -0,0 +1,74 @@
+use najaf_cemetery_processor::parser::ParseOptions;
+use najaf_cemetery_processor::processor::{DataProcessor, DEFAULT_MAX_DEPTH};
+use sqlx::postgres::PgPoolOptions;
+use std::sync::Arc;
+
+#[tokio::test]
+async fn every_record_is_reported_without_a_database() {
+    // Nothing listens here, so any query would fail the test
+    let pool = PgPoolOptions::new()
+        .connect_lazy("postgres://localhost/unused")
+        .unwrap();
+    let processor = DataProcessor::new(Arc::new(pool));
+    
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("burials.csv"),
+        "record_id,deceased_name,deceased_name_arabic,death_date,burial_date,burial_location\n\
+         V-1,Ali Hassan,,2024-10-31,2024-11-01,Wadi al-Salam\n\
+         V-2,Zainab Kadhim,,2024-11-02,2024-11-01,Wadi al-Salam\n\
+         V-3,Hussein Jawad,,someday,2024-11-01,Wadi al-Salam\n\
+         V-1,Ali Hassan,,2024-10-31,2024-11-01,Wadi al-Salam\n",
+    ).unwrap();
+    std::fs::write(dir.path().join("broken.json"), "{ not json").unwrap();
+    
+    let report = processor
+        .validate_path(dir.path(), &ParseOptions::default(), DEFAULT_MAX_DEPTH)
+        .await
+        .unwrap();
+    
+    assert_eq!((report.records_read, report.records_valid, report.records_invalid), (4, 2, 2));
+    assert_eq!(report.file_errors.len(), 1);
+    assert!(report.file_errors[0].contains("broken.json"));
+    
+    let ids: Vec<_> = report.records.iter().map(|record| record.record_id.as_deref()).collect();
+    assert_eq!(ids, [Some("V-1"), Some("V-2"), Some("V-3"), Some("V-1")]);
+    assert!(report.records.iter().all(|record| record.file == "burials.csv"));
+    
+    let before_death = &report.records[1];
+    assert!(!before_death.valid);
+    assert_eq!(before_death.errors[0].code, Some("burial_before_death"));
+    
+    let bad_date = &report.records[2];
+    assert!(!bad_date.valid);
+    assert_eq!(bad_date.line_number, Some(4));
+    assert!(bad_date.errors[0].message.starts_with("death_date"));
+    
+    assert!(report.records[0].valid && report.records[0].warnings.is_empty());
+    assert_eq!(report.records[3].warnings, ["record_id is also used by an earlier record"]);
+}
+
+#[tokio::test]
+async fn valid_records_with_coordinates_are_counted() {
+    let pool = PgPoolOptions::new()
+        .connect_lazy("postgres://localhost/unused")
+        .unwrap();
+    let processor = DataProcessor::new(Arc::new(pool));
+    
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("burials.csv"),
+        "record_id,deceased_name,deceased_name_arabic,death_date,burial_date,burial_location,latitude,longitude\n\
+         C-1,Ali Hassan,,2024-10-31,2024-11-01,Wadi al-Salam,32.0123,44.3312\n\
+         C-2,Zainab Kadhim,,2024-10-31,2024-11-01,Wadi al-Salam,,\n\
+         C-3,Hussein Jawad,,2024-11-02,2024-11-01,Wadi al-Salam,32.0125,44.3315\n",
+    ).unwrap();
+    
+    let report = processor
+        .validate_path(dir.path(), &ParseOptions::default(), DEFAULT_MAX_DEPTH)
+        .await
+        .unwrap();
+    
+    // C-3 has coordinates but would fail, so it is not counted
+    assert_eq!((report.records_valid, report.records_with_coordinates), (2, 1));
+}
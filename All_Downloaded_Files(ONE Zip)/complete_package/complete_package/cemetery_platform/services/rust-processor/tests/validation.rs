@@ -0,0 +1,250 @@
+use chrono::NaiveDate;
+use najaf_cemetery_processor::models::{
+    CemeteryBounds, DateLimits, DeceasedRecord, ErrorDetails, ProcessingResult, RecordStatus, SwappedCoordinates,
+    ValidationError,
+};
+
+/// Roughly the extent of Wadi al-Salam.
+const NAJAF_BOUNDS: CemeteryBounds = CemeteryBounds {
+    min_lon: 44.28,
+    min_lat: 31.98,
+    max_lon: 44.36,
+    max_lat: 32.05,
+};
+
+fn record_at(latitude: f64, longitude: f64) -> DeceasedRecord {
+    DeceasedRecord {
+        record_id: "2024001".to_string(),
+        deceased_name: "John Doe".to_string(),
+        deceased_name_arabic: None,
+        father_name: None,
+        grandfather_name: None,
+        death_date: NaiveDate::from_ymd_opt(2024, 10, 31).unwrap(),
+        death_location: None,
+        burial_date: NaiveDate::from_ymd_opt(2024, 11, 1).unwrap(),
+        burial_location: "Wadi al-Salam".to_string(),
+        section: Some("A".to_string()),
+        row_number: Some(12),
+        plot_number: Some(45),
+        grave_number: None,
+        latitude: Some(latitude),
+        longitude: Some(longitude),
+        footprint: None,
+        birth_date: None,
+        age_at_death: None,
+        cause_of_death: None,
+        cause_of_death_code: None,
+        national_id: None,
+        family_contact: None,
+        additional_data: None,
+        processing_status: RecordStatus::default(),
+    }
+}
+
+#[test]
+fn coordinates_inside_bounds_pass() {
+    let record = record_at(32.0175, 44.3142);
+    assert_eq!(record.validate_with_bounds(Some(&NAJAF_BOUNDS)), Ok(()));
+}
+
+#[test]
+fn coordinates_outside_bounds_are_rejected() {
+    // Valid on the globe, but in Europe
+    let record = record_at(48.8566, 2.3522);
+    assert_eq!(record.validate(), Ok(()));
+    assert_eq!(
+        record.validate_with_bounds(Some(&NAJAF_BOUNDS)),
+        Err(vec![ValidationError::OutsideCemeteryBounds { latitude: 48.8566, longitude: 2.3522 }])
+    );
+    
+    // Swapped latitude and longitude
+    let record = record_at(44.3142, 32.0175);
+    assert_eq!(
+        record.validate_with_bounds(Some(&NAJAF_BOUNDS)),
+        Err(vec![ValidationError::OutsideCemeteryBounds { latitude: 44.3142, longitude: 32.0175 }])
+    );
+}
+
+#[test]
+fn global_ranges_apply_without_bounds() {
+    assert_eq!(record_at(95.0, 44.3142).validate(), Err(vec![ValidationError::InvalidLatitude(95.0)]));
+    assert_eq!(record_at(32.0175, 190.0).validate(), Err(vec![ValidationError::InvalidLongitude(190.0)]));
+}
+
+#[test]
+fn bounds_parse_in_bbox_order() {
+    let bounds: CemeteryBounds = "44.28, 31.98, 44.36, 32.05".parse().unwrap();
+    assert!(bounds.contains(32.0175, 44.3142));
+    assert!(!bounds.contains(44.3142, 32.0175));
+    
+    assert!("44.28,31.98,44.36".parse::<CemeteryBounds>().is_err());
+    assert!("44.36,31.98,44.28,32.05".parse::<CemeteryBounds>().is_err());
+    assert!("a,b,c,d".parse::<CemeteryBounds>().is_err());
+}
+
+#[test]
+fn swapped_najaf_pair_is_detected_within_bounds() {
+    // Latitude 44 is a valid latitude, so only the bounds reveal the swap
+    let swapped = record_at(44.3142, 32.0175);
+    assert!(!swapped.has_swapped_coordinates(None));
+    assert!(swapped.has_swapped_coordinates(Some(&NAJAF_BOUNDS)));
+    
+    assert!(!record_at(32.0175, 44.3142).has_swapped_coordinates(Some(&NAJAF_BOUNDS)));
+}
+
+#[test]
+fn impossible_latitude_is_detected_without_bounds() {
+    // A longitude beyond 90 typed into the latitude column
+    assert!(record_at(120.5, 15.2).has_swapped_coordinates(None));
+    assert!(!record_at(120.5, 150.2).has_swapped_coordinates(None));
+}
+
+#[test]
+fn swapped_coordinates_are_rejected_by_default() {
+    let mut record = record_at(44.3142, 32.0175);
+    
+    assert_eq!(
+        record.fix_swapped_coordinates(Some(&NAJAF_BOUNDS), SwappedCoordinates::default()),
+        Err(ValidationError::CoordinatesSwapped { latitude: 44.3142, longitude: 32.0175 })
+    );
+    assert_eq!(record.latitude, Some(44.3142));
+}
+
+#[test]
+fn swapped_coordinates_can_be_corrected() {
+    let mut record = record_at(44.3142, 32.0175);
+    
+    record.fix_swapped_coordinates(Some(&NAJAF_BOUNDS), SwappedCoordinates::Swap).unwrap();
+    
+    assert_eq!(record.latitude, Some(32.0175));
+    assert_eq!(record.longitude, Some(44.3142));
+    assert_eq!(record.validate_with_bounds(Some(&NAJAF_BOUNDS)), Ok(()));
+    assert_eq!(
+        record.additional_data.unwrap()["coordinates_swapped"],
+        serde_json::json!({ "original_latitude": 44.3142, "original_longitude": 32.0175 })
+    );
+}
+
+#[test]
+fn all_problems_are_reported_at_once() {
+    let mut record = record_at(95.0, 190.0);
+    record.deceased_name.clear();
+    record.burial_location.clear();
+    record.burial_date = NaiveDate::from_ymd_opt(2024, 10, 30).unwrap();
+    
+    let errors = record.validate().unwrap_err();
+    assert_eq!(
+        errors,
+        vec![
+            ValidationError::MissingField("deceased_name"),
+            ValidationError::MissingField("burial_location"),
+            ValidationError::BurialBeforeDeath,
+            ValidationError::InvalidLatitude(95.0),
+            ValidationError::InvalidLongitude(190.0),
+        ]
+    );
+    
+    let fields: Vec<_> = errors.iter().map(|e| e.field()).collect();
+    assert_eq!(
+        fields,
+        vec![Some("deceased_name"), Some("burial_location"), Some("burial_date"), Some("latitude"), Some("longitude")]
+    );
+}
+
+#[test]
+fn validation_errors_keep_their_messages() {
+    let details = ErrorDetails::from_validation("2024001", &ValidationError::MissingField("deceased_name"));
+    
+    assert_eq!(details.record_id.as_deref(), Some("2024001"));
+    assert_eq!(details.message, "deceased_name is required");
+    assert_eq!(details.code, Some("missing_field"));
+    assert_eq!(details.field, Some("deceased_name"));
+    
+    assert_eq!(ValidationError::BurialBeforeDeath.to_string(), "burial_date cannot be before death_date");
+    assert_eq!(ValidationError::InvalidLatitude(95.0).to_string(), "Invalid latitude");
+}
+
+#[test]
+fn ages_outside_the_plausible_range_are_rejected() {
+    let mut record = record_at(32.0175, 44.3142);
+    
+    record.age_at_death = Some(-1);
+    assert_eq!(record.validate(), Err(vec![ValidationError::InvalidAge(-1)]));
+    
+    record.age_at_death = Some(131);
+    assert_eq!(record.validate(), Err(vec![ValidationError::InvalidAge(131)]));
+    
+    record.age_at_death = Some(0);
+    assert_eq!(record.validate(), Ok(()));
+}
+
+#[test]
+fn age_is_checked_against_birth_and_death_dates() {
+    let mut record = record_at(32.0175, 44.3142);
+    record.birth_date = NaiveDate::from_ymd_opt(1950, 11, 15);
+    
+    // 73 completed years by 31 October 2024
+    record.age_at_death = Some(73);
+    assert_eq!(record.validate(), Ok(()));
+    assert_eq!(record.age_discrepancy(), None);
+    
+    // Small differences pass with a warning
+    record.age_at_death = Some(75);
+    assert_eq!(record.validate(), Ok(()));
+    let discrepancy = record.age_discrepancy().unwrap();
+    assert!(discrepancy.contains("75") && discrepancy.contains("73"), "{}", discrepancy);
+    
+    record.age_at_death = Some(76);
+    assert_eq!(record.validate(), Err(vec![ValidationError::AgeMismatch { age: 76, expected: 73 }]));
+    
+    record.age_at_death = None;
+    record.birth_date = NaiveDate::from_ymd_opt(2024, 12, 1);
+    let errors = record.validate().unwrap_err();
+    assert_eq!(errors, vec![ValidationError::BirthAfterDeath]);
+    assert_eq!(errors[0].field(), Some("birth_date"));
+}
+
+#[test]
+fn failure_ratio_is_the_share_of_records_read_that_failed() {
+    let mut result = ProcessingResult::already_processed("abc", Vec::new());
+    assert_eq!(result.failure_ratio(), 0.0);
+    
+    result.records_read = 8;
+    result.records_failed = 2;
+    assert_eq!(result.failure_ratio(), 0.25);
+}
+
+#[test]
+fn dates_outside_the_plausible_range_are_rejected() {
+    let limits = DateLimits { earliest_year: 1900, future_burial_grace_days: 7 };
+    let today = NaiveDate::from_ymd_opt(2024, 11, 1).unwrap();
+    let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+    
+    let mut record = record_at(32.0175, 44.3142);
+    assert!(record.check_date_limits(&limits, today).is_empty());
+    
+    // A day of slack for time zones, and the grace period for burials
+    record.death_date = date(2024, 11, 2);
+    record.burial_date = date(2024, 11, 9);
+    assert!(record.check_date_limits(&limits, today).is_empty());
+    
+    record.burial_date = date(2099, 11, 1);
+    record.death_date = date(1899, 12, 31);
+    let errors = record.check_date_limits(&limits, today);
+    assert_eq!(
+        errors,
+        vec![
+            ValidationError::DateTooEarly { field: "death_date", date: date(1899, 12, 31), earliest_year: 1900 },
+            ValidationError::DateInFuture { field: "burial_date", date: date(2099, 11, 1) },
+        ]
+    );
+    assert_eq!(errors[1].to_string(), "burial_date 2099-11-01 is in the future");
+    assert_eq!((errors[1].code(), errors[1].field()), ("date_in_future", Some("burial_date")));
+    
+    record.death_date = date(2024, 11, 3);
+    record.burial_date = date(2024, 11, 3);
+    assert_eq!(
+        record.check_date_limits(&limits, today),
+        vec![ValidationError::DateInFuture { field: "death_date", date: date(2024, 11, 3) }]
+    );
+}
@@ -0,0 +1,32 @@
+use najaf_cemetery_processor::database::RetryPolicy;
+use najaf_cemetery_processor::webhook::{parse_callback_url, sign, WebhookNotifier};
+use std::time::Duration;
+
+#[test]
+fn signatures_are_hex_hmac_sha256() {
+    // RFC 4231, test case 2
+    assert_eq!(
+        sign(b"Jefe", b"what do ya want for nothing?"),
+        "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+}
+
+#[test]
+fn callback_urls_must_be_http() {
+    assert!(parse_callback_url("https://hooks.example.org/cemetery?token=1").is_ok());
+    assert!(parse_callback_url("http://10.0.0.5:9000/imports").is_ok());
+    
+    assert!(parse_callback_url("ftp://hooks.example.org/").unwrap_err().contains("http"));
+    assert!(parse_callback_url("file:///etc/passwd").unwrap_err().contains("http"));
+    assert!(parse_callback_url("/relative/path").unwrap_err().contains("valid URL"));
+}
+
+#[tokio::test]
+async fn delivery_gives_up_after_the_last_attempt() {
+    // Nothing listens on a port that was just released
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let retry = RetryPolicy { max_attempts: 2, initial_backoff: Duration::from_millis(1) };
+    let notifier = WebhookNotifier::new("secret", Duration::from_secs(1), retry).unwrap();
+    
+    assert!(!notifier.deliver(&format!("http://127.0.0.1:{}/hook", port), b"{}".to_vec()).await);
+}
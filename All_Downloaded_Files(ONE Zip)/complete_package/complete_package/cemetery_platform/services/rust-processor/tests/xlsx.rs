@@ -0,0 +1,120 @@
+use chrono::NaiveDate;
+use najaf_cemetery_processor::parser::{DataParser, ParseOptions};
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+const CONTENT_TYPES: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">
+<Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/>
+<Default Extension="xml" ContentType="application/xml"/>
+<Override PartName="/xl/workbook.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml"/>
+<Override PartName="/xl/worksheets/sheet1.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/>
+</Types>"#;
+
+const ROOT_RELS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="xl/workbook.xml"/>
+</Relationships>"#;
+
+const WORKBOOK: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships">
+<sheets><sheet name="Burials" sheetId="1" r:id="rId1"/></sheets>
+</workbook>"#;
+
+const WORKBOOK_RELS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet1.xml"/>
+</Relationships>"#;
+
+/// A cell of a worksheet row: text, or a number as Excel stores dates.
+enum Cell<'a> {
+    Text(&'a str),
+    Number(f64),
+    Empty,
+}
+
+/// Writes a one-sheet workbook with `rows`, the first being the headers.
+/// Text is stored inline, so no shared strings table is needed.
+fn write_workbook(path: &Path, rows: &[Vec<Cell>]) {
+    let mut sheet = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8"?><worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><sheetData>"#,
+    );
+    for (row_index, row) in rows.iter().enumerate() {
+        sheet.push_str(&format!("<row r=\"{}\">", row_index + 1));
+        for (column, cell) in row.iter().enumerate() {
+            let reference = format!("{}{}", (b'A' + column as u8) as char, row_index + 1);
+            match cell {
+                Cell::Text(text) => {
+                    sheet.push_str(&format!("<c r=\"{}\" t=\"inlineStr\"><is><t>{}</t></is></c>", reference, text))
+                }
+                Cell::Number(number) => sheet.push_str(&format!("<c r=\"{}\"><v>{}</v></c>", reference, number)),
+                Cell::Empty => {}
+            }
+        }
+        sheet.push_str("</row>");
+    }
+    sheet.push_str("</sheetData></worksheet>");
+    
+    let mut zip = ZipWriter::new(File::create(path).unwrap());
+    for (name, contents) in [
+        ("[Content_Types].xml", CONTENT_TYPES),
+        ("_rels/.rels", ROOT_RELS),
+        ("xl/workbook.xml", WORKBOOK),
+        ("xl/_rels/workbook.xml.rels", WORKBOOK_RELS),
+        ("xl/worksheets/sheet1.xml", sheet.as_str()),
+    ] {
+        zip.start_file(name, SimpleFileOptions::default()).unwrap();
+        zip.write_all(contents.as_bytes()).unwrap();
+    }
+    zip.finish().unwrap();
+}
+
+#[test]
+fn worksheets_are_read_by_header_with_iso_and_serial_dates() {
+    use Cell::*;
+    
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("register.xlsx");
+    // Columns in an order of the office's own, with a column the parser
+    // does not know
+    write_workbook(&path, &[
+        vec![
+            Text("burial_location"), Text("record_id"), Text("burial_date"), Text("deceased_name"),
+            Text("death_date"), Text("section"), Text("registrar"), Text("birth_date"),
+        ],
+        vec![
+            Text("Wadi al-Salam"), Text("X-1"), Text("2024-11-01"), Text("Ali Hassan"),
+            Text("2024-10-31"), Text("A"), Text("Kadhim"), Empty,
+        ],
+        // 45597, 45596 and 18582 are 2024-11-01, 2024-10-31 and 1950-11-15
+        // as Excel serials
+        vec![
+            Text("Wadi al-Salam"), Text("X-2"), Number(45597.0), Text("Zainab Kadhim"),
+            Number(45596.0), Empty, Empty, Number(18582.0),
+        ],
+    ]);
+    
+    let parsed = DataParser::detect_and_parse(&path, &ParseOptions::default()).unwrap();
+    assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+    assert_eq!(parsed.records.len(), 2);
+    
+    let november_first = NaiveDate::from_ymd_opt(2024, 11, 1).unwrap();
+    let iso = &parsed.records[0];
+    assert_eq!((iso.record_id.as_str(), iso.deceased_name.as_str()), ("X-1", "Ali Hassan"));
+    assert_eq!(iso.burial_date, november_first);
+    assert_eq!(iso.section.as_deref(), Some("A"));
+    
+    let serial = &parsed.records[1];
+    assert_eq!(serial.burial_date, november_first);
+    assert_eq!(serial.death_date, NaiveDate::from_ymd_opt(2024, 10, 31).unwrap());
+    assert_eq!(serial.birth_date, NaiveDate::from_ymd_opt(1950, 11, 15));
+    assert_eq!(iso.birth_date, None);
+    
+    // Empty cells are missing values
+    assert_eq!(serial.section, None);
+    assert_eq!(serial.additional_data, None);
+    assert_eq!(iso.additional_data, Some(serde_json::json!({ "registrar": "Kadhim" })));
+}